@@ -0,0 +1,392 @@
+//! Arch Linux news gate
+//!
+//! Arch publishes news items when an upgrade needs manual intervention.
+//! Items published since the last full system upgrade are surfaced with the
+//! update check, and an unread item naming a pending package blocks the
+//! unattended `pacman -Syu` until the user marks the news as read.
+//!
+//! Setting `PIXXIDEN_ARCH_NEWS_FEED` to a URL or a local file replaces the
+//! official feed, so the gate can be exercised against a crafted feed.
+
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::update_failures::{UpdateError, UpdateFailureKind};
+
+const FEED_URL: &str = "https://archlinux.org/feeds/news/";
+const APP_DIR: &str = "com.Pixxiden.launcher";
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+const PACMAN_LOG: &str = "/var/log/pacman.log";
+const MAX_AGE_DAYS: i64 = 30; // Without any upgrade on record
+
+/// Arch news item published since the last upgrade
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NewsItem {
+    pub title: String,
+    pub link: String,
+    pub published: String,              // RFC 3339
+    pub affected_packages: Vec<String>, // Pending packages mentioned by the item
+}
+
+/// Raw feed entry
+#[derive(Debug, Default, Clone)]
+struct FeedItem {
+    title: String,
+    link: String,
+    published: Option<DateTime<FixedOffset>>,
+    text: String, // Description without markup
+}
+
+/// Remove HTML tags from an item description
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Parse the `<item>` entries of an RSS feed
+fn parse_feed(xml: &str) -> Vec<FeedItem> {
+    let mut reader = Reader::from_str(xml);
+    let mut items = Vec::new();
+    let mut item: Option<FeedItem> = None;
+    let mut field: Option<String> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                if name == "item" {
+                    item = Some(FeedItem::default());
+                } else if item.is_some() {
+                    field = Some(name);
+                }
+            }
+            Ok(Event::End(e)) => {
+                if e.name().as_ref() == b"item" {
+                    items.extend(item.take());
+                }
+                field = None;
+            }
+            Ok(Event::Text(text)) => {
+                if let Ok(text) = text.decode() {
+                    push_field(item.as_mut(), field.as_deref(), &text);
+                }
+            }
+            Ok(Event::CData(data)) => {
+                push_field(
+                    item.as_mut(),
+                    field.as_deref(),
+                    &String::from_utf8_lossy(&data),
+                );
+            }
+            Ok(Event::GeneralRef(entity)) => {
+                if let Ok(Some(c)) = entity.resolve_char_ref() {
+                    push_field(item.as_mut(), field.as_deref(), &c.to_string());
+                } else if let Some(resolved) = entity
+                    .decode()
+                    .ok()
+                    .and_then(|name| quick_xml::escape::resolve_predefined_entity(&name))
+                {
+                    push_field(item.as_mut(), field.as_deref(), resolved);
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                log::warn!("Invalid Arch news feed: {}", e);
+                break;
+            }
+            Ok(_) => {}
+        }
+    }
+
+    for item in &mut items {
+        item.text = strip_html(&item.text);
+    }
+    items
+}
+
+fn push_field(item: Option<&mut FeedItem>, field: Option<&str>, text: &str) {
+    let Some(item) = item else { return };
+    match field {
+        Some("title") => item.title.push_str(text),
+        Some("link") => item.link.push_str(text),
+        Some("description") => item.text.push_str(text),
+        Some("pubDate") => item.published = DateTime::parse_from_rfc2822(text.trim()).ok(),
+        _ => {}
+    }
+}
+
+/// Pending packages named in a news item.
+/// Case-sensitive on whole tokens, so "Arch Linux" does not match `linux`.
+fn mentioned_packages(text: &str, pending: &[String]) -> Vec<String> {
+    let tokens: Vec<&str> = text
+        .split(|c: char| !(c.is_ascii_alphanumeric() || "@._+-".contains(c)))
+        .map(|t| t.trim_end_matches(['.', '-']))
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    pending
+        .iter()
+        .filter(|name| tokens.contains(&name.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// Start of the last full system upgrade that completed, from pacman.log
+fn last_full_upgrade(log: &str) -> Option<DateTime<FixedOffset>> {
+    let mut started = None;
+    let mut completed = None;
+
+    for line in log.lines() {
+        let Some(stamp) = line.strip_prefix('[').and_then(|l| l.split(']').next()) else {
+            continue;
+        };
+        if line.contains("[PACMAN] Running ") {
+            // A new pacman run, whatever was started before did not complete
+            started = None;
+        } else if line.contains("[PACMAN] starting full system upgrade") {
+            started = parse_log_time(stamp);
+        } else if line.contains("[ALPM] transaction completed") && started.is_some() {
+            completed = started.take();
+        }
+    }
+
+    completed
+}
+
+/// `2024-05-01T12:00:00+0200`, or `2019-01-01 12:00` (local time) in older logs
+fn parse_log_time(stamp: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_str(stamp, "%Y-%m-%dT%H:%M:%S%z")
+        .ok()
+        .or_else(|| {
+            let naive = NaiveDateTime::parse_from_str(stamp, "%Y-%m-%d %H:%M").ok()?;
+            let local = chrono::Local.from_local_datetime(&naive).single()?;
+            Some(local.fixed_offset())
+        })
+}
+
+/// Items published after `since`, with the pending packages they mention
+fn unread_items(
+    items: Vec<FeedItem>,
+    since: DateTime<FixedOffset>,
+    pending: &[String],
+) -> Vec<NewsItem> {
+    items
+        .into_iter()
+        .filter_map(|item| {
+            let published = item.published.filter(|p| *p > since)?;
+            let text = format!("{} {}", item.title, item.text);
+            Some(NewsItem {
+                affected_packages: mentioned_packages(&text, pending),
+                title: item.title.trim().to_string(),
+                link: item.link.trim().to_string(),
+                published: published.to_rfc3339(),
+            })
+        })
+        .collect()
+}
+
+fn app_dir(base: Option<PathBuf>) -> Option<PathBuf> {
+    base.map(|dir| dir.join(APP_DIR))
+}
+
+fn read_marker_path() -> Option<PathBuf> {
+    app_dir(dirs::data_dir()).map(|dir| dir.join("arch-news-read"))
+}
+
+/// Fetch the feed, served from the cache for an hour and when offline
+async fn fetch_feed() -> Result<String, String> {
+    let source = std::env::var("PIXXIDEN_ARCH_NEWS_FEED").unwrap_or_else(|_| FEED_URL.into());
+    if !source.starts_with("http://") && !source.starts_with("https://") {
+        return std::fs::read_to_string(&source)
+            .map_err(|e| format!("Failed to read news feed {}: {}", source, e));
+    }
+
+    let cache = app_dir(dirs::cache_dir()).map(|dir| dir.join("arch-news.xml"));
+    let cached = |max_age: Option<Duration>| -> Option<String> {
+        let path = cache.as_deref()?;
+        let age = std::fs::metadata(path)
+            .ok()?
+            .modified()
+            .ok()?
+            .elapsed()
+            .ok()?;
+        if max_age.is_some_and(|max| age > max) {
+            return None;
+        }
+        std::fs::read_to_string(path).ok()
+    };
+
+    if let Some(feed) = cached(Some(CACHE_TTL)) {
+        return Ok(feed);
+    }
+
+    let fetched = async {
+        reqwest::Client::new()
+            .get(&source)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await
+    }
+    .await;
+
+    match fetched {
+        Ok(feed) => {
+            if let Some(path) = cache.as_deref() {
+                let _ = path.parent().map(std::fs::create_dir_all);
+                if let Err(e) = std::fs::write(path, &feed) {
+                    log::warn!("Failed to cache Arch news: {}", e);
+                }
+            }
+            Ok(feed)
+        }
+        Err(e) => cached(None).ok_or_else(|| format!("Failed to fetch Arch news: {}", e)),
+    }
+}
+
+/// Since when news is considered unread: the last upgrade or the last "mark as read"
+fn unread_since(read_marker: Option<&Path>) -> DateTime<FixedOffset> {
+    let upgrade = std::fs::read_to_string(PACMAN_LOG)
+        .ok()
+        .and_then(|log| last_full_upgrade(&log));
+    let read = read_marker
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|stamp| DateTime::parse_from_rfc3339(stamp.trim()).ok());
+
+    let fallback = (chrono::Local::now() - chrono::Duration::days(MAX_AGE_DAYS)).fixed_offset();
+    upgrade.into_iter().chain(read).max().unwrap_or(fallback)
+}
+
+/// Unread Arch news, with the pending packages each item mentions
+pub async fn unread_news(pending: &[String]) -> Result<Vec<NewsItem>, String> {
+    let feed = fetch_feed().await?;
+    let since = unread_since(read_marker_path().as_deref());
+    Ok(unread_items(parse_feed(&feed), since, pending))
+}
+
+/// Mark every news item published so far as read
+pub fn mark_read() -> Result<(), String> {
+    let path = read_marker_path().ok_or("Unable to locate the data directory")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create data directory: {}", e))?;
+    }
+    std::fs::write(&path, chrono::Local::now().to_rfc3339())
+        .map_err(|e| format!("Failed to save Arch news state: {}", e))
+}
+
+/// Error blocking the upgrade when unread news mentions a pending package
+pub fn intervention_error(news: &[NewsItem]) -> Option<UpdateError> {
+    let item = news.iter().find(|n| !n.affected_packages.is_empty())?;
+    Some(UpdateError {
+        kind: UpdateFailureKind::ManualIntervention,
+        message: item.title.clone(),
+        packages: item.affected_packages.clone(),
+        remediation: format!(
+            "Read the Arch news at {} and apply the manual steps, then mark the news as read \
+             and retry.",
+            item.link
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEED: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0"><channel><title>Arch Linux: Recent news updates</title>
+<item><title>linux-firmware &gt;= 20250508.788aadc8-2 upgrade requires manual intervention</title>
+<link>https://archlinux.org/news/linux-firmware-2025050878aadc8-2-upgrade-requires-manual-intervention/</link>
+<description>&lt;p&gt;Starting with version &lt;code&gt;20250508.788aadc8-2&lt;/code&gt;, the firmware for NVIDIA GPUs has been split.&lt;/p&gt;</description>
+<pubDate>Sat, 21 Jun 2025 00:00:00 +0000</pubDate></item>
+<item><title>Valkey to replace Redis in the [extra] Repository</title>
+<link>https://archlinux.org/news/valkey-to-replace-redis-in-the-extra-repository/</link>
+<description>&lt;p&gt;Arch Linux will drop redis.&lt;/p&gt;</description>
+<pubDate>Thu, 17 Apr 2025 00:00:00 +0000</pubDate></item>
+</channel></rss>"#;
+
+    fn pending(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_feed() {
+        let items = parse_feed(FEED);
+        assert_eq!(items.len(), 2);
+        assert!(items[0].title.starts_with("linux-firmware >= 20250508"));
+        assert!(items[0].text.contains("firmware for NVIDIA GPUs"));
+        assert!(!items[0].text.contains("<p>"));
+        assert_eq!(
+            items[1].published.unwrap().to_rfc3339(),
+            "2025-04-17T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_mentioned_packages() {
+        let text = "linux-firmware >= 20250508 upgrade. Arch Linux will drop redis.";
+        assert_eq!(
+            mentioned_packages(
+                text,
+                &pending(&["linux", "linux-firmware", "redis", "mesa"])
+            ),
+            ["linux-firmware", "redis"]
+        );
+    }
+
+    #[test]
+    fn test_last_full_upgrade() {
+        let log = "[2025-06-01T10:00:00+0200] [PACMAN] starting full system upgrade\n\
+                   [2025-06-01T10:02:00+0200] [ALPM] transaction completed\n\
+                   [2025-06-20T09:00:00+0200] [PACMAN] starting full system upgrade\n\
+                   [2025-06-20T09:00:05+0200] [ALPM] transaction failed\n\
+                   [2025-06-21T09:00:00+0200] [PACMAN] Running 'pacman -S steam'\n\
+                   [2025-06-21T09:00:10+0200] [ALPM] transaction completed\n";
+        assert_eq!(
+            last_full_upgrade(log).unwrap().to_rfc3339(),
+            "2025-06-01T10:00:00+02:00"
+        );
+        assert!(last_full_upgrade("").is_none());
+    }
+
+    #[test]
+    fn test_unread_items_gate() {
+        let since = DateTime::parse_from_rfc3339("2025-05-01T00:00:00+00:00").unwrap();
+        let news = unread_items(
+            parse_feed(FEED),
+            since,
+            &pending(&["linux-firmware", "mesa"]),
+        );
+        assert_eq!(news.len(), 1);
+        assert_eq!(news[0].affected_packages, ["linux-firmware"]);
+
+        let error = intervention_error(&news).unwrap();
+        assert_eq!(error.kind, UpdateFailureKind::ManualIntervention);
+        assert!(error
+            .remediation
+            .contains("https://archlinux.org/news/linux-firmware"));
+
+        let news = unread_items(parse_feed(FEED), since, &pending(&["mesa"]));
+        assert!(intervention_error(&news).is_none());
+    }
+}
@@ -0,0 +1,461 @@
+//! Audio output/input management through PipeWire or PulseAudio
+//!
+//! Talks to the sound server with `pactl` (provided by `pipewire-pulse` on
+//! PipeWire systems) and parses its JSON output. An `AudioMonitor` follows
+//! `pactl subscribe` so the overlay reflects changes made outside Pixxiden.
+
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+/// Kind of audio device
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioDeviceKind {
+    Sink,   // Output (speakers, HDMI, headset)
+    Source, // Input (microphone)
+}
+
+impl AudioDeviceKind {
+    fn pactl_name(&self) -> &'static str {
+        match self {
+            AudioDeviceKind::Sink => "sink",
+            AudioDeviceKind::Source => "source",
+        }
+    }
+}
+
+/// An audio sink or source
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioDevice {
+    pub index: u32,
+    pub name: String,
+    pub description: String,
+    pub kind: AudioDeviceKind,
+    pub form_factor: Option<String>, // "headset", "speaker", "tv"... when reported
+    pub volume: u32,                 // 0-150 (percent)
+    pub muted: bool,
+    pub is_default: bool,
+}
+
+/// Snapshot of the audio devices and defaults
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioState {
+    pub sinks: Vec<AudioDevice>,
+    pub sources: Vec<AudioDevice>,
+    pub default_sink: Option<String>,
+    pub default_source: Option<String>,
+}
+
+/// Maximum volume accepted, matching the usual desktop "over-amplification" limit
+const MAX_VOLUME_PERCENT: u32 = 150;
+const RESTART_DELAY_MIN: Duration = Duration::from_secs(1);
+const RESTART_DELAY_MAX: Duration = Duration::from_secs(60);
+
+fn run_pactl(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("pactl")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run pactl: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "pactl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Parse the average volume (percent) over all channels of a pactl JSON device
+fn parse_volume(volume: &serde_json::Value) -> u32 {
+    let Some(channels) = volume.as_object() else {
+        return 0;
+    };
+
+    let percents: Vec<u32> = channels
+        .values()
+        .filter_map(|c| c.get("value_percent").and_then(|v| v.as_str()))
+        .filter_map(|p| p.trim_end_matches('%').trim().parse().ok())
+        .collect();
+
+    if percents.is_empty() {
+        return 0;
+    }
+    percents.iter().sum::<u32>() / percents.len() as u32
+}
+
+/// Parse the output of `pactl --format=json list sinks|sources`
+fn parse_devices(json: &str, kind: AudioDeviceKind, default: Option<&str>) -> Vec<AudioDevice> {
+    let Ok(serde_json::Value::Array(items)) = serde_json::from_str(json) else {
+        return vec![];
+    };
+
+    items
+        .iter()
+        .filter_map(|item| {
+            let name = item.get("name")?.as_str()?.to_string();
+
+            // Monitor sources mirror an output, they are not real inputs
+            if kind == AudioDeviceKind::Source
+                && (name.ends_with(".monitor")
+                    || item
+                        .get("monitor_of_sink")
+                        .is_some_and(|m| m.as_str().is_some_and(|s| !s.is_empty() && s != "n/a")))
+            {
+                return None;
+            }
+
+            let properties = item.get("properties");
+            let form_factor = properties
+                .and_then(|p| p.get("device.form_factor"))
+                .and_then(|f| f.as_str())
+                .map(|f| f.to_string());
+
+            Some(AudioDevice {
+                index: item.get("index")?.as_u64()? as u32,
+                description: item
+                    .get("description")
+                    .and_then(|d| d.as_str())
+                    .unwrap_or(&name)
+                    .to_string(),
+                kind,
+                form_factor,
+                volume: item.get("volume").map(parse_volume).unwrap_or(0),
+                muted: item.get("mute").and_then(|m| m.as_bool()).unwrap_or(false),
+                is_default: default == Some(name.as_str()),
+                name,
+            })
+        })
+        .collect()
+}
+
+fn list_devices(kind: AudioDeviceKind, default: Option<&str>) -> Result<Vec<AudioDevice>, String> {
+    let list = format!("{}s", kind.pactl_name());
+    let json = run_pactl(&["--format=json", "list", &list])?;
+    Ok(parse_devices(&json, kind, default))
+}
+
+/// Get the audio devices and the current defaults
+pub fn get_audio_state() -> Result<AudioState, String> {
+    let default_sink = run_pactl(&["get-default-sink"])
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+    let default_source = run_pactl(&["get-default-source"])
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+
+    Ok(AudioState {
+        sinks: list_devices(AudioDeviceKind::Sink, default_sink.as_deref())?,
+        sources: list_devices(AudioDeviceKind::Source, default_source.as_deref())?,
+        default_sink,
+        default_source,
+    })
+}
+
+/// Make a device the default and move the running streams to it
+pub fn set_default_device(kind: AudioDeviceKind, name: &str) -> Result<(), String> {
+    run_pactl(&[&format!("set-default-{}", kind.pactl_name()), name])?;
+
+    // Streams pinned to the previous device would otherwise keep playing there
+    let streams = match kind {
+        AudioDeviceKind::Sink => "sink-inputs",
+        AudioDeviceKind::Source => "source-outputs",
+    };
+    let move_cmd = match kind {
+        AudioDeviceKind::Sink => "move-sink-input",
+        AudioDeviceKind::Source => "move-source-output",
+    };
+
+    if let Ok(list) = run_pactl(&["list", "short", streams]) {
+        for id in list.lines().filter_map(|l| l.split_whitespace().next()) {
+            if let Err(e) = run_pactl(&[move_cmd, id, name]) {
+                log::warn!("Failed to move stream {} to {}: {}", id, name, e);
+            }
+        }
+    }
+
+    log::info!("Default {} set to {}", kind.pactl_name(), name);
+    Ok(())
+}
+
+/// Set the volume of a device (percent, capped at 150%)
+pub fn set_volume(kind: AudioDeviceKind, name: &str, volume: u32) -> Result<(), String> {
+    let volume = volume.min(MAX_VOLUME_PERCENT);
+    run_pactl(&[
+        &format!("set-{}-volume", kind.pactl_name()),
+        name,
+        &format!("{}%", volume),
+    ])?;
+    Ok(())
+}
+
+/// Mute or unmute a device
+pub fn set_mute(kind: AudioDeviceKind, name: &str, muted: bool) -> Result<(), String> {
+    run_pactl(&[
+        &format!("set-{}-mute", kind.pactl_name()),
+        name,
+        if muted { "1" } else { "0" },
+    ])?;
+    Ok(())
+}
+
+/// Check whether a `pactl subscribe` line affects devices or defaults
+fn is_device_event(line: &str) -> bool {
+    // Format: "Event 'change' on sink #52"
+    let Some(target) = line.split(" on ").nth(1) else {
+        return false;
+    };
+    let facility = target.split(" #").next().unwrap_or("").trim();
+    matches!(facility, "sink" | "source" | "server" | "card")
+}
+
+/// Start `pactl subscribe`, with its stdout to read the events from
+fn spawn_subscribe() -> Result<(Child, ChildStdout), String> {
+    let mut child = Command::new("pactl")
+        .arg("subscribe")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run pactl: {}", e))?;
+    let stdout = child.stdout.take().expect("Failed to capture stdout");
+    Ok((child, stdout))
+}
+
+/// Delay before the next `pactl subscribe` restart, doubling up to a minute
+fn next_restart_delay(delay: Duration) -> Duration {
+    (delay * 2).min(RESTART_DELAY_MAX)
+}
+
+/// Audio monitor that follows the sound server and emits `audio-changed`
+/// whenever devices, defaults, volume or mute change.
+pub struct AudioMonitor {
+    running: Mutex<Arc<AtomicBool>>, // Replaced on each start, only its worker reads it
+    child: Arc<Mutex<Option<Child>>>,
+}
+
+impl AudioMonitor {
+    pub fn new() -> Self {
+        Self {
+            running: Mutex::new(Arc::new(AtomicBool::new(false))),
+            child: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Start following `pactl subscribe` in a background thread,
+    /// restarting it with a growing delay whenever it exits
+    pub fn start(&self, app_handle: AppHandle) {
+        let mut running = self.running.lock().unwrap();
+        if running.load(Ordering::Relaxed) {
+            log::warn!("Audio monitor already running");
+            return;
+        }
+
+        let (child, stdout) = match spawn_subscribe() {
+            Ok(subscription) => subscription,
+            Err(e) => {
+                log::warn!("Audio monitor unavailable (pactl not found): {}", e);
+                return;
+            }
+        };
+
+        *self.child.lock().unwrap() = Some(child);
+        // A worker still sleeping after stop() keeps its own cleared flag
+        *running = Arc::new(AtomicBool::new(true));
+        let running = running.clone();
+        let child = self.child.clone();
+
+        std::thread::spawn(move || {
+            log::info!("Starting audio monitor...");
+            let mut last_state = get_audio_state().ok();
+            let mut stdout = Some(stdout);
+            let mut delay = RESTART_DELAY_MIN;
+
+            // Events come in bursts; only emit when the visible state changed
+            let refresh = |last_state: &mut Option<AudioState>| {
+                let Ok(state) = get_audio_state() else {
+                    return;
+                };
+                if last_state.as_ref() != Some(&state) {
+                    if let Err(e) = app_handle.emit("audio-changed", &state) {
+                        log::error!("Failed to emit audio change event: {}", e);
+                    }
+                    *last_state = Some(state);
+                }
+            };
+
+            while running.load(Ordering::Relaxed) {
+                if let Some(stdout) = stdout.take() {
+                    let started = Instant::now();
+                    for line in BufReader::new(stdout).lines() {
+                        if !running.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        let Ok(line) = line else { break };
+                        if is_device_event(&line) {
+                            refresh(&mut last_state);
+                        }
+                    }
+                    // The slot may already hold the child of a later start()
+                    let mut current = child.lock().unwrap();
+                    if !running.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let exited = current.take();
+                    drop(current);
+                    if let Some(mut exited) = exited {
+                        let _ = exited.wait();
+                    }
+                    // A subscription that held up for a while was not crash-looping
+                    if started.elapsed() >= RESTART_DELAY_MAX {
+                        delay = RESTART_DELAY_MIN;
+                    }
+                    log::warn!("pactl subscribe exited, restarting in {:?}", delay);
+                }
+
+                std::thread::sleep(delay);
+                delay = next_restart_delay(delay);
+                if !running.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                match spawn_subscribe() {
+                    Ok((mut subscription, output)) => {
+                        // Either stop() kills this child, or it already cleared `running`
+                        let mut current = child.lock().unwrap();
+                        if !running.load(Ordering::Relaxed) {
+                            let _ = subscription.kill();
+                            let _ = subscription.wait();
+                            break;
+                        }
+                        *current = Some(subscription);
+                        drop(current);
+                        stdout = Some(output);
+                        // Changes made while the sound server was away
+                        refresh(&mut last_state);
+                    }
+                    Err(e) => log::warn!("Failed to restart pactl subscribe: {}", e),
+                }
+            }
+
+            log::info!("Audio monitor stopped");
+        });
+    }
+
+    /// Stop the audio monitor
+    pub fn stop(&self) {
+        self.running.lock().unwrap().store(false, Ordering::Relaxed);
+        if let Some(mut child) = self.child.lock().unwrap().take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Default for AudioMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for AudioMonitor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SINKS_JSON: &str = r#"[
+        {
+            "index": 52,
+            "state": "RUNNING",
+            "name": "alsa_output.pci-0000_03_00.1.hdmi-stereo",
+            "description": "Navi HDMI Audio",
+            "mute": false,
+            "volume": {
+                "front-left": {"value": 65536, "value_percent": "100%", "db": "0.00 dB"},
+                "front-right": {"value": 52429, "value_percent": "80%", "db": "-5.81 dB"}
+            },
+            "properties": {"device.form_factor": "tv"}
+        },
+        {
+            "index": 61,
+            "state": "SUSPENDED",
+            "name": "bluez_output.00_1B_66_AA_BB_CC.1",
+            "description": "Headset",
+            "mute": true,
+            "volume": {"mono": {"value": 32768, "value_percent": "50%", "db": "-18.06 dB"}},
+            "properties": {}
+        }
+    ]"#;
+
+    #[test]
+    fn test_parse_sinks() {
+        let sinks = parse_devices(
+            SINKS_JSON,
+            AudioDeviceKind::Sink,
+            Some("bluez_output.00_1B_66_AA_BB_CC.1"),
+        );
+        assert_eq!(sinks.len(), 2);
+        assert_eq!(sinks[0].index, 52);
+        assert_eq!(sinks[0].volume, 90);
+        assert_eq!(sinks[0].form_factor.as_deref(), Some("tv"));
+        assert!(!sinks[0].is_default);
+        assert!(sinks[1].muted);
+        assert!(sinks[1].is_default);
+        assert_eq!(sinks[1].volume, 50);
+    }
+
+    #[test]
+    fn test_parse_sources_skips_monitors() {
+        let json = r#"[
+            {"index": 1, "name": "alsa_output.hdmi-stereo.monitor", "description": "Monitor of HDMI",
+             "mute": false, "volume": {}, "monitor_of_sink": "alsa_output.hdmi-stereo"},
+            {"index": 2, "name": "alsa_input.usb-mic", "description": "USB Mic",
+             "mute": false, "volume": {}, "monitor_of_sink": "n/a"}
+        ]"#;
+        let sources = parse_devices(json, AudioDeviceKind::Source, None);
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].name, "alsa_input.usb-mic");
+    }
+
+    #[test]
+    fn test_parse_devices_invalid_json() {
+        assert!(parse_devices("not json", AudioDeviceKind::Sink, None).is_empty());
+    }
+
+    #[test]
+    fn test_is_device_event() {
+        assert!(is_device_event("Event 'change' on sink #52"));
+        assert!(is_device_event("Event 'new' on source #12"));
+        assert!(is_device_event("Event 'change' on server #-1"));
+        assert!(!is_device_event("Event 'change' on sink-input #130"));
+        assert!(!is_device_event("Event 'new' on client #200"));
+    }
+
+    #[test]
+    fn test_next_restart_delay() {
+        assert_eq!(
+            next_restart_delay(RESTART_DELAY_MIN),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            next_restart_delay(Duration::from_secs(32)),
+            RESTART_DELAY_MAX
+        );
+        assert_eq!(next_restart_delay(RESTART_DELAY_MAX), RESTART_DELAY_MAX);
+    }
+}
@@ -0,0 +1,224 @@
+//! AUR updates through paru or yay
+//!
+//! Only used when one of the helpers is installed. Helpers build packages
+//! with makepkg, which refuses to run as root, so they always run as the
+//! user and never through the sudoers rules. Installing the built packages
+//! goes through polkit (`--sudo pkexec`), as the whitelisted pacman commands
+//! do not cover arbitrary package files.
+
+use serde::{Deserialize, Serialize};
+
+use crate::package_manager::CommandRunner;
+use crate::system_updates::{
+    categorize_package, is_critical_package, PacmanProgress, Status, UpdatePackage,
+    UpdateProgressEvent, UpdateSource,
+};
+use crate::update_failures::{self, UpdateError, UpdateFailureKind};
+use crate::update_jobs::UpdateJob;
+
+/// Supported AUR helpers
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AurHelper {
+    Paru,
+    Yay,
+}
+
+impl AurHelper {
+    pub fn program(self) -> &'static str {
+        match self {
+            AurHelper::Paru => "paru",
+            AurHelper::Yay => "yay",
+        }
+    }
+
+    /// Non-interactive AUR-only upgrade, skipping PKGBUILD reviews and diffs
+    fn upgrade_args(self, ignore: &str) -> Vec<&str> {
+        let base: &[&str] = match self {
+            AurHelper::Paru => &["-Sua", "--noconfirm", "--skipreview", "--sudo", "pkexec"],
+            AurHelper::Yay => &[
+                "-Sua",
+                "--noconfirm",
+                "--answerdiff",
+                "None",
+                "--answerclean",
+                "None",
+                "--sudo",
+                "pkexec",
+            ],
+        };
+        let mut args = base.to_vec();
+        if !ignore.is_empty() {
+            args.extend(["--ignore", ignore]);
+        }
+        args
+    }
+}
+
+/// Installed helper, paru first
+pub fn detect() -> Option<AurHelper> {
+    [AurHelper::Paru, AurHelper::Yay]
+        .into_iter()
+        .find(|helper| which::which(helper.program()).is_ok())
+}
+
+/// `-Qua` lines: `proton-ge-custom-bin 1:GE-Proton9-15-1 -> 1:GE-Proton9-16-1`
+fn parse_aur_updates(stdout: &str) -> Vec<UpdatePackage> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [name, current, "->", new, ..] = parts.as_slice() else {
+                return None;
+            };
+            Some(UpdatePackage {
+                name: name.to_string(),
+                current_version: current.to_string(),
+                new_version: new.to_string(),
+                category: categorize_package(name),
+                size: 0, // Sources are only fetched at build time
+                installed_size_delta: 0,
+                critical: is_critical_package(name),
+                source: UpdateSource::Aur,
+            })
+        })
+        .collect()
+}
+
+/// Pending AUR updates, from the AUR RPC (no root needed)
+pub fn check_updates(
+    runner: &dyn CommandRunner,
+    helper: AurHelper,
+) -> Result<Vec<UpdatePackage>, String> {
+    let output = runner.output(helper.program(), &["-Qua"])?;
+    // Like pacman -Qu, exits with 1 when nothing is out of date
+    if !output.success() && output.stdout.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(parse_aur_updates(&output.stdout))
+}
+
+/// `==> Making package: proton-ge-custom-bin 1:GE-Proton9-16-1 (Mon Sep 16 …)`
+fn built_package(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix("==> Making package: ")?
+        .split_whitespace()
+        .next()
+}
+
+/// Build failures are reported by makepkg or the helper, not pacman
+fn aur_failure(lines: &[String], helper: AurHelper) -> UpdateError {
+    let mut error = update_failures::classify_pacman_failure(lines);
+    if error.kind == UpdateFailureKind::Unknown {
+        error.message = lines
+            .iter()
+            .map(|l| l.trim())
+            .find(|l| l.starts_with("==> ERROR:") || l.contains("error making:"))
+            .or_else(|| {
+                lines
+                    .iter()
+                    .map(|l| l.trim())
+                    .find(|l| l.starts_with("error:"))
+            })
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("AUR update with {} failed", helper.program()));
+    }
+    error
+}
+
+/// Build and install every pending AUR update but the held ones, streaming
+/// progress like pacman. Packages that were pending but not installed are
+/// reported as failed.
+pub async fn install_updates(
+    runner: &dyn CommandRunner,
+    helper: AurHelper,
+    job: &UpdateJob,
+    pending: &[UpdatePackage],
+    holds: &[String],
+) -> Result<(Vec<String>, Vec<String>, Option<UpdateError>), String> {
+    let mut progress = PacmanProgress::new(pending.iter().map(|p| (p.name.clone(), 0)).collect());
+    let total = pending.len().max(1) as f32;
+    let mut built = 0;
+    let start = std::time::Instant::now();
+    let mut output: Vec<String> = Vec::new();
+    let ignore = holds.join(",");
+
+    let result = runner
+        .stream(
+            helper.program(),
+            &helper.upgrade_args(&ignore),
+            job,
+            &mut |line| {
+                // Building counts as the download phase of pacman's progress
+                if let Some(name) = built_package(&line) {
+                    job.progress(UpdateProgressEvent {
+                        stage: Status::Downloading,
+                        package_name: name.to_string(),
+                        progress: built as f32 / total * 45.0,
+                        package_progress: 0.0,
+                        downloaded: "".to_string(),
+                        total: "".to_string(),
+                        speed: "".to_string(),
+                        eta: "".to_string(),
+                    });
+                    built += 1;
+                }
+                if line.starts_with(":: Processing package changes") {
+                    job.enter_commit();
+                }
+                for event in progress.feed(&line, start.elapsed()) {
+                    job.progress(event);
+                }
+                output.push(line);
+            },
+        )
+        .await?;
+
+    let installed = progress.installed;
+    let failed: Vec<String> = pending
+        .iter()
+        .map(|p| p.name.clone())
+        .filter(|name| !installed.contains(name))
+        .collect();
+    let error = (!result.success()).then(|| {
+        let mut lines = result.stderr_lines();
+        lines.extend(output);
+        aur_failure(&lines, helper)
+    });
+
+    Ok((installed, failed, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_aur_updates() {
+        let stdout = ":: Looking for AUR upgrades...\n\
+                      proton-ge-custom-bin 1:GE-Proton9-15-1 -> 1:GE-Proton9-16-1\n\
+                      gamescope-git 3.15.9.r12.g1a2b3c4-1 -> 3.15.11.r3.g5d6e7f8-1\n";
+        let packages = parse_aur_updates(stdout);
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name, "proton-ge-custom-bin");
+        assert_eq!(packages[0].current_version, "1:GE-Proton9-15-1");
+        assert_eq!(packages[0].new_version, "1:GE-Proton9-16-1");
+        assert_eq!(packages[1].name, "gamescope-git");
+        assert!(packages.iter().all(|p| p.source == UpdateSource::Aur));
+    }
+
+    #[test]
+    fn test_aur_failure() {
+        let lines = vec![
+            "==> Making package: gamescope-git 3.15.11.r3.g5d6e7f8-1".to_string(),
+            "==> ERROR: A failure occurred in build().".to_string(),
+            "error: failed to build 'gamescope-git-3.15.11.r3.g5d6e7f8-1':".to_string(),
+        ];
+        let error = aur_failure(&lines, AurHelper::Paru);
+        assert_eq!(error.message, "==> ERROR: A failure occurred in build().");
+        assert_eq!(
+            aur_failure(&[], AurHelper::Yay).message,
+            "AUR update with yay failed"
+        );
+    }
+}
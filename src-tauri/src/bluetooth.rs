@@ -0,0 +1,343 @@
+//! Bluetooth device pairing through BlueZ (D-Bus `org.bluez`)
+//!
+//! Only gamepads and audio headsets are listed by default, the launcher has
+//! no use for keyboards, phones or random BLE beacons.
+
+use serde::{Deserialize, Serialize};
+use zbus::Connection;
+
+use crate::dbus::{self, ManagedObjects, Properties};
+
+const BLUEZ: &str = "org.bluez";
+const ADAPTER_IFACE: &str = "org.bluez.Adapter1";
+const DEVICE_IFACE: &str = "org.bluez.Device1";
+const BATTERY_IFACE: &str = "org.bluez.Battery1";
+
+/// Kind of Bluetooth device relevant to the launcher
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BluetoothDeviceKind {
+    Gamepad,
+    Headset,
+    Other,
+}
+
+/// A Bluetooth device known to BlueZ (discovered or paired)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BluetoothDevice {
+    pub address: String,
+    pub name: String,
+    pub kind: BluetoothDeviceKind,
+    pub paired: bool,
+    pub trusted: bool,
+    pub connected: bool,
+    pub rssi: Option<i16>,   // Only set while discovering
+    pub battery: Option<u8>, // Percent, when the device reports it
+}
+
+/// Classify a device from its BlueZ icon name and Class of Device
+fn classify(icon: Option<&str>, class: Option<u32>) -> BluetoothDeviceKind {
+    match icon {
+        Some("input-gaming") => return BluetoothDeviceKind::Gamepad,
+        Some("audio-headset") | Some("audio-headphones") | Some("audio-card") => {
+            return BluetoothDeviceKind::Headset;
+        }
+        _ => {}
+    }
+
+    // Class of Device: major class in bits 8-12, minor class in bits 2-7
+    if let Some(class) = class {
+        let major = (class >> 8) & 0x1f;
+        let minor = (class >> 2) & 0x3f;
+        match major {
+            // Peripheral: joystick (0x01) / gamepad (0x02) in the low minor bits
+            0x05 if matches!(minor & 0x0f, 0x01 | 0x02) => return BluetoothDeviceKind::Gamepad,
+            // Audio/Video: wearable headset, hands-free, headphones
+            0x04 if matches!(minor, 0x01 | 0x02 | 0x06) => return BluetoothDeviceKind::Headset,
+            _ => {}
+        }
+    }
+
+    BluetoothDeviceKind::Other
+}
+
+fn device_from_props(device: &Properties, battery: Option<&Properties>) -> Option<BluetoothDevice> {
+    let address = dbus::prop_str(device, "Address")?;
+    let icon = dbus::prop_str(device, "Icon");
+
+    Some(BluetoothDevice {
+        name: dbus::prop_str(device, "Alias")
+            .or_else(|| dbus::prop_str(device, "Name"))
+            .unwrap_or_else(|| address.clone()),
+        kind: classify(icon.as_deref(), dbus::prop_u32(device, "Class")),
+        paired: dbus::prop_bool(device, "Paired").unwrap_or(false),
+        trusted: dbus::prop_bool(device, "Trusted").unwrap_or(false),
+        connected: dbus::prop_bool(device, "Connected").unwrap_or(false),
+        rssi: dbus::prop_i16(device, "RSSI"),
+        battery: battery.and_then(|b| dbus::prop_u8(b, "Percentage")),
+        address,
+    })
+}
+
+/// Extract the devices (with their object path) from the BlueZ object tree
+fn parse_devices(objects: &ManagedObjects) -> Vec<(String, BluetoothDevice)> {
+    let mut devices: Vec<(String, BluetoothDevice)> = objects
+        .iter()
+        .filter_map(|(path, interfaces)| {
+            let props = interfaces
+                .iter()
+                .find(|(name, _)| name.as_str() == DEVICE_IFACE)
+                .map(|(_, props)| props)?;
+            let battery = interfaces
+                .iter()
+                .find(|(name, _)| name.as_str() == BATTERY_IFACE)
+                .map(|(_, props)| props);
+            Some((path.to_string(), device_from_props(props, battery)?))
+        })
+        .collect();
+
+    // Connected first, then paired, then by name
+    devices.sort_by(|(_, a), (_, b)| {
+        b.connected
+            .cmp(&a.connected)
+            .then(b.paired.cmp(&a.paired))
+            .then(a.name.cmp(&b.name))
+    });
+    devices
+}
+
+async fn find_adapter(conn: &Connection, objects: &ManagedObjects) -> Result<String, String> {
+    let path = objects
+        .iter()
+        .filter(|(_, interfaces)| interfaces.keys().any(|n| n.as_str() == ADAPTER_IFACE))
+        .map(|(path, _)| path.to_string())
+        .min() // hci0 before hci1
+        .ok_or("No Bluetooth adapter found")?;
+
+    // Make sure the adapter is on before using it
+    let adapter = dbus::proxy(conn, BLUEZ, &path, ADAPTER_IFACE).await?;
+    if !adapter
+        .get_property::<bool>("Powered")
+        .await
+        .unwrap_or(false)
+    {
+        adapter
+            .set_property("Powered", true)
+            .await
+            .map_err(|e| format!("Failed to power on Bluetooth adapter: {}", e))?;
+    }
+
+    Ok(path)
+}
+
+async fn find_device_path(conn: &Connection, address: &str) -> Result<String, String> {
+    let objects = dbus::managed_objects(conn, BLUEZ, "/").await?;
+    parse_devices(&objects)
+        .into_iter()
+        .find(|(_, d)| d.address.eq_ignore_ascii_case(address))
+        .map(|(path, _)| path)
+        .ok_or_else(|| format!("Bluetooth device not found: {}", address))
+}
+
+async fn call_device(address: &str, method: &str) -> Result<(), String> {
+    let conn = dbus::system_bus().await?;
+    let path = find_device_path(&conn, address).await?;
+    let device = dbus::proxy(&conn, BLUEZ, &path, DEVICE_IFACE).await?;
+    device
+        .call_method(method, &())
+        .await
+        .map_err(|e| format!("Bluetooth {} failed for {}: {}", method, address, e))?;
+    Ok(())
+}
+
+/// Discovery session kept on one system bus connection: BlueZ ties a scan to
+/// the D-Bus client that started it, ends it when that client disconnects and
+/// only lets that client stop it
+pub struct BluetoothDiscovery {
+    conn: tokio::sync::Mutex<Option<Connection>>,
+}
+
+impl BluetoothDiscovery {
+    pub fn new() -> Self {
+        Self {
+            conn: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// Start scanning for nearby devices
+    pub async fn start(&self) -> Result<(), String> {
+        let mut conn = self.conn.lock().await;
+        if conn.is_none() {
+            *conn = Some(dbus::system_bus().await?);
+        }
+        let conn = conn.as_ref().unwrap();
+        let objects = dbus::managed_objects(conn, BLUEZ, "/").await?;
+        let path = find_adapter(conn, &objects).await?;
+        let adapter = dbus::proxy(conn, BLUEZ, &path, ADAPTER_IFACE).await?;
+
+        adapter
+            .call_method("StartDiscovery", &())
+            .await
+            .map_err(|e| format!("Failed to start Bluetooth discovery: {}", e))?;
+        log::info!("Bluetooth discovery started on {}", path);
+        Ok(())
+    }
+
+    /// Stop scanning for nearby devices
+    pub async fn stop(&self) -> Result<(), String> {
+        // Dropping the connection ends the session even if the call fails
+        let Some(conn) = self.conn.lock().await.take() else {
+            return Ok(());
+        };
+        let objects = dbus::managed_objects(&conn, BLUEZ, "/").await?;
+        let path = find_adapter(&conn, &objects).await?;
+        let adapter = dbus::proxy(&conn, BLUEZ, &path, ADAPTER_IFACE).await?;
+
+        adapter
+            .call_method("StopDiscovery", &())
+            .await
+            .map_err(|e| format!("Failed to stop Bluetooth discovery: {}", e))?;
+        log::info!("Bluetooth discovery stopped on {}", path);
+        Ok(())
+    }
+}
+
+impl Default for BluetoothDiscovery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// List known and discovered devices (gamepads and headsets unless `all` is set)
+pub async fn list_devices(all: bool) -> Result<Vec<BluetoothDevice>, String> {
+    let conn = dbus::system_bus().await?;
+    let objects = dbus::managed_objects(&conn, BLUEZ, "/").await?;
+
+    Ok(parse_devices(&objects)
+        .into_iter()
+        .map(|(_, device)| device)
+        .filter(|d| all || d.kind != BluetoothDeviceKind::Other)
+        .collect())
+}
+
+/// Get a single device by address
+pub async fn get_device(address: &str) -> Result<BluetoothDevice, String> {
+    list_devices(true)
+        .await?
+        .into_iter()
+        .find(|d| d.address.eq_ignore_ascii_case(address))
+        .ok_or_else(|| format!("Bluetooth device not found: {}", address))
+}
+
+/// Pair with a device
+pub async fn pair_device(address: &str) -> Result<(), String> {
+    call_device(address, "Pair").await?;
+    log::info!("Bluetooth device paired: {}", address);
+    Ok(())
+}
+
+/// Trust a device so it can reconnect on its own (controllers waking up)
+pub async fn trust_device(address: &str) -> Result<(), String> {
+    let conn = dbus::system_bus().await?;
+    let path = find_device_path(&conn, address).await?;
+    let device = dbus::proxy(&conn, BLUEZ, &path, DEVICE_IFACE).await?;
+    device
+        .set_property("Trusted", true)
+        .await
+        .map_err(|e| format!("Failed to trust {}: {}", address, e))?;
+    Ok(())
+}
+
+/// Connect to a paired device
+pub async fn connect_device(address: &str) -> Result<BluetoothDevice, String> {
+    call_device(address, "Connect").await?;
+    log::info!("Bluetooth device connected: {}", address);
+    get_device(address).await
+}
+
+/// Disconnect a device
+pub async fn disconnect_device(address: &str) -> Result<(), String> {
+    call_device(address, "Disconnect").await
+}
+
+/// Remove a device and its pairing
+pub async fn forget_device(address: &str) -> Result<(), String> {
+    let conn = dbus::system_bus().await?;
+    let objects = dbus::managed_objects(&conn, BLUEZ, "/").await?;
+    let device_path = find_device_path(&conn, address).await?;
+    let adapter_path = find_adapter(&conn, &objects).await?;
+    let adapter = dbus::proxy(&conn, BLUEZ, &adapter_path, ADAPTER_IFACE).await?;
+
+    let device_path = zbus::zvariant::ObjectPath::try_from(device_path.as_str())
+        .map_err(|e| format!("Invalid device path: {}", e))?;
+    adapter
+        .call_method("RemoveDevice", &(device_path,))
+        .await
+        .map_err(|e| format!("Failed to forget {}: {}", address, e))?;
+    log::info!("Bluetooth device forgotten: {}", address);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zbus::zvariant::{OwnedValue, Str};
+
+    #[test]
+    fn test_classify_by_icon() {
+        assert_eq!(
+            classify(Some("input-gaming"), None),
+            BluetoothDeviceKind::Gamepad
+        );
+        assert_eq!(
+            classify(Some("audio-headset"), None),
+            BluetoothDeviceKind::Headset
+        );
+        assert_eq!(classify(Some("phone"), None), BluetoothDeviceKind::Other);
+    }
+
+    #[test]
+    fn test_classify_by_class() {
+        // DualSense: peripheral, gamepad
+        assert_eq!(classify(None, Some(0x002508)), BluetoothDeviceKind::Gamepad);
+        // Generic headset: audio/video, wearable headset
+        assert_eq!(classify(None, Some(0x240404)), BluetoothDeviceKind::Headset);
+        // Keyboard: peripheral, keyboard
+        assert_eq!(classify(None, Some(0x002540)), BluetoothDeviceKind::Other);
+    }
+
+    #[test]
+    fn test_device_from_props() {
+        let mut props = Properties::new();
+        props.insert(
+            "Address".to_string(),
+            OwnedValue::from(Str::from("AA:BB:CC:DD:EE:FF")),
+        );
+        props.insert(
+            "Alias".to_string(),
+            OwnedValue::from(Str::from("Xbox Wireless Controller")),
+        );
+        props.insert(
+            "Icon".to_string(),
+            OwnedValue::from(Str::from("input-gaming")),
+        );
+        props.insert("Paired".to_string(), OwnedValue::from(true));
+        props.insert("RSSI".to_string(), OwnedValue::from(-60i16));
+
+        let mut battery = Properties::new();
+        battery.insert("Percentage".to_string(), OwnedValue::from(80u8));
+
+        let device = device_from_props(&props, Some(&battery)).unwrap();
+        assert_eq!(device.name, "Xbox Wireless Controller");
+        assert_eq!(device.kind, BluetoothDeviceKind::Gamepad);
+        assert!(device.paired && !device.connected);
+        assert_eq!(device.rssi, Some(-60));
+        assert_eq!(device.battery, Some(80));
+    }
+
+    #[test]
+    fn test_device_without_address_is_skipped() {
+        assert!(device_from_props(&Properties::new(), None).is_none());
+    }
+}
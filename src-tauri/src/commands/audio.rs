@@ -0,0 +1,25 @@
+use crate::audio::{self, AudioDeviceKind, AudioState};
+
+/// List audio outputs/inputs and the current defaults
+#[tauri::command]
+pub fn get_audio_state() -> Result<AudioState, String> {
+    audio::get_audio_state()
+}
+
+/// Switch the default output or input (running streams follow)
+#[tauri::command]
+pub fn set_default_audio_device(kind: AudioDeviceKind, name: String) -> Result<(), String> {
+    audio::set_default_device(kind, &name)
+}
+
+/// Set the volume of an audio device (percent)
+#[tauri::command]
+pub fn set_audio_volume(kind: AudioDeviceKind, name: String, volume: u32) -> Result<(), String> {
+    audio::set_volume(kind, &name, volume)
+}
+
+/// Mute or unmute an audio device
+#[tauri::command]
+pub fn set_audio_mute(kind: AudioDeviceKind, name: String, muted: bool) -> Result<(), String> {
+    audio::set_mute(kind, &name, muted)
+}
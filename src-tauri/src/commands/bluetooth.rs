@@ -0,0 +1,70 @@
+use crate::bluetooth::{self, BluetoothDevice, BluetoothDiscovery};
+use crate::gamepad::GamepadMonitor;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+/// Start scanning for nearby Bluetooth devices
+#[tauri::command]
+pub async fn start_bluetooth_discovery(
+    discovery: State<'_, Arc<BluetoothDiscovery>>,
+) -> Result<(), String> {
+    discovery.start().await
+}
+
+/// Stop scanning for Bluetooth devices
+#[tauri::command]
+pub async fn stop_bluetooth_discovery(
+    discovery: State<'_, Arc<BluetoothDiscovery>>,
+) -> Result<(), String> {
+    discovery.stop().await
+}
+
+/// List Bluetooth gamepads and headsets (every device when `all` is set)
+#[tauri::command]
+pub async fn list_bluetooth_devices(all: Option<bool>) -> Result<Vec<BluetoothDevice>, String> {
+    bluetooth::list_devices(all.unwrap_or(false)).await
+}
+
+/// Pair, trust and connect a device in one go, announcing new controllers
+#[tauri::command]
+pub async fn pair_bluetooth_device(
+    app: AppHandle,
+    monitor: State<'_, Arc<GamepadMonitor>>,
+    address: String,
+) -> Result<BluetoothDevice, String> {
+    bluetooth::pair_device(&address).await?;
+    bluetooth::trust_device(&address).await?;
+    let device = bluetooth::connect_device(&address).await?;
+    monitor.announce_bluetooth(&app, &device);
+    Ok(device)
+}
+
+/// Trust a Bluetooth device so it reconnects automatically
+#[tauri::command]
+pub async fn trust_bluetooth_device(address: String) -> Result<(), String> {
+    bluetooth::trust_device(&address).await
+}
+
+/// Connect to a paired Bluetooth device
+#[tauri::command]
+pub async fn connect_bluetooth_device(
+    app: AppHandle,
+    monitor: State<'_, Arc<GamepadMonitor>>,
+    address: String,
+) -> Result<BluetoothDevice, String> {
+    let device = bluetooth::connect_device(&address).await?;
+    monitor.announce_bluetooth(&app, &device);
+    Ok(device)
+}
+
+/// Disconnect a Bluetooth device
+#[tauri::command]
+pub async fn disconnect_bluetooth_device(address: String) -> Result<(), String> {
+    bluetooth::disconnect_device(&address).await
+}
+
+/// Remove a Bluetooth device and its pairing
+#[tauri::command]
+pub async fn forget_bluetooth_device(address: String) -> Result<(), String> {
+    bluetooth::forget_device(&address).await
+}
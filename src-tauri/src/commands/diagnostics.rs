@@ -0,0 +1,25 @@
+use crate::diagnostics;
+use std::path::PathBuf;
+use tauri::Manager;
+
+/// Write a redacted diagnostics bundle (.tar.gz) to `dest` and return its path
+#[tauri::command]
+pub async fn export_diagnostics_bundle(
+    app: tauri::AppHandle,
+    dest: String,
+) -> Result<String, String> {
+    let log_dir = app
+        .path()
+        .app_log_dir()
+        .map_err(|e| format!("Failed to resolve log directory: {}", e))?;
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve data directory: {}", e))?;
+
+    tokio::task::spawn_blocking(move || {
+        diagnostics::export_bundle(&log_dir, &data_dir, &PathBuf::from(dest))
+    })
+    .await
+    .map_err(|e| format!("Diagnostics task failed: {}", e))?
+}
@@ -0,0 +1,37 @@
+use crate::display::{self, DisplayChange, DisplayManager, DisplayOutput, PendingDisplayChange};
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+/// List connected display outputs and their modes
+#[tauri::command]
+pub fn get_display_outputs() -> Result<Vec<DisplayOutput>, String> {
+    display::list_outputs()
+}
+
+/// Apply a display change; it is reverted unless confirmed in time
+#[tauri::command]
+pub fn apply_display_change(
+    app: AppHandle,
+    manager: State<'_, Arc<DisplayManager>>,
+    change: DisplayChange,
+) -> Result<PendingDisplayChange, String> {
+    manager.apply(app, change)
+}
+
+/// Keep a pending display change
+#[tauri::command]
+pub fn confirm_display_change(
+    manager: State<'_, Arc<DisplayManager>>,
+    id: u64,
+) -> Result<(), String> {
+    manager.confirm(id)
+}
+
+/// Revert a pending display change immediately
+#[tauri::command]
+pub fn revert_display_change(
+    manager: State<'_, Arc<DisplayManager>>,
+    id: u64,
+) -> Result<(), String> {
+    manager.revert(id)
+}
@@ -1,16 +1,34 @@
 // Commands module - Re-exports all command modules
 //
 // Structure:
+// - audio.rs - Audio output/input devices
+// - bluetooth.rs - Bluetooth controller/headset pairing
+// - diagnostics.rs - Diagnostics bundle export
+// - display.rs - Display outputs and modes
+// - network.rs - Wi-Fi networks
 // - system.rs - System info and settings
 // - updates.rs - System updates
 // - runners.rs - Proton-GE runner management
+// - prerequisites.rs - Gaming prerequisites (32-bit libs, Vulkan, gamemode)
 
+mod audio;
+mod bluetooth;
+mod diagnostics;
+mod display;
+mod network;
+mod prerequisites;
 mod runners;
 mod system;
 mod updates;
 mod window;
 
 // Re-export all commands
+pub use audio::*;
+pub use bluetooth::*;
+pub use diagnostics::*;
+pub use display::*;
+pub use network::*;
+pub use prerequisites::*;
 pub use runners::*;
 pub use system::*;
 pub use updates::*;
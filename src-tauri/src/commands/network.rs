@@ -0,0 +1,41 @@
+use crate::network::{self, WifiNetwork, WifiStatus};
+
+/// Get the Wi-Fi radio state and current connection
+#[tauri::command]
+pub async fn get_wifi_status() -> Result<WifiStatus, String> {
+    network::get_status().await
+}
+
+/// Turn the Wi-Fi radio on or off
+#[tauri::command]
+pub async fn set_wifi_enabled(enabled: bool) -> Result<(), String> {
+    network::set_enabled(enabled).await
+}
+
+/// Ask the Wi-Fi adapter to rescan for networks
+#[tauri::command]
+pub async fn scan_wifi_networks() -> Result<(), String> {
+    network::request_scan().await
+}
+
+/// List visible Wi-Fi networks with signal strength
+#[tauri::command]
+pub async fn list_wifi_networks() -> Result<Vec<WifiNetwork>, String> {
+    network::list_networks().await
+}
+
+/// Connect to a Wi-Fi network (hidden networks need `hidden: true`)
+#[tauri::command]
+pub async fn connect_wifi_network(
+    ssid: String,
+    password: Option<String>,
+    hidden: Option<bool>,
+) -> Result<(), String> {
+    network::connect(&ssid, password.as_deref(), hidden.unwrap_or(false)).await
+}
+
+/// Forget a saved Wi-Fi network
+#[tauri::command]
+pub async fn forget_wifi_network(ssid: String) -> Result<(), String> {
+    network::forget(&ssid).await
+}
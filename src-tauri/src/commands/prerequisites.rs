@@ -0,0 +1,22 @@
+use crate::prerequisites::{self, Prerequisite, PrerequisiteStatus};
+use crate::system_updates::UpdateReport;
+use crate::update_jobs::UpdateJobs;
+use std::sync::Arc;
+use tauri::{State, Window};
+
+/// Check which gaming prerequisites (32-bit libraries, Vulkan, gamemode) are installed
+#[tauri::command]
+pub fn check_gaming_prerequisites() -> Vec<PrerequisiteStatus> {
+    prerequisites::check_prerequisites()
+}
+
+/// Install missing gaming prerequisites through the distro package manager,
+/// as an update job
+#[tauri::command]
+pub async fn install_gaming_prerequisites(
+    window: Window,
+    jobs: State<'_, Arc<UpdateJobs>>,
+    prerequisites: Vec<Prerequisite>,
+) -> Result<UpdateReport, String> {
+    prerequisites::install_prerequisites(window, &jobs, prerequisites).await
+}
@@ -1,6 +1,19 @@
+use crate::arch_news;
+use crate::fwupd::{self, FirmwareDevice, FirmwareReport};
+use crate::os_release::DistroInfo;
+use crate::ostree::{self, OstreeStatus};
+use crate::package_holds;
+use crate::reboot::RebootStatus;
+use crate::snapshots::{self, Snapshot, SnapshotTool};
+use crate::steamos::{self, SteamOsStatus};
 use crate::sudoers::{self, SudoersStatus};
-use crate::system_updates::{self, Distro, UpdateCheckResult, UpdateReport};
-use tauri::Window;
+use crate::system_updates::{self, Distro, UpdateCheckResult, UpdatePackage, UpdateReport};
+use crate::update_failures::{self, UpdateError};
+use crate::update_history::{UpdateHistory, UpdateRun, UpdateRunDetails};
+use crate::update_jobs::{UpdateJobInfo, UpdateJobs};
+use crate::update_scheduler::{ScheduledCheck, UpdateSchedule, UpdateScheduler};
+use std::sync::Arc;
+use tauri::{State, Window};
 
 /// Get the detected Linux distribution
 #[tauri::command]
@@ -8,6 +21,12 @@ pub fn get_distro() -> Distro {
     system_updates::detect_distro()
 }
 
+/// Get the detected distribution with its os-release details
+#[tauri::command]
+pub fn get_distro_info() -> DistroInfo {
+    system_updates::detect_distro_info()
+}
+
 /// Check if sudoers is configured for Pixxiden updates
 #[tauri::command]
 pub fn is_sudoers_configured() -> Result<SudoersStatus, String> {
@@ -26,16 +45,154 @@ pub async fn check_system_updates() -> Result<UpdateCheckResult, String> {
     system_updates::check_system_updates().await
 }
 
-/// Install system updates
+/// Install system updates, optionally after a filesystem snapshot
+#[tauri::command]
+pub async fn install_system_updates(
+    window: Window,
+    jobs: State<'_, Arc<UpdateJobs>>,
+    history: State<'_, Arc<UpdateHistory>>,
+    packages: Vec<UpdatePackage>,
+    snapshot: bool,
+) -> Result<UpdateReport, String> {
+    system_updates::install_system_updates(window, &jobs, &history, &packages, snapshot).await
+}
+
+/// Get the packages held back from system updates
+#[tauri::command]
+pub fn get_package_holds() -> Vec<String> {
+    package_holds::load()
+}
+
+/// Replace the packages held back from system updates, returning the saved list.
+/// Updates the sudoers rules, hence the password.
+#[tauri::command]
+pub async fn set_package_holds(
+    packages: Vec<String>,
+    password: String,
+) -> Result<Vec<String>, String> {
+    package_holds::save(packages, password).await
+}
+
+/// List past update runs, most recent first
+#[tauri::command]
+pub async fn list_update_history(
+    history: State<'_, Arc<UpdateHistory>>,
+    limit: Option<u32>,
+) -> Result<Vec<UpdateRun>, String> {
+    history.list_runs(limit.unwrap_or(50)).await
+}
+
+/// Get a past update run with its packages and the changes made outside Pixxiden before it
+#[tauri::command]
+pub async fn get_update_run(
+    history: State<'_, Arc<UpdateHistory>>,
+    id: i64,
+) -> Result<UpdateRunDetails, String> {
+    history.run_details(id).await
+}
+
+/// Mark the Arch news published so far as read, lifting the upgrade gate
+#[tauri::command]
+pub fn mark_arch_news_read() -> Result<(), String> {
+    arch_news::mark_read()
+}
+
+/// Get the SteamOS image versions, update channel and read-only state
+#[tauri::command]
+pub async fn get_steamos_status() -> Result<SteamOsStatus, String> {
+    steamos::get_status().await
+}
+
+/// Get the ostree deployments (booted, staged, rollback)
+#[tauri::command]
+pub async fn get_ostree_status() -> Result<OstreeStatus, String> {
+    tauri::async_runtime::spawn_blocking(ostree::get_status)
+        .await
+        .map_err(|e| format!("Status task failed: {}", e))?
+}
+
+/// Boot the previous ostree deployment on the next restart
+#[tauri::command]
+pub async fn rollback_ostree_deployment() -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(ostree::rollback)
+        .await
+        .map_err(|e| format!("Rollback task failed: {}", e))?
+}
+
+/// List devices with a firmware update available through fwupd
+#[tauri::command]
+pub async fn list_firmware_updates() -> Result<Vec<FirmwareDevice>, String> {
+    fwupd::list_updates().await
+}
+
+/// Download and apply a device firmware update, emitting `firmware-progress` events
+#[tauri::command]
+pub async fn install_firmware_update(
+    window: Window,
+    device_id: String,
+) -> Result<FirmwareReport, String> {
+    fwupd::install_update(&window, &device_id).await
+}
+
+/// Get the tool used for pre-update snapshots, if any
+#[tauri::command]
+pub fn get_snapshot_tool() -> Option<SnapshotTool> {
+    snapshots::detect_tool()
+}
+
+/// List recent pre-update snapshots created by Pixxiden
+#[tauri::command]
+pub async fn list_update_snapshots() -> Result<Vec<Snapshot>, String> {
+    tauri::async_runtime::spawn_blocking(snapshots::list_snapshots)
+        .await
+        .map_err(|e| format!("Snapshot task failed: {}", e))?
+}
+
+/// Cancel a running system update (only while downloading)
+#[tauri::command]
+pub fn cancel_system_update(jobs: State<'_, Arc<UpdateJobs>>, id: u64) -> Result<(), String> {
+    jobs.cancel(id)
+}
+
+/// Get the running system update job, if any
+#[tauri::command]
+pub fn get_active_update_job(jobs: State<'_, Arc<UpdateJobs>>) -> Option<UpdateJobInfo> {
+    jobs.active()
+}
+
+/// Check for a stale package manager lock or an interrupted dpkg run
+#[tauri::command]
+pub fn check_interrupted_update() -> Option<UpdateError> {
+    update_failures::detect_interrupted_update()
+}
+
+/// Get the background update check settings
+#[tauri::command]
+pub fn get_update_schedule(scheduler: State<'_, Arc<UpdateScheduler>>) -> UpdateSchedule {
+    scheduler.schedule()
+}
+
+/// Save the background update check settings
+#[tauri::command]
+pub fn set_update_schedule(
+    scheduler: State<'_, Arc<UpdateScheduler>>,
+    schedule: UpdateSchedule,
+) -> Result<(), String> {
+    scheduler.set_schedule(schedule)
+}
+
+/// Get the result of the last background update check, if any
 #[tauri::command]
-pub async fn install_system_updates(window: Window) -> Result<UpdateReport, String> {
-    system_updates::install_system_updates(window).await
+pub fn get_last_update_check(scheduler: State<'_, Arc<UpdateScheduler>>) -> Option<ScheduledCheck> {
+    scheduler.last_check()
 }
 
-/// Check if system requires reboot
+/// Check if system requires reboot, and why
 #[tauri::command]
-pub fn requires_system_reboot() -> bool {
-    system_updates::requires_system_reboot()
+pub async fn requires_system_reboot() -> Result<RebootStatus, String> {
+    tauri::async_runtime::spawn_blocking(system_updates::requires_system_reboot)
+        .await
+        .map_err(|e| format!("Reboot check task failed: {}", e))
 }
 
 /// Reboot the system
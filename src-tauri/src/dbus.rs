@@ -0,0 +1,102 @@
+//! D-Bus helpers shared by the system service integrations (BlueZ, NetworkManager,
+//! fwupd, UPower) and desktop notifications
+//!
+//! Setting `PIXXIDEN_SYSTEM_BUS_ADDRESS` redirects every system bus call to
+//! another bus, e.g. a session bus running python-dbusmock templates, so the
+//! integrations can be exercised without real hardware.
+
+use std::collections::HashMap;
+use zbus::zvariant::OwnedValue;
+use zbus::{Connection, Proxy};
+
+pub use zbus::fdo::ManagedObjects;
+
+/// Properties of one interface, as returned by `GetAll`/`GetManagedObjects`
+pub type Properties = HashMap<String, OwnedValue>;
+
+/// Connect to the system bus (or to the mock bus when overridden)
+pub async fn system_bus() -> Result<Connection, String> {
+    if let Ok(address) = std::env::var("PIXXIDEN_SYSTEM_BUS_ADDRESS") {
+        return zbus::connection::Builder::address(address.as_str())
+            .map_err(|e| format!("Invalid D-Bus address {}: {}", address, e))?
+            .build()
+            .await
+            .map_err(|e| format!("Failed to connect to D-Bus at {}: {}", address, e));
+    }
+
+    Connection::system()
+        .await
+        .map_err(|e| format!("Failed to connect to system D-Bus: {}", e))
+}
+
+/// Connect to the user's session bus
+pub async fn session_bus() -> Result<Connection, String> {
+    Connection::session()
+        .await
+        .map_err(|e| format!("Failed to connect to session D-Bus: {}", e))
+}
+
+/// Create a proxy for an interface of a remote object
+pub async fn proxy<'a>(
+    conn: &Connection,
+    destination: &'a str,
+    path: &'a str,
+    interface: &'a str,
+) -> Result<Proxy<'a>, String> {
+    Proxy::new(conn, destination, path, interface)
+        .await
+        .map_err(|e| format!("Failed to create D-Bus proxy for {}: {}", path, e))
+}
+
+/// Fetch all objects exported by a service implementing `org.freedesktop.DBus.ObjectManager`
+pub async fn managed_objects(
+    conn: &Connection,
+    destination: &str,
+    path: &str,
+) -> Result<ManagedObjects, String> {
+    let manager = proxy(
+        conn,
+        destination,
+        path,
+        "org.freedesktop.DBus.ObjectManager",
+    )
+    .await?;
+    manager
+        .call("GetManagedObjects", &())
+        .await
+        .map_err(|e| format!("Failed to list {} objects: {}", destination, e))
+}
+
+pub fn prop_str(props: &Properties, key: &str) -> Option<String> {
+    props
+        .get(key)
+        .and_then(|v| <&str>::try_from(v).ok())
+        .map(|s| s.to_string())
+}
+
+pub fn prop_bool(props: &Properties, key: &str) -> Option<bool> {
+    props.get(key).and_then(|v| bool::try_from(v).ok())
+}
+
+pub fn prop_u32(props: &Properties, key: &str) -> Option<u32> {
+    props.get(key).and_then(|v| u32::try_from(v).ok())
+}
+
+pub fn prop_u64(props: &Properties, key: &str) -> Option<u64> {
+    props.get(key).and_then(|v| u64::try_from(v).ok())
+}
+
+pub fn prop_str_list(props: &Properties, key: &str) -> Option<Vec<String>> {
+    props
+        .get(key)
+        .and_then(|v| v.try_clone().ok())
+        .and_then(|v| Vec::<String>::try_from(v).ok())
+}
+
+pub fn prop_i16(props: &Properties, key: &str) -> Option<i16> {
+    props.get(key).and_then(|v| i16::try_from(v).ok())
+}
+
+pub fn prop_u8(props: &Properties, key: &str) -> Option<u8> {
+    props.get(key).and_then(|v| u8::try_from(v).ok())
+}
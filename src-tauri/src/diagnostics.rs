@@ -0,0 +1,406 @@
+//! Diagnostics bundle export
+//!
+//! Gathers what we ask for in bug reports (app logs, system info, distro,
+//! sudoers status, runners, recent Proton logs) into a single `.tar.gz`.
+//! Every text file goes through `redact` before being archived.
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tar::{Builder, Header};
+
+use crate::sudoers::{self, SudoersStatus};
+use crate::system::{self, DiskInfo, SystemInfo};
+use crate::system_updates::{self, Distro};
+
+/// Only the end of large logs is kept, that is where the failure is
+const MAX_LOG_BYTES: u64 = 2_000_000;
+/// Number of game logs included, most recent first
+const MAX_GAME_LOGS: usize = 5;
+/// Game logs older than this are ignored
+const GAME_LOG_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Keys whose values are masked in any collected text
+const SECRET_KEYS: &[&str] = &[
+    "access_token",
+    "refresh_token",
+    "client_secret",
+    "api_key",
+    "apikey",
+    "password",
+    "token",
+    "bearer",
+];
+
+const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SystemReport {
+    app_version: String,
+    system: Option<SystemInfo>,
+    disks: Vec<DiskInfo>,
+    distro: Distro,
+    sudoers: Option<SudoersStatus>,
+    gpus: Vec<String>,
+}
+
+/// Mask the value following a secret key starting at `at`, if any.
+/// `lower` is the ASCII-lowercased line so byte offsets match the original.
+fn secret_value(lower: &str, at: usize) -> Option<(usize, usize)> {
+    let bytes = lower.as_bytes();
+    if at > 0 && bytes[at - 1].is_ascii_alphanumeric() {
+        return None;
+    }
+
+    let key = SECRET_KEYS
+        .iter()
+        .find(|key| lower[at..].starts_with(*key))?;
+    let mut i = at + key.len();
+
+    if *key == "bearer" {
+        // "Bearer <token>"
+        if bytes.get(i) != Some(&b' ') {
+            return None;
+        }
+        i += 1;
+    } else {
+        // key=value, key: value, "key": "value"
+        while matches!(bytes.get(i), Some(b'"') | Some(b'\'') | Some(b' ')) {
+            i += 1;
+        }
+        if !matches!(bytes.get(i), Some(b'=') | Some(b':')) {
+            return None;
+        }
+        i += 1;
+        while matches!(bytes.get(i), Some(b'"') | Some(b'\'') | Some(b' ')) {
+            i += 1;
+        }
+    }
+
+    let start = i;
+    while let Some(b) = bytes.get(i) {
+        if b.is_ascii_whitespace() || matches!(b, b'"' | b'\'' | b'&' | b',' | b';' | b'}') {
+            break;
+        }
+        i += 1;
+    }
+
+    (i > start).then_some((start, i))
+}
+
+fn redact_secrets(line: &str) -> String {
+    let lower = line.to_ascii_lowercase();
+    let mut out = String::with_capacity(line.len());
+    let mut i = 0;
+
+    while i < line.len() {
+        if let Some((start, end)) = secret_value(&lower, i) {
+            out.push_str(&line[i..start]);
+            out.push_str(REDACTED);
+            i = end;
+            continue;
+        }
+        let c = line[i..].chars().next().unwrap_or_default();
+        out.push(c);
+        i += c.len_utf8();
+    }
+
+    out
+}
+
+/// Replace `word` only where it is not part of a longer identifier
+fn replace_word(text: &str, word: &str, replacement: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(pos) = rest.find(word) {
+        let before = rest[..pos].chars().next_back();
+        let after = rest[pos + word.len()..].chars().next();
+        let standalone = !before.is_some_and(|c| c.is_alphanumeric())
+            && !after.is_some_and(|c| c.is_alphanumeric());
+
+        out.push_str(&rest[..pos]);
+        out.push_str(if standalone { replacement } else { word });
+        rest = &rest[pos + word.len()..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Replace `path` only where it ends a path component, so `/home/al`
+/// leaves `/home/alice` alone
+fn replace_path(text: &str, path: &str, replacement: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(pos) = rest.find(path) {
+        let after = rest[pos + path.len()..].chars().next();
+        let bounded = matches!(after, None | Some('/' | '"' | '\''))
+            || after.is_some_and(char::is_whitespace);
+
+        out.push_str(&rest[..pos]);
+        out.push_str(if bounded { replacement } else { path });
+        rest = &rest[pos + path.len()..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Strip home directory paths, the username and tokens from collected text
+pub fn redact(text: &str, home: &str, username: &str) -> String {
+    let mut text = text.to_string();
+
+    if home.len() > 1 {
+        text = replace_path(&text, home.trim_end_matches('/'), "~");
+    }
+    if !username.is_empty() {
+        text = replace_word(&text, username, "<user>");
+    }
+
+    text.split('\n')
+        .map(redact_secrets)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Read a text file, keeping only its last `MAX_LOG_BYTES`
+fn read_log_tail(path: &Path) -> Result<String, String> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file =
+        fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    if len > MAX_LOG_BYTES {
+        file.seek(SeekFrom::Start(len - MAX_LOG_BYTES))
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    }
+
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// GPU lines from lspci (VGA / 3D / Display controllers)
+fn gpu_info() -> Vec<String> {
+    let Ok(output) = Command::new("lspci").output() else {
+        return Vec::new();
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| l.contains("VGA") || l.contains("3D controller") || l.contains("Display"))
+        .map(|l| l.to_string())
+        .collect()
+}
+
+/// Names of the runners installed in `runners_dir`
+fn list_runners(runners_dir: &Path) -> Vec<String> {
+    let mut runners: Vec<String> = fs::read_dir(runners_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.path().is_dir())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    runners.sort();
+    runners
+}
+
+/// Recent Proton logs (`PROTON_LOG=1` writes `~/steam-<appid>.log`)
+fn recent_game_logs(home: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(home) else {
+        return Vec::new();
+    };
+    let cutoff = SystemTime::now() - GAME_LOG_MAX_AGE;
+
+    let mut logs: Vec<(SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            name.starts_with("steam-") && name.ends_with(".log")
+        })
+        .filter_map(|e| {
+            let modified = e.metadata().ok()?.modified().ok()?;
+            (modified >= cutoff).then(|| (modified, e.path()))
+        })
+        .collect();
+
+    logs.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    logs.into_iter()
+        .take(MAX_GAME_LOGS)
+        .map(|(_, path)| path)
+        .collect()
+}
+
+fn append_text<W: std::io::Write>(
+    tar: &mut Builder<W>,
+    name: &str,
+    contents: &str,
+) -> Result<(), String> {
+    let mut header = Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+    );
+    header.set_cksum();
+
+    tar.append_data(&mut header, name, contents.as_bytes())
+        .map_err(|e| format!("Failed to add {} to bundle: {}", name, e))
+}
+
+/// Write the diagnostics bundle to `dest` and return its path.
+/// `log_dir` holds the (rotated) app logs, `data_dir` the app data with `runners/`.
+pub fn export_bundle(log_dir: &Path, data_dir: &Path, dest: &Path) -> Result<String, String> {
+    let home = dirs::home_dir().unwrap_or_default();
+    let home_str = home.to_string_lossy().to_string();
+    let username = std::env::var("USER")
+        .or_else(|_| std::env::var("LOGNAME"))
+        .unwrap_or_default();
+    let redact = |text: &str| redact(text, &home_str, &username);
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let file = fs::File::create(dest)
+        .map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+    let mut tar = Builder::new(GzEncoder::new(file, Compression::default()));
+
+    // System report
+    let report = SystemReport {
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        system: system::get_system_info().ok(),
+        disks: system::get_disk_info().unwrap_or_default(),
+        distro: system_updates::detect_distro(),
+        sudoers: sudoers::is_sudoers_configured().ok(),
+        gpus: gpu_info(),
+    };
+    let report = serde_json::to_string_pretty(&report)
+        .map_err(|e| format!("Failed to serialize system report: {}", e))?;
+    append_text(&mut tar, "system.json", &redact(&report))?;
+
+    if let Ok(os_release) = fs::read_to_string("/etc/os-release") {
+        append_text(&mut tar, "os-release", &os_release)?;
+    }
+
+    let runners = list_runners(&data_dir.join("runners"));
+    append_text(&mut tar, "runners.txt", &(runners.join("\n") + "\n"))?;
+
+    // App logs, including the rotated ones
+    if let Ok(entries) = fs::read_dir(log_dir) {
+        for path in entries.flatten().map(|e| e.path()).filter(|p| p.is_file()) {
+            let name = path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            match read_log_tail(&path) {
+                Ok(text) => append_text(&mut tar, &format!("logs/{}", name), &redact(&text))?,
+                Err(e) => log::warn!("Skipping log in diagnostics bundle: {}", e),
+            }
+        }
+    }
+
+    for path in recent_game_logs(&home) {
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        match read_log_tail(&path) {
+            Ok(text) => append_text(&mut tar, &format!("game-logs/{}", name), &redact(&text))?,
+            Err(e) => log::warn!("Skipping game log in diagnostics bundle: {}", e),
+        }
+    }
+
+    tar.into_inner()
+        .and_then(|gz| gz.finish())
+        .map_err(|e| format!("Failed to write diagnostics bundle: {}", e))?;
+
+    log::info!("Diagnostics bundle written to {}", dest.display());
+    Ok(dest.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_home_and_username() {
+        let text = "Loading /home/alice/Games/foo.exe as alice (alice-pc)";
+        assert_eq!(
+            redact(text, "/home/alice", "alice"),
+            "Loading ~/Games/foo.exe as <user> (<user>-pc)"
+        );
+        assert_eq!(
+            redact(
+                "HOME=/home/al cwd=\"/home/al\" /home/alice/x",
+                "/home/al",
+                ""
+            ),
+            "HOME=~ cwd=\"~\" /home/alice/x"
+        );
+    }
+
+    #[test]
+    fn test_redact_username_inside_words_is_kept() {
+        assert_eq!(redact("deckard on deck", "", "deck"), "deckard on <user>");
+    }
+
+    #[test]
+    fn test_redact_tokens() {
+        assert_eq!(
+            redact(r#"{"access_token": "eyJhbGci", "expires": 3600}"#, "", ""),
+            r#"{"access_token": "<redacted>", "expires": 3600}"#
+        );
+        assert_eq!(
+            redact("GET /api?token=abc123&page=2", "", ""),
+            "GET /api?token=<redacted>&page=2"
+        );
+        assert_eq!(
+            redact("Authorization: Bearer xyz.abc", "", ""),
+            "Authorization: Bearer <redacted>"
+        );
+        assert_eq!(redact("tokens loaded: 3", "", ""), "tokens loaded: 3");
+    }
+
+    #[test]
+    fn test_export_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_dir = dir.path().join("logs");
+        fs::create_dir_all(&log_dir).unwrap();
+        fs::write(log_dir.join("pixxiden.log"), "refresh_token=secret\n").unwrap();
+        fs::create_dir_all(dir.path().join("data/runners/GE-Proton9-20")).unwrap();
+
+        let dest = dir.path().join("out/bundle.tar.gz");
+        export_bundle(&log_dir, &dir.path().join("data"), &dest).unwrap();
+
+        let mut archive =
+            tar::Archive::new(flate2::read::GzDecoder::new(fs::File::open(&dest).unwrap()));
+        let mut files = std::collections::HashMap::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().to_string_lossy().to_string();
+            let mut contents = String::new();
+            std::io::Read::read_to_string(&mut entry, &mut contents).unwrap();
+            files.insert(name, contents);
+        }
+
+        assert!(files.contains_key("system.json"));
+        assert_eq!(files["runners.txt"], "GE-Proton9-20\n");
+        assert_eq!(files["logs/pixxiden.log"], "refresh_token=<redacted>\n");
+    }
+}
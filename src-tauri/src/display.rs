@@ -0,0 +1,914 @@
+//! Display output and mode management
+//!
+//! Each compositor exposes output configuration through its own tool, so the
+//! backend is picked from the current session: `kscreen-doctor` on KDE Plasma,
+//! `gnome-monitor-config` on GNOME, `wlr-randr` on wlroots compositors and
+//! `xrandr` on X11. Mode changes are reverted automatically unless the user
+//! confirms them in time.
+
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Seconds the user has to confirm a display change before it is reverted
+pub const REVERT_TIMEOUT_SECS: u64 = 15;
+
+/// Tool used to query and configure outputs
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DisplayBackend {
+    KscreenDoctor,      // KDE Plasma (Wayland and X11)
+    GnomeMonitorConfig, // GNOME (Mutter)
+    WlrRandr,           // wlroots compositors (Sway, Hyprland, ...)
+    Xrandr,             // Any X11 session
+}
+
+impl DisplayBackend {
+    fn binary(&self) -> &'static str {
+        match self {
+            DisplayBackend::KscreenDoctor => "kscreen-doctor",
+            DisplayBackend::GnomeMonitorConfig => "gnome-monitor-config",
+            DisplayBackend::WlrRandr => "wlr-randr",
+            DisplayBackend::Xrandr => "xrandr",
+        }
+    }
+}
+
+/// A display mode supported by an output
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayMode {
+    pub id: String, // Backend-specific identifier, passed back when applying
+    pub width: u32,
+    pub height: u32,
+    pub refresh_rate: f64,
+    pub preferred: bool,
+    pub current: bool,
+}
+
+/// A physical display output
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayOutput {
+    pub name: String,
+    pub enabled: bool,
+    pub primary: bool,
+    pub scale: f64,
+    pub modes: Vec<DisplayMode>,
+    #[serde(skip)]
+    pub position: Option<(i32, i32)>, // Logical monitor position (GNOME only)
+}
+
+impl DisplayOutput {
+    pub fn current_mode(&self) -> Option<&DisplayMode> {
+        self.modes.iter().find(|m| m.current)
+    }
+}
+
+/// Requested configuration for one output (unset fields are left unchanged)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayChange {
+    pub output: String,
+    pub mode_id: Option<String>,
+    pub scale: Option<f64>,
+    pub enabled: Option<bool>,
+    pub primary: Option<bool>,
+    #[serde(skip)]
+    pub position: Option<(i32, i32)>, // Kept when GNOME rebuilds the layout
+}
+
+/// A display change waiting for confirmation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingDisplayChange {
+    pub id: u64,
+    pub revert_in_seconds: u64,
+}
+
+/// Pick the display backend for the current session
+pub fn detect_backend() -> Option<DisplayBackend> {
+    let session = std::env::var("XDG_SESSION_TYPE").unwrap_or_default();
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    let candidate = backend_for_session(&session, &desktop);
+
+    if which::which(candidate.binary()).is_ok() {
+        return Some(candidate);
+    }
+
+    // The preferred tool is missing, fall back to the generic one for the session
+    let fallback = if session == "wayland" {
+        DisplayBackend::WlrRandr
+    } else {
+        DisplayBackend::Xrandr
+    };
+    which::which(fallback.binary()).ok().map(|_| fallback)
+}
+
+fn backend_for_session(session: &str, desktop: &str) -> DisplayBackend {
+    let desktop = desktop.to_lowercase();
+    if desktop.contains("kde") {
+        DisplayBackend::KscreenDoctor
+    } else if desktop.contains("gnome") {
+        DisplayBackend::GnomeMonitorConfig
+    } else if session == "wayland" {
+        DisplayBackend::WlrRandr
+    } else {
+        DisplayBackend::Xrandr
+    }
+}
+
+fn run(binary: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(binary)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", binary, e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            binary,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Parse a "1920x1080" resolution
+fn parse_resolution(s: &str) -> Option<(u32, u32)> {
+    let (w, h) = s.split_once('x')?;
+    Some((w.trim().parse().ok()?, h.trim().parse().ok()?))
+}
+
+/// Parse `xrandr --query`
+fn parse_xrandr(output: &str) -> Vec<DisplayOutput> {
+    let mut outputs: Vec<DisplayOutput> = Vec::new();
+
+    for line in output.lines() {
+        if !line.starts_with(' ') {
+            // Output header: "HDMI-1 connected primary 1920x1080+0+0 (normal ...) 527mm x 296mm"
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 2 || parts[1] != "connected" {
+                continue;
+            }
+            outputs.push(DisplayOutput {
+                name: parts[0].to_string(),
+                // A geometry right after "connected [primary]" means the output is active
+                enabled: parts
+                    .iter()
+                    .skip(2)
+                    .take(2)
+                    .any(|p| p.contains('+') && p.contains('x')),
+                primary: parts.get(2) == Some(&"primary"),
+                scale: 1.0,
+                modes: Vec::new(),
+                position: None,
+            });
+            continue;
+        }
+
+        // Mode line: "   1920x1080     60.00*+  50.00    59.94"
+        let Some(current) = outputs.last_mut() else {
+            continue;
+        };
+        let mut parts = line.split_whitespace();
+        let Some((width, height)) = parts.next().and_then(parse_resolution) else {
+            continue;
+        };
+
+        for rate in parts {
+            let current_mode = rate.contains('*');
+            let preferred = rate.contains('+');
+            let value = rate.trim_end_matches(['*', '+']);
+            if value.is_empty() {
+                // xrandr prints a lone "+" when the preferred flag follows a space
+                if let Some(last) = current.modes.last_mut() {
+                    last.preferred = true;
+                }
+                continue;
+            }
+            let Ok(refresh_rate) = value.parse::<f64>() else {
+                continue;
+            };
+            current.modes.push(DisplayMode {
+                id: format!("{}x{}@{}", width, height, value),
+                width,
+                height,
+                refresh_rate,
+                preferred,
+                current: current_mode,
+            });
+        }
+    }
+
+    outputs
+}
+
+/// Parse `wlr-randr --json`
+fn parse_wlr_randr(json: &str) -> Vec<DisplayOutput> {
+    let Ok(serde_json::Value::Array(items)) = serde_json::from_str(json) else {
+        return vec![];
+    };
+
+    items
+        .iter()
+        .filter_map(|item| {
+            let modes = item
+                .get("modes")
+                .and_then(|m| m.as_array())
+                .map(|modes| {
+                    modes
+                        .iter()
+                        .filter_map(|m| {
+                            let width = m.get("width")?.as_u64()? as u32;
+                            let height = m.get("height")?.as_u64()? as u32;
+                            let refresh_rate = m.get("refresh")?.as_f64()?;
+                            Some(DisplayMode {
+                                id: format!("{}x{}@{:.3}Hz", width, height, refresh_rate),
+                                width,
+                                height,
+                                refresh_rate,
+                                preferred: m.get("preferred").and_then(|v| v.as_bool())
+                                    == Some(true),
+                                current: m.get("current").and_then(|v| v.as_bool()) == Some(true),
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            Some(DisplayOutput {
+                name: item.get("name")?.as_str()?.to_string(),
+                enabled: item
+                    .get("enabled")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false),
+                primary: false, // wlroots has no notion of a primary output
+                scale: item.get("scale").and_then(|v| v.as_f64()).unwrap_or(1.0),
+                modes,
+                position: None,
+            })
+        })
+        .collect()
+}
+
+/// Parse `kscreen-doctor --json`
+fn parse_kscreen(json: &str) -> Vec<DisplayOutput> {
+    let Ok(root) = serde_json::from_str::<serde_json::Value>(json) else {
+        return vec![];
+    };
+    let Some(items) = root.get("outputs").and_then(|o| o.as_array()) else {
+        return vec![];
+    };
+
+    items
+        .iter()
+        .filter(|item| item.get("connected").and_then(|v| v.as_bool()) == Some(true))
+        .filter_map(|item| {
+            let current_id = item.get("currentModeId").and_then(|v| v.as_str());
+            let preferred: Vec<&str> = item
+                .get("preferredModes")
+                .and_then(|v| v.as_array())
+                .map(|p| p.iter().filter_map(|v| v.as_str()).collect())
+                .unwrap_or_default();
+
+            let modes = item
+                .get("modes")
+                .and_then(|m| m.as_array())
+                .map(|modes| {
+                    modes
+                        .iter()
+                        .filter_map(|m| {
+                            let id = m.get("id")?.as_str()?;
+                            let size = m.get("size")?;
+                            Some(DisplayMode {
+                                id: id.to_string(),
+                                width: size.get("width")?.as_u64()? as u32,
+                                height: size.get("height")?.as_u64()? as u32,
+                                refresh_rate: m.get("refreshRate")?.as_f64()?,
+                                preferred: preferred.contains(&id),
+                                current: current_id == Some(id),
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            Some(DisplayOutput {
+                name: item.get("name")?.as_str()?.to_string(),
+                enabled: item
+                    .get("enabled")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false),
+                // Plasma 6 orders outputs by priority, 1 being the primary one
+                primary: item.get("priority").and_then(|v| v.as_u64()) == Some(1)
+                    || item.get("primary").and_then(|v| v.as_bool()) == Some(true),
+                scale: item.get("scale").and_then(|v| v.as_f64()).unwrap_or(1.0),
+                modes,
+                position: None,
+            })
+        })
+        .collect()
+}
+
+/// Parse `gnome-monitor-config list`
+fn parse_gnome_monitor_config(output: &str) -> Vec<DisplayOutput> {
+    let mut outputs: Vec<DisplayOutput> = Vec::new();
+    let mut logical_scale: Option<f64> = None;
+    let mut logical_primary = false;
+    let mut logical_position: Option<(i32, i32)> = None;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+
+        // "Monitor [ DP-1 ] ON"
+        if let Some(rest) = trimmed.strip_prefix("Monitor [") {
+            if let Some((name, state)) = rest.split_once(']') {
+                outputs.push(DisplayOutput {
+                    name: name.trim().to_string(),
+                    enabled: state.trim() == "ON",
+                    primary: false,
+                    scale: 1.0,
+                    modes: Vec::new(),
+                    position: None,
+                });
+            }
+            continue;
+        }
+
+        // Logical monitor block: "x: 0, y: 0, scale: 1.5, rotation: normal, primary: yes"
+        if trimmed.starts_with("x:") && trimmed.contains("scale:") {
+            let field = |name: &str| {
+                trimmed
+                    .split(',')
+                    .find_map(|f| f.trim().strip_prefix(name))
+                    .map(str::trim)
+            };
+            logical_scale = field("scale:").and_then(|s| s.parse().ok());
+            logical_position = field("x:")
+                .and_then(|x| x.parse().ok())
+                .zip(field("y:").and_then(|y| y.parse().ok()));
+            logical_primary = trimmed.contains("primary: yes");
+            continue;
+        }
+
+        // "monitors: DP-1, HDMI-1" closes a logical monitor block
+        if let Some(names) = trimmed.strip_prefix("monitors:") {
+            for name in names.split(',').map(|n| n.trim()) {
+                if let Some(output) = outputs.iter_mut().find(|o| o.name == name) {
+                    output.scale = logical_scale.unwrap_or(1.0);
+                    output.primary = logical_primary;
+                    output.position = logical_position;
+                }
+            }
+            continue;
+        }
+
+        // "1920x1080@60.000 [id: '1920x1080@60.000'] [preferred scale = 1 (1, 2)] CURRENT PREFERRED"
+        let Some(current) = outputs.last_mut() else {
+            continue;
+        };
+        let Some((mode, rest)) = trimmed.split_once(' ') else {
+            continue;
+        };
+        let Some((resolution, refresh)) = mode.split_once('@') else {
+            continue;
+        };
+        let (Some((width, height)), Ok(refresh_rate)) =
+            (parse_resolution(resolution), refresh.parse::<f64>())
+        else {
+            continue;
+        };
+        let id = rest
+            .split_once("[id: '")
+            .and_then(|(_, r)| r.split_once('\''))
+            .map(|(id, _)| id.to_string())
+            .unwrap_or_else(|| mode.to_string());
+
+        current.modes.push(DisplayMode {
+            id,
+            width,
+            height,
+            refresh_rate,
+            preferred: rest.contains("PREFERRED"),
+            current: rest.contains("CURRENT"),
+        });
+    }
+
+    outputs
+}
+
+/// List the connected outputs and their modes
+pub fn list_outputs() -> Result<Vec<DisplayOutput>, String> {
+    let backend = detect_backend().ok_or("No supported display configuration tool found")?;
+    list_outputs_with(backend)
+}
+
+fn list_outputs_with(backend: DisplayBackend) -> Result<Vec<DisplayOutput>, String> {
+    let outputs = match backend {
+        DisplayBackend::KscreenDoctor => parse_kscreen(&run(backend.binary(), &["--json"])?),
+        DisplayBackend::GnomeMonitorConfig => {
+            parse_gnome_monitor_config(&run(backend.binary(), &["list"])?)
+        }
+        DisplayBackend::WlrRandr => parse_wlr_randr(&run(backend.binary(), &["--json"])?),
+        DisplayBackend::Xrandr => parse_xrandr(&run(backend.binary(), &["--query"])?),
+    };
+    Ok(outputs)
+}
+
+/// Build the arguments configuring one output with a given backend. For GNOME
+/// this is one logical monitor of a `set` command; disabled outputs are left out.
+fn change_args(backend: DisplayBackend, change: &DisplayChange) -> Result<Vec<String>, String> {
+    let output = &change.output;
+    let mut args: Vec<String> = Vec::new();
+
+    match backend {
+        DisplayBackend::KscreenDoctor => {
+            if let Some(enabled) = change.enabled {
+                let state = if enabled { "enable" } else { "disable" };
+                args.push(format!("output.{}.{}", output, state));
+            }
+            if let Some(mode) = &change.mode_id {
+                args.push(format!("output.{}.mode.{}", output, mode));
+            }
+            if let Some(scale) = change.scale {
+                args.push(format!("output.{}.scale.{}", output, scale));
+            }
+            if change.primary == Some(true) {
+                args.push(format!("output.{}.primary", output));
+            }
+        }
+        DisplayBackend::GnomeMonitorConfig => {
+            if change.enabled == Some(false) {
+                return Ok(args);
+            }
+            args.push("-L".to_string());
+            if change.primary == Some(true) {
+                args.push("-p".to_string());
+            }
+            if let Some((x, y)) = change.position {
+                args.extend([
+                    "-x".to_string(),
+                    x.to_string(),
+                    "-y".to_string(),
+                    y.to_string(),
+                ]);
+            }
+            args.extend(["-M".to_string(), output.clone()]);
+            if let Some(mode) = &change.mode_id {
+                args.extend(["-m".to_string(), mode.clone()]);
+            }
+            if let Some(scale) = change.scale {
+                args.extend(["-s".to_string(), scale.to_string()]);
+            }
+        }
+        DisplayBackend::WlrRandr => {
+            args.extend(["--output".to_string(), output.clone()]);
+            match change.enabled {
+                Some(false) => args.push("--off".to_string()),
+                Some(true) => args.push("--on".to_string()),
+                None => {}
+            }
+            if let Some(mode) = &change.mode_id {
+                args.extend(["--mode".to_string(), mode.clone()]);
+            }
+            if let Some(scale) = change.scale {
+                args.extend(["--scale".to_string(), scale.to_string()]);
+            }
+        }
+        DisplayBackend::Xrandr => {
+            if change.scale.is_some_and(|s| (s - 1.0).abs() > f64::EPSILON) {
+                return Err("Scaling is not supported on X11".to_string());
+            }
+            args.extend(["--output".to_string(), output.clone()]);
+            if change.enabled == Some(false) {
+                args.push("--off".to_string());
+            } else if let Some(mode) = &change.mode_id {
+                let (resolution, rate) = mode.split_once('@').unwrap_or((mode.as_str(), ""));
+                args.extend(["--mode".to_string(), resolution.to_string()]);
+                if !rate.is_empty() {
+                    args.extend(["--rate".to_string(), rate.to_string()]);
+                }
+            } else if change.enabled == Some(true) {
+                args.push("--auto".to_string());
+            }
+            if change.primary == Some(true) {
+                args.push("--primary".to_string());
+            }
+        }
+    }
+
+    Ok(args)
+}
+
+/// Build the command line applying changes to several outputs at once
+fn layout_args(backend: DisplayBackend, changes: &[DisplayChange]) -> Result<Vec<String>, String> {
+    let mut args: Vec<String> = Vec::new();
+    for change in changes {
+        args.extend(change_args(backend, change)?);
+    }
+    if backend == DisplayBackend::GnomeMonitorConfig {
+        // `set` replaces the whole layout, outputs left out are turned off
+        if args.is_empty() {
+            return Err("At least one output must stay enabled".to_string());
+        }
+        args.insert(0, "set".to_string());
+    }
+    Ok(args)
+}
+
+fn apply_layout_with(backend: DisplayBackend, changes: &[DisplayChange]) -> Result<(), String> {
+    let args = layout_args(backend, changes)?;
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+    run(backend.binary(), &args)?;
+    log::info!(
+        "Display change applied with {}: {:?}",
+        backend.binary(),
+        changes
+    );
+    Ok(())
+}
+
+/// The changes restoring every output to its current state, primary included
+fn snapshot(outputs: &[DisplayOutput]) -> Vec<DisplayChange> {
+    outputs
+        .iter()
+        .map(|output| DisplayChange {
+            output: output.name.clone(),
+            mode_id: output.current_mode().map(|m| m.id.clone()),
+            scale: Some(output.scale),
+            enabled: Some(output.enabled),
+            primary: Some(output.primary),
+            position: output.position,
+        })
+        .collect()
+}
+
+/// The current layout with a change on top, for tools replacing the whole layout
+fn merged_layout(
+    outputs: &[DisplayOutput],
+    change: &DisplayChange,
+) -> Result<Vec<DisplayChange>, String> {
+    let mut layout = snapshot(outputs);
+    let target = layout
+        .iter_mut()
+        .find(|c| c.output == change.output)
+        .ok_or_else(|| format!("Output not found: {}", change.output))?;
+    target.mode_id = change.mode_id.clone().or(target.mode_id.take());
+    target.scale = change.scale.or(target.scale);
+    target.enabled = change.enabled.or(target.enabled);
+    target.primary = change.primary.or(target.primary);
+
+    if change.primary == Some(true) {
+        for other in layout.iter_mut().filter(|c| c.output != change.output) {
+            other.primary = Some(false);
+        }
+    }
+    Ok(layout)
+}
+
+struct PendingRevert {
+    id: u64,
+    backend: DisplayBackend,
+    previous: Vec<DisplayChange>,
+}
+
+/// Display manager holding the change awaiting confirmation
+pub struct DisplayManager {
+    pending: Arc<Mutex<Option<PendingRevert>>>,
+    next_id: Mutex<u64>,
+}
+
+impl DisplayManager {
+    pub fn new() -> Self {
+        Self {
+            pending: Arc::new(Mutex::new(None)),
+            next_id: Mutex::new(1),
+        }
+    }
+
+    /// Apply a change and schedule its revert unless confirmed in time
+    pub fn apply(
+        &self,
+        app_handle: AppHandle,
+        change: DisplayChange,
+    ) -> Result<PendingDisplayChange, String> {
+        let backend = detect_backend().ok_or("No supported display configuration tool found")?;
+
+        // A change still pending is reverted first, so that the new revert
+        // goes back to the last confirmed layout, not to the unconfirmed one
+        if let Some(stale) = self.pending.lock().unwrap().take() {
+            if let Err(e) = apply_layout_with(stale.backend, &stale.previous) {
+                log::error!("Failed to revert pending display change: {}", e);
+            }
+        }
+
+        let outputs = list_outputs_with(backend)?;
+        if !outputs.iter().any(|o| o.name == change.output) {
+            return Err(format!("Output not found: {}", change.output));
+        }
+        let previous = snapshot(&outputs);
+
+        let changes = if backend == DisplayBackend::GnomeMonitorConfig {
+            merged_layout(&outputs, &change)?
+        } else {
+            vec![change]
+        };
+        apply_layout_with(backend, &changes)?;
+
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        *self.pending.lock().unwrap() = Some(PendingRevert {
+            id,
+            backend,
+            previous,
+        });
+
+        let pending = self.pending.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(REVERT_TIMEOUT_SECS)).await;
+
+            let revert = {
+                let mut pending = pending.lock().unwrap();
+                match pending.as_ref() {
+                    Some(p) if p.id == id => pending.take(),
+                    _ => None,
+                }
+            };
+
+            if let Some(revert) = revert {
+                log::info!("Display change {} not confirmed, reverting", id);
+                if let Err(e) = apply_layout_with(revert.backend, &revert.previous) {
+                    log::error!("Failed to revert display change: {}", e);
+                }
+                let _ = app_handle.emit("display-reverted", id);
+            }
+        });
+
+        Ok(PendingDisplayChange {
+            id,
+            revert_in_seconds: REVERT_TIMEOUT_SECS,
+        })
+    }
+
+    /// Keep a pending change
+    pub fn confirm(&self, id: u64) -> Result<(), String> {
+        let mut pending = self.pending.lock().unwrap();
+        match pending.as_ref() {
+            Some(p) if p.id == id => {
+                pending.take();
+                log::info!("Display change {} confirmed", id);
+                Ok(())
+            }
+            _ => Err("No pending display change to confirm (already reverted?)".to_string()),
+        }
+    }
+
+    /// Revert a pending change immediately
+    pub fn revert(&self, id: u64) -> Result<(), String> {
+        let revert = {
+            let mut pending = self.pending.lock().unwrap();
+            match pending.as_ref() {
+                Some(p) if p.id == id => pending.take(),
+                _ => None,
+            }
+        };
+
+        let revert = revert.ok_or("No pending display change to revert")?;
+        apply_layout_with(revert.backend, &revert.previous)
+    }
+}
+
+impl Default for DisplayManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_for_session() {
+        assert_eq!(
+            backend_for_session("wayland", "KDE"),
+            DisplayBackend::KscreenDoctor
+        );
+        assert_eq!(
+            backend_for_session("wayland", "ubuntu:GNOME"),
+            DisplayBackend::GnomeMonitorConfig
+        );
+        assert_eq!(
+            backend_for_session("wayland", "Hyprland"),
+            DisplayBackend::WlrRandr
+        );
+        assert_eq!(backend_for_session("x11", "XFCE"), DisplayBackend::Xrandr);
+    }
+
+    #[test]
+    fn test_parse_xrandr() {
+        let output = "\
+Screen 0: minimum 320 x 200, current 1920 x 1080, maximum 16384 x 16384
+HDMI-1 connected primary 1920x1080+0+0 (normal left inverted right x axis y axis) 527mm x 296mm
+   1920x1080     60.00*+  50.00    59.94
+   1280x720      60.00    50.00
+DP-1 disconnected (normal left inverted right x axis y axis)
+DP-2 connected (normal left inverted right x axis y axis)
+   3840x2160     60.00 +  30.00
+";
+        let outputs = parse_xrandr(output);
+        assert_eq!(outputs.len(), 2);
+
+        let hdmi = &outputs[0];
+        assert!(hdmi.enabled && hdmi.primary);
+        assert_eq!(hdmi.modes.len(), 5);
+        let current = hdmi.current_mode().unwrap();
+        assert_eq!(current.id, "1920x1080@60.00");
+        assert!(current.preferred);
+
+        let dp = &outputs[1];
+        assert!(!dp.enabled && !dp.primary);
+        assert!(dp.modes[0].preferred);
+        assert!(!dp.modes[1].preferred);
+    }
+
+    #[test]
+    fn test_parse_wlr_randr() {
+        let json = r#"[{"name": "DP-1", "enabled": true, "scale": 1.5,
+            "modes": [
+                {"width": 2560, "height": 1440, "refresh": 143.998, "preferred": true, "current": true},
+                {"width": 1920, "height": 1080, "refresh": 60.0, "preferred": false, "current": false}
+            ]}]"#;
+        let outputs = parse_wlr_randr(json);
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].scale, 1.5);
+        assert_eq!(outputs[0].current_mode().unwrap().id, "2560x1440@143.998Hz");
+    }
+
+    #[test]
+    fn test_parse_kscreen() {
+        let json = r#"{"outputs": [
+            {"name": "HDMI-A-1", "connected": true, "enabled": true, "priority": 1, "scale": 2,
+             "currentModeId": "2", "preferredModes": ["1"],
+             "modes": [
+                {"id": "1", "name": "3840x2160@60", "refreshRate": 60.0, "size": {"width": 3840, "height": 2160}},
+                {"id": "2", "name": "1920x1080@120", "refreshRate": 119.88, "size": {"width": 1920, "height": 1080}}
+             ]},
+            {"name": "DP-2", "connected": false, "enabled": false, "modes": []}
+        ]}"#;
+        let outputs = parse_kscreen(json);
+        assert_eq!(outputs.len(), 1);
+        assert!(outputs[0].primary);
+        assert_eq!(outputs[0].scale, 2.0);
+        assert_eq!(outputs[0].current_mode().unwrap().width, 1920);
+        assert!(outputs[0].modes[0].preferred);
+    }
+
+    #[test]
+    fn test_parse_gnome_monitor_config() {
+        let output = "\
+Monitor [ HDMI-1 ] ON
+  display-name: LG TV
+  3840x2160@60.000 [id: '3840x2160@60.000'] [preferred scale = 2 (1, 2)] PREFERRED
+  1920x1080@60.000 [id: '1920x1080@60.000'] [preferred scale = 1 (1, 2)] CURRENT
+Logical monitor #0:
+  x: 0, y: 0, scale: 1, rotation: normal, primary: yes
+  monitors: HDMI-1
+";
+        let outputs = parse_gnome_monitor_config(output);
+        assert_eq!(outputs.len(), 1);
+        assert!(outputs[0].enabled && outputs[0].primary);
+        assert_eq!(outputs[0].modes.len(), 2);
+        assert_eq!(outputs[0].current_mode().unwrap().id, "1920x1080@60.000");
+        assert!(outputs[0].modes[0].preferred);
+    }
+
+    #[test]
+    fn test_change_args() {
+        let change = DisplayChange {
+            output: "HDMI-1".to_string(),
+            mode_id: Some("1920x1080@60.00".to_string()),
+            scale: None,
+            enabled: None,
+            primary: Some(true),
+            position: None,
+        };
+        assert_eq!(
+            change_args(DisplayBackend::Xrandr, &change).unwrap(),
+            vec![
+                "--output",
+                "HDMI-1",
+                "--mode",
+                "1920x1080",
+                "--rate",
+                "60.00",
+                "--primary"
+            ]
+        );
+        assert_eq!(
+            change_args(DisplayBackend::KscreenDoctor, &change).unwrap(),
+            vec![
+                "output.HDMI-1.mode.1920x1080@60.00",
+                "output.HDMI-1.primary"
+            ]
+        );
+
+        let scaled = DisplayChange {
+            scale: Some(2.0),
+            ..change
+        };
+        assert!(change_args(DisplayBackend::Xrandr, &scaled).is_err());
+        assert_eq!(
+            change_args(DisplayBackend::WlrRandr, &scaled).unwrap(),
+            vec![
+                "--output",
+                "HDMI-1",
+                "--mode",
+                "1920x1080@60.00",
+                "--scale",
+                "2"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_revert_layout() {
+        let output = "\
+Monitor [ DP-1 ] ON
+  2560x1440@143.998 [id: '2560x1440@143.998'] [preferred scale = 1 (1, 2)] CURRENT PREFERRED
+Monitor [ HDMI-1 ] ON
+  3840x2160@60.000 [id: '3840x2160@60.000'] [preferred scale = 2 (1, 2)] PREFERRED
+  1920x1080@60.000 [id: '1920x1080@60.000'] [preferred scale = 1 (1, 2)] CURRENT
+Logical monitor #0:
+  x: 0, y: 0, scale: 1, rotation: normal, primary: yes
+  monitors: DP-1
+Logical monitor #1:
+  x: 2560, y: 0, scale: 1, rotation: normal, primary: no
+  monitors: HDMI-1
+";
+        let outputs = parse_gnome_monitor_config(output);
+        assert_eq!(outputs[1].position, Some((2560, 0)));
+
+        // Making HDMI-1 primary keeps DP-1 enabled where it is
+        let change = DisplayChange {
+            output: "HDMI-1".to_string(),
+            mode_id: Some("3840x2160@60.000".to_string()),
+            scale: None,
+            enabled: None,
+            primary: Some(true),
+            position: None,
+        };
+        let layout = merged_layout(&outputs, &change).unwrap();
+        assert_eq!(
+            layout_args(DisplayBackend::GnomeMonitorConfig, &layout).unwrap(),
+            vec![
+                "set",
+                "-L",
+                "-x",
+                "0",
+                "-y",
+                "0",
+                "-M",
+                "DP-1",
+                "-m",
+                "2560x1440@143.998",
+                "-s",
+                "1",
+                "-L",
+                "-p",
+                "-x",
+                "2560",
+                "-y",
+                "0",
+                "-M",
+                "HDMI-1",
+                "-m",
+                "3840x2160@60.000",
+                "-s",
+                "1"
+            ]
+        );
+
+        // The revert hands primary back to DP-1
+        let previous = snapshot(&outputs);
+        assert_eq!(previous[0].primary, Some(true));
+        assert_eq!(previous[1].primary, Some(false));
+        let args = layout_args(DisplayBackend::KscreenDoctor, &previous).unwrap();
+        assert!(args.contains(&"output.DP-1.primary".to_string()));
+        assert!(!args.contains(&"output.HDMI-1.primary".to_string()));
+
+        let off = DisplayChange {
+            enabled: Some(false),
+            ..previous[0].clone()
+        };
+        assert!(layout_args(DisplayBackend::GnomeMonitorConfig, &[off]).is_err());
+    }
+}
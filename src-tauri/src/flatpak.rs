@@ -0,0 +1,358 @@
+//! Flatpak application and runtime updates
+//!
+//! Pending updates are listed for both the user and the system installation.
+//! The user installation is updated without root, the system one through the
+//! sudoers-whitelisted `flatpak update --system`.
+
+use std::collections::HashMap;
+use std::process::Command;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command as TokioCommand;
+
+use crate::system_updates::{
+    categorize_package, parse_size, PackageCategory, Status, UpdatePackage, UpdateProgressEvent,
+    UpdateSource,
+};
+use crate::update_jobs::UpdateJob;
+
+/// Flatpak installation scope
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Installation {
+    User,
+    System,
+}
+
+impl Installation {
+    const ALL: [Installation; 2] = [Installation::User, Installation::System];
+
+    fn flag(self) -> &'static str {
+        match self {
+            Installation::User => "--user",
+            Installation::System => "--system",
+        }
+    }
+}
+
+/// Whether flatpak is installed at all
+pub fn is_available() -> bool {
+    which::which("flatpak").is_ok()
+}
+
+/// GL runtime extensions track the host graphics driver
+fn is_gl_extension(application: &str) -> bool {
+    application.starts_with("org.freedesktop.Platform.GL.")
+        || application.starts_with("org.freedesktop.Platform.GL32.")
+}
+
+/// Update source of an application or runtime, GL extensions apart
+pub fn update_source(application: &str) -> UpdateSource {
+    if is_gl_extension(application) {
+        UpdateSource::FlatpakGl
+    } else {
+        UpdateSource::Flatpak
+    }
+}
+
+/// Parse tab-separated `application version branch download-size` rows
+fn parse_remote_updates(stdout: &str) -> Vec<(String, String, String, u64)> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let mut cols = line.split('\t');
+            let application = cols.next()?.trim();
+            if application.is_empty() {
+                return None;
+            }
+            let version = cols.next().unwrap_or("").trim();
+            let branch = cols.next().unwrap_or("").trim();
+            let size = parse_size(cols.next().unwrap_or(""));
+            Some((
+                application.to_string(),
+                version.to_string(),
+                branch.to_string(),
+                size,
+            ))
+        })
+        .collect()
+}
+
+/// Installed versions keyed by `application/branch`
+fn installed_versions(installation: Installation) -> HashMap<String, String> {
+    let Ok(output) = Command::new("flatpak")
+        .args([
+            "list",
+            installation.flag(),
+            "--columns=application,branch,version",
+        ])
+        .output()
+    else {
+        return HashMap::new();
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut cols = line.split('\t');
+            let application = cols.next()?.trim();
+            let branch = cols.next()?.trim();
+            let version = cols.next().unwrap_or("").trim();
+            Some((format!("{}/{}", application, branch), version.to_string()))
+        })
+        .collect()
+}
+
+fn remote_updates(
+    installation: Installation,
+    apps_only: bool,
+) -> Result<Vec<(String, String, String, u64)>, String> {
+    let mut cmd = Command::new("flatpak");
+    cmd.args([
+        "remote-ls",
+        "--updates",
+        installation.flag(),
+        "--columns=application,version,branch,download-size",
+    ]);
+    if apps_only {
+        cmd.arg("--app");
+    }
+
+    let output = cmd
+        .output()
+        .map_err(|e| format!("Failed to run flatpak remote-ls: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "flatpak remote-ls failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(parse_remote_updates(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// List pending Flatpak updates of the user and system installations
+pub fn check_updates() -> Result<Vec<UpdatePackage>, String> {
+    let mut packages: Vec<UpdatePackage> = Vec::new();
+
+    for installation in Installation::ALL {
+        let updates = remote_updates(installation, false)?;
+        if updates.is_empty() {
+            continue;
+        }
+        let apps: Vec<String> = remote_updates(installation, true)?
+            .into_iter()
+            .map(|(application, ..)| application)
+            .collect();
+        let installed = installed_versions(installation);
+
+        for (application, version, branch, size) in updates {
+            if packages.iter().any(|p| p.name == application) {
+                continue;
+            }
+
+            let source = update_source(&application);
+            let category = if source == UpdateSource::FlatpakGl {
+                PackageCategory::Graphics
+            } else if apps.contains(&application) {
+                categorize_package(&application)
+            } else {
+                PackageCategory::Library // Runtime
+            };
+
+            packages.push(UpdatePackage {
+                current_version: installed
+                    .get(&format!("{}/{}", application, branch))
+                    .filter(|v| !v.is_empty())
+                    .cloned()
+                    .unwrap_or_else(|| branch.clone()),
+                new_version: if version.is_empty() { branch } else { version },
+                name: application,
+                category,
+                size,
+                installed_size_delta: 0, // Shared runtimes make this unknowable up front
+                critical: false,         // Sandboxed, never requires a reboot
+                source,
+            });
+        }
+    }
+
+    Ok(packages)
+}
+
+/// Application ID from a flatpak ref (`app/org.foo.Bar/x86_64/stable` -> `org.foo.Bar`)
+fn ref_application(reference: &str) -> String {
+    let parts: Vec<&str> = reference.split('/').collect();
+    match parts.as_slice() {
+        [_kind, application, _arch, _branch] => application.to_string(),
+        [application, _arch, _branch] => application.to_string(),
+        _ => reference.to_string(),
+    }
+}
+
+/// Parse one `flatpak update` output line into (application, failed)
+fn parse_update_line(line: &str) -> Option<(String, bool)> {
+    let line = line.trim();
+
+    // "Error: Failed to update app/org.foo.Bar/x86_64/stable: ..."
+    if let Some(idx) = line.find("Failed to update ") {
+        let rest = &line[idx + "Failed to update ".len()..];
+        let reference = rest.split(|c: char| c == ':' || c.is_whitespace()).next()?;
+        return Some((ref_application(reference), true));
+    }
+
+    // "Updating app/org.foo.Bar/x86_64/stable" / "Installing runtime/..."
+    let reference = line
+        .strip_prefix("Updating ")
+        .or_else(|| line.strip_prefix("Installing "))?
+        .split_whitespace()
+        .next()?;
+    // Skip counters such as "Updating 1/3…"
+    (reference.split('/').count() >= 3).then(|| (ref_application(reference), false))
+}
+
+async fn update_installation(
+    installation: Installation,
+    job: &UpdateJob,
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let mut cmd = match installation {
+        Installation::User => TokioCommand::new("flatpak"),
+        Installation::System => {
+            let mut cmd = TokioCommand::new("sudo");
+            cmd.arg("flatpak");
+            cmd
+        }
+    };
+    let mut child = cmd
+        .args(["update", installation.flag(), "--noninteractive", "-y"])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start flatpak: {}", e))?;
+
+    let stderr = child.stderr.take().expect("Failed to capture stderr");
+    let stderr_task = tokio::spawn(async move {
+        let mut lines = Vec::new();
+        let mut reader = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = reader.next_line().await {
+            lines.push(line);
+        }
+        lines
+    });
+
+    let stdout = child.stdout.take().expect("Failed to capture stdout");
+    let mut reader = BufReader::new(stdout).lines();
+    let mut installed: Vec<String> = Vec::new();
+    let mut failed: Vec<String> = Vec::new();
+
+    while let Ok(Some(line)) = reader.next_line().await {
+        match parse_update_line(&line) {
+            Some((application, false)) => {
+                job.progress(UpdateProgressEvent {
+                    stage: Status::Installing,
+                    package_name: application.clone(),
+                    progress: 50.0,
+                    package_progress: 0.0,
+                    downloaded: "".to_string(),
+                    total: "".to_string(),
+                    speed: "".to_string(),
+                    eta: "".to_string(),
+                });
+                if !installed.contains(&application) {
+                    installed.push(application);
+                }
+            }
+            Some((application, true)) if !failed.contains(&application) => failed.push(application),
+            _ => {}
+        }
+    }
+
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| format!("Failed to wait for flatpak: {}", e))?;
+
+    let stderr_lines = stderr_task.await.unwrap_or_default();
+    for line in &stderr_lines {
+        if let Some((application, true)) = parse_update_line(line) {
+            if !failed.contains(&application) {
+                failed.push(application);
+            }
+        }
+    }
+    installed.retain(|application| !failed.contains(application));
+
+    if !status.success() && failed.is_empty() {
+        let error = stderr_lines
+            .iter()
+            .find(|l| l.starts_with("error:") || l.starts_with("Error:"))
+            .cloned()
+            .unwrap_or_else(|| "Flatpak update failed".to_string());
+        return Err(error);
+    }
+
+    Ok((installed, failed))
+}
+
+/// Update the user installation, then the system one
+pub async fn install_updates(job: &UpdateJob) -> (Vec<String>, Vec<String>) {
+    let mut installed = Vec::new();
+    let mut failed = Vec::new();
+
+    for installation in Installation::ALL {
+        match update_installation(installation, job).await {
+            Ok((ok, ko)) => {
+                installed.extend(ok);
+                failed.extend(ko);
+            }
+            Err(e) => log::warn!("Flatpak {:?} update failed: {}", installation, e),
+        }
+    }
+
+    (installed, failed)
+}
+
+/// Sudoers rule allowing Pixxiden to update the system installation
+pub fn sudoers_rules(username: &str) -> String {
+    format!("{username} ALL=(ALL) NOPASSWD: /usr/bin/flatpak update --system --noninteractive -y\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote_updates() {
+        let stdout = "com.heroicgameslauncher.hgl\t2.15.2\tstable\t120.5 MB\n\
+                      org.freedesktop.Platform.GL.default\t24.0.5\t23.08\t150.0 MB\n";
+        let updates = parse_remote_updates(stdout);
+        assert_eq!(updates.len(), 2);
+        assert_eq!(
+            updates[0],
+            (
+                "com.heroicgameslauncher.hgl".to_string(),
+                "2.15.2".to_string(),
+                "stable".to_string(),
+                120_500_000
+            )
+        );
+        assert!(is_gl_extension(&updates[1].0));
+        assert!(!is_gl_extension(&updates[0].0));
+    }
+
+    #[test]
+    fn test_parse_update_line() {
+        assert_eq!(
+            parse_update_line("Updating app/com.discordapp.Discord/x86_64/stable"),
+            Some(("com.discordapp.Discord".to_string(), false))
+        );
+        assert_eq!(
+            parse_update_line(
+                "Error: Failed to update runtime/org.freedesktop.Platform.GL.nvidia-550-78/x86_64/1.4: Could not connect"
+            ),
+            Some(("org.freedesktop.Platform.GL.nvidia-550-78".to_string(), true))
+        );
+        assert_eq!(parse_update_line("Looking for updates…"), None);
+        assert_eq!(parse_update_line("Updating 1/3…"), None);
+    }
+}
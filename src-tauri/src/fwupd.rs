@@ -0,0 +1,605 @@
+//! Firmware updates through fwupd (D-Bus `org.freedesktop.fwupd`)
+//!
+//! Controllers, docks, SSDs and the system firmware get their releases from
+//! LVFS. The cabinet archive is downloaded and checked here, then handed to
+//! the daemon, which flashes the device or, for UEFI capsules, stages the
+//! update for the next boot. fwupd authorizes signed LVFS payloads for the
+//! active session through polkit, so no sudoers rule is needed.
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::os::fd::AsFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::io::AsyncWriteExt;
+use zbus::zvariant::{Fd, Value};
+use zbus::Proxy;
+
+use crate::dbus::{self, Properties};
+use crate::system_updates::{format_size, Status, UpdateProgressEvent};
+
+const FWUPD: &str = "org.freedesktop.fwupd";
+const FWUPD_PATH: &str = "/";
+const FWUPD_IFACE: &str = "org.freedesktop.fwupd";
+const APP_DIR: &str = "com.Pixxiden.launcher";
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const DOWNLOAD_SHARE: f32 = 30.0; // Share of the overall progress spent downloading
+
+// FwupdDeviceFlags
+const DEVICE_FLAG_UPDATABLE: u64 = 1 << 1;
+const DEVICE_FLAG_NEEDS_REBOOT: u64 = 1 << 8;
+const DEVICE_FLAG_NEEDS_SHUTDOWN: u64 = 1 << 17;
+
+// FwupdUpdateState
+const UPDATE_STATE_NEEDS_REBOOT: u32 = 4;
+
+// FwupdStatus
+const STATUS_DEVICE_RESTART: u32 = 4;
+const STATUS_DEVICE_VERIFY: u32 = 6;
+const STATUS_SCHEDULING: u32 = 7;
+
+/// Urgency of a firmware release, as set by the vendor
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FirmwareUrgency {
+    Unknown,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl FirmwareUrgency {
+    fn from_fwupd(value: u32) -> Self {
+        match value {
+            1 => FirmwareUrgency::Low,
+            2 => FirmwareUrgency::Medium,
+            3 => FirmwareUrgency::High,
+            4 => FirmwareUrgency::Critical,
+            _ => FirmwareUrgency::Unknown,
+        }
+    }
+}
+
+/// A firmware release published on LVFS
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FirmwareRelease {
+    pub version: String,
+    pub summary: String,
+    pub release_notes: String, // Plain text, converted from AppStream markup
+    pub size: u64,             // Cabinet archive size in bytes
+    pub urgency: FirmwareUrgency,
+    #[serde(skip)]
+    locations: Vec<String>, // Download URLs
+    #[serde(skip)]
+    checksums: Vec<String>, // Archive SHA1/SHA256 hex digests
+}
+
+/// A device with a firmware update available
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FirmwareDevice {
+    pub id: String, // fwupd device ID
+    pub name: String,
+    pub vendor: String,
+    pub current_version: String,
+    pub requires_reboot: bool, // Applied at the next boot or on restart
+    pub release: FirmwareRelease,
+}
+
+/// Outcome of a firmware update
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FirmwareReport {
+    pub device_id: String,
+    pub version: String,
+    pub requires_reboot: bool,
+}
+
+/// Convert AppStream release markup (`<p>`, `<ul><li>`) to plain text
+fn release_notes(markup: &str) -> String {
+    let mut reader = Reader::from_str(markup);
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    let mut flush = |current: &mut String| {
+        let line = current.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        current.clear();
+    };
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) if e.name().as_ref() == b"li" => {
+                flush(&mut current);
+                current.push_str("• ");
+            }
+            Ok(Event::End(e)) if matches!(e.name().as_ref(), b"p" | b"li") => flush(&mut current),
+            Ok(Event::Text(text)) => {
+                if let Ok(text) = text.decode() {
+                    current.push_str(&text);
+                }
+            }
+            Ok(Event::GeneralRef(entity)) => {
+                if let Ok(Some(c)) = entity.resolve_char_ref() {
+                    current.push(c);
+                } else if let Some(resolved) = entity
+                    .decode()
+                    .ok()
+                    .and_then(|name| quick_xml::escape::resolve_predefined_entity(&name))
+                {
+                    current.push_str(resolved);
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                log::debug!("Invalid fwupd release description: {}", e);
+                break;
+            }
+            Ok(_) => {}
+        }
+    }
+    flush(&mut current);
+
+    lines.join("\n")
+}
+
+/// Checksums are an array on recent daemons, a comma-separated string on older ones
+fn release_checksums(release: &Properties) -> Vec<String> {
+    dbus::prop_str_list(release, "Checksum")
+        .or_else(|| {
+            dbus::prop_str(release, "Checksum")
+                .map(|s| s.split(',').map(|c| c.trim().to_string()).collect())
+        })
+        .unwrap_or_default()
+}
+
+fn release_from_props(release: &Properties) -> Option<FirmwareRelease> {
+    let version = dbus::prop_str(release, "Version")?;
+    let locations = dbus::prop_str_list(release, "Locations")
+        .filter(|l| !l.is_empty())
+        .or_else(|| dbus::prop_str(release, "Uri").map(|uri| vec![uri]))
+        .unwrap_or_default();
+
+    Some(FirmwareRelease {
+        version,
+        summary: dbus::prop_str(release, "Summary").unwrap_or_default(),
+        release_notes: dbus::prop_str(release, "Description")
+            .map(|d| release_notes(&d))
+            .unwrap_or_default(),
+        size: dbus::prop_u64(release, "Size").unwrap_or(0),
+        urgency: FirmwareUrgency::from_fwupd(dbus::prop_u32(release, "Urgency").unwrap_or(0)),
+        locations,
+        checksums: release_checksums(release),
+    })
+}
+
+/// Whether applying firmware to the device needs a reboot or a shutdown
+fn device_requires_reboot(device: &Properties) -> bool {
+    let flags = dbus::prop_u64(device, "Flags").unwrap_or(0);
+    flags & (DEVICE_FLAG_NEEDS_REBOOT | DEVICE_FLAG_NEEDS_SHUTDOWN) != 0
+        || dbus::prop_u32(device, "UpdateState") == Some(UPDATE_STATE_NEEDS_REBOOT)
+        // UEFI capsules are only flashed by the firmware on the next boot
+        || dbus::prop_str(device, "Plugin").is_some_and(|p| p.starts_with("uefi"))
+}
+
+fn device_from_props(device: &Properties, release: FirmwareRelease) -> Option<FirmwareDevice> {
+    let id = dbus::prop_str(device, "DeviceId")?;
+    if dbus::prop_u64(device, "Flags").unwrap_or(0) & DEVICE_FLAG_UPDATABLE == 0 {
+        return None;
+    }
+
+    Some(FirmwareDevice {
+        name: dbus::prop_str(device, "Name").unwrap_or_else(|| id.clone()),
+        vendor: dbus::prop_str(device, "Vendor").unwrap_or_default(),
+        current_version: dbus::prop_str(device, "Version").unwrap_or_default(),
+        requires_reboot: device_requires_reboot(device),
+        release,
+        id,
+    })
+}
+
+/// The SHA256 digest among the release checksums (SHA1 ones are too weak to bother)
+fn sha256_checksum(checksums: &[String]) -> Option<&str> {
+    checksums
+        .iter()
+        .map(|c| c.as_str())
+        .find(|c| c.len() == 64 && c.chars().all(|ch| ch.is_ascii_hexdigit()))
+}
+
+/// Progress stage reported for a fwupd daemon status
+fn progress_stage(status: u32) -> Status {
+    match status {
+        STATUS_DEVICE_RESTART | STATUS_DEVICE_VERIFY | STATUS_SCHEDULING => Status::Configuring,
+        _ => Status::Installing,
+    }
+}
+
+fn emit_progress(
+    window: &tauri::Window,
+    stage: Status,
+    device: &str,
+    progress: f32,
+    package_progress: f32,
+    downloaded: String,
+    total: String,
+) {
+    let _ = window.emit(
+        "firmware-progress",
+        UpdateProgressEvent {
+            stage,
+            package_name: device.to_string(),
+            progress,
+            package_progress,
+            downloaded,
+            total,
+            speed: "".to_string(),
+            eta: "".to_string(),
+        },
+    );
+}
+
+async fn daemon(conn: &zbus::Connection) -> Result<Proxy<'_>, String> {
+    dbus::proxy(conn, FWUPD, FWUPD_PATH, FWUPD_IFACE).await
+}
+
+async fn get_devices(daemon: &Proxy<'_>) -> Result<Vec<Properties>, String> {
+    daemon
+        .call("GetDevices", &())
+        .await
+        .map_err(|e| format!("Failed to list fwupd devices: {}", e))
+}
+
+/// Releases newer than the installed firmware, newest first
+async fn get_upgrades(daemon: &Proxy<'_>, device_id: &str) -> Vec<Properties> {
+    // fwupd answers with an error when there is nothing to upgrade
+    match daemon
+        .call::<_, _, Vec<Properties>>("GetUpgrades", &(device_id,))
+        .await
+    {
+        Ok(releases) => releases,
+        Err(e) => {
+            log::debug!("No firmware upgrade for {}: {}", device_id, e);
+            Vec::new()
+        }
+    }
+}
+
+async fn find_device(daemon: &Proxy<'_>, device_id: &str) -> Result<Properties, String> {
+    get_devices(daemon)
+        .await?
+        .into_iter()
+        .find(|d| dbus::prop_str(d, "DeviceId").as_deref() == Some(device_id))
+        .ok_or_else(|| format!("Firmware device {} not found", device_id))
+}
+
+/// List devices with a newer firmware release available
+pub async fn list_updates() -> Result<Vec<FirmwareDevice>, String> {
+    let conn = dbus::system_bus().await?;
+    let daemon = daemon(&conn).await?;
+
+    let mut updates = Vec::new();
+    for device in get_devices(&daemon).await? {
+        let Some(id) = dbus::prop_str(&device, "DeviceId") else {
+            continue;
+        };
+        if dbus::prop_u64(&device, "Flags").unwrap_or(0) & DEVICE_FLAG_UPDATABLE == 0 {
+            continue;
+        }
+        let release = get_upgrades(&daemon, &id)
+            .await
+            .first()
+            .and_then(release_from_props);
+        if let Some(update) = release.and_then(|r| device_from_props(&device, r)) {
+            updates.push(update);
+        }
+    }
+
+    Ok(updates)
+}
+
+/// Download the release archive to the cache, verifying its checksum
+async fn download_release(
+    window: &tauri::Window,
+    device: &str,
+    release: &FirmwareRelease,
+) -> Result<PathBuf, String> {
+    let url = release
+        .locations
+        .iter()
+        .find(|l| l.starts_with("https://") || l.starts_with("http://"))
+        .ok_or("The firmware release has no download location")?;
+    let file_name = url
+        .split('?')
+        .next()
+        .and_then(|u| u.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("firmware.cab");
+
+    let dir = dirs::cache_dir()
+        .map(|dir| dir.join(APP_DIR).join("firmware"))
+        .ok_or("No cache directory")?;
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Failed to create firmware cache: {}", e))?;
+    let path = dir.join(file_name);
+
+    let mut response = reqwest::get(url.as_str())
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to download firmware: {}", e))?;
+    let total = response.content_length().unwrap_or(release.size);
+    let mut file = tokio::fs::File::create(&path)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+
+    let mut hasher = Sha256::new();
+    let mut downloaded: u64 = 0;
+    let mut last_percent = 0;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to download firmware: {}", e))?
+    {
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        hasher.update(&chunk);
+        downloaded += chunk.len() as u64;
+
+        let percent = (downloaded * 100).checked_div(total).unwrap_or(0).min(100) as u32;
+        if percent != last_percent {
+            last_percent = percent;
+            emit_progress(
+                window,
+                Status::Downloading,
+                device,
+                percent as f32 * DOWNLOAD_SHARE / 100.0,
+                percent as f32,
+                format_size(downloaded),
+                format_size(total),
+            );
+        }
+    }
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    let digest: String = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if let Some(expected) = sha256_checksum(&release.checksums) {
+        if !expected.eq_ignore_ascii_case(&digest) {
+            let _ = tokio::fs::remove_file(&path).await;
+            return Err(format!(
+                "Firmware checksum mismatch (expected {}, got {})",
+                expected, digest
+            ));
+        }
+    }
+
+    Ok(path)
+}
+
+/// Hand the archive to the daemon, relaying its progress until the install call returns
+async fn flash(
+    window: &tauri::Window,
+    daemon: &Proxy<'_>,
+    device_id: &str,
+    name: &str,
+    file: &std::fs::File,
+    path: &Path,
+) -> zbus::Result<()> {
+    let filename = path.to_string_lossy().to_string();
+    let mut options: HashMap<&str, Value> = HashMap::new();
+    options.insert("reason", Value::from("user-action"));
+    options.insert("filename", Value::from(filename.as_str()));
+    let body = (device_id, Fd::from(file.as_fd()), options);
+
+    let install = daemon.call_method("Install", &body);
+    tokio::pin!(install);
+    let mut ticker = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            result = &mut install => return result.map(|_| ()),
+            _ = ticker.tick() => {
+                let status: u32 = daemon.get_property("Status").await.unwrap_or(0);
+                let percent: u32 = daemon.get_property("Percentage").await.unwrap_or(0);
+                let percent = percent.min(100) as f32;
+                emit_progress(
+                    window,
+                    progress_stage(status),
+                    name,
+                    DOWNLOAD_SHARE + percent * (100.0 - DOWNLOAD_SHARE) / 100.0,
+                    percent,
+                    "".to_string(),
+                    "".to_string(),
+                );
+            }
+        }
+    }
+}
+
+/// Download and apply the newest firmware of a device, emitting `firmware-progress` events
+pub async fn install_update(
+    window: &tauri::Window,
+    device_id: &str,
+) -> Result<FirmwareReport, String> {
+    let conn = dbus::system_bus().await?;
+    let daemon = daemon(&conn).await?;
+
+    let device = find_device(&daemon, device_id).await?;
+    let name = dbus::prop_str(&device, "Name").unwrap_or_else(|| device_id.to_string());
+    let release = get_upgrades(&daemon, device_id)
+        .await
+        .first()
+        .and_then(release_from_props)
+        .ok_or_else(|| format!("No firmware update available for {}", name))?;
+
+    log::info!("Updating {} firmware to {}", name, release.version);
+    let path = download_release(window, &name, &release).await?;
+    let file = std::fs::File::open(&path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+    let result = flash(window, &daemon, device_id, &name, &file, &path).await;
+    drop(file);
+    let _ = std::fs::remove_file(&path);
+    result.map_err(|e| format!("Firmware update of {} failed: {}", name, e))?;
+
+    // The daemon flags devices that only finish updating after a reboot
+    let requires_reboot = device_requires_reboot(&device)
+        || find_device(&daemon, device_id)
+            .await
+            .is_ok_and(|d| device_requires_reboot(&d));
+
+    log::info!(
+        "{} firmware updated to {}{}",
+        name,
+        release.version,
+        if requires_reboot {
+            ", reboot required"
+        } else {
+            ""
+        }
+    );
+    Ok(FirmwareReport {
+        device_id: device_id.to_string(),
+        version: release.version,
+        requires_reboot,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zbus::zvariant::{OwnedValue, Str};
+
+    fn release_props() -> Properties {
+        let mut props = Properties::new();
+        props.insert("Version".to_string(), OwnedValue::from(Str::from("1.0.12")));
+        props.insert(
+            "Summary".to_string(),
+            OwnedValue::from(Str::from("Firmware for the 8BitDo Ultimate controller")),
+        );
+        props.insert(
+            "Description".to_string(),
+            OwnedValue::from(Str::from(
+                "<p>This release fixes:</p><ul><li>Stick drift &amp; dead zones</li>\
+                 <li>Rumble on\n  Linux</li></ul>",
+            )),
+        );
+        props.insert("Size".to_string(), OwnedValue::from(524_288u64));
+        props.insert("Urgency".to_string(), OwnedValue::from(3u32));
+        props.insert(
+            "Uri".to_string(),
+            OwnedValue::from(Str::from("https://fwupd.org/downloads/abc-8bitdo.cab")),
+        );
+        props.insert(
+            "Checksum".to_string(),
+            OwnedValue::from(Str::from(
+                "0c9e1d3c4f1ab0a6d3f1e4bbd1a37ea5b0b7f1aa,\
+                 2f5a1c7be1d0e9e0bbdaf7a06e5a6d9f01c3ef1d4c7c6a4b2f8f2c1b5f0de9aa",
+            )),
+        );
+        props
+    }
+
+    fn device_props(flags: u64, plugin: &str) -> Properties {
+        let mut props = Properties::new();
+        props.insert(
+            "DeviceId".to_string(),
+            OwnedValue::from(Str::from("a45df35ac0e948ee180fe216a5f703f32dda163f")),
+        );
+        props.insert(
+            "Name".to_string(),
+            OwnedValue::from(Str::from("Ultimate Controller")),
+        );
+        props.insert("Vendor".to_string(), OwnedValue::from(Str::from("8BitDo")));
+        props.insert("Version".to_string(), OwnedValue::from(Str::from("1.0.10")));
+        props.insert("Plugin".to_string(), OwnedValue::from(Str::from(plugin)));
+        props.insert("Flags".to_string(), OwnedValue::from(flags));
+        props
+    }
+
+    #[test]
+    fn test_release_notes() {
+        assert_eq!(
+            release_notes(
+                "<p>This release fixes:</p><ul><li>Stick drift &amp; dead zones</li>\
+                 <li>Rumble on\n  Linux</li></ul>"
+            ),
+            "This release fixes:\n• Stick drift & dead zones\n• Rumble on Linux"
+        );
+        assert_eq!(release_notes("Plain text"), "Plain text");
+    }
+
+    #[test]
+    fn test_release_from_props() {
+        let release = release_from_props(&release_props()).unwrap();
+        assert_eq!(release.version, "1.0.12");
+        assert_eq!(release.urgency, FirmwareUrgency::High);
+        assert_eq!(release.size, 524_288);
+        assert_eq!(
+            release.locations,
+            vec!["https://fwupd.org/downloads/abc-8bitdo.cab"]
+        );
+        assert_eq!(
+            sha256_checksum(&release.checksums),
+            Some("2f5a1c7be1d0e9e0bbdaf7a06e5a6d9f01c3ef1d4c7c6a4b2f8f2c1b5f0de9aa")
+        );
+        assert!(release_from_props(&Properties::new()).is_none());
+    }
+
+    #[test]
+    fn test_device_from_props() {
+        let release = release_from_props(&release_props()).unwrap();
+
+        let device = device_from_props(
+            &device_props(DEVICE_FLAG_UPDATABLE, "ebitdo"),
+            release.clone(),
+        )
+        .unwrap();
+        assert_eq!(device.name, "Ultimate Controller");
+        assert_eq!(device.current_version, "1.0.10");
+        assert!(!device.requires_reboot);
+
+        // Not updatable from the running system
+        assert!(device_from_props(&device_props(0, "ebitdo"), release.clone()).is_none());
+    }
+
+    #[test]
+    fn test_device_requires_reboot() {
+        assert!(device_requires_reboot(&device_props(
+            DEVICE_FLAG_UPDATABLE,
+            "uefi_capsule"
+        )));
+        assert!(device_requires_reboot(&device_props(
+            DEVICE_FLAG_UPDATABLE | DEVICE_FLAG_NEEDS_REBOOT,
+            "nvme"
+        )));
+
+        let mut staged = device_props(DEVICE_FLAG_UPDATABLE, "nvme");
+        assert!(!device_requires_reboot(&staged));
+        staged.insert(
+            "UpdateState".to_string(),
+            OwnedValue::from(UPDATE_STATE_NEEDS_REBOOT),
+        );
+        assert!(device_requires_reboot(&staged));
+    }
+
+    #[test]
+    fn test_progress_stage() {
+        assert_eq!(progress_stage(5), Status::Installing); // device-write
+        assert_eq!(progress_stage(STATUS_DEVICE_VERIFY), Status::Configuring);
+        assert_eq!(progress_stage(STATUS_DEVICE_RESTART), Status::Configuring);
+    }
+}
@@ -1,9 +1,20 @@
 use gilrs::{Button, Event, EventType, Gilrs};
+use serde::Serialize;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tauri::{AppHandle, Emitter, Manager};
 
+use crate::bluetooth::{BluetoothDevice, BluetoothDeviceKind};
+
+/// Payload of the `gamepad-connected` event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GamepadConnectedEvent {
+    pub name: String,
+    pub bluetooth_address: Option<String>, // Set when announced right after Bluetooth pairing
+}
+
 /// Gamepad monitor that listens for Guide/PS button presses
 /// and emits events to toggle the overlay + focus the main window.
 pub struct GamepadMonitor {
@@ -82,6 +93,13 @@ impl GamepadMonitor {
                         EventType::Connected => {
                             if let Some(gamepad) = gilrs.connected_gamepad(id) {
                                 log::info!("🎮 Gamepad connected: {}", gamepad.name());
+                                let _ = app_handle.emit(
+                                    "gamepad-connected",
+                                    GamepadConnectedEvent {
+                                        name: gamepad.name().to_string(),
+                                        bluetooth_address: None,
+                                    },
+                                );
                             }
                         }
                         EventType::Disconnected => {
@@ -99,6 +117,25 @@ impl GamepadMonitor {
         });
     }
 
+    /// Announce a controller connected over Bluetooth without waiting for
+    /// its input device to show up
+    pub fn announce_bluetooth(&self, app_handle: &AppHandle, device: &BluetoothDevice) {
+        if device.kind != BluetoothDeviceKind::Gamepad || !device.connected {
+            return;
+        }
+
+        log::info!("🎮 Bluetooth gamepad connected: {}", device.name);
+        if let Err(e) = app_handle.emit(
+            "gamepad-connected",
+            GamepadConnectedEvent {
+                name: device.name.clone(),
+                bluetooth_address: Some(device.address.clone()),
+            },
+        ) {
+            log::error!("Failed to emit gamepad connected event: {}", e);
+        }
+    }
+
     /// Stop the gamepad monitor
     pub fn stop(&self) {
         log::info!("Stopping gamepad monitor...");
@@ -1,40 +1,113 @@
+mod arch_news;
+mod audio;
+mod aur;
+mod bluetooth;
 mod commands;
+mod dbus;
+mod diagnostics;
+mod display;
+mod flatpak;
+mod fwupd;
 mod gamepad;
+mod network;
+mod os_release;
+mod ostree;
+mod package_holds;
+mod package_log;
+mod package_manager;
+mod prerequisites;
+mod reboot;
 mod runners;
+mod snapshots;
+mod steamos;
 mod sudoers;
 mod system;
 mod system_updates;
+mod update_failures;
+mod update_history;
+mod update_jobs;
+mod update_scheduler;
 
 #[cfg(test)]
 mod tests;
 
 use commands::{
+    apply_display_change,
+    cancel_system_update,
     check_for_updates,
+    check_gaming_prerequisites,
+    check_interrupted_update,
     check_paths_exist,
     // Runners (Proton-GE) — only heavy I/O stays in Rust
     check_system_updates,
     configure_sudoers,
+    confirm_display_change,
+    connect_bluetooth_device,
+    connect_wifi_network,
+    disconnect_bluetooth_device,
     download_file,
+    export_diagnostics_bundle,
     extract_runner_tarball,
     // Window management
     focus_main_window,
+    forget_bluetooth_device,
+    forget_wifi_network,
+    get_active_update_job,
+    get_audio_state,
     get_disk_info,
+    get_display_outputs,
     // System Updates
     get_distro,
+    get_distro_info,
+    get_last_update_check,
+    get_ostree_status,
+    get_package_holds,
     get_settings,
+    get_snapshot_tool,
+    get_steamos_status,
     get_system_info,
+    get_update_run,
+    get_update_schedule,
+    get_wifi_status,
     hide_main_window,
+    install_firmware_update,
+    install_gaming_prerequisites,
     install_system_updates,
     is_sudoers_configured,
+    list_bluetooth_devices,
+    list_firmware_updates,
+    list_update_history,
+    list_update_snapshots,
+    list_wifi_networks,
+    mark_arch_news_read,
+    pair_bluetooth_device,
     reboot_system,
     requires_system_reboot,
     restore_main_window,
+    revert_display_change,
+    rollback_ostree_deployment,
     save_settings,
+    scan_wifi_networks,
+    set_audio_mute,
+    set_audio_volume,
+    set_default_audio_device,
+    set_package_holds,
+    set_update_schedule,
+    set_wifi_enabled,
     shutdown_system,
+    start_bluetooth_discovery,
+    stop_bluetooth_discovery,
+    trust_bluetooth_device,
 };
+use audio::AudioMonitor;
+use bluetooth::BluetoothDiscovery;
+use display::DisplayManager;
 use gamepad::GamepadMonitor;
 use std::sync::Arc;
 use tauri::Manager;
+use update_history::UpdateHistory;
+use update_jobs::UpdateJobs;
+use update_scheduler::UpdateScheduler;
 use tauri_plugin_autostart::ManagerExt;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -103,6 +176,36 @@ pub fn run() {
         app.manage(gamepad_monitor);
         log::info!("Gamepad monitoring started automatically");
 
+        // Follow audio device changes made outside Pixxiden
+        let audio_monitor = Arc::new(AudioMonitor::new());
+        audio_monitor.start(app.handle().clone());
+        app.manage(audio_monitor);
+
+        // Display changes awaiting user confirmation
+        app.manage(Arc::new(DisplayManager::new()));
+
+        // Bluetooth scan, owned by the connection that started it
+        app.manage(Arc::new(BluetoothDiscovery::new()));
+
+        // Running system update, so it can be cancelled
+        app.manage(Arc::new(UpdateJobs::new()));
+        if let Some(interrupted) = update_failures::detect_interrupted_update() {
+            log::warn!(
+                "Previous system update did not finish: {} ({})",
+                interrupted.message,
+                interrupted.remediation
+            );
+        }
+
+        // Past update runs, in the database shared with the frontend
+        let database = app.path().app_config_dir()?.join(update_history::DATABASE_FILE);
+        app.manage(Arc::new(UpdateHistory::new(database)));
+
+        // Periodic update checks with notifications
+        let update_scheduler = Arc::new(UpdateScheduler::new());
+        update_scheduler.start(app.handle().clone());
+        app.manage(update_scheduler);
+
         // Enable autostart on first launch
         let handle = app.handle().clone();
         tauri::async_runtime::spawn(async move {
@@ -135,12 +238,62 @@ pub fn run() {
         save_settings,
         // System Updates
         get_distro,
+        get_distro_info,
         is_sudoers_configured,
         configure_sudoers,
         check_system_updates,
         install_system_updates,
+        cancel_system_update,
+        get_active_update_job,
+        check_interrupted_update,
+        get_snapshot_tool,
+        list_update_snapshots,
+        mark_arch_news_read,
+        get_steamos_status,
+        get_ostree_status,
+        rollback_ostree_deployment,
+        list_firmware_updates,
+        install_firmware_update,
+        get_update_schedule,
+        set_update_schedule,
+        get_last_update_check,
+        list_update_history,
+        get_update_run,
+        get_package_holds,
+        set_package_holds,
         requires_system_reboot,
         reboot_system,
+        // Gaming prerequisites
+        check_gaming_prerequisites,
+        install_gaming_prerequisites,
+        // Audio
+        get_audio_state,
+        set_default_audio_device,
+        set_audio_volume,
+        set_audio_mute,
+        // Display
+        get_display_outputs,
+        apply_display_change,
+        confirm_display_change,
+        revert_display_change,
+        // Bluetooth
+        start_bluetooth_discovery,
+        stop_bluetooth_discovery,
+        list_bluetooth_devices,
+        pair_bluetooth_device,
+        trust_bluetooth_device,
+        connect_bluetooth_device,
+        disconnect_bluetooth_device,
+        forget_bluetooth_device,
+        // Wi-Fi
+        get_wifi_status,
+        set_wifi_enabled,
+        scan_wifi_networks,
+        list_wifi_networks,
+        connect_wifi_network,
+        forget_wifi_network,
+        // Diagnostics
+        export_diagnostics_bundle,
         // Runners (Proton-GE) — only heavy I/O stays in Rust
         download_file,
         extract_runner_tarball,
@@ -150,5 +303,13 @@ pub fn run() {
         restore_main_window,
     ]);
 
-    builder.run(tauri::generate_context!()).expect("error while running tauri application");
+    builder
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Do not leave a download running in the background after quitting
+            if let tauri::RunEvent::Exit = event {
+                app.state::<Arc<UpdateJobs>>().shutdown();
+            }
+        });
 }
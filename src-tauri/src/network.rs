@@ -0,0 +1,482 @@
+//! Wi-Fi management through NetworkManager (D-Bus `org.freedesktop.NetworkManager`)
+//!
+//! Lets a session without a desktop join a network: list access points,
+//! connect (including hidden SSIDs), forget saved networks and show the
+//! current connection.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::Connection;
+
+use crate::dbus;
+
+const NM: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+const NM_SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
+const DEVICE_IFACE: &str = "org.freedesktop.NetworkManager.Device";
+const WIRELESS_IFACE: &str = "org.freedesktop.NetworkManager.Device.Wireless";
+const AP_IFACE: &str = "org.freedesktop.NetworkManager.AccessPoint";
+const SETTINGS_IFACE: &str = "org.freedesktop.NetworkManager.Settings";
+const CONNECTION_IFACE: &str = "org.freedesktop.NetworkManager.Settings.Connection";
+const ACTIVE_IFACE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+
+/// NM_DEVICE_TYPE_WIFI
+const DEVICE_TYPE_WIFI: u32 = 2;
+
+// NMMetered
+const METERED_YES: u32 = 1;
+const METERED_GUESS_YES: u32 = 3;
+
+/// How long to wait for a connection to come up before giving up
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Security of a Wi-Fi network
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WifiSecurity {
+    Open,
+    Wep,
+    Wpa,        // WPA/WPA2 personal
+    Wpa3,       // SAE
+    Enterprise, // 802.1X, needs credentials we don't handle
+}
+
+/// A Wi-Fi network seen by the adapter
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WifiNetwork {
+    pub ssid: String,
+    pub strength: u8, // 0-100
+    pub security: WifiSecurity,
+    pub frequency: u32, // MHz
+    pub connected: bool,
+    pub saved: bool,
+}
+
+/// Current Wi-Fi state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WifiStatus {
+    pub enabled: bool,
+    pub interface: Option<String>,
+    pub ssid: Option<String>,
+    pub strength: Option<u8>,
+}
+
+/// Derive the security from the access point flags (NM80211ApFlags/ApSecurityFlags)
+fn security_from_flags(flags: u32, wpa_flags: u32, rsn_flags: u32) -> WifiSecurity {
+    const KEY_MGMT_PSK: u32 = 0x100;
+    const KEY_MGMT_802_1X: u32 = 0x200;
+    const KEY_MGMT_SAE: u32 = 0x400;
+
+    let key_mgmt = wpa_flags | rsn_flags;
+    if key_mgmt & KEY_MGMT_802_1X != 0 {
+        WifiSecurity::Enterprise
+    } else if key_mgmt & KEY_MGMT_SAE != 0 && key_mgmt & KEY_MGMT_PSK == 0 {
+        WifiSecurity::Wpa3
+    } else if key_mgmt != 0 {
+        WifiSecurity::Wpa
+    } else if flags & 0x1 != 0 {
+        WifiSecurity::Wep
+    } else {
+        WifiSecurity::Open
+    }
+}
+
+/// Keep the strongest access point per SSID, strongest networks first
+fn dedupe_networks(mut networks: Vec<WifiNetwork>) -> Vec<WifiNetwork> {
+    networks.retain(|n| !n.ssid.is_empty());
+    networks.sort_by(|a, b| {
+        b.connected
+            .cmp(&a.connected)
+            .then(b.strength.cmp(&a.strength))
+    });
+
+    let mut unique: Vec<WifiNetwork> = Vec::new();
+    for network in networks {
+        if !unique.iter().any(|n| n.ssid == network.ssid) {
+            unique.push(network);
+        }
+    }
+    unique
+}
+
+/// Build the settings of a new Wi-Fi connection
+fn connection_settings<'a>(
+    ssid: &'a str,
+    password: Option<&'a str>,
+    security: WifiSecurity,
+    hidden: bool,
+) -> HashMap<&'static str, HashMap<&'static str, Value<'a>>> {
+    let mut settings = HashMap::new();
+
+    let mut connection = HashMap::new();
+    connection.insert("id", Value::from(ssid));
+    connection.insert("type", Value::from("802-11-wireless"));
+    settings.insert("connection", connection);
+
+    let mut wireless = HashMap::new();
+    wireless.insert("ssid", Value::from(ssid.as_bytes().to_vec()));
+    if hidden {
+        wireless.insert("hidden", Value::from(true));
+    }
+    settings.insert("802-11-wireless", wireless);
+
+    if let Some(password) = password.filter(|p| !p.is_empty()) {
+        let mut security_settings = HashMap::new();
+        match security {
+            WifiSecurity::Wep => {
+                security_settings.insert("key-mgmt", Value::from("none"));
+                security_settings.insert("wep-key0", Value::from(password));
+            }
+            WifiSecurity::Wpa3 => {
+                security_settings.insert("key-mgmt", Value::from("sae"));
+                security_settings.insert("psk", Value::from(password));
+            }
+            _ => {
+                security_settings.insert("key-mgmt", Value::from("wpa-psk"));
+                security_settings.insert("psk", Value::from(password));
+            }
+        }
+        settings.insert("802-11-wireless-security", security_settings);
+    }
+
+    settings
+}
+
+/// Find the first Wi-Fi device managed by NetworkManager
+async fn wifi_device(conn: &Connection) -> Result<OwnedObjectPath, String> {
+    let nm = dbus::proxy(conn, NM, NM_PATH, NM).await?;
+    let devices: Vec<OwnedObjectPath> = nm
+        .call("GetDevices", &())
+        .await
+        .map_err(|e| format!("Failed to list network devices: {}", e))?;
+
+    for path in devices {
+        let device = dbus::proxy(conn, NM, path.as_str(), DEVICE_IFACE).await?;
+        if device.get_property::<u32>("DeviceType").await.ok() == Some(DEVICE_TYPE_WIFI) {
+            return Ok(path);
+        }
+    }
+
+    Err("No Wi-Fi adapter found".to_string())
+}
+
+async fn access_point(conn: &Connection, path: &str) -> Result<(WifiNetwork, String), String> {
+    let ap = dbus::proxy(conn, NM, path, AP_IFACE).await?;
+    let ssid: Vec<u8> = ap.get_property("Ssid").await.unwrap_or_default();
+    let flags: u32 = ap.get_property("Flags").await.unwrap_or(0);
+    let wpa_flags: u32 = ap.get_property("WpaFlags").await.unwrap_or(0);
+    let rsn_flags: u32 = ap.get_property("RsnFlags").await.unwrap_or(0);
+
+    let network = WifiNetwork {
+        ssid: String::from_utf8_lossy(&ssid).to_string(),
+        strength: ap.get_property("Strength").await.unwrap_or(0),
+        security: security_from_flags(flags, wpa_flags, rsn_flags),
+        frequency: ap.get_property("Frequency").await.unwrap_or(0),
+        connected: false,
+        saved: false,
+    };
+    Ok((network, path.to_string()))
+}
+
+/// Saved Wi-Fi connections as (settings path, SSID)
+async fn saved_connections(conn: &Connection) -> Result<Vec<(OwnedObjectPath, String)>, String> {
+    let settings = dbus::proxy(conn, NM, NM_SETTINGS_PATH, SETTINGS_IFACE).await?;
+    let paths: Vec<OwnedObjectPath> = settings
+        .call("ListConnections", &())
+        .await
+        .map_err(|e| format!("Failed to list saved connections: {}", e))?;
+
+    let mut saved = Vec::new();
+    for path in paths {
+        let connection = dbus::proxy(conn, NM, path.as_str(), CONNECTION_IFACE).await?;
+        let Ok(config) = connection
+            .call::<_, _, HashMap<String, HashMap<String, OwnedValue>>>("GetSettings", &())
+            .await
+        else {
+            continue;
+        };
+
+        let ssid = config
+            .get("802-11-wireless")
+            .and_then(|w| w.get("ssid"))
+            .and_then(|v| v.try_clone().ok())
+            .and_then(|v| Vec::<u8>::try_from(v).ok());
+        if let Some(ssid) = ssid {
+            saved.push((path, String::from_utf8_lossy(&ssid).to_string()));
+        }
+    }
+
+    Ok(saved)
+}
+
+/// Ask the adapter to rescan; results show up in `list_networks` a few seconds later
+pub async fn request_scan() -> Result<(), String> {
+    let conn = dbus::system_bus().await?;
+    let device_path = wifi_device(&conn).await?;
+    let wireless = dbus::proxy(&conn, NM, device_path.as_str(), WIRELESS_IFACE).await?;
+
+    let options: HashMap<&str, Value> = HashMap::new();
+    wireless
+        .call_method("RequestScan", &(options,))
+        .await
+        .map_err(|e| format!("Failed to scan Wi-Fi networks: {}", e))?;
+    Ok(())
+}
+
+/// List visible Wi-Fi networks, strongest first
+pub async fn list_networks() -> Result<Vec<WifiNetwork>, String> {
+    let conn = dbus::system_bus().await?;
+    let device_path = wifi_device(&conn).await?;
+    let wireless = dbus::proxy(&conn, NM, device_path.as_str(), WIRELESS_IFACE).await?;
+
+    let ap_paths: Vec<OwnedObjectPath> = wireless
+        .call("GetAllAccessPoints", &())
+        .await
+        .map_err(|e| format!("Failed to list access points: {}", e))?;
+    let active: Option<OwnedObjectPath> = wireless.get_property("ActiveAccessPoint").await.ok();
+    let saved = saved_connections(&conn).await.unwrap_or_default();
+
+    let mut networks = Vec::new();
+    for path in ap_paths {
+        let Ok((mut network, path)) = access_point(&conn, path.as_str()).await else {
+            continue;
+        };
+        network.connected = active.as_ref().is_some_and(|a| a.as_str() == path);
+        network.saved = saved.iter().any(|(_, ssid)| *ssid == network.ssid);
+        networks.push(network);
+    }
+
+    Ok(dedupe_networks(networks))
+}
+
+/// Get the Wi-Fi radio state and current connection
+pub async fn get_status() -> Result<WifiStatus, String> {
+    let conn = dbus::system_bus().await?;
+    let nm = dbus::proxy(&conn, NM, NM_PATH, NM).await?;
+    let enabled: bool = nm.get_property("WirelessEnabled").await.unwrap_or(false);
+
+    let Ok(device_path) = wifi_device(&conn).await else {
+        return Ok(WifiStatus {
+            enabled,
+            interface: None,
+            ssid: None,
+            strength: None,
+        });
+    };
+
+    let device = dbus::proxy(&conn, NM, device_path.as_str(), DEVICE_IFACE).await?;
+    let wireless = dbus::proxy(&conn, NM, device_path.as_str(), WIRELESS_IFACE).await?;
+    let interface: Option<String> = device.get_property("Interface").await.ok();
+    let active: Option<OwnedObjectPath> = wireless.get_property("ActiveAccessPoint").await.ok();
+
+    // "/" means no active access point
+    let current = match active.filter(|a| a.as_str() != "/") {
+        Some(path) => access_point(&conn, path.as_str()).await.ok(),
+        None => None,
+    };
+
+    Ok(WifiStatus {
+        enabled,
+        interface,
+        ssid: current.as_ref().map(|(n, _)| n.ssid.clone()),
+        strength: current.as_ref().map(|(n, _)| n.strength),
+    })
+}
+
+/// Turn the Wi-Fi radio on or off
+pub async fn set_enabled(enabled: bool) -> Result<(), String> {
+    let conn = dbus::system_bus().await?;
+    let nm = dbus::proxy(&conn, NM, NM_PATH, NM).await?;
+    nm.set_property("WirelessEnabled", enabled)
+        .await
+        .map_err(|e| format!("Failed to toggle Wi-Fi: {}", e))
+}
+
+/// Whether the primary connection is metered (set by the user, or guessed
+/// by NetworkManager for mobile broadband and phone hotspots)
+pub async fn is_metered() -> Result<bool, String> {
+    let conn = dbus::system_bus().await?;
+    let nm = dbus::proxy(&conn, NM, NM_PATH, NM).await?;
+    let metered: u32 = nm
+        .get_property("Metered")
+        .await
+        .map_err(|e| format!("Failed to read metered state: {}", e))?;
+    Ok(matches!(metered, METERED_YES | METERED_GUESS_YES))
+}
+
+/// Wait for an active connection to be fully up
+async fn wait_for_activation(conn: &Connection, active: &OwnedObjectPath) -> Result<(), String> {
+    const ACTIVATED: u32 = 2;
+    const DEACTIVATED: u32 = 4;
+
+    let proxy = dbus::proxy(conn, NM, active.as_str(), ACTIVE_IFACE).await?;
+    let start = std::time::Instant::now();
+
+    while start.elapsed() < CONNECT_TIMEOUT {
+        match proxy.get_property::<u32>("State").await {
+            Ok(ACTIVATED) => return Ok(()),
+            Ok(DEACTIVATED) | Err(_) => {
+                return Err("Connection failed (wrong password?)".to_string());
+            }
+            _ => tokio::time::sleep(Duration::from_millis(500)).await,
+        }
+    }
+
+    Err("Connection timed out".to_string())
+}
+
+/// Connect to a network; a saved network reconnects without a password
+pub async fn connect(ssid: &str, password: Option<&str>, hidden: bool) -> Result<(), String> {
+    let conn = dbus::system_bus().await?;
+    let device_path = wifi_device(&conn).await?;
+    let nm = dbus::proxy(&conn, NM, NM_PATH, NM).await?;
+    let saved = saved_connections(&conn).await.unwrap_or_default();
+    let existing: Vec<&OwnedObjectPath> = saved
+        .iter()
+        .filter(|(_, s)| s == ssid)
+        .map(|(path, _)| path)
+        .collect();
+
+    let root = ObjectPath::try_from("/").expect("valid object path");
+
+    if let (Some(path), None) = (existing.first(), password) {
+        let active: OwnedObjectPath = nm
+            .call("ActivateConnection", &(*path, &device_path, &root))
+            .await
+            .map_err(|e| format!("Failed to connect to {}: {}", ssid, e))?;
+        wait_for_activation(&conn, &active).await?;
+        log::info!("Connected to Wi-Fi network {}", ssid);
+        return Ok(());
+    }
+
+    let has_password = password.is_some_and(|p| !p.is_empty());
+    let security = if hidden {
+        if has_password {
+            WifiSecurity::Wpa
+        } else {
+            WifiSecurity::Open
+        }
+    } else {
+        list_networks()
+            .await?
+            .into_iter()
+            .find(|n| n.ssid == ssid)
+            .map(|n| n.security)
+            .ok_or_else(|| format!("Network not found: {}", ssid))?
+    };
+
+    if security == WifiSecurity::Enterprise {
+        return Err("Enterprise (802.1X) networks are not supported".to_string());
+    }
+    if security != WifiSecurity::Open && !has_password {
+        return Err(format!("A password is required for {}", ssid));
+    }
+
+    let settings = connection_settings(ssid, password, security, hidden);
+    let (added, active): (OwnedObjectPath, OwnedObjectPath) = nm
+        .call("AddAndActivateConnection", &(settings, &device_path, &root))
+        .await
+        .map_err(|e| format!("Failed to connect to {}: {}", ssid, e))?;
+
+    // The saved profile is only replaced once the new one works
+    let activation = wait_for_activation(&conn, &active).await;
+    let stale = if activation.is_ok() {
+        existing
+    } else {
+        vec![&added]
+    };
+    for path in stale {
+        let connection = dbus::proxy(&conn, NM, path.as_str(), CONNECTION_IFACE).await?;
+        if let Err(e) = connection.call_method("Delete", &()).await {
+            log::warn!("Failed to delete Wi-Fi profile {}: {}", path.as_str(), e);
+        }
+    }
+    activation?;
+
+    log::info!("Connected to Wi-Fi network {}", ssid);
+    Ok(())
+}
+
+/// Delete the saved profiles of a network
+pub async fn forget(ssid: &str) -> Result<(), String> {
+    let conn = dbus::system_bus().await?;
+    let saved = saved_connections(&conn).await?;
+    let mut found = false;
+
+    for (path, _) in saved.iter().filter(|(_, s)| s == ssid) {
+        let connection = dbus::proxy(&conn, NM, path.as_str(), CONNECTION_IFACE).await?;
+        connection
+            .call_method("Delete", &())
+            .await
+            .map_err(|e| format!("Failed to forget {}: {}", ssid, e))?;
+        found = true;
+    }
+
+    if !found {
+        return Err(format!("No saved network named {}", ssid));
+    }
+    log::info!("Forgot Wi-Fi network {}", ssid);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn network(ssid: &str, strength: u8, connected: bool) -> WifiNetwork {
+        WifiNetwork {
+            ssid: ssid.to_string(),
+            strength,
+            security: WifiSecurity::Wpa,
+            frequency: 2412,
+            connected,
+            saved: false,
+        }
+    }
+
+    #[test]
+    fn test_security_from_flags() {
+        assert_eq!(security_from_flags(0, 0, 0), WifiSecurity::Open);
+        assert_eq!(security_from_flags(1, 0, 0), WifiSecurity::Wep);
+        assert_eq!(security_from_flags(1, 0x188, 0x188), WifiSecurity::Wpa);
+        assert_eq!(security_from_flags(1, 0, 0x400), WifiSecurity::Wpa3);
+        // WPA2/WPA3 transition mode still accepts a PSK
+        assert_eq!(security_from_flags(1, 0, 0x500), WifiSecurity::Wpa);
+        assert_eq!(security_from_flags(1, 0, 0x200), WifiSecurity::Enterprise);
+    }
+
+    #[test]
+    fn test_dedupe_networks() {
+        let networks = dedupe_networks(vec![
+            network("Home", 40, false),
+            network("", 90, false),
+            network("Home", 70, false),
+            network("Neighbour", 80, false),
+            network("Office", 20, true),
+        ]);
+        let ssids: Vec<&str> = networks.iter().map(|n| n.ssid.as_str()).collect();
+        assert_eq!(ssids, vec!["Office", "Neighbour", "Home"]);
+        assert_eq!(networks[2].strength, 70);
+    }
+
+    #[test]
+    fn test_connection_settings_hidden_wpa3() {
+        let settings = connection_settings("Hidden", Some("secret"), WifiSecurity::Wpa3, true);
+        assert_eq!(settings["802-11-wireless"]["hidden"], Value::from(true));
+        assert_eq!(
+            settings["802-11-wireless-security"]["key-mgmt"],
+            Value::from("sae")
+        );
+    }
+
+    #[test]
+    fn test_connection_settings_open() {
+        let settings = connection_settings("Cafe", None, WifiSecurity::Open, false);
+        assert!(!settings.contains_key("802-11-wireless-security"));
+        assert!(!settings["802-11-wireless"].contains_key("hidden"));
+    }
+}
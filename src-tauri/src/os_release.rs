@@ -0,0 +1,329 @@
+//! os-release parsing and distribution identification
+//!
+//! `/etc/os-release` is a list of shell-compatible `KEY=value` assignments
+//! (see os-release(5)): values may be quoted with single or double quotes and
+//! contain backslash escapes, and `ID_LIKE` lists several space-separated IDs,
+//! closest first.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::system_updates::Distro;
+
+const OS_RELEASE_PATHS: [&str; 2] = ["/etc/os-release", "/usr/lib/os-release"];
+
+/// Gaming-oriented distributions, told apart from their base distribution
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GamingVariant {
+    CachyOS,   // Arch-based, optimized kernels and packages
+    Nobara,    // Fedora-based, gaming patches
+    Bazzite,   // Fedora Atomic image
+    ChimeraOS, // Arch-based image, updated with frzr
+    SteamOS,   // Steam Deck image
+}
+
+/// Distribution described by os-release
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DistroInfo {
+    pub family: Distro,       // Decides which package manager is used
+    pub id: String,           // `ID`, e.g. cachyos
+    pub id_like: Vec<String>, // `ID_LIKE`, closest first
+    pub name: String,         // `NAME`, e.g. CachyOS Linux
+    pub pretty_name: String,  // `PRETTY_NAME`, falls back to `NAME`
+    pub version_id: Option<String>,
+    pub variant_id: Option<String>,
+    pub gaming_variant: Option<GamingVariant>,
+}
+
+/// Unquote and unescape a value the way a POSIX shell would
+fn unquote(raw: &str) -> String {
+    let mut value = String::with_capacity(raw.len());
+    let mut quote: Option<char> = None;
+    let mut chars = raw.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => value.push(c),
+            // Inside double quotes, only these characters can be escaped
+            (Some(_), '\\') => match chars.next() {
+                Some(next @ ('"' | '\\' | '$' | '`')) => value.push(next),
+                Some(next) => {
+                    value.push('\\');
+                    value.push(next);
+                }
+                None => value.push('\\'),
+            },
+            (None, '\\') => value.extend(chars.next()),
+            // Unquoted whitespace ends the value
+            (None, c) if c.is_whitespace() => break,
+            (_, c) => value.push(c),
+        }
+    }
+    value
+}
+
+/// Parse os-release contents into a key/value map
+pub fn parse(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            let valid_key = !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+            valid_key.then(|| (key.to_string(), unquote(value)))
+        })
+        .collect()
+}
+
+/// Contents of the system's os-release file
+pub fn read() -> String {
+    OS_RELEASE_PATHS
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .unwrap_or_default()
+}
+
+/// Family of a single `ID` or `ID_LIKE` entry
+fn family_of(id: &str) -> Option<Distro> {
+    match id {
+        "arch" | "cachyos" | "endeavouros" | "manjaro" | "garuda" | "chimeraos" => {
+            Some(Distro::Arch)
+        }
+        "steamos" => Some(Distro::SteamOS),
+        "debian" | "ubuntu" | "pop" | "linuxmint" | "elementary" | "zorin" => Some(Distro::Debian),
+        "fedora" | "nobara" => Some(Distro::Fedora),
+        "bazzite" | "bluefin" | "aurora" => Some(Distro::Ostree),
+        "suse" | "opensuse" => Some(Distro::OpenSuse),
+        id if id.starts_with("opensuse-") => Some(Distro::OpenSuse),
+        _ => None,
+    }
+}
+
+/// Fedora Atomic desktops keep `ID=fedora` and only differ by `VARIANT_ID`
+fn is_atomic_variant(variant_id: &str) -> bool {
+    matches!(
+        variant_id,
+        "silverblue" | "kinoite" | "sericea" | "onyx" | "cosmic-atomic" | "iot" | "coreos"
+    )
+}
+
+fn gaming_variant_of(id: &str, variant_id: Option<&str>) -> Option<GamingVariant> {
+    match id {
+        "cachyos" => Some(GamingVariant::CachyOS),
+        "nobara" => Some(GamingVariant::Nobara),
+        "bazzite" => Some(GamingVariant::Bazzite),
+        "chimeraos" => Some(GamingVariant::ChimeraOS),
+        "steamos" => Some(GamingVariant::SteamOS),
+        // Older Bazzite images only set their name in VARIANT_ID
+        _ => variant_id
+            .filter(|v| v.starts_with("bazzite"))
+            .map(|_| GamingVariant::Bazzite),
+    }
+}
+
+impl DistroInfo {
+    /// Identify the distribution from os-release contents
+    pub fn from_os_release(contents: &str) -> Self {
+        let fields = parse(contents);
+        let get = |key: &str| fields.get(key).filter(|v| !v.is_empty()).cloned();
+
+        let id = get("ID").unwrap_or_else(|| "linux".to_string());
+        let id_like: Vec<String> = get("ID_LIKE")
+            .map(|v| v.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default();
+        let variant_id = get("VARIANT_ID");
+        let gaming_variant = gaming_variant_of(&id, variant_id.as_deref());
+
+        let family = if gaming_variant == Some(GamingVariant::Bazzite)
+            || variant_id.as_deref().is_some_and(is_atomic_variant)
+        {
+            Distro::Ostree
+        } else {
+            std::iter::once(&id)
+                .chain(&id_like)
+                .find_map(|id| family_of(id))
+                .unwrap_or(Distro::Unknown)
+        };
+
+        let name = get("NAME").unwrap_or_else(|| "Linux".to_string());
+        Self {
+            family,
+            pretty_name: get("PRETTY_NAME").unwrap_or_else(|| name.clone()),
+            name,
+            id,
+            id_like,
+            version_id: get("VERSION_ID"),
+            variant_id,
+            gaming_variant,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! fixture {
+        ($name:literal) => {
+            (
+                $name,
+                include_str!(concat!("../tests/fixtures/os-release/", $name)),
+            )
+        };
+    }
+
+    #[test]
+    fn test_parse_quoting() {
+        let fields = parse(fixture!("popcorn").1);
+        assert_eq!(fields["NAME"], "Popcorn \"Linux\"");
+        assert_eq!(fields["PRETTY_NAME"], "Popcorn $NAME");
+        assert_eq!(fields["VERSION"], "1.0 $stable \\ `edge`");
+        assert_eq!(fields["VERSION_ID"], "1.0");
+        assert_eq!(fields["ID_LIKE"], "");
+        assert_eq!(fields.len(), 6);
+    }
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(unquote("arch"), "arch");
+        assert_eq!(unquote("\"ubuntu debian\""), "ubuntu debian");
+        assert_eq!(unquote("'40.20240720.0'"), "40.20240720.0");
+        assert_eq!(unquote(r#"'single \" kept'"#), r#"single \" kept"#);
+        assert_eq!(unquote(r#""a\nb""#), r#"a\nb"#);
+        assert_eq!(unquote(r"Pop\!_OS"), "Pop!_OS");
+        assert_eq!(unquote("rolling # comment"), "rolling");
+    }
+
+    #[test]
+    fn test_from_os_release() {
+        use GamingVariant::*;
+
+        let cases = [
+            (fixture!("arch"), Distro::Arch, "Arch Linux", None, None),
+            (
+                fixture!("cachyos"),
+                Distro::Arch,
+                "CachyOS",
+                None,
+                Some(CachyOS),
+            ),
+            (
+                fixture!("endeavouros"),
+                Distro::Arch,
+                "EndeavourOS",
+                None,
+                None,
+            ),
+            (
+                fixture!("chimeraos"),
+                Distro::Arch,
+                "ChimeraOS",
+                Some("46"),
+                Some(ChimeraOS),
+            ),
+            (
+                fixture!("steamos"),
+                Distro::SteamOS,
+                "SteamOS",
+                Some("3.5.19"),
+                Some(SteamOS),
+            ),
+            (
+                fixture!("debian"),
+                Distro::Debian,
+                "Debian GNU/Linux 12 (bookworm)",
+                Some("12"),
+                None,
+            ),
+            (
+                fixture!("ubuntu"),
+                Distro::Debian,
+                "Ubuntu 24.04.1 LTS",
+                Some("24.04"),
+                None,
+            ),
+            (
+                fixture!("pop"),
+                Distro::Debian,
+                "Pop!_OS 22.04 LTS",
+                Some("22.04"),
+                None,
+            ),
+            (
+                fixture!("fedora"),
+                Distro::Fedora,
+                "Fedora Linux 40 (Workstation Edition)",
+                Some("40"),
+                None,
+            ),
+            (
+                fixture!("silverblue"),
+                Distro::Ostree,
+                "Fedora Linux 40.20240720.0 (Silverblue)",
+                Some("40"),
+                None,
+            ),
+            (
+                fixture!("nobara"),
+                Distro::Fedora,
+                "Nobara Linux 40 (KDE Plasma)",
+                Some("40"),
+                Some(Nobara),
+            ),
+            (
+                fixture!("bazzite"),
+                Distro::Ostree,
+                "Bazzite 40 (FROM Fedora Kinoite)",
+                Some("40"),
+                Some(Bazzite),
+            ),
+            (
+                fixture!("opensuse-tumbleweed"),
+                Distro::OpenSuse,
+                "openSUSE Tumbleweed",
+                Some("20240918"),
+                None,
+            ),
+            (
+                fixture!("popcorn"),
+                Distro::Unknown,
+                "Popcorn $NAME",
+                Some("1.0"),
+                None,
+            ),
+        ];
+
+        for ((fixture, contents), family, pretty_name, version_id, gaming_variant) in cases {
+            let info = DistroInfo::from_os_release(contents);
+            assert_eq!(info.family, family, "{}", fixture);
+            assert_eq!(info.pretty_name, pretty_name, "{}", fixture);
+            assert_eq!(info.version_id.as_deref(), version_id, "{}", fixture);
+            assert_eq!(info.gaming_variant, gaming_variant, "{}", fixture);
+        }
+    }
+
+    #[test]
+    fn test_id_like_order() {
+        let info = DistroInfo::from_os_release(fixture!("nobara").1);
+        assert_eq!(info.id_like, vec!["rhel", "centos", "fedora"]);
+
+        let info = DistroInfo::from_os_release(fixture!("pop").1);
+        assert_eq!(info.id_like, vec!["ubuntu", "debian"]);
+    }
+
+    #[test]
+    fn test_empty_os_release() {
+        let info = DistroInfo::from_os_release("");
+        assert_eq!(info.id, "linux");
+        assert_eq!(info.pretty_name, "Linux");
+        assert_eq!(info.family, Distro::Unknown);
+    }
+}
@@ -0,0 +1,606 @@
+//! Updates of image-based ostree systems (Fedora Atomic, Bazzite, bootc)
+//!
+//! An upgrade is staged as a new deployment that is only booted into on the
+//! next restart, and the previous deployment is kept for rollback. rpm-ostree
+//! is used when present (it also handles layered packages), bootc on images
+//! that only ship bootc.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command as TokioCommand;
+
+use crate::system_updates::{
+    categorize_package, PackageCategory, Status, UpdatePackage, UpdateProgressEvent, UpdateSource,
+};
+use crate::update_failures::UpdateError;
+use crate::update_jobs::UpdateJob;
+
+/// Name of the OS image in update lists when no package diff is available
+pub const IMAGE_NAME: &str = "Image système";
+
+const OSTREE_BOOTED: &str = "/run/ostree-booted";
+const EXIT_NO_UPDATE: i32 = 77; // `rpm-ostree upgrade --check` exit code when up to date
+
+/// Tool managing the deployments
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum OstreeTool {
+    RpmOstree,
+    Bootc,
+}
+
+/// A bootable deployment
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Deployment {
+    pub version: String,
+    pub checksum: String,  // ostree commit or container image digest
+    pub timestamp: String, // RFC 3339
+    pub origin: String,    // Container image reference or ostree refspec
+    pub booted: bool,
+    pub staged: bool,
+}
+
+/// Deployments of the system, in boot order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OstreeStatus {
+    pub tool: OstreeTool,
+    pub deployments: Vec<Deployment>,
+    pub reboot_pending: bool,     // The next boot uses another deployment
+    pub rollback_available: bool, // A previous deployment can be booted again
+}
+
+/// Upgrade reported by a check: the new version and the package diff, if known
+#[derive(Debug, Clone, Default, PartialEq)]
+struct PendingUpgrade {
+    version: String,
+    packages: Vec<(String, String, String)>, // (name, current, new)
+}
+
+/// rpm-ostree when available, bootc otherwise, on a booted ostree system
+pub fn detect_tool() -> Option<OstreeTool> {
+    if !Path::new(OSTREE_BOOTED).exists() {
+        return None;
+    }
+    if which::which("rpm-ostree").is_ok() {
+        return Some(OstreeTool::RpmOstree);
+    }
+    if which::which("bootc").is_ok() {
+        return Some(OstreeTool::Bootc);
+    }
+    None
+}
+
+fn tool() -> Result<OstreeTool, String> {
+    detect_tool().ok_or_else(|| "Neither rpm-ostree nor bootc is available".to_string())
+}
+
+/// Split a `name-version-release.arch` NEVRA into name and version-release
+fn split_nevra(nevra: &str) -> (String, String) {
+    let without_arch = match nevra.rsplit_once('.') {
+        Some((rest, "x86_64" | "i686" | "aarch64" | "noarch")) => rest,
+        _ => nevra,
+    };
+    let mut parts = without_arch.rsplitn(3, '-');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(release), Some(version), Some(name)) => {
+            (name.to_string(), format!("{}-{}", version, release))
+        }
+        _ => (nevra.to_string(), String::new()),
+    }
+}
+
+/// Parse `rpm-ostree upgrade --check`/`--preview` output
+fn parse_rpm_ostree_check(stdout: &str) -> Option<PendingUpgrade> {
+    let mut lines = stdout
+        .lines()
+        .skip_while(|l| !l.starts_with("AvailableUpdate:"));
+    lines.next()?;
+
+    let mut upgrade = PendingUpgrade::default();
+    let mut section = "";
+    for line in lines {
+        let (key, value) = match line.split_once(": ") {
+            Some((key, value)) if !key.trim().is_empty() && !key.trim().contains(' ') => {
+                (key.trim(), value.trim())
+            }
+            _ => ("", line.trim()),
+        };
+        if !key.is_empty() {
+            section = key;
+        }
+
+        match section {
+            "Version" => {
+                // "39.20231110.0 (2023-11-10T00:42:12Z)"
+                upgrade.version = value.split_whitespace().next().unwrap_or("").to_string();
+            }
+            "Upgraded" | "Downgraded" => {
+                // "firefox 119.0-1.fc39 -> 119.0.1-1.fc39"
+                let tokens: Vec<&str> = value.split_whitespace().collect();
+                if let [name, current, "->", new] = tokens.as_slice() {
+                    upgrade
+                        .packages
+                        .push((name.to_string(), current.to_string(), new.to_string()));
+                }
+            }
+            "Added" if !value.is_empty() => {
+                let (name, version) = split_nevra(value);
+                upgrade.packages.push((name, String::new(), version));
+            }
+            _ => {}
+        }
+    }
+
+    Some(upgrade)
+}
+
+/// Parse `bootc upgrade --check` output
+fn parse_bootc_check(stdout: &str) -> Option<PendingUpgrade> {
+    let mut lines = stdout
+        .lines()
+        .skip_while(|l| !l.starts_with("Update available"));
+    lines.next()?;
+
+    let version = lines
+        .find_map(|l| l.trim().strip_prefix("Version:"))
+        .map(|v| v.trim().to_string())
+        .unwrap_or_default();
+    Some(PendingUpgrade {
+        version,
+        packages: Vec::new(),
+    })
+}
+
+/// `rpm-ostree status --json` deployments, in boot order
+fn parse_rpm_ostree_status(json: &str) -> Option<Vec<Deployment>> {
+    let status: serde_json::Value = serde_json::from_str(json).ok()?;
+    let deployments = status.get("deployments")?.as_array()?;
+
+    Some(
+        deployments
+            .iter()
+            .map(|d| {
+                let text = |key: &str| {
+                    d.get(key)
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string()
+                };
+                let flag = |key: &str| d.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+                Deployment {
+                    version: text("version"),
+                    checksum: text("checksum"),
+                    timestamp: d
+                        .get("timestamp")
+                        .and_then(|t| t.as_i64())
+                        .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                        .map(|t| t.to_rfc3339())
+                        .unwrap_or_default(),
+                    origin: d
+                        .get("container-image-reference")
+                        .or_else(|| d.get("origin"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string(),
+                    booted: flag("booted"),
+                    staged: flag("staged"),
+                }
+            })
+            .collect(),
+    )
+}
+
+/// `bootc status --json` entries, in boot order, with whether a rollback is queued
+fn parse_bootc_status(json: &str) -> Option<(Vec<Deployment>, bool)> {
+    let host: serde_json::Value = serde_json::from_str(json).ok()?;
+    let status = host.get("status")?;
+
+    let entry = |key: &str| -> Option<Deployment> {
+        let image = status.get(key)?.get("image")?;
+        let text =
+            |v: Option<&serde_json::Value>| v.and_then(|v| v.as_str()).unwrap_or("").to_string();
+        Some(Deployment {
+            version: text(image.get("version")),
+            checksum: text(image.get("imageDigest")),
+            timestamp: text(image.get("timestamp")),
+            origin: text(image.get("image").and_then(|i| i.get("image"))),
+            booted: key == "booted",
+            staged: key == "staged",
+        })
+    };
+
+    let rollback_queued = status
+        .get("rollbackQueued")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let order: &[&str] = if rollback_queued {
+        &["rollback", "booted"]
+    } else {
+        &["staged", "booted", "rollback"]
+    };
+    Some((
+        order.iter().filter_map(|key| entry(key)).collect(),
+        rollback_queued,
+    ))
+}
+
+fn run(command: &[&str]) -> Result<std::process::Output, String> {
+    Command::new(command[0])
+        .args(&command[1..])
+        .env("LC_ALL", "C")
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", command.join(" "), e))
+}
+
+/// Deployments and rollback state of the system
+pub fn get_status() -> Result<OstreeStatus, String> {
+    let tool = tool()?;
+
+    let (deployments, rollback_queued) = match tool {
+        OstreeTool::RpmOstree => {
+            let output = run(&["rpm-ostree", "status", "--json"])?;
+            let deployments = parse_rpm_ostree_status(&String::from_utf8_lossy(&output.stdout))
+                .ok_or("Invalid rpm-ostree status output")?;
+            (deployments, false)
+        }
+        OstreeTool::Bootc => {
+            let output = run(&["sudo", "bootc", "status", "--json"])?;
+            parse_bootc_status(&String::from_utf8_lossy(&output.stdout))
+                .ok_or("Invalid bootc status output")?
+        }
+    };
+
+    // The first deployment is the one booted next
+    let reboot_pending = rollback_queued || deployments.first().is_some_and(|d| !d.booted);
+    let rollback_available = deployments.iter().any(|d| !d.booted && !d.staged);
+
+    Ok(OstreeStatus {
+        tool,
+        deployments,
+        reboot_pending,
+        rollback_available,
+    })
+}
+
+/// Whether a staged upgrade or a rollback waits for a reboot
+pub fn reboot_pending() -> bool {
+    get_status().is_ok_and(|status| status.reboot_pending)
+}
+
+async fn check_upgrade(tool: OstreeTool) -> Result<Option<PendingUpgrade>, String> {
+    match tool {
+        OstreeTool::RpmOstree => {
+            // The package diff needs --preview, which container origins do not support
+            for flag in ["--preview", "--check"] {
+                let output = TokioCommand::new("sudo")
+                    .args(["rpm-ostree", "upgrade", flag])
+                    .env("LC_ALL", "C")
+                    .output()
+                    .await
+                    .map_err(|e| format!("Failed to run rpm-ostree: {}", e))?;
+                match output.status.code() {
+                    Some(0) => {
+                        return Ok(parse_rpm_ostree_check(&String::from_utf8_lossy(
+                            &output.stdout,
+                        )))
+                    }
+                    Some(EXIT_NO_UPDATE) => return Ok(None),
+                    _ => log::debug!(
+                        "rpm-ostree upgrade {} failed: {}",
+                        flag,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ),
+                }
+            }
+            Err("rpm-ostree upgrade --check failed".to_string())
+        }
+        OstreeTool::Bootc => {
+            let output = TokioCommand::new("sudo")
+                .args(["bootc", "upgrade", "--check"])
+                .env("LC_ALL", "C")
+                .output()
+                .await
+                .map_err(|e| format!("Failed to run bootc: {}", e))?;
+            if !output.status.success() {
+                return Err(format!(
+                    "bootc upgrade --check failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+            Ok(parse_bootc_check(&String::from_utf8_lossy(&output.stdout)))
+        }
+    }
+}
+
+/// Pending changes of the next deployment: its package diff, or the image itself
+pub async fn check_updates() -> Result<Vec<UpdatePackage>, String> {
+    let tool = tool()?;
+    let Some(upgrade) = check_upgrade(tool).await? else {
+        return Ok(Vec::new());
+    };
+
+    // Everything only applies once the new deployment is booted
+    if upgrade.packages.is_empty() {
+        let current = tokio::task::spawn_blocking(get_status)
+            .await
+            .map_err(|e| format!("Status task failed: {}", e))??
+            .deployments
+            .into_iter()
+            .find(|d| d.booted)
+            .map(|d| d.version)
+            .unwrap_or_default();
+        return Ok(vec![UpdatePackage {
+            name: IMAGE_NAME.to_string(),
+            current_version: current,
+            new_version: upgrade.version,
+            category: PackageCategory::System,
+            size: 0,
+            installed_size_delta: 0,
+            critical: true,
+            source: UpdateSource::OsImage,
+        }]);
+    }
+
+    Ok(upgrade
+        .packages
+        .into_iter()
+        .map(|(name, current, new)| UpdatePackage {
+            category: categorize_package(&name),
+            name,
+            current_version: current,
+            new_version: new,
+            size: 0,
+            installed_size_delta: 0,
+            critical: true,
+            source: UpdateSource::OsImage,
+        })
+        .collect())
+}
+
+/// Percentage of an rpm-ostree/bootc progress line (`Receiving objects: 42% (123/456)`)
+fn parse_progress(line: &str) -> Option<f32> {
+    line.split_whitespace()
+        .find_map(|t| t.strip_suffix('%')?.parse::<f32>().ok())
+        .map(|p| p.clamp(0.0, 100.0))
+}
+
+/// Stage the upgrade as the next deployment. Returns whether one was staged.
+pub async fn stage_upgrade(job: &UpdateJob) -> Result<bool, UpdateError> {
+    let tool = tool().map_err(UpdateError::unknown)?;
+    let command: &[&str] = match tool {
+        OstreeTool::RpmOstree => &["rpm-ostree", "upgrade"],
+        OstreeTool::Bootc => &["bootc", "upgrade"],
+    };
+
+    let mut child = TokioCommand::new("sudo")
+        .args(command)
+        .env("LC_ALL", "C")
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| UpdateError::unknown(format!("Failed to start {}: {}", command[0], e)))?;
+    job.attach(child.id());
+
+    let stderr = child.stderr.take().expect("Failed to capture stderr");
+    let stderr_task = tokio::spawn(async move {
+        let mut lines = Vec::new();
+        let mut reader = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = reader.next_line().await {
+            lines.push(line);
+        }
+        lines
+    });
+
+    let stdout = child.stdout.take().expect("Failed to capture stdout");
+    let mut reader = BufReader::new(stdout).lines();
+    let mut staged = false;
+    while let Ok(Some(line)) = reader.next_line().await {
+        if line.starts_with("No upgrade available") || line.starts_with("No changes in") {
+            continue;
+        }
+        let (stage, progress) =
+            if line.starts_with("Staging deployment") || line.starts_with("Queued for next boot") {
+                staged = true;
+                (Status::Installing, 100.0)
+            } else if let Some(progress) = parse_progress(&line) {
+                (Status::Downloading, progress)
+            } else {
+                continue;
+            };
+        job.progress(UpdateProgressEvent {
+            stage,
+            package_name: IMAGE_NAME.to_string(),
+            progress,
+            package_progress: progress,
+            downloaded: "".to_string(),
+            total: "".to_string(),
+            speed: "".to_string(),
+            eta: "".to_string(),
+        });
+    }
+
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| UpdateError::unknown(format!("Failed to wait for {}: {}", command[0], e)))?;
+    job.detach();
+    let stderr_lines = stderr_task.await.unwrap_or_default();
+
+    if !status.success() {
+        return Err(UpdateError::unknown(
+            stderr_lines
+                .iter()
+                .find(|l| l.starts_with("error:") || l.starts_with("ERROR"))
+                .cloned()
+                .unwrap_or_else(|| format!("{} failed", command.join(" "))),
+        ));
+    }
+
+    // Older rpm-ostree versions deploy without staging; the status tells
+    Ok(staged || reboot_pending())
+}
+
+/// Make the previous deployment the default for the next boot
+pub fn rollback() -> Result<(), String> {
+    let command: &[&str] = match tool()? {
+        OstreeTool::RpmOstree => &["sudo", "rpm-ostree", "rollback"],
+        OstreeTool::Bootc => &["sudo", "bootc", "rollback"],
+    };
+    let output = run(command)?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} rollback failed: {}",
+            command[1],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    log::info!("Rolled back to the previous {} deployment", command[1]);
+    Ok(())
+}
+
+/// Sudoers rules allowing Pixxiden to check, stage and roll back deployments
+pub fn sudoers_rules(username: &str) -> String {
+    format!(
+        r#"
+# Allow Pixxiden to stage and roll back ostree deployments
+{username} ALL=(ALL) NOPASSWD: /usr/bin/rpm-ostree upgrade --preview
+{username} ALL=(ALL) NOPASSWD: /usr/bin/rpm-ostree upgrade --check
+{username} ALL=(ALL) NOPASSWD: /usr/bin/rpm-ostree upgrade
+{username} ALL=(ALL) NOPASSWD: /usr/bin/rpm-ostree rollback
+{username} ALL=(ALL) NOPASSWD: /usr/bin/bootc status --json
+{username} ALL=(ALL) NOPASSWD: /usr/bin/bootc upgrade --check
+{username} ALL=(ALL) NOPASSWD: /usr/bin/bootc upgrade
+{username} ALL=(ALL) NOPASSWD: /usr/bin/bootc rollback
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rpm_ostree_preview() {
+        let stdout = "1 metadata, 0 content objects fetched; 469 B transferred in 1 seconds\n\
+Note: --check and --preview may be unreliable.  See https://github.com/coreos/rpm-ostree/issues/1579\n\
+AvailableUpdate:\n\
+        Version: 39.20231110.0 (2023-11-10T00:42:12Z)\n\
+         Commit: 4b5e0d6a1d0c2f1e7d6b9c1a0e2f3d4c5b6a7980\n\
+   GPGSignature: Valid signature by 115DF9AEF857853EE8445D0A0727707EA15B79CC\n\
+       Upgraded: firefox 119.0-1.fc39 -> 119.0.1-1.fc39\n\
+                 mesa-dri-drivers 23.2.1-1.fc39 -> 23.2.1-2.fc39\n\
+          Added: gamescope-3.13.8-1.fc39.x86_64\n";
+        let upgrade = parse_rpm_ostree_check(stdout).unwrap();
+        assert_eq!(upgrade.version, "39.20231110.0");
+        assert_eq!(
+            upgrade.packages,
+            vec![
+                (
+                    "firefox".to_string(),
+                    "119.0-1.fc39".to_string(),
+                    "119.0.1-1.fc39".to_string()
+                ),
+                (
+                    "mesa-dri-drivers".to_string(),
+                    "23.2.1-1.fc39".to_string(),
+                    "23.2.1-2.fc39".to_string()
+                ),
+                (
+                    "gamescope".to_string(),
+                    "".to_string(),
+                    "3.13.8-1.fc39".to_string()
+                ),
+            ]
+        );
+        assert!(parse_rpm_ostree_check("No updates available.\n").is_none());
+    }
+
+    #[test]
+    fn test_parse_rpm_ostree_container_check() {
+        let stdout =
+            "Pulling manifest: ostree-image-signed:docker://ghcr.io/ublue-os/bazzite:stable\n\
+AvailableUpdate:\n\
+        Version: 40.20240616.0 (2024-06-16T05:06:27Z)\n\
+         Digest: sha256:3f1a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7f8\n";
+        let upgrade = parse_rpm_ostree_check(stdout).unwrap();
+        assert_eq!(upgrade.version, "40.20240616.0");
+        assert!(upgrade.packages.is_empty());
+    }
+
+    #[test]
+    fn test_parse_bootc_check() {
+        let stdout = "Update available for: docker://quay.io/fedora/fedora-bootc:40\n  \
+                      Version: 40.20240619.0\n  Digest: sha256:abcd\n";
+        assert_eq!(parse_bootc_check(stdout).unwrap().version, "40.20240619.0");
+        assert!(
+            parse_bootc_check("No changes in: docker://quay.io/fedora/fedora-bootc:40\n").is_none()
+        );
+    }
+
+    #[test]
+    fn test_parse_rpm_ostree_status() {
+        let json = r#"{"deployments": [
+            {"checksum": "aaa", "version": "40.20240616.0", "timestamp": 1718514387, "booted": false, "staged": true,
+             "container-image-reference": "ostree-image-signed:docker://ghcr.io/ublue-os/bazzite:stable"},
+            {"checksum": "bbb", "version": "40.20240610.0", "timestamp": 1718000000, "booted": true, "staged": false,
+             "origin": "fedora:fedora/40/x86_64/kinoite"}
+        ]}"#;
+        let deployments = parse_rpm_ostree_status(json).unwrap();
+        assert_eq!(deployments.len(), 2);
+        assert!(deployments[0].staged && !deployments[0].booted);
+        assert_eq!(deployments[0].timestamp, "2024-06-16T05:06:27+00:00");
+        assert_eq!(deployments[1].origin, "fedora:fedora/40/x86_64/kinoite");
+    }
+
+    #[test]
+    fn test_parse_bootc_status() {
+        let json = r#"{"apiVersion": "org.containers.bootc/v1", "kind": "BootcHost", "status": {
+            "staged": null,
+            "booted": {"image": {"image": {"image": "quay.io/fedora/fedora-bootc:40", "transport": "registry"},
+                       "version": "40.20240619.0", "timestamp": "2024-06-19T00:00:00Z", "imageDigest": "sha256:bbb"}},
+            "rollback": {"image": {"image": {"image": "quay.io/fedora/fedora-bootc:40", "transport": "registry"},
+                         "version": "40.20240612.0", "timestamp": "2024-06-12T00:00:00Z", "imageDigest": "sha256:aaa"}},
+            "rollbackQueued": true
+        }}"#;
+        let (deployments, rollback_queued) = parse_bootc_status(json).unwrap();
+        assert!(rollback_queued);
+        assert_eq!(deployments[0].version, "40.20240612.0");
+        assert!(deployments[1].booted);
+        assert_eq!(deployments[1].origin, "quay.io/fedora/fedora-bootc:40");
+    }
+
+    #[test]
+    fn test_split_nevra() {
+        assert_eq!(
+            split_nevra("gamescope-3.13.8-1.fc39.x86_64"),
+            ("gamescope".to_string(), "3.13.8-1.fc39".to_string())
+        );
+        assert_eq!(
+            split_nevra("mesa-vulkan-drivers-23.2.1-2.fc39"),
+            (
+                "mesa-vulkan-drivers".to_string(),
+                "23.2.1-2.fc39".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_progress() {
+        assert_eq!(
+            parse_progress("Receiving objects: 42% (123/456) 12.3 MB"),
+            Some(42.0)
+        );
+        assert_eq!(parse_progress("Staging deployment...done"), None);
+    }
+
+    #[test]
+    fn test_sudoers_rules() {
+        let rules = sudoers_rules("testuser");
+        assert!(rules.contains("/usr/bin/rpm-ostree upgrade --preview"));
+        assert!(rules.contains("/usr/bin/bootc rollback"));
+    }
+}
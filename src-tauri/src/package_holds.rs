@@ -0,0 +1,363 @@
+//! Packages kept at their installed version
+//!
+//! The hold list belongs to Pixxiden and applies to the upgrades it runs:
+//! pacman and dnf skip the packages for the run (`--ignore`, `--exclude`),
+//! apt gets them marked with `apt-mark hold`. A dry run first makes sure no
+//! other upgrade depends on a held version.
+//!
+//! Saving the list asks for the password: the sudoers rules are regenerated
+//! with the exact command lines for the new list, as a wildcard would let
+//! any option through to the package manager, and apt holds are changed
+//! right away.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::sudoers;
+use crate::system_updates::{self, dnf_package_name, Distro, UpdatePackage};
+use crate::update_failures::{UpdateError, UpdateFailureKind};
+
+const APP_DIR: &str = "com.Pixxiden.launcher";
+const HOLDS_FILE: &str = "package-holds.json";
+
+/// An upgrade that a held package would leave with an unsatisfied dependency
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HoldConflict {
+    pub package: String,    // Package that cannot be upgraded
+    pub dependency: String, // Requirement only the held upgrade provides
+}
+
+/// Package names as accepted by pacman, apt and dnf. They end up on sudo
+/// command lines, so anything that could pass for an option is refused.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 255
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "@._+-".contains(c))
+}
+
+fn holds_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_DIR).join(HOLDS_FILE))
+}
+
+/// Sorted names without duplicates
+fn normalize(packages: Vec<String>) -> Vec<String> {
+    let mut packages: Vec<String> = packages
+        .into_iter()
+        .map(|name| name.trim().to_string())
+        .collect();
+    packages.sort();
+    packages.dedup();
+    packages
+}
+
+/// The saved hold list, empty when there is none
+pub fn load() -> Vec<String> {
+    let Some(contents) = holds_path().and_then(|path| std::fs::read_to_string(path).ok()) else {
+        return Vec::new();
+    };
+    match serde_json::from_str::<Vec<String>>(&contents) {
+        Ok(packages) => normalize(packages)
+            .into_iter()
+            .filter(|name| is_valid_name(name))
+            .collect(),
+        Err(e) => {
+            log::warn!("Ignoring invalid {}: {}", HOLDS_FILE, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Sorted hold list, refusing names that are not package names
+fn validate(packages: Vec<String>) -> Result<Vec<String>, String> {
+    let packages = normalize(packages);
+    if let Some(invalid) = packages.iter().find(|name| !is_valid_name(name)) {
+        return Err(format!("Invalid package name: {}", invalid));
+    }
+    Ok(packages)
+}
+
+/// Hold list as one sudoers argument (`a\,b`), matching `holds.join(",")`
+pub fn sudoers_list(holds: &[String]) -> String {
+    holds.join("\\,")
+}
+
+fn write(packages: &[String]) -> Result<(), String> {
+    let path = holds_path().ok_or("No data directory")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(packages)
+        .map_err(|e| format!("Failed to serialize {}: {}", HOLDS_FILE, e))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// apt-mark the added packages and release the removed ones
+fn sync_apt_holds(password: &str, previous: &[String], holds: &[String]) -> Result<(), String> {
+    let added: Vec<&str> = holds
+        .iter()
+        .filter(|name| !previous.contains(name))
+        .map(String::as_str)
+        .collect();
+    let removed: Vec<&str> = previous
+        .iter()
+        .filter(|name| !holds.contains(name))
+        .map(String::as_str)
+        .collect();
+
+    if !added.is_empty() {
+        let mut args = vec!["apt-mark", "hold"];
+        args.extend(added);
+        sudoers::run_with_password(password, &args)?;
+    }
+    if !removed.is_empty() {
+        let mut args = vec!["apt-mark", "unhold"];
+        args.extend(removed);
+        sudoers::run_with_password(password, &args)?;
+    }
+    Ok(())
+}
+
+/// Replace the hold list, returning it as saved. The sudoers rules only
+/// allow the command lines of the saved list, so they are updated first.
+/// apt-mark comes last and the saved list is put back if it fails.
+pub async fn save(packages: Vec<String>, password: String) -> Result<Vec<String>, String> {
+    let holds = validate(packages)?;
+    let previous = load();
+
+    sudoers::configure_sudoers_with_holds(password.clone(), &holds).await?;
+    write(&holds)?;
+
+    if system_updates::detect_distro() == Distro::Debian {
+        if let Err(e) = sync_apt_holds(&password, &previous, &holds) {
+            // Undo the marks that went through, then the list and its rules
+            if let Err(e) = sync_apt_holds(&password, &holds, &previous) {
+                log::warn!("Failed to restore apt holds: {}", e);
+            }
+            write(&previous)?;
+            sudoers::configure_sudoers_with_holds(password, &previous).await?;
+            return Err(e);
+        }
+    }
+    Ok(holds)
+}
+
+/// Split pending updates into upgradable and held ones
+pub fn split_held(
+    packages: Vec<UpdatePackage>,
+    holds: &[String],
+) -> (Vec<UpdatePackage>, Vec<UpdatePackage>) {
+    packages.into_iter().partition(|p| !holds.contains(&p.name))
+}
+
+fn push_conflict(conflicts: &mut Vec<HoldConflict>, package: &str, dependency: &str) {
+    if !conflicts.iter().any(|c| c.package == package) {
+        conflicts.push(HoldConflict {
+            package: package.to_string(),
+            dependency: dependency.to_string(),
+        });
+    }
+}
+
+/// `pacman -Sup --ignore` output:
+/// `warning: cannot resolve "nvidia-utils=560.35.03-1", a dependency of "lib32-nvidia-utils"`
+/// and `:: unable to satisfy dependency 'nvidia-utils=560.35.03-1' required by lib32-nvidia-utils`
+pub fn parse_pacman_conflicts(output: &str) -> Vec<HoldConflict> {
+    let mut conflicts = Vec::new();
+    for line in output.lines().map(str::trim) {
+        if let Some(rest) = line.strip_prefix("warning: cannot resolve \"") {
+            if let Some((dependency, package)) = rest.split_once("\", a dependency of \"") {
+                push_conflict(&mut conflicts, package.trim_end_matches('"'), dependency);
+            }
+        } else if let Some(rest) = line.strip_prefix(":: unable to satisfy dependency '") {
+            if let Some((dependency, package)) = rest.split_once("' required by ") {
+                push_conflict(&mut conflicts, package, dependency);
+            }
+        }
+    }
+    conflicts
+}
+
+/// `dnf upgrade --assumeno --exclude` problems:
+/// ` - package akmod-nvidia-3:560.35.03-1.fc40.x86_64 requires nvidia-kmod-common >= 3:560.35.03, but none of the providers can be installed`
+pub fn parse_dnf_conflicts(output: &str) -> Vec<HoldConflict> {
+    let mut conflicts = Vec::new();
+    for line in output.lines() {
+        let Some((_, rest)) = line.split_once("package ") else {
+            continue;
+        };
+        let Some((nevra, rest)) = rest.split_once(" requires ") else {
+            continue;
+        };
+        let Some((dependency, _)) = rest.split_once(", but none of the providers") else {
+            continue;
+        };
+        push_conflict(&mut conflicts, &dnf_package_name(nevra), dependency);
+    }
+    conflicts
+}
+
+/// Packages listed under "The following packages have been kept back:"
+/// in `apt-get -s upgrade` output
+pub fn parse_apt_kept_back(output: &str) -> Vec<String> {
+    let mut kept_back = Vec::new();
+    let mut in_list = false;
+    for line in output.lines() {
+        if line.starts_with("The following packages have been kept back:") {
+            in_list = true;
+        } else if in_list && line.starts_with(' ') {
+            kept_back.extend(line.split_whitespace().map(str::to_string));
+        } else {
+            in_list = false;
+        }
+    }
+    kept_back
+}
+
+/// `apt-get -s install` unmet dependencies:
+/// ` libnvidia-gl-550 : Depends: libnvidia-common-550 (= 550.120-0ubuntu1) but 550.107-0ubuntu1 is to be installed`
+pub fn parse_apt_conflicts(output: &str) -> Vec<HoldConflict> {
+    let mut conflicts = Vec::new();
+    let mut package = None;
+    for line in output.lines() {
+        // Further dependencies of the same package are only indented
+        let rest = match line.split_once(" : ") {
+            Some((name, rest)) if line.starts_with(' ') => {
+                package = Some(name.trim().to_string());
+                rest
+            }
+            _ => line.trim_start(),
+        };
+        let (Some(package), Some(dependency)) = (&package, rest.strip_prefix("Depends: ")) else {
+            continue;
+        };
+        let dependency = dependency.split(" but ").next().unwrap_or(dependency);
+        push_conflict(&mut conflicts, package, dependency.trim());
+    }
+    conflicts
+}
+
+/// Error stopping an upgrade before it starts
+pub fn conflict_error(conflicts: &[HoldConflict]) -> UpdateError {
+    let names: Vec<&str> = conflicts.iter().map(|c| c.package.as_str()).collect();
+    UpdateError {
+        kind: UpdateFailureKind::Dependency,
+        message: format!("Held packages block the upgrade of {}", names.join(", ")),
+        packages: names.iter().map(|name| name.to_string()).collect(),
+        remediation: "Release the hold on the packages they depend on, or hold them as well, \
+                      then retry."
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_name() {
+        assert!(is_valid_name("nvidia-dkms"));
+        assert!(is_valid_name("linux-cachyos"));
+        assert!(is_valid_name("libstdc++6"));
+        assert!(is_valid_name("lib32-nvidia-utils"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("--overwrite=*"));
+        assert!(!is_valid_name("mesa nvidia"));
+        assert!(!is_valid_name("mesa,nvidia"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let holds = validate(vec![
+            " nvidia-utils".to_string(),
+            "linux".to_string(),
+            "nvidia-utils".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(holds, vec!["linux", "nvidia-utils"]);
+        assert_eq!(sudoers_list(&holds), "linux\\,nvidia-utils");
+        assert!(validate(vec!["x --config /tmp/evil.conf".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_pacman_conflicts() {
+        let output = ":: Starting full system upgrade...\n\
+                      warning: nvidia-utils: ignoring package upgrade (560.35.03-1 => 565.57.01-1)\n\
+                      warning: cannot resolve \"nvidia-utils=565.57.01\", a dependency of \"lib32-nvidia-utils\"\n\
+                      :: The following package cannot be upgraded due to unresolvable dependencies:\n\
+                      \x20     lib32-nvidia-utils\n\
+                      error: failed to prepare transaction (could not satisfy dependencies)\n\
+                      :: unable to satisfy dependency 'nvidia-utils=565.57.01' required by lib32-nvidia-utils\n\
+                      :: unable to satisfy dependency 'nvidia-utils=565.57.01' required by nvidia-settings\n";
+        assert_eq!(
+            parse_pacman_conflicts(output),
+            vec![
+                HoldConflict {
+                    package: "lib32-nvidia-utils".to_string(),
+                    dependency: "nvidia-utils=565.57.01".to_string(),
+                },
+                HoldConflict {
+                    package: "nvidia-settings".to_string(),
+                    dependency: "nvidia-utils=565.57.01".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_dnf_conflicts() {
+        let output = "Error: \n Problem: package akmod-nvidia-3:565.57.01-1.fc40.x86_64 requires nvidia-kmod-common >= 3:565.57.01, but none of the providers can be installed\n  - cannot install the best update candidate for package akmod-nvidia-3:560.35.03-1.fc40.x86_64\n";
+        let conflicts = parse_dnf_conflicts(output);
+        assert_eq!(
+            conflicts,
+            vec![HoldConflict {
+                package: "akmod-nvidia".to_string(),
+                dependency: "nvidia-kmod-common >= 3:565.57.01".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_apt_conflicts() {
+        let upgrade = "Calculating upgrade...\n\
+                       The following packages have been kept back:\n\
+                       \x20 libnvidia-gl-550 nvidia-utils-550\n\
+                       The following packages will be upgraded:\n\
+                       \x20 mesa-vulkan-drivers\n\
+                       1 upgraded, 0 newly installed, 0 to remove and 2 not upgraded.\n";
+        assert_eq!(
+            parse_apt_kept_back(upgrade),
+            vec!["libnvidia-gl-550", "nvidia-utils-550"]
+        );
+
+        let install = "Some packages could not be installed.\n\
+                       The following packages have unmet dependencies:\n\
+                       \x20libnvidia-gl-550 : Depends: libnvidia-common-550 (= 550.120-0ubuntu1) but 550.107-0ubuntu1 is to be installed\n\
+                       \x20                   Depends: libnvidia-egl-550 (= 550.120-0ubuntu1) but it is not going to be installed\n\
+                       E: Unable to correct problems, you have held broken packages.\n";
+        assert_eq!(
+            parse_apt_conflicts(install),
+            vec![HoldConflict {
+                package: "libnvidia-gl-550".to_string(),
+                dependency: "libnvidia-common-550 (= 550.120-0ubuntu1)".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_conflict_error() {
+        let error = conflict_error(&parse_pacman_conflicts(
+            ":: unable to satisfy dependency 'linux=6.10.10' required by linux-headers\n",
+        ));
+        assert_eq!(error.kind, UpdateFailureKind::Dependency);
+        assert_eq!(error.packages, vec!["linux-headers"]);
+        assert_eq!(
+            error.message,
+            "Held packages block the upgrade of linux-headers"
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! Package manager logs (`/var/log/pacman.log`, `/var/log/dpkg.log`)
+//!
+//! Every transaction is logged there, whoever started it, so reading them
+//! around Pixxiden's own update runs shows what was installed or removed from
+//! a terminal or another tool in between.
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serde::Serialize;
+
+use crate::system_updates::Distro;
+
+const PACMAN_LOG: &str = "/var/log/pacman.log";
+const DPKG_LOG: &str = "/var/log/dpkg.log";
+
+/// What a transaction did to a package
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PackageAction {
+    Installed,
+    Upgraded,
+    Downgraded,
+    Reinstalled,
+    Removed,
+}
+
+/// A package change found in the package manager log
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageChange {
+    pub time: DateTime<Utc>,
+    pub action: PackageAction,
+    pub name: String,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+}
+
+fn to_utc<Tz: TimeZone>(naive: NaiveDateTime, local: &Tz) -> Option<DateTime<Utc>> {
+    local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// `[2024-07-01T09:58:41+0200]`, or `[2019-03-02 18:05]` in local time before pacman 5.2
+fn parse_pacman_time<Tz: TimeZone>(stamp: &str, local: &Tz) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_str(stamp, "%Y-%m-%dT%H:%M:%S%z")
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            to_utc(
+                NaiveDateTime::parse_from_str(stamp, "%Y-%m-%d %H:%M").ok()?,
+                local,
+            )
+        })
+}
+
+/// ALPM lines of pacman.log: `upgraded mesa (1:24.1.1-1 -> 1:24.1.2-1)`
+pub fn parse_pacman_log<Tz: TimeZone>(contents: &str, local: &Tz) -> Vec<PackageChange> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (stamp, rest) = line.strip_prefix('[')?.split_once("] ")?;
+            let message = rest.strip_prefix("[ALPM] ")?;
+            let (action, rest) = message.split_once(' ')?;
+            let (name, versions) = rest.split_once(" (")?;
+            let versions = versions.strip_suffix(')')?;
+
+            let (action, old_version, new_version) = match action {
+                "installed" => (PackageAction::Installed, None, Some(versions)),
+                "reinstalled" => (PackageAction::Reinstalled, Some(versions), Some(versions)),
+                "removed" => (PackageAction::Removed, Some(versions), None),
+                "upgraded" | "downgraded" => {
+                    let (old, new) = versions.split_once(" -> ")?;
+                    let action = if action == "upgraded" {
+                        PackageAction::Upgraded
+                    } else {
+                        PackageAction::Downgraded
+                    };
+                    (action, Some(old), Some(new))
+                }
+                _ => return None,
+            };
+
+            Some(PackageChange {
+                time: parse_pacman_time(stamp, local)?,
+                action,
+                name: name.to_string(),
+                old_version: old_version.map(str::to_string),
+                new_version: new_version.map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+/// dpkg.log lines: `2024-07-01 09:58:41 upgrade mesa-vulkan-drivers:amd64 24.0.5-1 24.0.9-1`,
+/// with `<none>` standing for a missing version. Times are local.
+pub fn parse_dpkg_log<Tz: TimeZone>(contents: &str, local: &Tz) -> Vec<PackageChange> {
+    let version = |v: &str| (v != "<none>").then(|| v.to_string());
+
+    contents
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [date, time, action, package, old, new] = fields.as_slice() else {
+                return None;
+            };
+            let (old_version, new_version) = (version(old), version(new));
+
+            let action = match *action {
+                "install" => PackageAction::Installed,
+                "remove" => PackageAction::Removed,
+                "upgrade" if old_version == new_version => PackageAction::Reinstalled,
+                "upgrade" => PackageAction::Upgraded,
+                // `purge` follows `remove`, `configure` follows `install`/`upgrade`
+                _ => return None,
+            };
+            let naive =
+                NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M:%S")
+                    .ok()?;
+
+            Some(PackageChange {
+                time: to_utc(naive, local)?,
+                action,
+                // Architecture qualifier (`:amd64`) dropped, like in the update list
+                name: package.split(':').next().unwrap_or(package).to_string(),
+                old_version,
+                new_version,
+            })
+        })
+        .collect()
+}
+
+/// Package changes logged between two instants, open-ended on either side when `None`
+pub fn changes_between(
+    distro: &Distro,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> Vec<PackageChange> {
+    let (path, parse): (_, fn(&str, &chrono::Local) -> Vec<PackageChange>) = match distro {
+        Distro::Arch | Distro::SteamOS => (PACMAN_LOG, parse_pacman_log),
+        Distro::Debian => (DPKG_LOG, parse_dpkg_log),
+        _ => return Vec::new(),
+    };
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+
+    parse(&contents, &chrono::Local)
+        .into_iter()
+        .filter(|c| since.iter().all(|&t| c.time >= t) && until.iter().all(|&t| c.time <= t))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn summary(
+        changes: &[PackageChange],
+    ) -> Vec<(PackageAction, &str, Option<&str>, Option<&str>)> {
+        changes
+            .iter()
+            .map(|c| {
+                (
+                    c.action,
+                    c.name.as_str(),
+                    c.old_version.as_deref(),
+                    c.new_version.as_deref(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_pacman_log() {
+        let changes = parse_pacman_log(
+            include_str!("../tests/fixtures/package-logs/pacman.log"),
+            &Utc,
+        );
+        use PackageAction::*;
+        assert_eq!(
+            summary(&changes),
+            [
+                (Upgraded, "firefox", Some("65.0.1-1"), Some("65.0.2-1")),
+                (Upgraded, "mesa", Some("1:24.1.1-1"), Some("1:24.1.2-1")),
+                (
+                    Upgraded,
+                    "lib32-mesa",
+                    Some("1:24.1.1-1"),
+                    Some("1:24.1.2-1")
+                ),
+                (
+                    Upgraded,
+                    "linux",
+                    Some("6.9.6.arch1-1"),
+                    Some("6.9.7.arch1-1")
+                ),
+                (Installed, "discord", None, Some("1:0.0.58-1")),
+                (Removed, "gamemode", Some("1.8.1-1"), None),
+                (Downgraded, "mesa", Some("1:24.1.2-1"), Some("1:24.1.1-1")),
+                (
+                    Reinstalled,
+                    "linux",
+                    Some("6.9.7.arch1-1"),
+                    Some("6.9.7.arch1-1")
+                ),
+            ]
+        );
+        assert_eq!(changes[0].time.to_rfc3339(), "2019-03-02T18:05:00+00:00");
+        assert_eq!(changes[1].time.to_rfc3339(), "2024-07-01T07:58:41+00:00");
+    }
+
+    #[test]
+    fn test_parse_dpkg_log() {
+        let cest = FixedOffset::east_opt(2 * 3600).unwrap();
+        let changes = parse_dpkg_log(
+            include_str!("../tests/fixtures/package-logs/dpkg.log"),
+            &cest,
+        );
+        use PackageAction::*;
+        assert_eq!(
+            summary(&changes),
+            [
+                (
+                    Upgraded,
+                    "mesa-vulkan-drivers",
+                    Some("24.0.5-1"),
+                    Some("24.0.9-1")
+                ),
+                (Upgraded, "gamemode", Some("1.7-2"), Some("1.8.1-1")),
+                (Installed, "steam-installer", None, Some("1:1.0.0.79-1")),
+                (Removed, "libgamemode0", Some("1.8.1-1"), None),
+            ]
+        );
+        assert_eq!(changes[0].time.to_rfc3339(), "2024-07-01T07:58:41+00:00");
+    }
+}
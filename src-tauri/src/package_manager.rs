@@ -0,0 +1,779 @@
+//! Package manager backends
+//!
+//! Every supported package manager implements [`PackageManager`], and the one
+//! matching the running distribution is picked at runtime. Backends run their
+//! commands through a [`CommandRunner`]: on the real system it spawns them,
+//! while the fake backend replays a recorded [`Transcript`] through the same
+//! parsers, for end-to-end tests and the demo mode of the updates screen.
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command as TokioCommand;
+
+use crate::aur::{self, AurHelper};
+use crate::package_holds::HoldConflict;
+use crate::reboot::{self, RebootReason};
+use crate::system_updates::{
+    self, Apt, Distro, Dnf, InstallOutcome, Ostree, Pacman, SteamOs, UpdatePackage, Zypper,
+};
+use crate::update_jobs::UpdateJob;
+
+/// Turns on demo mode: the path of a transcript to replay, or any other
+/// value for the bundled one
+pub const DEMO_ENV: &str = "PIXXIDEN_UPDATE_DEMO";
+
+const DEMO_TRANSCRIPT: &str = include_str!("../transcripts/arch.json");
+
+/// A package manager able to list and apply system updates
+#[async_trait]
+pub trait PackageManager: Send + Sync {
+    /// Distribution family handled by this backend
+    fn distro(&self) -> Distro;
+
+    /// Pending upgrades
+    async fn check(&self) -> Result<Vec<UpdatePackage>, String>;
+
+    /// Apply every pending upgrade but the held packages, reporting progress
+    /// through the job
+    async fn install(&self, job: &UpdateJob, holds: &[String]) -> Result<InstallOutcome, String>;
+
+    /// Install packages by name (gaming prerequisites), reporting progress
+    /// through the job
+    async fn install_packages(
+        &self,
+        _job: &UpdateJob,
+        _packages: &[String],
+    ) -> Result<InstallOutcome, String> {
+        Err(format!(
+            "Installing packages is not supported on {}",
+            self.distro()
+        ))
+    }
+
+    /// Whether upgrades can leave packages of the hold list alone
+    fn supports_holds(&self) -> bool {
+        false
+    }
+
+    /// Upgrades that need a newer version of a held package (dry run)
+    fn hold_conflicts(&self, _holds: &[String]) -> Vec<HoldConflict> {
+        Vec::new()
+    }
+
+    /// Sudoers rules for the exact command lines skipping the held packages
+    fn hold_sudoers_rules(&self, _username: &str, _holds: &[String]) -> String {
+        String::new()
+    }
+
+    /// Why installed updates only take effect after a reboot
+    fn reboot_reasons(&self) -> Vec<RebootReason>;
+
+    /// Sudoers rules for the commands run as root, reboot included
+    fn sudoers_rules(&self, username: &str) -> String;
+
+    /// Demo backends leave the system alone: no snapshots, Flatpaks or Arch news
+    fn is_demo(&self) -> bool {
+        false
+    }
+}
+
+/// Exit code and output of a finished command
+#[derive(Debug, Clone, Default)]
+pub struct CommandOutput {
+    pub code: Option<i32>, // None when killed by a signal
+    pub stdout: String,    // Empty for streamed commands
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+
+    pub fn stderr_lines(&self) -> Vec<String> {
+        self.stderr.lines().map(str::to_string).collect()
+    }
+}
+
+/// Runs the commands of a backend
+#[async_trait]
+pub trait CommandRunner: Send + Sync {
+    /// Run a command to completion
+    fn output(&self, program: &str, args: &[&str]) -> Result<CommandOutput, String>;
+
+    /// Run a command attached to the job, handing each stdout line to
+    /// `on_line` as soon as it is printed
+    async fn stream(
+        &self,
+        program: &str,
+        args: &[&str],
+        job: &UpdateJob,
+        on_line: &mut (dyn FnMut(String) + Send),
+    ) -> Result<CommandOutput, String>;
+}
+
+/// Name of a command in error messages (`sudo pacman -Syu` -> `pacman`)
+fn command_name<'a>(program: &'a str, args: &[&'a str]) -> &'a str {
+    match (program, args.first()) {
+        ("sudo", Some(command)) => command,
+        _ => program,
+    }
+}
+
+/// Runs commands on the system, in the C locale as their output is parsed
+pub struct SystemRunner;
+
+#[async_trait]
+impl CommandRunner for SystemRunner {
+    fn output(&self, program: &str, args: &[&str]) -> Result<CommandOutput, String> {
+        let output = std::process::Command::new(program)
+            .args(args)
+            .env("LC_ALL", "C")
+            .output()
+            .map_err(|e| format!("Failed to run {}: {}", command_name(program, args), e))?;
+
+        Ok(CommandOutput {
+            code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+
+    async fn stream(
+        &self,
+        program: &str,
+        args: &[&str],
+        job: &UpdateJob,
+        on_line: &mut (dyn FnMut(String) + Send),
+    ) -> Result<CommandOutput, String> {
+        let name = command_name(program, args);
+        let mut child = TokioCommand::new(program)
+            .args(args)
+            .env("LC_ALL", "C")
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", name, e))?;
+        job.attach(child.id());
+
+        // Collect stderr lines while stdout is being parsed
+        let stderr = child.stderr.take().expect("Failed to capture stderr");
+        let stderr_task = tokio::spawn(async move {
+            let mut lines = Vec::new();
+            let mut reader = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = reader.next_line().await {
+                lines.push(line);
+            }
+            lines
+        });
+
+        let stdout = child.stdout.take().expect("Failed to capture stdout");
+        let mut reader = BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = reader.next_line().await {
+            on_line(line);
+        }
+
+        let status = child
+            .wait()
+            .await
+            .map_err(|e| format!("Failed to wait for {}: {}", name, e))?;
+        job.detach();
+
+        Ok(CommandOutput {
+            code: status.code(),
+            stdout: String::new(),
+            stderr: stderr_task.await.unwrap_or_default().join("\n"),
+        })
+    }
+}
+
+/// A command of a transcript with its recorded output
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordedCommand {
+    pub command: String, // Also matches longer command lines starting with it
+    #[serde(default)]
+    pub code: i32,
+    #[serde(default)]
+    pub stdout: String,
+    #[serde(default)]
+    pub stderr: String,
+}
+
+/// Recorded package manager session, replayed by the fake backend
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transcript {
+    pub distro: Distro, // Decides which parsers read the output
+    pub commands: Vec<RecordedCommand>,
+    #[serde(default)]
+    pub reboot_needed: bool,
+    #[serde(default)]
+    pub aur_helper: Option<AurHelper>, // Replays `<helper> -Qua` and `-Sua` too
+    #[serde(default)]
+    pub line_delay_ms: u64, // Pause between streamed lines
+}
+
+impl Transcript {
+    pub fn parse(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse transcript: {}", e))
+    }
+
+    /// First recorded command matching a command line
+    fn find(&self, program: &str, args: &[&str]) -> Result<&RecordedCommand, String> {
+        let line = std::iter::once(program)
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.commands
+            .iter()
+            .find(|c| line == c.command || line.starts_with(&format!("{} ", c.command)))
+            .ok_or_else(|| format!("No recorded output for `{}`", line))
+    }
+}
+
+#[async_trait]
+impl CommandRunner for Transcript {
+    fn output(&self, program: &str, args: &[&str]) -> Result<CommandOutput, String> {
+        let recorded = self.find(program, args)?;
+        Ok(CommandOutput {
+            code: Some(recorded.code),
+            stdout: recorded.stdout.clone(),
+            stderr: recorded.stderr.clone(),
+        })
+    }
+
+    async fn stream(
+        &self,
+        program: &str,
+        args: &[&str],
+        job: &UpdateJob,
+        on_line: &mut (dyn FnMut(String) + Send),
+    ) -> Result<CommandOutput, String> {
+        let recorded = self.find(program, args)?;
+
+        for line in recorded.stdout.lines() {
+            // Like an interrupted package manager
+            if job.is_cancelled() {
+                return Ok(CommandOutput {
+                    code: Some(130),
+                    stdout: String::new(),
+                    stderr: "Interrupted".to_string(),
+                });
+            }
+            if self.line_delay_ms > 0 {
+                tokio::time::sleep(Duration::from_millis(self.line_delay_ms)).await;
+            }
+            on_line(line.to_string());
+        }
+
+        Ok(CommandOutput {
+            code: Some(recorded.code),
+            stdout: String::new(),
+            stderr: recorded.stderr.clone(),
+        })
+    }
+}
+
+/// Backend replaying a transcript through the parsers of its distribution
+pub struct FakePackageManager {
+    transcript: Arc<Transcript>,
+    backend: Box<dyn PackageManager>,
+}
+
+impl FakePackageManager {
+    pub fn new(transcript: Transcript) -> Result<Self, String> {
+        let transcript = Arc::new(transcript);
+        let runner: Arc<dyn CommandRunner> = transcript.clone();
+        // Image-based backends drive their own tools and cannot be replayed
+        let backend: Box<dyn PackageManager> = match transcript.distro {
+            Distro::Arch => {
+                Box::new(Pacman::new(runner, false).with_aur_helper(transcript.aur_helper))
+            }
+            Distro::Debian => Box::new(Apt::new(runner)),
+            Distro::Fedora => Box::new(Dnf::new(runner)),
+            Distro::OpenSuse => Box::new(Zypper::new(runner)),
+            ref distro => return Err(format!("{} transcripts cannot be replayed", distro)),
+        };
+        Ok(Self {
+            transcript,
+            backend,
+        })
+    }
+}
+
+#[async_trait]
+impl PackageManager for FakePackageManager {
+    fn distro(&self) -> Distro {
+        self.transcript.distro.clone()
+    }
+
+    async fn check(&self) -> Result<Vec<UpdatePackage>, String> {
+        self.backend.check().await
+    }
+
+    async fn install(&self, job: &UpdateJob, holds: &[String]) -> Result<InstallOutcome, String> {
+        self.backend.install(job, holds).await
+    }
+
+    async fn install_packages(
+        &self,
+        job: &UpdateJob,
+        packages: &[String],
+    ) -> Result<InstallOutcome, String> {
+        self.backend.install_packages(job, packages).await
+    }
+
+    fn supports_holds(&self) -> bool {
+        self.backend.supports_holds()
+    }
+
+    fn hold_conflicts(&self, holds: &[String]) -> Vec<HoldConflict> {
+        self.backend.hold_conflicts(holds)
+    }
+
+    fn hold_sudoers_rules(&self, username: &str, holds: &[String]) -> String {
+        self.backend.hold_sudoers_rules(username, holds)
+    }
+
+    /// The recorded kernel upgrade, against the running kernel
+    fn reboot_reasons(&self) -> Vec<RebootReason> {
+        if !self.transcript.reboot_needed {
+            return Vec::new();
+        }
+        vec![RebootReason::KernelUpdated {
+            running: reboot::running_kernel().unwrap_or_default(),
+        }]
+    }
+
+    fn sudoers_rules(&self, username: &str) -> String {
+        self.backend.sudoers_rules(username)
+    }
+
+    fn is_demo(&self) -> bool {
+        true
+    }
+}
+
+/// Backend of a distribution, running commands on the system
+pub fn for_distro(distro: &Distro) -> Option<Box<dyn PackageManager>> {
+    let runner: Arc<dyn CommandRunner> = Arc::new(SystemRunner);
+    Some(match distro {
+        Distro::Arch => Box::new(Pacman::new(runner, true).with_aur_helper(aur::detect())),
+        Distro::SteamOS => Box::new(SteamOs::new(runner)),
+        Distro::Ostree => Box::new(Ostree),
+        Distro::Debian => Box::new(Apt::new(runner)),
+        Distro::Fedora => Box::new(Dnf::new(runner)),
+        Distro::OpenSuse => Box::new(Zypper::new(runner)),
+        Distro::Unknown => return None,
+    })
+}
+
+/// Transcript to replay when demo mode is on
+fn demo_transcript() -> Result<Option<Transcript>, String> {
+    let Some(value) = std::env::var_os(DEMO_ENV).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    let path = Path::new(&value);
+    let transcript = if path.is_file() {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Transcript::parse(&json)?
+    } else {
+        Transcript::parse(DEMO_TRANSCRIPT)?
+    };
+    Ok(Some(transcript))
+}
+
+/// Backend used by the update commands: the demo transcript in demo mode,
+/// else the running distribution's
+pub fn current() -> Result<Box<dyn PackageManager>, String> {
+    if let Some(transcript) = demo_transcript()? {
+        return Ok(Box::new(FakePackageManager::new(transcript)?));
+    }
+    for_distro(&system_updates::detect_distro())
+        .ok_or_else(|| "Unable to detect Linux distribution".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system_updates::{
+        check_updates_with, install_updates_with, Status, UpdateProgressEvent, UpdateSource,
+    };
+    use crate::update_failures::UpdateFailureKind;
+    use crate::update_history::{PackageStatus, RunStatus, UpdateHistory, DATABASE_FILE};
+    use crate::update_jobs::{UpdateEvents, UpdateJobInfo, UpdateJobs, UpdatePhase};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordedEvents {
+        phases: Mutex<Vec<UpdatePhase>>,
+        progress: Mutex<Vec<UpdateProgressEvent>>,
+    }
+
+    impl UpdateEvents for RecordedEvents {
+        fn job_changed(&self, info: UpdateJobInfo) {
+            self.phases.lock().unwrap().push(info.phase);
+        }
+
+        fn progress(&self, event: UpdateProgressEvent) {
+            self.progress.lock().unwrap().push(event);
+        }
+    }
+
+    fn history() -> (tempfile::TempDir, UpdateHistory) {
+        let dir = tempfile::tempdir().unwrap();
+        let history = UpdateHistory::new(dir.path().join(DATABASE_FILE));
+        (dir, history)
+    }
+
+    fn fake(json: &str) -> FakePackageManager {
+        let mut transcript = Transcript::parse(json).unwrap();
+        transcript.line_delay_ms = 0;
+        FakePackageManager::new(transcript).unwrap()
+    }
+
+    #[test]
+    fn test_transcript_matching() {
+        let transcript = Transcript::parse(DEMO_TRANSCRIPT).unwrap();
+        assert!(transcript.output("pacman", &["-Qu"]).unwrap().success());
+        assert!(transcript
+            .output("expac", &["-S", "%n\t%k\t%m", "linux", "mesa"])
+            .is_ok());
+        // Prefixes only match on word boundaries
+        assert!(transcript.output("pacman", &["-Qul"]).is_err());
+        assert_eq!(
+            transcript.output("dnf", &["check-update"]).unwrap_err(),
+            "No recorded output for `dnf check-update`"
+        );
+    }
+
+    #[test]
+    fn test_image_transcripts_rejected() {
+        let json = r#"{"distro": "ostree", "commands": []}"#;
+        assert!(FakePackageManager::new(Transcript::parse(json).unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_with_transcript() {
+        let backend = fake(DEMO_TRANSCRIPT);
+        let result = check_updates_with(&backend, &[]).await.unwrap();
+
+        assert!(result.demo);
+        assert_eq!(result.distro, Distro::Arch);
+        let names: Vec<&str> = result.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["gamemode", "lib32-mesa", "linux", "mesa", "pipewire"]
+        );
+        let linux = &result.packages[2];
+        assert_eq!(linux.current_version, "6.10.9.arch1-1");
+        assert_eq!(linux.new_version, "6.10.10.arch1-1");
+        assert_eq!(linux.size, 142_606_336);
+        assert!(result.requires_reboot);
+        assert!(result.news.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_install_with_transcript() {
+        let backend = fake(DEMO_TRANSCRIPT);
+        let events = Arc::new(RecordedEvents::default());
+        let jobs = UpdateJobs::new();
+        let (_dir, history) = history();
+
+        let report =
+            install_updates_with(&backend, events.clone(), &jobs, &history, &[], &[], true)
+                .await
+                .unwrap();
+
+        assert_eq!(report.installed_successfully, 5);
+        assert!(report.failed.is_empty());
+        assert!(report.error.is_none());
+        assert!(report.requires_reboot);
+        assert!(report.snapshot.is_none());
+        assert!(jobs.active().is_none());
+
+        let runs = history.list_runs(10).await.unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].status, RunStatus::Succeeded);
+        assert_eq!(runs[0].package_count, 5);
+        assert!(runs[0].demo);
+
+        assert_eq!(
+            *events.phases.lock().unwrap(),
+            vec![
+                UpdatePhase::Downloading,
+                UpdatePhase::Committing,
+                UpdatePhase::Finished
+            ]
+        );
+        let progress = events.progress.lock().unwrap();
+        assert_eq!(progress[0].stage, Status::Downloading);
+        assert_eq!(progress[0].package_name, "linux");
+        let last = progress.last().unwrap();
+        assert_eq!(last.stage, Status::Configuring);
+        assert_eq!(last.progress, 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_install_failure_with_transcript() {
+        let backend = fake(include_str!(
+            "../tests/fixtures/transcripts/apt-conflict.json"
+        ));
+        let events = Arc::new(RecordedEvents::default());
+        let jobs = UpdateJobs::new();
+        let (_dir, history) = history();
+
+        let result = check_updates_with(&backend, &[]).await.unwrap();
+        assert_eq!(result.packages.len(), 2);
+        assert_eq!(result.packages[1].name, "mesa-vulkan-drivers");
+        assert_eq!(result.packages[1].current_version, "24.0.5-1");
+
+        let report = install_updates_with(
+            &backend,
+            events,
+            &jobs,
+            &history,
+            &result.packages,
+            &[],
+            false,
+        )
+        .await
+        .unwrap();
+        let error = report.error.unwrap();
+        assert_eq!(error.kind, UpdateFailureKind::FileConflict);
+        assert_eq!(report.failed, vec!["mesa-vulkan-drivers"]);
+        assert_eq!(report.installed_successfully, 1);
+        assert!(!report.requires_reboot);
+
+        let run = history.list_runs(1).await.unwrap().remove(0);
+        let details = history.run_details(run.id).await.unwrap();
+        assert_eq!(details.run.status, RunStatus::Failed);
+        assert_eq!(details.error.unwrap().kind, UpdateFailureKind::FileConflict);
+        let mesa = details
+            .packages
+            .iter()
+            .find(|p| p.name == "mesa-vulkan-drivers")
+            .unwrap();
+        assert_eq!(mesa.status, PackageStatus::Failed);
+        assert_eq!(mesa.old_version, "24.0.5-1");
+    }
+
+    #[tokio::test]
+    async fn test_aur_with_transcript() {
+        let backend = fake(include_str!("../tests/fixtures/transcripts/arch-aur.json"));
+        let events = Arc::new(RecordedEvents::default());
+        let jobs = UpdateJobs::new();
+        let (_dir, history) = history();
+
+        let result = check_updates_with(&backend, &[]).await.unwrap();
+        let sources: Vec<(&str, UpdateSource)> = result
+            .packages
+            .iter()
+            .map(|p| (p.name.as_str(), p.source))
+            .collect();
+        assert_eq!(
+            sources,
+            vec![
+                ("mesa", UpdateSource::System),
+                ("gamescope-git", UpdateSource::Aur),
+                ("proton-ge-custom-bin", UpdateSource::Aur),
+            ]
+        );
+
+        let report = install_updates_with(
+            &backend,
+            events.clone(),
+            &jobs,
+            &history,
+            &result.packages,
+            &[],
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.installed, vec!["mesa", "proton-ge-custom-bin"]);
+        assert_eq!(report.failed, vec!["gamescope-git"]);
+        let error = report.error.unwrap();
+        assert_eq!(error.message, "==> ERROR: A failure occurred in build().");
+
+        // Builds are reported like pacman downloads
+        let progress = events.progress.lock().unwrap();
+        assert!(progress
+            .iter()
+            .any(|e| e.stage == Status::Downloading && e.package_name == "gamescope-git"));
+        assert!(progress
+            .iter()
+            .any(|e| e.stage == Status::Installing && e.package_name == "proton-ge-custom-bin"));
+    }
+
+    #[tokio::test]
+    async fn test_install_packages_with_transcript() {
+        let backend = fake(include_str!(
+            "../tests/fixtures/transcripts/apt-prerequisites.json"
+        ));
+        let events = Arc::new(RecordedEvents::default());
+        let jobs = UpdateJobs::new();
+        let job = jobs.start(events.clone()).unwrap();
+
+        // i386 is enabled first, then each package gets its own command
+        let packages = vec![
+            "libvulkan1:i386".to_string(),
+            "mesa-vulkan-drivers:i386".to_string(),
+        ];
+        let outcome = backend.install_packages(&job, &packages).await.unwrap();
+        jobs.finish(&job);
+
+        assert_eq!(outcome.installed, vec!["libvulkan1:i386"]);
+        assert_eq!(outcome.failed, vec!["mesa-vulkan-drivers:i386"]);
+        let error = outcome.error.unwrap();
+        assert_eq!(error.kind, UpdateFailureKind::Dependency);
+        assert!(error
+            .packages
+            .contains(&"mesa-vulkan-drivers:i386".to_string()));
+
+        let progress = events.progress.lock().unwrap();
+        let stages: Vec<(&str, Status)> = progress
+            .iter()
+            .map(|e| (e.package_name.as_str(), e.stage.clone()))
+            .collect();
+        assert_eq!(
+            stages[..4],
+            [
+                ("libvulkan1:i386", Status::Downloading),
+                ("libvulkan1:i386", Status::Installing),
+                ("libvulkan1:i386", Status::Configuring),
+                ("libvulkan1:i386", Status::Configuring),
+            ]
+        );
+        assert_eq!(progress[3].progress, 50.0);
+        assert_eq!(
+            progress.last().unwrap().package_name,
+            "mesa-vulkan-drivers:i386"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_holds_with_transcript() {
+        let backend = fake(include_str!(
+            "../tests/fixtures/transcripts/arch-holds.json"
+        ));
+        let events = Arc::new(RecordedEvents::default());
+        let jobs = UpdateJobs::new();
+        let (_dir, history) = history();
+
+        // lib32-nvidia-utils cannot follow without the held nvidia-utils
+        let holds = vec!["nvidia-utils".to_string()];
+        let result = check_updates_with(&backend, &holds).await.unwrap();
+        let names: Vec<&str> = result.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["lib32-nvidia-utils", "mesa"]);
+        assert_eq!(result.held.len(), 1);
+        assert_eq!(result.held[0].name, "nvidia-utils");
+        assert_eq!(result.total_size, 40_370_176 + 9_961_472);
+        assert_eq!(result.hold_conflicts.len(), 1);
+        assert_eq!(result.hold_conflicts[0].package, "lib32-nvidia-utils");
+
+        let report = install_updates_with(
+            &backend,
+            events.clone(),
+            &jobs,
+            &history,
+            &result.packages,
+            &holds,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.error.unwrap().kind, UpdateFailureKind::Dependency);
+        assert_eq!(report.failed, vec!["lib32-nvidia-utils"]);
+        assert!(report.installed.is_empty());
+        assert!(events.progress.lock().unwrap().is_empty());
+
+        // Holding both lets the rest through
+        let holds = vec!["lib32-nvidia-utils".to_string(), "nvidia-utils".to_string()];
+        let result = check_updates_with(&backend, &holds).await.unwrap();
+        assert_eq!(result.packages.len(), 1);
+        assert_eq!(result.held.len(), 2);
+        assert!(result.hold_conflicts.is_empty());
+
+        let report = install_updates_with(
+            &backend,
+            events,
+            &jobs,
+            &history,
+            &result.packages,
+            &holds,
+            false,
+        )
+        .await
+        .unwrap();
+        assert!(report.error.is_none());
+        assert_eq!(report.installed, vec!["mesa"]);
+
+        let run = history.list_runs(1).await.unwrap().remove(0);
+        assert_eq!(run.package_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_apt_holds_with_transcript() {
+        let backend = fake(include_str!("../tests/fixtures/transcripts/apt-holds.json"));
+        let events = Arc::new(RecordedEvents::default());
+        let jobs = UpdateJobs::new();
+        let (_dir, history) = history();
+
+        // apt keeps libnvidia-gl-550 back along with the held libnvidia-common-550
+        let holds = vec!["libnvidia-common-550".to_string()];
+        let result = check_updates_with(&backend, &holds).await.unwrap();
+        let names: Vec<&str> = result.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["libnvidia-gl-550", "mesa-vulkan-drivers"]);
+        assert_eq!(result.held.len(), 1);
+        assert_eq!(result.hold_conflicts.len(), 1);
+        assert_eq!(result.hold_conflicts[0].package, "libnvidia-gl-550");
+        assert_eq!(
+            result.hold_conflicts[0].dependency,
+            "libnvidia-common-550 (= 550.120-0ubuntu1)"
+        );
+
+        let report = install_updates_with(
+            &backend,
+            events.clone(),
+            &jobs,
+            &history,
+            &result.packages,
+            &holds,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.error.unwrap().kind, UpdateFailureKind::Dependency);
+        assert_eq!(report.failed, vec!["libnvidia-gl-550"]);
+        assert!(events.progress.lock().unwrap().is_empty());
+
+        // Holding both lets the rest through
+        let holds = vec![
+            "libnvidia-common-550".to_string(),
+            "libnvidia-gl-550".to_string(),
+        ];
+        let result = check_updates_with(&backend, &holds).await.unwrap();
+        assert_eq!(result.packages.len(), 1);
+        assert!(result.hold_conflicts.is_empty());
+
+        let report = install_updates_with(
+            &backend,
+            events,
+            &jobs,
+            &history,
+            &result.packages,
+            &holds,
+            false,
+        )
+        .await
+        .unwrap();
+        assert!(report.error.is_none());
+        assert_eq!(report.installed, vec!["mesa-vulkan-drivers"]);
+    }
+}
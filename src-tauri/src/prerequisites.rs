@@ -0,0 +1,676 @@
+//! Gaming prerequisites — detection and installation of the system packages
+//! Wine/Proton needs (32-bit libraries, 32-bit Vulkan, gamemode).
+//!
+//! Installation runs as an update job through the package manager backend,
+//! like system updates, and reports `UpdateProgressEvent`s. Each package is
+//! installed with its own command so the sudoers rules can whitelist exact
+//! command lines instead of a wildcard.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::package_manager::{self, CommandRunner};
+use crate::system_updates::{Distro, InstallOutcome, Status, UpdateProgressEvent, UpdateReport};
+use crate::update_failures::{self, UpdateError};
+use crate::update_jobs::{UpdateJob, UpdateJobs};
+
+/// A system prerequisite for running Windows games
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Prerequisite {
+    Lib32Linker,
+    Lib32Gl,
+    Lib32Freetype,
+    Lib32X11,
+    Lib32Vulkan,
+    Gamemode,
+}
+
+impl Prerequisite {
+    pub const ALL: [Prerequisite; 6] = [
+        Prerequisite::Lib32Linker,
+        Prerequisite::Lib32Gl,
+        Prerequisite::Lib32Freetype,
+        Prerequisite::Lib32X11,
+        Prerequisite::Lib32Vulkan,
+        Prerequisite::Gamemode,
+    ];
+
+    /// Paths checked to decide whether the prerequisite is installed
+    /// (any of them existing is enough)
+    fn paths(&self, distro: &Distro) -> Vec<String> {
+        let library = match self {
+            Prerequisite::Lib32Linker => "ld-linux.so.2",
+            Prerequisite::Lib32Gl => "libGL.so.1",
+            Prerequisite::Lib32Freetype => "libfreetype.so.6",
+            Prerequisite::Lib32X11 => "libX11.so.6",
+            Prerequisite::Lib32Vulkan => "libvulkan.so.1",
+            Prerequisite::Gamemode => {
+                return vec![
+                    "/usr/bin/gamemoded".to_string(),
+                    "/usr/libexec/gamemoded".to_string(),
+                ];
+            }
+        };
+
+        let mut paths: Vec<String> = lib32_dirs(distro)
+            .iter()
+            .map(|dir| format!("{}/{}", dir, library))
+            .collect();
+        // The 32-bit loader path is baked into every i386 binary
+        if *self == Prerequisite::Lib32Linker {
+            paths.push("/lib/ld-linux.so.2".to_string());
+        }
+        paths
+    }
+}
+
+/// Directories holding 32-bit libraries. rpm distributions put them in
+/// `/usr/lib`, which is 64-bit elsewhere (rpm uses `/usr/lib64` for that).
+fn lib32_dirs(distro: &Distro) -> &'static [&'static str] {
+    match distro {
+        Distro::Arch | Distro::SteamOS => &["/usr/lib32"],
+        Distro::Debian => &["/usr/lib/i386-linux-gnu"],
+        Distro::Fedora | Distro::OpenSuse | Distro::Ostree => &["/usr/lib"],
+        Distro::Unknown => &["/usr/lib32", "/usr/lib/i386-linux-gnu"],
+    }
+}
+
+impl std::fmt::Display for Prerequisite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Prerequisite::Lib32Linker => write!(f, "32-bit linker (ld-linux.so.2)"),
+            Prerequisite::Lib32Gl => write!(f, "libGL (32-bit)"),
+            Prerequisite::Lib32Freetype => write!(f, "libfreetype (32-bit)"),
+            Prerequisite::Lib32X11 => write!(f, "libX11 (32-bit)"),
+            Prerequisite::Lib32Vulkan => write!(f, "Vulkan (32-bit)"),
+            Prerequisite::Gamemode => write!(f, "GameMode"),
+        }
+    }
+}
+
+/// Installation status of a prerequisite
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrerequisiteStatus {
+    pub prerequisite: Prerequisite,
+    pub name: String,
+    pub installed: bool,
+}
+
+/// GPU vendor, used to pick the right Vulkan driver package
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuVendor {
+    Amd,
+    Intel,
+    Nvidia,
+}
+
+/// Detect the GPU vendors present from the DRM sysfs entries
+fn detect_gpu_vendors() -> Vec<GpuVendor> {
+    let mut vendors = Vec::new();
+
+    let Ok(entries) = std::fs::read_dir("/sys/class/drm") else {
+        return vendors;
+    };
+
+    for entry in entries.flatten() {
+        let vendor_file = entry.path().join("device/vendor");
+        let Ok(id) = std::fs::read_to_string(vendor_file) else {
+            continue;
+        };
+        let vendor = match id.trim() {
+            "0x1002" => GpuVendor::Amd,
+            "0x8086" => GpuVendor::Intel,
+            "0x10de" => GpuVendor::Nvidia,
+            _ => continue,
+        };
+        if !vendors.contains(&vendor) {
+            vendors.push(vendor);
+        }
+    }
+
+    vendors
+}
+
+/// Whether an ICD manifest registers a 32-bit Vulkan driver
+fn is_32bit_icd(manifest: &str, nvidia_lib32: bool) -> bool {
+    let name = manifest.to_lowercase();
+    // NVIDIA ships a single manifest, only the 32-bit driver makes it count
+    name.contains("i686") || name.contains("i386") || (name.starts_with("nvidia") && nvidia_lib32)
+}
+
+/// Check whether a 32-bit Vulkan driver (ICD) is registered
+fn has_32bit_vulkan_icd(distro: &Distro) -> bool {
+    let Ok(entries) = std::fs::read_dir("/usr/share/vulkan/icd.d") else {
+        return false;
+    };
+
+    // The 32-bit NVIDIA driver is loaded through the manifest shared by both architectures
+    let nvidia_lib32 = lib32_dirs(distro)
+        .iter()
+        .any(|dir| Path::new(dir).join("libGLX_nvidia.so.0").exists());
+    entries
+        .flatten()
+        .any(|entry| is_32bit_icd(&entry.file_name().to_string_lossy(), nvidia_lib32))
+}
+
+/// Check which gaming prerequisites are installed
+pub fn check_prerequisites() -> Vec<PrerequisiteStatus> {
+    let distro = crate::system_updates::detect_distro();
+    Prerequisite::ALL
+        .iter()
+        .map(|prerequisite| {
+            let mut installed = prerequisite
+                .paths(&distro)
+                .iter()
+                .any(|p| Path::new(p).exists());
+            if *prerequisite == Prerequisite::Lib32Vulkan {
+                installed = installed && has_32bit_vulkan_icd(&distro);
+            }
+
+            PrerequisiteStatus {
+                prerequisite: *prerequisite,
+                name: prerequisite.to_string(),
+                installed,
+            }
+        })
+        .collect()
+}
+
+/// Map a prerequisite to the package names providing it on a distribution
+fn packages_for(
+    prerequisite: Prerequisite,
+    distro: &Distro,
+    gpus: &[GpuVendor],
+) -> Vec<&'static str> {
+    let mut packages = Vec::new();
+
+    match distro {
+        Distro::Arch => match prerequisite {
+            Prerequisite::Lib32Linker => packages.push("lib32-glibc"),
+            Prerequisite::Lib32Gl => packages.push("lib32-mesa"),
+            Prerequisite::Lib32Freetype => packages.push("lib32-freetype2"),
+            Prerequisite::Lib32X11 => packages.push("lib32-libx11"),
+            Prerequisite::Lib32Vulkan => {
+                packages.push("lib32-vulkan-icd-loader");
+                for gpu in gpus {
+                    packages.push(match gpu {
+                        GpuVendor::Amd => "lib32-vulkan-radeon",
+                        GpuVendor::Intel => "lib32-vulkan-intel",
+                        GpuVendor::Nvidia => "lib32-nvidia-utils",
+                    });
+                }
+            }
+            Prerequisite::Gamemode => packages.extend(["gamemode", "lib32-gamemode"]),
+        },
+        Distro::Debian => match prerequisite {
+            Prerequisite::Lib32Linker => packages.push("libc6:i386"),
+            Prerequisite::Lib32Gl => packages.push("libgl1:i386"),
+            Prerequisite::Lib32Freetype => packages.push("libfreetype6:i386"),
+            Prerequisite::Lib32X11 => packages.push("libx11-6:i386"),
+            Prerequisite::Lib32Vulkan => {
+                packages.push("libvulkan1:i386");
+                // The NVIDIA 32-bit driver package is tied to the installed
+                // driver branch, so only Mesa drivers are handled here
+                if gpus.iter().any(|g| *g != GpuVendor::Nvidia) {
+                    packages.push("mesa-vulkan-drivers:i386");
+                }
+            }
+            Prerequisite::Gamemode => packages.push("gamemode"),
+        },
+        Distro::Fedora => match prerequisite {
+            Prerequisite::Lib32Linker => packages.push("glibc.i686"),
+            Prerequisite::Lib32Gl => packages.push("mesa-libGL.i686"),
+            Prerequisite::Lib32Freetype => packages.push("freetype.i686"),
+            Prerequisite::Lib32X11 => packages.push("libX11.i686"),
+            Prerequisite::Lib32Vulkan => {
+                packages.push("vulkan-loader.i686");
+                if gpus.iter().any(|g| *g != GpuVendor::Nvidia) {
+                    packages.push("mesa-vulkan-drivers.i686");
+                }
+            }
+            Prerequisite::Gamemode => packages.push("gamemode"),
+        },
+        Distro::OpenSuse => match prerequisite {
+            Prerequisite::Lib32Linker => packages.push("glibc-32bit"),
+            Prerequisite::Lib32Gl => packages.push("Mesa-libGL1-32bit"),
+            Prerequisite::Lib32Freetype => packages.push("libfreetype6-32bit"),
+            Prerequisite::Lib32X11 => packages.push("libX11-6-32bit"),
+            Prerequisite::Lib32Vulkan => {
+                packages.push("libvulkan1-32bit");
+                for gpu in gpus {
+                    match gpu {
+                        GpuVendor::Amd => packages.push("libvulkan_radeon-32bit"),
+                        GpuVendor::Intel => packages.push("libvulkan_intel-32bit"),
+                        // Shipped by the NVIDIA repository's G06 driver packages
+                        GpuVendor::Nvidia => {}
+                    }
+                }
+            }
+            Prerequisite::Gamemode => packages.extend(["gamemode", "libgamemode0-32bit"]),
+        },
+        Distro::SteamOS | Distro::Ostree | Distro::Unknown => {}
+    }
+
+    packages
+}
+
+/// Build the deduplicated list of packages to install for a set of prerequisites
+pub fn install_plan(
+    prerequisites: &[Prerequisite],
+    distro: &Distro,
+    gpus: &[GpuVendor],
+) -> Result<Vec<String>, String> {
+    match distro {
+        Distro::SteamOS => {
+            return Err("SteamOS already ships the gaming prerequisites".to_string());
+        }
+        Distro::Ostree => {
+            return Err(
+                "Image-based systems ship the gaming prerequisites in the image or the Steam \
+                 Flatpak; layer extra packages with rpm-ostree install"
+                    .to_string(),
+            );
+        }
+        Distro::Unknown => {
+            return Err("Unable to detect Linux distribution".to_string());
+        }
+        _ => {}
+    }
+
+    // Without a detected GPU, assume a Mesa driver
+    let gpus = if gpus.is_empty() {
+        &[GpuVendor::Amd][..]
+    } else {
+        gpus
+    };
+
+    let mut plan: Vec<String> = Vec::new();
+    for prerequisite in prerequisites {
+        for package in packages_for(*prerequisite, distro, gpus) {
+            if !plan.iter().any(|p| p == package) {
+                plan.push(package.to_string());
+            }
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Every package a prerequisite may map to on a distribution, regardless of GPU
+fn all_packages(distro: &Distro) -> Vec<&'static str> {
+    let gpus = [GpuVendor::Amd, GpuVendor::Intel, GpuVendor::Nvidia];
+    let mut packages: Vec<&'static str> = Vec::new();
+    for prerequisite in Prerequisite::ALL {
+        for package in packages_for(prerequisite, distro, &gpus) {
+            if !packages.contains(&package) {
+                packages.push(package);
+            }
+        }
+    }
+    packages
+}
+
+/// The privileged command installing a single package
+fn install_command(distro: &Distro, package: &str) -> Option<Vec<String>> {
+    let args: &[&str] = match distro {
+        Distro::Arch => &["pacman", "-S", "--needed", "--noconfirm"],
+        Distro::Debian => &["apt", "install", "-y"],
+        Distro::Fedora => &["dnf", "install", "-y"],
+        Distro::OpenSuse => &["zypper", "--non-interactive", "install"],
+        Distro::SteamOS | Distro::Ostree | Distro::Unknown => return None,
+    };
+
+    let mut command: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    command.push(package.to_string());
+    Some(command)
+}
+
+/// Sudoers rules allowing Pixxiden to install the prerequisite packages
+pub fn sudoers_rules(username: &str, distro: &Distro) -> String {
+    let mut rules = String::new();
+
+    if matches!(distro, Distro::Debian) {
+        rules.push_str(&format!(
+            "{username} ALL=(ALL) NOPASSWD: /usr/bin/dpkg --add-architecture i386\n"
+        ));
+    }
+
+    for package in all_packages(distro) {
+        if let Some(command) = install_command(distro, package) {
+            rules.push_str(&format!(
+                "{username} ALL=(ALL) NOPASSWD: /usr/bin/{}\n",
+                command.join(" ")
+            ));
+        }
+    }
+
+    if rules.is_empty() {
+        return rules;
+    }
+
+    format!(
+        "\n# Allow Pixxiden to install gaming prerequisites\n{}",
+        rules
+    )
+}
+
+/// Check whether the i386 architecture is enabled in dpkg
+fn is_i386_enabled(runner: &dyn CommandRunner) -> bool {
+    runner
+        .output("dpkg", &["--print-foreign-architectures"])
+        .map(|out| out.stdout.lines().any(|l| l.trim() == "i386"))
+        .unwrap_or(false)
+}
+
+/// Enable multiarch on Debian-based systems so `:i386` packages resolve
+fn enable_i386_architecture(runner: &dyn CommandRunner) -> Result<(), String> {
+    if !runner
+        .output("sudo", &["dpkg", "--add-architecture", "i386"])?
+        .success()
+    {
+        return Err("Failed to enable i386 architecture".to_string());
+    }
+    if !runner.output("sudo", &["apt", "update"])?.success() {
+        return Err("Failed to refresh package lists".to_string());
+    }
+    Ok(())
+}
+
+/// Map a package manager output line to an install stage
+fn stage_for_line(line: &str) -> Option<Status> {
+    let line = line.trim_start();
+    if line.contains("downloading") || line.starts_with("Get:") || line.starts_with("Downloading") {
+        Some(Status::Downloading)
+    } else if line.contains("installing")
+        || line.starts_with("Unpacking")
+        || line.starts_with("Installing")
+    {
+        Some(Status::Installing)
+    } else if line.starts_with("Setting up") || line.starts_with("Running scriptlet") {
+        Some(Status::Configuring)
+    } else {
+        None
+    }
+}
+
+/// Error for a package that failed to install, from the package manager's output
+fn install_failure(distro: &Distro, package: &str, lines: &[String]) -> UpdateError {
+    let mut error = match distro {
+        Distro::Arch => update_failures::classify_pacman_failure(lines),
+        Distro::Debian => update_failures::classify_apt_failure(lines),
+        // stderr comes first
+        _ => UpdateError::unknown(
+            lines
+                .iter()
+                .find(|l| !l.trim().is_empty())
+                .cloned()
+                .unwrap_or_else(|| format!("Failed to install {}", package)),
+        ),
+    };
+    if !error.packages.iter().any(|p| p == package) {
+        error.packages.push(package.to_string());
+    }
+    error
+}
+
+/// Install packages one command at a time through a backend's runner,
+/// reporting progress through the job
+pub async fn install_packages_with(
+    runner: &dyn CommandRunner,
+    distro: &Distro,
+    job: &UpdateJob,
+    packages: &[String],
+) -> Result<InstallOutcome, String> {
+    if matches!(distro, Distro::Debian)
+        && packages.iter().any(|p| p.ends_with(":i386"))
+        && !is_i386_enabled(runner)
+    {
+        enable_i386_architecture(runner)?;
+    }
+
+    let total = packages.len();
+    let mut installed = Vec::new();
+    let mut failed = Vec::new();
+    let mut error = None;
+
+    for (index, package) in packages.iter().enumerate() {
+        if job.is_cancelled() {
+            error = Some(UpdateError::cancelled());
+            break;
+        }
+        let Some(command) = install_command(distro, package) else {
+            failed.push(package.clone());
+            continue;
+        };
+        let args: Vec<&str> = command.iter().map(String::as_str).collect();
+
+        let progress = |stage: Status, package_progress: f32| UpdateProgressEvent {
+            stage,
+            package_name: package.clone(),
+            progress: (index as f32 + package_progress / 100.0) / total as f32 * 100.0,
+            package_progress,
+            downloaded: "".to_string(),
+            total: "".to_string(),
+            speed: "".to_string(),
+            eta: "".to_string(),
+        };
+        job.progress(progress(Status::Downloading, 0.0));
+
+        let mut current_stage = Status::Downloading;
+        let mut output: Vec<String> = Vec::new();
+        let result = runner
+            .stream("sudo", &args, job, &mut |line| {
+                if let Some(stage) = stage_for_line(&line) {
+                    if stage != current_stage {
+                        current_stage = stage.clone();
+                        job.progress(progress(stage, 0.0));
+                    }
+                }
+                output.push(line);
+            })
+            .await?;
+
+        if result.success() {
+            job.progress(progress(Status::Configuring, 100.0));
+            installed.push(package.clone());
+        } else {
+            // Dependency problems are explained on stdout
+            let mut lines = result.stderr_lines();
+            lines.extend(output);
+            log::warn!(
+                "Failed to install prerequisite package {}: {}",
+                package,
+                result.stderr.trim()
+            );
+            failed.push(package.clone());
+            // The first failure is reported, later ones often follow from it
+            error.get_or_insert_with(|| install_failure(distro, package, &lines));
+        }
+    }
+
+    Ok(InstallOutcome {
+        installed,
+        failed,
+        error,
+        staged: false,
+    })
+}
+
+/// Install the packages providing the given prerequisites, as an update job
+pub async fn install_prerequisites(
+    window: tauri::Window,
+    jobs: &UpdateJobs,
+    prerequisites: Vec<Prerequisite>,
+) -> Result<UpdateReport, String> {
+    let backend = package_manager::current()?;
+    let start_time = std::time::Instant::now();
+    let plan = install_plan(&prerequisites, &backend.distro(), &detect_gpu_vendors())?;
+
+    let job = jobs.start(Arc::new(window))?;
+    let result = backend.install_packages(&job, &plan).await;
+    jobs.finish(&job);
+    let InstallOutcome {
+        installed,
+        failed,
+        error,
+        ..
+    } = result?;
+
+    Ok(UpdateReport {
+        total_packages: plan.len() as u32,
+        installed_successfully: installed.len() as u32,
+        installed,
+        failed,
+        requires_reboot: false,
+        duration_seconds: start_time.elapsed().as_secs(),
+        error, // First failure, all of them are listed in `failed`
+        snapshot: None,
+        flatpaks: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_plan_arch_vulkan_amd() {
+        let plan = install_plan(
+            &[Prerequisite::Lib32Vulkan],
+            &Distro::Arch,
+            &[GpuVendor::Amd],
+        )
+        .unwrap();
+        assert_eq!(plan, vec!["lib32-vulkan-icd-loader", "lib32-vulkan-radeon"]);
+    }
+
+    #[test]
+    fn test_install_plan_debian_deduplicates() {
+        let plan = install_plan(
+            &[
+                Prerequisite::Lib32Vulkan,
+                Prerequisite::Gamemode,
+                Prerequisite::Gamemode,
+            ],
+            &Distro::Debian,
+            &[GpuVendor::Intel, GpuVendor::Amd],
+        )
+        .unwrap();
+        assert_eq!(
+            plan,
+            vec!["libvulkan1:i386", "mesa-vulkan-drivers:i386", "gamemode"]
+        );
+    }
+
+    #[test]
+    fn test_paths_arch() {
+        let paths = Prerequisite::Lib32Gl.paths(&Distro::Arch);
+        assert_eq!(paths, vec!["/usr/lib32/libGL.so.1"]);
+        assert_eq!(
+            Prerequisite::Lib32Linker.paths(&Distro::SteamOS),
+            vec!["/usr/lib32/ld-linux.so.2", "/lib/ld-linux.so.2"]
+        );
+    }
+
+    #[test]
+    fn test_paths_debian() {
+        let paths = Prerequisite::Lib32Vulkan.paths(&Distro::Debian);
+        assert_eq!(paths, vec!["/usr/lib/i386-linux-gnu/libvulkan.so.1"]);
+    }
+
+    #[test]
+    fn test_paths_fedora() {
+        // glibc.i686 and friends install to /usr/lib, 64-bit goes to /usr/lib64
+        let paths = Prerequisite::Lib32X11.paths(&Distro::Fedora);
+        assert_eq!(paths, vec!["/usr/lib/libX11.so.6"]);
+        let paths = Prerequisite::Lib32Gl.paths(&Distro::Ostree);
+        assert_eq!(paths, vec!["/usr/lib/libGL.so.1"]);
+    }
+
+    #[test]
+    fn test_paths_opensuse() {
+        let paths = Prerequisite::Lib32Freetype.paths(&Distro::OpenSuse);
+        assert_eq!(paths, vec!["/usr/lib/libfreetype.so.6"]);
+    }
+
+    #[test]
+    fn test_paths_unknown() {
+        let paths = Prerequisite::Lib32Vulkan.paths(&Distro::Unknown);
+        assert!(!paths.contains(&"/usr/lib/libvulkan.so.1".to_string()));
+        assert_eq!(
+            Prerequisite::Gamemode.paths(&Distro::Unknown),
+            vec!["/usr/bin/gamemoded", "/usr/libexec/gamemoded"]
+        );
+    }
+
+    #[test]
+    fn test_is_32bit_icd() {
+        assert!(is_32bit_icd("radeon_icd.i686.json", false));
+        assert!(is_32bit_icd("intel_icd.i386.json", false));
+        assert!(!is_32bit_icd("radeon_icd.x86_64.json", false));
+        assert!(!is_32bit_icd("nvidia_icd.json", false));
+        assert!(is_32bit_icd("nvidia_icd.json", true));
+    }
+
+    #[test]
+    fn test_install_failure() {
+        let error = install_failure(
+            &Distro::Fedora,
+            "mesa-vulkan-drivers.i686",
+            &[
+                "Error: Unable to find a match: mesa-vulkan-drivers.i686".to_string(),
+                String::new(),
+            ],
+        );
+        assert_eq!(
+            error.message,
+            "Error: Unable to find a match: mesa-vulkan-drivers.i686"
+        );
+        assert_eq!(error.packages, vec!["mesa-vulkan-drivers.i686"]);
+
+        let error = install_failure(&Distro::Fedora, "gamemode", &[]);
+        assert_eq!(error.message, "Failed to install gamemode");
+    }
+
+    #[test]
+    fn test_install_plan_unsupported() {
+        assert!(install_plan(&[Prerequisite::Gamemode], &Distro::SteamOS, &[]).is_err());
+        assert!(install_plan(&[Prerequisite::Gamemode], &Distro::Ostree, &[]).is_err());
+        assert!(install_plan(&[Prerequisite::Gamemode], &Distro::Unknown, &[]).is_err());
+    }
+
+    #[test]
+    fn test_sudoers_rules_cover_install_plan() {
+        let rules = sudoers_rules("testuser", &Distro::Debian);
+        assert!(rules.contains("/usr/bin/dpkg --add-architecture i386"));
+        assert!(rules.contains("/usr/bin/apt install -y mesa-vulkan-drivers:i386"));
+
+        let rules = sudoers_rules("testuser", &Distro::Arch);
+        assert!(rules.contains("/usr/bin/pacman -S --needed --noconfirm lib32-nvidia-utils"));
+        assert!(sudoers_rules("testuser", &Distro::Unknown).is_empty());
+    }
+
+    #[test]
+    fn test_stage_for_line() {
+        assert_eq!(
+            stage_for_line(":: downloading lib32-gamemode..."),
+            Some(Status::Downloading)
+        );
+        assert_eq!(
+            stage_for_line("Get:1 http://deb.debian.org/debian bookworm/main i386 libc6"),
+            Some(Status::Downloading)
+        );
+        assert_eq!(
+            stage_for_line("Unpacking libc6:i386 (2.36-9) ..."),
+            Some(Status::Installing)
+        );
+        assert_eq!(
+            stage_for_line("Setting up gamemode (1.7-2) ..."),
+            Some(Status::Configuring)
+        );
+        assert_eq!(stage_for_line("resolving dependencies..."), None);
+    }
+}
@@ -0,0 +1,260 @@
+//! Reboot-required detection
+//!
+//! Each package manager backend reports why the running system is out of
+//! date (removed kernel modules, distribution flags, staged deployments);
+//! processes still mapping deleted libraries are listed on top, since they
+//! keep running old code until restarted.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const KERNEL_RELEASE: &str = "/proc/sys/kernel/osrelease";
+const MODULES_DIR: &str = "/usr/lib/modules";
+const REBOOT_REQUIRED: &str = "/var/run/reboot-required";
+const REBOOT_REQUIRED_PKGS: &str = "/var/run/reboot-required.pkgs";
+
+/// Why the running system does not match what is installed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum RebootReason {
+    /// Modules of the running kernel were removed by a kernel upgrade
+    KernelUpdated { running: String },
+    /// Core packages flagged by the distribution (empty when it does not say which)
+    CoreUpdated { packages: Vec<String> },
+    /// An ostree/bootc deployment waits for the next boot
+    StagedDeployment,
+    /// Processes still using deleted libraries, fixed by restarting them
+    StaleLibraries { processes: Vec<StaleProcess> },
+}
+
+impl RebootReason {
+    /// Stale libraries only need the listed processes restarted
+    pub fn requires_reboot(&self) -> bool {
+        !matches!(self, RebootReason::StaleLibraries { .. })
+    }
+}
+
+/// A process mapping libraries that were deleted or replaced on disk
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StaleProcess {
+    pub pid: u32,
+    pub name: String,
+    pub libraries: Vec<String>,
+}
+
+/// Reboot state of the system
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RebootStatus {
+    pub required: bool,
+    pub reasons: Vec<RebootReason>,
+}
+
+impl RebootStatus {
+    pub fn new(reasons: Vec<RebootReason>) -> Self {
+        Self {
+            required: reasons.iter().any(RebootReason::requires_reboot),
+            reasons,
+        }
+    }
+}
+
+/// Release of the running kernel (`uname -r`)
+pub fn running_kernel() -> Option<String> {
+    std::fs::read_to_string(KERNEL_RELEASE)
+        .ok()
+        .map(|release| release.trim().to_string())
+        .filter(|release| !release.is_empty())
+}
+
+/// The running kernel's module directory is gone once its package was
+/// upgraded or removed, whatever the package is called (`linux-zen`,
+/// `linux-cachyos`, `linux-lts`…)
+fn kernel_modules_removed(modules_dir: &Path, release: &str) -> bool {
+    modules_dir.is_dir() && !modules_dir.join(release).exists()
+}
+
+pub fn kernel_reason() -> Option<RebootReason> {
+    let running = running_kernel()?;
+    kernel_modules_removed(Path::new(MODULES_DIR), &running)
+        .then_some(RebootReason::KernelUpdated { running })
+}
+
+/// Packages listed in reboot-required.pkgs, in order, without duplicates
+fn parse_reboot_required_pkgs(contents: &str) -> Vec<String> {
+    let mut packages: Vec<String> = Vec::new();
+    for name in contents.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if !packages.iter().any(|p| p == name) {
+            packages.push(name.to_string());
+        }
+    }
+    packages
+}
+
+/// Debian/Ubuntu: flag file left by package scripts
+pub fn reboot_required_file() -> Option<RebootReason> {
+    if !Path::new(REBOOT_REQUIRED).exists() {
+        return None;
+    }
+    let packages = std::fs::read_to_string(REBOOT_REQUIRED_PKGS)
+        .map(|contents| parse_reboot_required_pkgs(&contents))
+        .unwrap_or_default();
+    Some(RebootReason::CoreUpdated { packages })
+}
+
+/// `needs-restarting -r` output: `  * kernel` lines under the summary
+pub fn parse_needs_restarting(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix("* "))
+        .map(|name| name.trim().to_string())
+        .collect()
+}
+
+/// Libraries of a `/proc/<pid>/maps` listing that were deleted or replaced
+fn parse_deleted_libraries(maps: &str) -> Vec<String> {
+    let mut libraries: Vec<String> = Vec::new();
+    for line in maps.lines() {
+        let Some(path) = line
+            .strip_suffix(" (deleted)")
+            .and_then(|rest| rest.split_whitespace().nth(5))
+        else {
+            continue;
+        };
+        // Shared memory and memfd mappings are deleted files too
+        let library = path.starts_with("/usr/") || path.starts_with("/lib");
+        if library && path.contains(".so") && !libraries.iter().any(|l| l == path) {
+            libraries.push(path.to_string());
+        }
+    }
+    libraries
+}
+
+/// Processes of `proc_dir` mapping deleted libraries. Without root, only the
+/// user's own processes are readable.
+fn stale_processes_in(proc_dir: &Path) -> Vec<StaleProcess> {
+    let Ok(entries) = std::fs::read_dir(proc_dir) else {
+        return Vec::new();
+    };
+
+    let mut processes: Vec<StaleProcess> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let maps = std::fs::read_to_string(entry.path().join("maps")).ok()?;
+            let libraries = parse_deleted_libraries(&maps);
+            if libraries.is_empty() {
+                return None;
+            }
+            let name = std::fs::read_to_string(entry.path().join("comm"))
+                .map(|comm| comm.trim().to_string())
+                .unwrap_or_default();
+            Some(StaleProcess {
+                pid,
+                name,
+                libraries,
+            })
+        })
+        .collect();
+    processes.sort_by_key(|p| p.pid);
+    processes
+}
+
+pub fn stale_libraries_reason() -> Option<RebootReason> {
+    let processes = stale_processes_in(Path::new("/proc"));
+    (!processes.is_empty()).then_some(RebootReason::StaleLibraries { processes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernel_modules_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("6.10.10-zen1-1-zen")).unwrap();
+        std::fs::create_dir(dir.path().join("6.6.52-1-lts")).unwrap();
+
+        assert!(!kernel_modules_removed(dir.path(), "6.10.10-zen1-1-zen"));
+        assert!(!kernel_modules_removed(dir.path(), "6.6.52-1-lts"));
+        assert!(kernel_modules_removed(dir.path(), "6.10.9-zen1-1-zen"));
+        // No module tree at all (containers): nothing to compare
+        assert!(!kernel_modules_removed(
+            &dir.path().join("missing"),
+            "6.10.9-zen1-1-zen"
+        ));
+    }
+
+    #[test]
+    fn test_parse_reboot_required_pkgs() {
+        let contents = "linux-image-6.1.0-25-amd64\nlibc6\nlinux-image-6.1.0-25-amd64\n\n";
+        assert_eq!(
+            parse_reboot_required_pkgs(contents),
+            vec!["linux-image-6.1.0-25-amd64", "libc6"]
+        );
+    }
+
+    #[test]
+    fn test_parse_needs_restarting() {
+        let stdout = "Core libraries or services have been updated since boot-up:\n  * glibc\n  * kernel\n\nReboot is required to fully utilize these updates.\nMore information: https://access.redhat.com/solutions/27943\n";
+        assert_eq!(parse_needs_restarting(stdout), vec!["glibc", "kernel"]);
+    }
+
+    #[test]
+    fn test_stale_processes() {
+        let proc_dir = tempfile::tempdir().unwrap();
+        let process = |pid: &str, comm: &str, maps: &str| {
+            let dir = proc_dir.path().join(pid);
+            std::fs::create_dir(&dir).unwrap();
+            std::fs::write(dir.join("comm"), comm).unwrap();
+            std::fs::write(dir.join("maps"), maps).unwrap();
+        };
+        process(
+            "1204",
+            "pipewire\n",
+            "7f2a1c000000-7f2a1c021000 r--p 00000000 00:1f 1234 /usr/lib/libpipewire-0.3.so.0.1200.0 (deleted)\n\
+             7f2a1c021000-7f2a1c0b0000 r-xp 00021000 00:1f 1234 /usr/lib/libpipewire-0.3.so.0.1200.0 (deleted)\n\
+             7f2a1d000000-7f2a1d1a0000 r-xp 00000000 00:1f 5678 /usr/lib/libc.so.6\n",
+        );
+        process(
+            "88",
+            "steam\n",
+            "7f00aa000000-7f00aa100000 rw-s 00000000 00:01 42 /memfd:pulseaudio (deleted)\n\
+             7f00ab000000-7f00ab100000 rw-s 00000000 00:19 43 /dev/shm/u1000-Shm_1 (deleted)\n",
+        );
+        process(
+            "977",
+            "gamescope\n",
+            "55d4e0000000-55d4e0100000 r-xp 00000000 00:1f 91 /usr/lib/libvulkan.so.1.3.295 (deleted)\n",
+        );
+        std::fs::create_dir(proc_dir.path().join("self-link")).unwrap();
+
+        let processes = stale_processes_in(proc_dir.path());
+        assert_eq!(
+            processes,
+            vec![
+                StaleProcess {
+                    pid: 977,
+                    name: "gamescope".to_string(),
+                    libraries: vec!["/usr/lib/libvulkan.so.1.3.295".to_string()],
+                },
+                StaleProcess {
+                    pid: 1204,
+                    name: "pipewire".to_string(),
+                    libraries: vec!["/usr/lib/libpipewire-0.3.so.0.1200.0".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reboot_status() {
+        let stale = RebootReason::StaleLibraries {
+            processes: Vec::new(),
+        };
+        assert!(!RebootStatus::new(vec![stale.clone()]).required);
+        assert!(RebootStatus::new(vec![RebootReason::StagedDeployment, stale]).required);
+        assert!(!RebootStatus::new(Vec::new()).required);
+    }
+}
@@ -3,11 +3,17 @@
 //! Only tar.gz extraction stays in Rust (uses bundled flate2/tar crates).
 //! All other operations (path resolution, exists checks, directory listing,
 //! removal) are handled JS-side via @tauri-apps/plugin-fs + @tauri-apps/api/path.
+//! The background update check also looks for a newer Proton-GE release here.
 
 use flate2::read::GzDecoder;
 use std::fs;
+use std::path::Path;
 use tar::Archive;
 
+use crate::system;
+
+const PROTON_GE_REPO: &str = "GloriousEggroll/proton-ge-custom";
+
 /// Extract a .tar.gz file to a destination directory
 pub fn extract_tarball(source: &str, dest: &str) -> Result<(), String> {
     let file = fs::File::open(source).map_err(|e| format!("Failed to open tarball: {}", e))?;
@@ -26,3 +32,33 @@ pub fn extract_tarball(source: &str, dest: &str) -> Result<(), String> {
 
     Ok(())
 }
+
+/// Installed Proton-GE versions (directories holding a `proton` script), newest first
+pub fn installed_versions(runners_dir: &Path) -> Vec<String> {
+    let mut versions: Vec<String> = fs::read_dir(runners_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.path().join("proton").is_file())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    versions.sort_by_key(|v| std::cmp::Reverse(system::version_numbers(v)));
+    versions
+}
+
+/// Newest installed version and the latest Proton-GE release, when that one
+/// is not installed yet. Nothing is reported before the first installation.
+pub async fn check_proton_update(runners_dir: &Path) -> Result<Option<(String, String)>, String> {
+    let installed = installed_versions(runners_dir);
+    let Some(newest) = installed.first() else {
+        return Ok(None);
+    };
+
+    let latest = system::latest_release_tag(PROTON_GE_REPO).await?;
+    Ok(
+        (!installed.contains(&latest) && system::is_newer_version(&latest, newest))
+            .then(|| (newest.clone(), latest)),
+    )
+}
@@ -0,0 +1,346 @@
+//! Pre-update filesystem snapshots
+//!
+//! A snapshot is taken through snapper or Timeshift when configured, or as a
+//! raw read-only btrfs subvolume snapshot of `/` otherwise, so a broken Mesa
+//! or kernel update can be rolled back from the bootloader or a live USB.
+//! Raw btrfs snapshots go through a root-owned helper installed with the
+//! sudoers file, which names the snapshot itself: the sudoers rule then takes
+//! no arguments instead of a destination path wildcard.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+
+const DESCRIPTION: &str = "Pixxiden pre-update";
+const SNAPPER_USERDATA: &str = "pixxiden=yes";
+const BTRFS_SNAPSHOT_DIR: &str = "/.pixxiden-snapshots";
+const BTRFS_PREFIX: &str = "pixxiden-";
+pub const BTRFS_HELPER_PATH: &str = "/usr/local/sbin/pixxiden-btrfs-snapshot";
+const MAX_LISTED: usize = 20;
+
+/// Tool used to take snapshots
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotTool {
+    Snapper,
+    Timeshift,
+    Btrfs,
+}
+
+/// A snapshot created by Pixxiden
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub tool: SnapshotTool,
+    pub id: String,   // snapper number, Timeshift name or subvolume name
+    pub date: String, // `YYYY-MM-DD HH:MM:SS`
+    pub description: String,
+}
+
+/// Pick the snapshot tool: a configured snapper or Timeshift first, raw btrfs otherwise
+pub fn detect_tool() -> Option<SnapshotTool> {
+    if which::which("snapper").is_ok() && Path::new("/etc/snapper/configs/root").exists() {
+        return Some(SnapshotTool::Snapper);
+    }
+    if which::which("timeshift").is_ok() && Path::new("/etc/timeshift/timeshift.json").exists() {
+        return Some(SnapshotTool::Timeshift);
+    }
+
+    let root_fs = Command::new("findmnt")
+        .args(["-n", "-o", "FSTYPE", "/"])
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_default();
+    if root_fs == "btrfs" && which::which("btrfs").is_ok() {
+        return Some(SnapshotTool::Btrfs);
+    }
+
+    None
+}
+
+fn run_sudo(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("sudo")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", args[0], e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Snapshot name from `timeshift --create` output ("Tagged snapshot '2024-05-01_12-00-01': ondemand")
+fn parse_timeshift_created(stdout: &str) -> Option<String> {
+    stdout.lines().find_map(|line| {
+        let rest = line.split("Tagged snapshot '").nth(1)?;
+        Some(rest.split('\'').next()?.to_string())
+    })
+}
+
+/// `2024-05-01_12-00-01` -> `2024-05-01 12:00:01`
+fn timeshift_date(name: &str) -> String {
+    match name.split_once('_') {
+        Some((day, time)) => format!("{} {}", day, time.replace('-', ":")),
+        None => name.to_string(),
+    }
+}
+
+/// Rows of `timeshift --list` ("0    >  2024-05-01_12-00-01  O     Pixxiden pre-update")
+fn parse_timeshift_list(stdout: &str) -> Vec<Snapshot> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let mut tokens = line.split_whitespace().peekable();
+            tokens.next()?.parse::<u32>().ok()?;
+            tokens.next_if_eq(&">");
+            let name = tokens.next()?;
+            let _tags = tokens.next()?;
+            let description = tokens.collect::<Vec<_>>().join(" ");
+            Some(Snapshot {
+                tool: SnapshotTool::Timeshift,
+                id: name.to_string(),
+                date: timeshift_date(name),
+                description,
+            })
+        })
+        .collect()
+}
+
+/// Rows of `snapper --csvout list --columns number,date,description,userdata`
+fn parse_snapper_list(stdout: &str) -> Vec<Snapshot> {
+    stdout
+        .lines()
+        .skip(1) // Header
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split(',').collect();
+            if cols.len() < 4 {
+                return None;
+            }
+            // Userdata is last, descriptions may contain commas
+            let (number, date, userdata) = (cols[0], cols[1], cols[cols.len() - 1]);
+            let description = cols[2..cols.len() - 1].join(",");
+            if !userdata.contains(SNAPPER_USERDATA) && description != DESCRIPTION {
+                return None;
+            }
+            Some(Snapshot {
+                tool: SnapshotTool::Snapper,
+                id: number.to_string(),
+                date: date.to_string(),
+                description,
+            })
+        })
+        .collect()
+}
+
+/// Snapshot from a btrfs subvolume name (`pixxiden-20240501-120001`)
+fn btrfs_snapshot(name: &str) -> Option<Snapshot> {
+    let stamp = name.strip_prefix(BTRFS_PREFIX)?;
+    let date = chrono::NaiveDateTime::parse_from_str(stamp, "%Y%m%d-%H%M%S").ok()?;
+    Some(Snapshot {
+        tool: SnapshotTool::Btrfs,
+        id: name.to_string(),
+        date: date.format("%Y-%m-%d %H:%M:%S").to_string(),
+        description: DESCRIPTION.to_string(),
+    })
+}
+
+/// Take a snapshot of the root filesystem before updating
+pub fn create_pre_update_snapshot() -> Result<Snapshot, String> {
+    let tool = detect_tool().ok_or("No snapshot tool (snapper, Timeshift or btrfs) found")?;
+    let now = chrono::Local::now();
+
+    let snapshot = match tool {
+        SnapshotTool::Snapper => {
+            let stdout = run_sudo(&[
+                "snapper",
+                "-c",
+                "root",
+                "create",
+                "--type",
+                "single",
+                "--cleanup-algorithm",
+                "number",
+                "--print-number",
+                "--description",
+                DESCRIPTION,
+                "--userdata",
+                SNAPPER_USERDATA,
+            ])?;
+            Snapshot {
+                tool,
+                id: stdout.trim().to_string(),
+                date: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+                description: DESCRIPTION.to_string(),
+            }
+        }
+        SnapshotTool::Timeshift => {
+            let stdout = run_sudo(&[
+                "timeshift",
+                "--create",
+                "--comments",
+                DESCRIPTION,
+                "--tags",
+                "O",
+                "--scripted",
+            ])?;
+            let name = parse_timeshift_created(&stdout)
+                .ok_or("Timeshift did not report the created snapshot")?;
+            Snapshot {
+                tool,
+                date: timeshift_date(&name),
+                id: name,
+                description: DESCRIPTION.to_string(),
+            }
+        }
+        SnapshotTool::Btrfs => {
+            let stdout = run_sudo(&[BTRFS_HELPER_PATH])?;
+            btrfs_snapshot(stdout.trim()).ok_or("Invalid btrfs snapshot name")?
+        }
+    };
+
+    log::info!(
+        "Pre-update snapshot created with {:?}: {}",
+        tool,
+        snapshot.id
+    );
+    Ok(snapshot)
+}
+
+/// Snapshots created by Pixxiden, newest first
+pub fn list_snapshots() -> Result<Vec<Snapshot>, String> {
+    let Some(tool) = detect_tool() else {
+        return Ok(Vec::new());
+    };
+
+    let mut snapshots = match tool {
+        SnapshotTool::Snapper => parse_snapper_list(&run_sudo(&[
+            "snapper",
+            "-c",
+            "root",
+            "--csvout",
+            "list",
+            "--columns",
+            "number,date,description,userdata",
+        ])?),
+        SnapshotTool::Timeshift => parse_timeshift_list(&run_sudo(&["timeshift", "--list"])?)
+            .into_iter()
+            .filter(|s| s.description == DESCRIPTION)
+            .collect(),
+        SnapshotTool::Btrfs => match std::fs::read_dir(BTRFS_SNAPSHOT_DIR) {
+            Ok(entries) => entries
+                .flatten()
+                .filter_map(|e| btrfs_snapshot(&e.file_name().to_string_lossy()))
+                .collect(),
+            Err(_) => Vec::new(),
+        },
+    };
+
+    snapshots.sort_by(|a, b| b.date.cmp(&a.date));
+    snapshots.truncate(MAX_LISTED);
+    Ok(snapshots)
+}
+
+/// Root-side script taking a read-only snapshot of `/` and printing its name
+pub fn btrfs_helper_script() -> String {
+    format!(
+        r#"#!/bin/sh
+# Pixxiden - Pre-update btrfs snapshot
+# Generated automatically - DO NOT EDIT MANUALLY
+set -e
+if [ ! -e {BTRFS_SNAPSHOT_DIR} ]; then
+    btrfs subvolume create {BTRFS_SNAPSHOT_DIR} >/dev/null
+fi
+name={BTRFS_PREFIX}$(date +%Y%m%d-%H%M%S)
+btrfs subvolume snapshot -r / "{BTRFS_SNAPSHOT_DIR}/$name" >/dev/null
+echo "$name"
+"#
+    )
+}
+
+/// Sudoers rules allowing Pixxiden to take and list its snapshots.
+/// The btrfs helper is only installed, and whitelisted, when btrfs is the tool.
+pub fn sudoers_rules(username: &str, tool: Option<SnapshotTool>) -> String {
+    let rules = format!(
+        r#"
+# Allow Pixxiden to snapshot the system before updating
+{username} ALL=(ALL) NOPASSWD: /usr/bin/snapper -c root create --type single --cleanup-algorithm number --print-number --description {DESCRIPTION} --userdata pixxiden\=yes
+{username} ALL=(ALL) NOPASSWD: /usr/bin/snapper -c root --csvout list --columns number\,date\,description\,userdata
+{username} ALL=(ALL) NOPASSWD: /usr/bin/timeshift --create --comments {DESCRIPTION} --tags O --scripted
+{username} ALL=(ALL) NOPASSWD: /usr/bin/timeshift --list
+"#
+    );
+    if tool == Some(SnapshotTool::Btrfs) {
+        rules + &format!("{username} ALL=(ALL) NOPASSWD: {BTRFS_HELPER_PATH} \"\"\n")
+    } else {
+        rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timeshift() {
+        assert_eq!(
+            parse_timeshift_created(
+                "Creating new backup...(BTRFS)\nTagged snapshot '2024-05-01_12-00-01': ondemand\n"
+            ),
+            Some("2024-05-01_12-00-01".to_string())
+        );
+
+        let list = "Device : /dev/nvme0n1p2\n\
+                    Num     Name                 Tags  Description\n\
+                    ------------------------------------------------\n\
+                    0    >  2024-04-28_09-00-00  D     \n\
+                    1    >  2024-05-01_12-00-01  O     Pixxiden pre-update\n";
+        let snapshots = parse_timeshift_list(list);
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[1].id, "2024-05-01_12-00-01");
+        assert_eq!(snapshots[1].date, "2024-05-01 12:00:01");
+        assert_eq!(snapshots[1].description, DESCRIPTION);
+        assert_eq!(snapshots[0].description, "");
+    }
+
+    #[test]
+    fn test_parse_snapper_list() {
+        let csv = "number,date,description,userdata\n\
+                   0,,current,\n\
+                   41,2024-04-30 20:11:02,zypp(zypper),important=yes\n\
+                   42,2024-05-01 12:00:01,Pixxiden pre-update,pixxiden=yes\n";
+        let snapshots = parse_snapper_list(csv);
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].id, "42");
+        assert_eq!(snapshots[0].date, "2024-05-01 12:00:01");
+    }
+
+    #[test]
+    fn test_btrfs_snapshot_name() {
+        let snapshot = btrfs_snapshot("pixxiden-20240501-120001").unwrap();
+        assert_eq!(snapshot.date, "2024-05-01 12:00:01");
+        assert!(btrfs_snapshot("@home").is_none());
+    }
+
+    #[test]
+    fn test_sudoers_rules() {
+        let rules = sudoers_rules("testuser", Some(SnapshotTool::Snapper));
+        assert!(rules.contains("--description Pixxiden pre-update --userdata pixxiden\\=yes"));
+        assert!(!rules.contains(BTRFS_HELPER_PATH));
+
+        let rules = sudoers_rules("testuser", Some(SnapshotTool::Btrfs));
+        assert!(rules.contains("NOPASSWD: /usr/local/sbin/pixxiden-btrfs-snapshot \"\"\n"));
+        assert!(!rules.contains('*'));
+    }
+
+    #[test]
+    fn test_btrfs_helper_script() {
+        let script = btrfs_helper_script();
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("name=pixxiden-$(date +%Y%m%d-%H%M%S)"));
+        assert!(script.contains("snapshot -r / \"/.pixxiden-snapshots/$name\""));
+    }
+}
@@ -0,0 +1,308 @@
+//! SteamOS atomic OS image updates
+//!
+//! SteamOS boots from an A/B pair of read-only root images, and updates
+//! replace the inactive image as a whole through `steamos-update`. pacman is
+//! only used once the user has disabled read-only mode themselves
+//! (`steamos-readonly disable`), knowing the next image update discards
+//! whatever it installed.
+
+use serde::Serialize;
+use std::process::Command;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command as TokioCommand;
+
+use crate::os_release;
+use crate::system_updates::{
+    PackageCategory, Status, UpdatePackage, UpdateProgressEvent, UpdateSource,
+};
+use crate::update_failures::UpdateError;
+use crate::update_jobs::UpdateJob;
+
+/// Name of the OS image in update lists and reports
+pub const IMAGE_NAME: &str = "SteamOS";
+
+const EXIT_NO_UPDATE: i32 = 7; // `steamos-update` exit code when already up to date
+
+/// OS image versions and update channel of a SteamOS device
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SteamOsStatus {
+    pub current_version: String,           // `VERSION_ID`, e.g. 3.5.19
+    pub current_build: String,             // `BUILD_ID`, e.g. 20240422.1
+    pub available_version: Option<String>, // Set when an image update is available
+    pub available_build: Option<String>,
+    pub channel: Option<String>, // stable, beta, preview, main...
+    pub read_only: bool,         // pacman is left alone while the root is read-only
+}
+
+/// Image offered by `steamos-update check`
+#[derive(Debug, Clone, Default, PartialEq)]
+struct ImageCandidate {
+    version: String,
+    build_id: String,
+    size: u64,
+}
+
+/// `VERSION_ID` and `BUILD_ID` of the booted image
+fn current_image() -> (String, String) {
+    let mut fields = os_release::parse(&os_release::read());
+    (
+        fields.remove("VERSION_ID").unwrap_or_default(),
+        fields.remove("BUILD_ID").unwrap_or_default(),
+    )
+}
+
+/// First candidate of the `steamos-atomupd-client --query-only` JSON printed by
+/// `steamos-update check` (minor updates are preferred over major ones)
+fn parse_check_output(stdout: &str) -> Option<ImageCandidate> {
+    let json: serde_json::Value = serde_json::from_str(&stdout[stdout.find('{')?..]).ok()?;
+    let image = ["minor", "major"]
+        .iter()
+        .find_map(|kind| json.get(kind)?.get("candidates")?.get(0)?.get("image"))?;
+
+    Some(ImageCandidate {
+        version: image.get("version")?.as_str()?.to_string(),
+        build_id: image
+            .get("buildid")
+            .and_then(|b| b.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        size: image
+            .get("estimated_size")
+            .and_then(|s| s.as_u64())
+            .unwrap_or(0),
+    })
+}
+
+/// Percentage and ETA of a `steamos-update` progress line (`42.17%  01m23s`)
+fn parse_progress(line: &str) -> Option<(f32, String)> {
+    let mut tokens = line.split_whitespace();
+    let percent = tokens
+        .by_ref()
+        .find_map(|t| t.strip_suffix('%')?.parse::<f32>().ok())?;
+    let eta = tokens
+        .next()
+        .filter(|t| t.ends_with('s') || t.ends_with('m'))
+        .unwrap_or("")
+        .to_string();
+    Some((percent.clamp(0.0, 100.0), eta))
+}
+
+/// `steamos-readonly status` prints `enabled` or `disabled`
+fn parse_read_only(stdout: &str, mount_options: &str) -> bool {
+    match stdout.trim() {
+        "disabled" => false,
+        "enabled" => true,
+        // Older images without the status command: look at the root mount
+        _ => mount_options.trim().split(',').any(|o| o == "ro"),
+    }
+}
+
+/// Whether the root filesystem is still read-only
+pub fn is_read_only() -> bool {
+    let status = Command::new("steamos-readonly")
+        .arg("status")
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+        .unwrap_or_default();
+    let mount_options = Command::new("findmnt")
+        .args(["-n", "-o", "OPTIONS", "/"])
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+        .unwrap_or_default();
+    parse_read_only(&status, &mount_options)
+}
+
+fn current_channel() -> Option<String> {
+    let output = Command::new("steamos-select-branch")
+        .arg("-c")
+        .output()
+        .ok()?;
+    let channel = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !channel.is_empty()).then_some(channel)
+}
+
+async fn check_image() -> Result<Option<ImageCandidate>, String> {
+    let output = TokioCommand::new("sudo")
+        .args(["steamos-update", "check"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run steamos-update: {}", e))?;
+
+    match output.status.code() {
+        Some(0) => Ok(Some(
+            parse_check_output(&String::from_utf8_lossy(&output.stdout)).unwrap_or_default(),
+        )),
+        Some(EXIT_NO_UPDATE) => Ok(None),
+        _ => Err(format!(
+            "steamos-update check failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+/// Current and available OS image versions
+pub async fn get_status() -> Result<SteamOsStatus, String> {
+    let (current_version, current_build) = current_image();
+    let candidate = check_image().await?;
+
+    Ok(SteamOsStatus {
+        current_version,
+        current_build,
+        available_version: candidate.as_ref().map(|c| c.version.clone()),
+        available_build: candidate.map(|c| c.build_id),
+        channel: tokio::task::spawn_blocking(current_channel)
+            .await
+            .unwrap_or_default(),
+        read_only: tokio::task::spawn_blocking(is_read_only)
+            .await
+            .unwrap_or(true),
+    })
+}
+
+fn image_label(version: &str, build: &str) -> String {
+    match (version.is_empty(), build.is_empty()) {
+        (false, false) => format!("{} ({})", version, build),
+        (false, true) => version.to_string(),
+        (true, false) => build.to_string(),
+        (true, true) => "?".to_string(),
+    }
+}
+
+/// The pending OS image update as an update list entry
+pub async fn check_image_update() -> Result<Option<UpdatePackage>, String> {
+    let Some(candidate) = check_image().await? else {
+        return Ok(None);
+    };
+    let (current_version, current_build) = current_image();
+
+    Ok(Some(UpdatePackage {
+        name: IMAGE_NAME.to_string(),
+        current_version: image_label(&current_version, &current_build),
+        new_version: image_label(&candidate.version, &candidate.build_id),
+        category: PackageCategory::System,
+        size: candidate.size,
+        installed_size_delta: 0, // Written to the inactive image slot
+        critical: true,          // Booted into on the next restart
+        source: UpdateSource::OsImage,
+    }))
+}
+
+/// Apply the OS image update. Returns whether an image was installed.
+///
+/// The image is written to the inactive slot, so the job stays cancellable
+/// until the end: an interrupted update leaves the running system untouched.
+pub async fn install_image_update(job: &UpdateJob) -> Result<bool, UpdateError> {
+    let mut child = TokioCommand::new("sudo")
+        .arg("steamos-update")
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| UpdateError::unknown(format!("Failed to start steamos-update: {}", e)))?;
+    job.attach(child.id());
+
+    let stderr = child.stderr.take().expect("Failed to capture stderr");
+    let stderr_task = tokio::spawn(async move {
+        let mut lines = Vec::new();
+        let mut reader = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = reader.next_line().await {
+            lines.push(line);
+        }
+        lines
+    });
+
+    // Progress is redrawn in place with carriage returns
+    let stdout = child.stdout.take().expect("Failed to capture stdout");
+    let mut segments = BufReader::new(stdout).split(b'\r');
+    while let Ok(Some(segment)) = segments.next_segment().await {
+        for line in String::from_utf8_lossy(&segment).lines() {
+            if let Some((percent, eta)) = parse_progress(line) {
+                job.progress(UpdateProgressEvent {
+                    stage: Status::Downloading,
+                    package_name: IMAGE_NAME.to_string(),
+                    progress: percent,
+                    package_progress: percent,
+                    downloaded: "".to_string(),
+                    total: "".to_string(),
+                    speed: "".to_string(),
+                    eta,
+                });
+            }
+        }
+    }
+
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| UpdateError::unknown(format!("Failed to wait for steamos-update: {}", e)))?;
+    job.detach();
+    let stderr_lines = stderr_task.await.unwrap_or_default();
+
+    match status.code() {
+        Some(0) => Ok(true),
+        Some(EXIT_NO_UPDATE) => Ok(false),
+        _ => Err(UpdateError::unknown(
+            stderr_lines
+                .iter()
+                .rev()
+                .find(|l| !l.trim().is_empty())
+                .cloned()
+                .unwrap_or_else(|| "steamos-update failed".to_string()),
+        )),
+    }
+}
+
+/// Sudoers rules allowing Pixxiden to check for and apply image updates
+pub fn sudoers_rules(username: &str) -> String {
+    format!(
+        r#"
+# Allow Pixxiden to update the SteamOS image
+{username} ALL=(ALL) NOPASSWD: /usr/bin/steamos-update check
+{username} ALL=(ALL) NOPASSWD: /usr/bin/steamos-update ""
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_check_output() {
+        let stdout = r#"Update available
+{"minor": {"release": "holo", "candidates": [{"image": {"product": "steamos", "release": "holo", "variant": "steamdeck", "arch": "amd64", "version": "3.6.9", "buildid": "20240730.1", "checkpoint": false, "estimated_size": 1048576000}, "update_path": "steamdeck/20240730.1/steamdeck-20240730.1-3.6.9.raucb"}]}}"#;
+        assert_eq!(
+            parse_check_output(stdout),
+            Some(ImageCandidate {
+                version: "3.6.9".to_string(),
+                build_id: "20240730.1".to_string(),
+                size: 1_048_576_000,
+            })
+        );
+        assert_eq!(parse_check_output("No update available"), None);
+    }
+
+    #[test]
+    fn test_parse_progress() {
+        assert_eq!(
+            parse_progress("42.17%  01m23s"),
+            Some((42.17, "01m23s".to_string()))
+        );
+        assert_eq!(parse_progress("100%"), Some((100.0, "".to_string())));
+        assert_eq!(parse_progress("Applying update"), None);
+    }
+
+    #[test]
+    fn test_parse_read_only() {
+        assert!(parse_read_only("enabled\n", "rw,relatime"));
+        assert!(!parse_read_only("disabled\n", "ro,relatime"));
+        assert!(parse_read_only("", "ro,relatime,space_cache=v2"));
+        assert!(!parse_read_only("", "rw,relatime"));
+    }
+
+    #[test]
+    fn test_image_label() {
+        assert_eq!(image_label("3.6.9", "20240730.1"), "3.6.9 (20240730.1)");
+        assert_eq!(image_label("", "20240730.1"), "20240730.1");
+    }
+}
@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::io::Write;
 use std::path::Path;
-use std::process::Command;
+use std::process::{Command, Stdio};
 
+use crate::package_manager;
+use crate::snapshots::{self, SnapshotTool};
 use crate::system_updates::Distro;
 
 /// Status of sudoers configuration
@@ -15,83 +17,29 @@ pub struct SudoersStatus {
 }
 
 const SUDOERS_FILE_PATH: &str = "/etc/sudoers.d/pixxiden";
+const SUDOERS_HEADER: &str =
+    "# Pixxiden - System update permissions\n# Generated automatically - DO NOT EDIT MANUALLY\n";
+
+/// Generate sudoers content based on distribution, the package hold list
+/// and the snapshot tool
+fn generate_sudoers_content(
+    username: &str,
+    distro: &Distro,
+    holds: &[String],
+    snapshot_tool: Option<SnapshotTool>,
+) -> String {
+    let Some(backend) = package_manager::for_distro(distro) else {
+        return String::new();
+    };
 
-/// Generate sudoers content based on distribution
-fn generate_sudoers_content(username: &str, distro: &Distro) -> String {
-    match distro {
-        Distro::Arch | Distro::SteamOS => {
-            format!(
-                r#"# Pixxiden - System update permissions
-# Generated automatically - DO NOT EDIT MANUALLY
-
-# Allow Pixxiden to check for updates
-{username} ALL=(ALL) NOPASSWD: /usr/bin/pacman -Qu
-{username} ALL=(ALL) NOPASSWD: /usr/bin/pacman -Sy
-{username} ALL=(ALL) NOPASSWD: /usr/bin/pacman -Syy
-
-# Allow Pixxiden to install updates
-{username} ALL=(ALL) NOPASSWD: /usr/bin/pacman -Syu --noconfirm
-{username} ALL=(ALL) NOPASSWD: /usr/bin/pacman -Su --noconfirm
-
-# Allow Pixxiden to clean package cache
-{username} ALL=(ALL) NOPASSWD: /usr/bin/pacman -Sc --noconfirm
-
-# Allow system reboot
-{username} ALL=(ALL) NOPASSWD: /usr/bin/systemctl reboot
-{username} ALL=(ALL) NOPASSWD: /usr/bin/systemctl poweroff
-"#,
-                username = username
-            )
-        }
-        Distro::Debian => {
-            format!(
-                r#"# Pixxiden - System update permissions
-# Generated automatically - DO NOT EDIT MANUALLY
-
-# Allow Pixxiden to check for updates
-{username} ALL=(ALL) NOPASSWD: /usr/bin/apt update
-{username} ALL=(ALL) NOPASSWD: /usr/bin/apt list --upgradable
-
-# Allow Pixxiden to install updates
-{username} ALL=(ALL) NOPASSWD: /usr/bin/apt upgrade -y
-{username} ALL=(ALL) NOPASSWD: /usr/bin/apt full-upgrade -y
-{username} ALL=(ALL) NOPASSWD: /usr/bin/apt dist-upgrade -y
-
-# Allow Pixxiden to clean
-{username} ALL=(ALL) NOPASSWD: /usr/bin/apt autoremove -y
-{username} ALL=(ALL) NOPASSWD: /usr/bin/apt autoclean
-
-# Allow system reboot
-{username} ALL=(ALL) NOPASSWD: /usr/bin/systemctl reboot
-{username} ALL=(ALL) NOPASSWD: /usr/bin/systemctl poweroff
-"#,
-                username = username
-            )
-        }
-        Distro::Fedora => {
-            format!(
-                r#"# Pixxiden - System update permissions
-# Generated automatically - DO NOT EDIT MANUALLY
-
-# Allow Pixxiden to check for updates
-{username} ALL=(ALL) NOPASSWD: /usr/bin/dnf check-update
-
-# Allow Pixxiden to install updates
-{username} ALL=(ALL) NOPASSWD: /usr/bin/dnf upgrade -y
-
-# Allow Pixxiden to clean
-{username} ALL=(ALL) NOPASSWD: /usr/bin/dnf autoremove -y
-{username} ALL=(ALL) NOPASSWD: /usr/bin/dnf clean all
-
-# Allow system reboot
-{username} ALL=(ALL) NOPASSWD: /usr/bin/systemctl reboot
-{username} ALL=(ALL) NOPASSWD: /usr/bin/systemctl poweroff
-"#,
-                username = username
-            )
-        }
-        Distro::Unknown => String::new(),
-    }
+    SUDOERS_HEADER.to_string()
+        + &backend.sudoers_rules(username)
+        + &backend.hold_sudoers_rules(username, holds)
+        // Package manager output is parsed, so the forced C locale must survive sudo
+        + &format!("\nDefaults:{username} env_keep += \"LC_ALL\"\n")
+        + &crate::prerequisites::sudoers_rules(username, distro)
+        + &crate::flatpak::sudoers_rules(username)
+        + &snapshots::sudoers_rules(username, snapshot_tool)
 }
 
 /// Get current username
@@ -130,6 +78,14 @@ pub fn is_sudoers_configured() -> Result<SudoersStatus, String> {
 
 /// Configure sudoers for Pixxiden (requires password once)
 pub async fn configure_sudoers(password: String) -> Result<(), String> {
+    configure_sudoers_with_holds(password, &crate::package_holds::load()).await
+}
+
+/// Configure sudoers with the exact command lines for a package hold list
+pub async fn configure_sudoers_with_holds(
+    password: String,
+    holds: &[String],
+) -> Result<(), String> {
     let distro = crate::system_updates::detect_distro();
 
     if matches!(distro, Distro::Unknown) {
@@ -137,40 +93,58 @@ pub async fn configure_sudoers(password: String) -> Result<(), String> {
     }
 
     let username = get_current_username()?;
-    let sudoers_content = generate_sudoers_content(&username, &distro);
+    let snapshot_tool = snapshots::detect_tool();
+    let sudoers_content = generate_sudoers_content(&username, &distro, holds, snapshot_tool);
 
     if sudoers_content.is_empty() {
         return Err("Unsupported distribution for sudoers configuration".to_string());
     }
 
-    // Create a temporary file with the sudoers content
-    let temp_path = "/tmp/pixxiden_sudoers_temp";
-    fs::write(temp_path, &sudoers_content)
+    // Private temporary files: root installs them, nobody else may swap them
+    let temp_file = private_temp_file(&sudoers_content)
         .map_err(|e| format!("Failed to write temporary sudoers file: {}", e))?;
+    let temp_path = temp_file.path().to_string_lossy().into_owned();
 
     // Validate the sudoers file using visudo
     let validate = Command::new("visudo")
-        .args(["-c", "-f", temp_path])
+        .args(["-c", "-f", &temp_path])
         .output()
         .map_err(|e| format!("Failed to validate sudoers file: {}", e))?;
 
     if !validate.status.success() {
-        fs::remove_file(temp_path).ok();
         return Err(format!(
             "Invalid sudoers syntax: {}",
             String::from_utf8_lossy(&validate.stderr)
         ));
     }
 
-    // Use sudo with password to copy the file to /etc/sudoers.d/
+    let mut install_commands = Vec::new();
+
+    // The btrfs snapshot helper is whitelisted by path, so it is installed root-owned
+    let helper_file = if snapshot_tool == Some(SnapshotTool::Btrfs) {
+        let helper_file = private_temp_file(&snapshots::btrfs_helper_script())
+            .map_err(|e| format!("Failed to write temporary snapshot helper: {}", e))?;
+        install_commands.push(format!(
+            "install -D -m 0755 -o root -g root {} {}",
+            helper_file.path().display(),
+            snapshots::BTRFS_HELPER_PATH
+        ));
+        Some(helper_file)
+    } else {
+        None
+    };
+
+    install_commands.push(format!(
+        "cp {} {} && chmod 0440 {} && chown root:root {}",
+        temp_path, SUDOERS_FILE_PATH, SUDOERS_FILE_PATH, SUDOERS_FILE_PATH
+    ));
+
+    // Use sudo with password to copy the files to /etc/sudoers.d/ (and /usr/local/sbin/)
     // We use `sh -c` with echo to pass the password
     let install_script = format!(
-        r#"echo '{}' | sudo -S sh -c 'cp {} {} && chmod 0440 {} && chown root:root {}'"#,
+        r#"echo '{}' | sudo -S sh -c '{}'"#,
         password.replace("'", "'\\''"), // Escape single quotes
-        temp_path,
-        SUDOERS_FILE_PATH,
-        SUDOERS_FILE_PATH,
-        SUDOERS_FILE_PATH
+        install_commands.join(" && ")
     );
 
     let install = Command::new("sh")
@@ -178,8 +152,9 @@ pub async fn configure_sudoers(password: String) -> Result<(), String> {
         .output()
         .map_err(|e| format!("Failed to install sudoers file: {}", e))?;
 
-    // Clean up temp file
-    fs::remove_file(temp_path).ok();
+    // Temporary files are removed when dropped
+    drop(temp_file);
+    drop(helper_file);
 
     if !install.status.success() {
         let stderr = String::from_utf8_lossy(&install.stderr);
@@ -207,26 +182,130 @@ pub async fn configure_sudoers(password: String) -> Result<(), String> {
     Ok(())
 }
 
+/// Write `contents` to a new temporary file only the current user can access
+fn private_temp_file(contents: &str) -> std::io::Result<tempfile::NamedTempFile> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(contents.as_bytes())?;
+    file.flush()?;
+    Ok(file)
+}
+
+/// Run a command as root with the user's password, for one-off changes
+/// that must not be whitelisted in the sudoers rules
+pub fn run_with_password(password: &str, args: &[&str]) -> Result<(), String> {
+    let mut child = Command::new("sudo")
+        .args(["-S", "-p", "", "--"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run sudo: {}", e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "{}", password).map_err(|e| format!("Failed to write to sudo: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run sudo: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("incorrect password") || stderr.contains("Sorry") {
+            return Err("Mot de passe incorrect".to_string());
+        }
+        return Err(format!("`{}` failed: {}", args.join(" "), stderr.trim()));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_generate_sudoers_content_arch() {
-        let content = generate_sudoers_content("testuser", &Distro::Arch);
+        let content = generate_sudoers_content("testuser", &Distro::Arch, &[], None);
         assert!(content.contains("testuser"));
         assert!(content.contains("pacman -Syu"));
         assert!(content.contains("NOPASSWD"));
+        assert!(content.contains("/usr/bin/flatpak update --system --noninteractive -y"));
+        assert!(content.contains("/usr/bin/timeshift --create --comments Pixxiden pre-update"));
+    }
+
+    #[test]
+    fn test_generate_sudoers_content_steamos() {
+        let content = generate_sudoers_content("testuser", &Distro::SteamOS, &[], None);
+        assert!(content.contains("/usr/bin/steamos-update check"));
+        // No arguments at all, not any arguments
+        assert!(content.contains("NOPASSWD: /usr/bin/steamos-update \"\"\n"));
+        assert!(content.contains("pacman -Syu"));
+        assert!(
+            !generate_sudoers_content("testuser", &Distro::Arch, &[], None)
+                .contains("steamos-update")
+        );
+    }
+
+    #[test]
+    fn test_generate_sudoers_content_ostree() {
+        let content = generate_sudoers_content("testuser", &Distro::Ostree, &[], None);
+        assert!(content.contains("testuser ALL=(ALL) NOPASSWD: /usr/bin/rpm-ostree upgrade"));
+        assert!(!content.contains("dnf upgrade"));
+    }
+
+    #[test]
+    fn test_generate_sudoers_content_opensuse() {
+        let content = generate_sudoers_content("testuser", &Distro::OpenSuse, &[], None);
+        assert!(content.contains("/usr/bin/zypper --non-interactive --xmlout dup"));
+        assert!(content.contains("zypper --non-interactive install libvulkan1-32bit"));
     }
 
     #[test]
     fn test_generate_sudoers_content_debian() {
-        let content = generate_sudoers_content("testuser", &Distro::Debian);
+        let content = generate_sudoers_content("testuser", &Distro::Debian, &[], None);
+        assert!(content.contains("/usr/bin/apt-get -o APT\\:\\:Status-Fd\\=1 -y upgrade"));
+        assert!(content.contains("Defaults:testuser env_keep += \"LC_ALL\""));
         assert!(content.contains("testuser"));
         assert!(content.contains("apt upgrade"));
         assert!(content.contains("NOPASSWD"));
     }
 
+    #[test]
+    fn test_generate_sudoers_content_includes_prerequisites() {
+        let content = generate_sudoers_content("testuser", &Distro::Arch, &[], None);
+        assert!(content.contains("pacman -S --needed --noconfirm lib32-vulkan-radeon"));
+        assert!(generate_sudoers_content("testuser", &Distro::Unknown, &[], None).is_empty());
+    }
+
+    #[test]
+    fn test_generate_sudoers_content_holds() {
+        let holds = vec!["lib32-nvidia-utils".to_string(), "nvidia-utils".to_string()];
+        let content = generate_sudoers_content("testuser", &Distro::Arch, &holds, None);
+        assert!(content.contains(
+            "testuser ALL=(ALL) NOPASSWD: /usr/bin/pacman -Syu --noconfirm --ignore lib32-nvidia-utils\\,nvidia-utils\n"
+        ));
+        let content = generate_sudoers_content("testuser", &Distro::Fedora, &holds, None);
+        assert!(content
+            .contains("/usr/bin/dnf upgrade -y --exclude\\=lib32-nvidia-utils\\,nvidia-utils\n"));
+        // Never a wildcard: extra options would run as root
+        for distro in [Distro::Arch, Distro::Debian, Distro::Fedora] {
+            let content = generate_sudoers_content("testuser", &distro, &holds, None);
+            assert!(!content.contains("--ignore *"));
+            assert!(!content.contains("--exclude\\=*"));
+            assert!(!content.contains("apt-mark"));
+        }
+    }
+
+    #[test]
+    fn test_generate_sudoers_content_btrfs_helper() {
+        let helper = "/usr/local/sbin/pixxiden-btrfs-snapshot \"\"";
+        let content = generate_sudoers_content("testuser", &Distro::Arch, &[], None);
+        assert!(!content.contains(helper));
+        let content =
+            generate_sudoers_content("testuser", &Distro::Arch, &[], Some(SnapshotTool::Btrfs));
+        assert!(content.contains(helper));
+    }
+
     #[test]
     fn test_get_current_username() {
         let username = get_current_username();
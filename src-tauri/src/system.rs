@@ -1,7 +1,13 @@
 use serde::{Deserialize, Serialize};
 use std::process::Command;
+use std::time::Duration;
 use sysinfo::{Disks, System};
 
+/// GitHub `owner/name` publishing Pixxiden releases, from the Cargo.toml `repository`
+fn pixxiden_repo() -> &'static str {
+    env!("CARGO_PKG_REPOSITORY").trim_start_matches("https://github.com/")
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SystemInfo {
@@ -84,10 +90,51 @@ pub fn get_disk_info() -> Result<Vec<DiskInfo>, String> {
     Ok(disk_infos)
 }
 
+/// Numeric components of a version or release tag (`GE-Proton9-20` gives `[9, 20]`)
+pub fn version_numbers(version: &str) -> Vec<u64> {
+    version
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|n| n.parse().ok())
+        .collect()
+}
+
+/// Whether `candidate` is a newer version than `current`
+pub fn is_newer_version(candidate: &str, current: &str) -> bool {
+    version_numbers(candidate) > version_numbers(current)
+}
+
+/// Tag of the latest release of a GitHub repository (`owner/name`)
+pub async fn latest_release_tag(repo: &str) -> Result<String, String> {
+    let release: serde_json::Value = reqwest::Client::new()
+        .get(format!(
+            "https://api.github.com/repos/{}/releases/latest",
+            repo
+        ))
+        .header("User-Agent", "Pixxiden")
+        .timeout(Duration::from_secs(15))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to fetch the latest {} release: {}", repo, e))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse the latest {} release: {}", repo, e))?;
+
+    release
+        .get("tag_name")
+        .and_then(|t| t.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| format!("Latest {} release has no tag", repo))
+}
+
+/// Newer Pixxiden release, if any
+pub async fn latest_pixxiden_release() -> Result<Option<String>, String> {
+    let tag = latest_release_tag(pixxiden_repo()).await?;
+    Ok(is_newer_version(&tag, env!("CARGO_PKG_VERSION")).then_some(tag))
+}
+
 pub async fn check_for_updates() -> Result<bool, String> {
-    // Placeholder for update checking logic
-    // In a real implementation, this would check GitHub releases or a custom update server
-    Ok(false)
+    Ok(latest_pixxiden_release().await?.is_some())
 }
 
 pub async fn shutdown_system() -> Result<(), String> {
@@ -137,3 +184,21 @@ pub fn save_settings(config: SettingsConfig) -> Result<(), String> {
     );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer_version() {
+        assert!(is_newer_version("v0.2.0", "0.1.0"));
+        assert!(is_newer_version("GE-Proton10-1", "GE-Proton9-20"));
+        assert!(!is_newer_version("v0.1.0", "0.1.0-alpha"));
+        assert!(!is_newer_version("GE-Proton9-9", "GE-Proton9-20"));
+    }
+
+    #[test]
+    fn test_pixxiden_repo() {
+        assert_eq!(pixxiden_repo(), "alshyra/pixxiden");
+    }
+}
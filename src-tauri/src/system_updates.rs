@@ -1,17 +1,37 @@
+use async_trait::async_trait;
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
 use std::process::Command;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command as TokioCommand;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::arch_news::{self, NewsItem};
+use crate::aur::{self, AurHelper};
+use crate::os_release::{self, DistroInfo};
+use crate::ostree;
+use crate::package_holds::{self, HoldConflict};
+use crate::package_log;
+use crate::package_manager::{self, CommandRunner, PackageManager};
+use crate::prerequisites;
+use crate::reboot::{self, RebootReason, RebootStatus};
+use crate::snapshots::{self, Snapshot};
+use crate::steamos;
+use crate::update_failures::{self, UpdateError};
+use crate::update_history::UpdateHistory;
+use crate::update_jobs::{UpdateEvents, UpdateJob, UpdateJobs};
 
 /// Linux distribution types supported
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Distro {
-    Arch,    // Arch Linux, CachyOS, EndeavourOS, Manjaro
-    SteamOS, // Steam Deck (Arch-based)
-    Debian,  // Debian, Ubuntu, Pop!_OS, Linux Mint
-    Fedora,  // Fedora
+    Arch,     // Arch Linux, CachyOS, EndeavourOS, Manjaro
+    SteamOS,  // Steam Deck (Arch-based)
+    Ostree,   // Fedora Atomic (Silverblue, Kinoite), Bazzite, bootc images
+    Debian,   // Debian, Ubuntu, Pop!_OS, Linux Mint
+    Fedora,   // Fedora
+    OpenSuse, // openSUSE Tumbleweed, Slowroll, Leap
     Unknown,
 }
 
@@ -20,8 +40,10 @@ impl std::fmt::Display for Distro {
         match self {
             Distro::Arch => write!(f, "Arch Linux"),
             Distro::SteamOS => write!(f, "SteamOS"),
+            Distro::Ostree => write!(f, "Fedora Atomic"),
             Distro::Debian => write!(f, "Debian/Ubuntu"),
             Distro::Fedora => write!(f, "Fedora"),
+            Distro::OpenSuse => write!(f, "openSUSE"),
             Distro::Unknown => write!(f, "Unknown"),
         }
     }
@@ -39,6 +61,17 @@ pub enum PackageCategory {
     Library,     // other libs
 }
 
+/// Where an update comes from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdateSource {
+    System,    // Distribution package manager
+    Flatpak,   // Flatpak applications and runtimes
+    FlatpakGl, // Flatpak GL runtime extensions (follow the host graphics driver)
+    OsImage,   // Atomic OS image (SteamOS)
+    Aur,       // AUR packages, built by paru or yay
+}
+
 /// Represents a package available for update
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -47,8 +80,10 @@ pub struct UpdatePackage {
     pub current_version: String,
     pub new_version: String,
     pub category: PackageCategory,
-    pub size: u64,
+    pub size: u64,                 // Download size in bytes
+    pub installed_size_delta: i64, // Change in installed size, in bytes
     pub critical: bool,
+    pub source: UpdateSource,
 }
 
 /// Result of checking for updates
@@ -58,7 +93,12 @@ pub struct UpdateCheckResult {
     pub distro: Distro,
     pub packages: Vec<UpdatePackage>,
     pub total_size: u64,
+    pub total_installed_size_delta: i64,
     pub requires_reboot: bool,
+    pub news: Vec<NewsItem>,               // Unread Arch news (Arch only)
+    pub demo: bool,                        // Replayed from a transcript, the system is left alone
+    pub held: Vec<UpdatePackage>,          // Updates skipped because of the hold list
+    pub hold_conflicts: Vec<HoldConflict>, // Upgrades the hold list would break
 }
 
 /// Result of installing updates
@@ -67,9 +107,23 @@ pub struct UpdateCheckResult {
 pub struct UpdateReport {
     pub total_packages: u32,
     pub installed_successfully: u32,
+    pub installed: Vec<String>,
     pub failed: Vec<String>,
     pub requires_reboot: bool,
     pub duration_seconds: u64,
+    pub error: Option<UpdateError>, // Set when the package manager exited with an error
+    pub snapshot: Option<Snapshot>, // Taken before updating, when requested
+    #[serde(skip)]
+    pub flatpaks: Vec<String>, // Installed/failed names updated through Flatpak
+}
+
+/// Packages handled by one package manager run
+#[derive(Debug, Default)]
+pub struct InstallOutcome {
+    pub installed: Vec<String>,
+    pub failed: Vec<String>,
+    pub error: Option<UpdateError>,
+    pub staged: bool, // A new OS image/deployment is booted into on the next restart
 }
 
 /// Progress event for updates
@@ -78,7 +132,8 @@ pub struct UpdateReport {
 pub struct UpdateProgressEvent {
     pub stage: Status,
     pub package_name: String,
-    pub progress: f32, // 0-100
+    pub progress: f32,         // Overall, 0-100
+    pub package_progress: f32, // Current package, 0-100
     pub downloaded: String,
     pub total: String,
     pub speed: String,
@@ -103,43 +158,32 @@ impl std::fmt::Display for Status {
     }
 }
 
-/// Detect the current Linux distribution
-pub fn detect_distro() -> Distro {
-    // Check for SteamOS first (special case)
-    if Path::new("/etc/steamos-release").exists() {
-        return Distro::SteamOS;
-    }
-
-    // Read /etc/os-release
-    if let Ok(content) = std::fs::read_to_string("/etc/os-release") {
-        let content_lower = content.to_lowercase();
-
-        // Check ID field
-        if content_lower.contains("id=arch")
-            || content_lower.contains("id=cachyos")
-            || content_lower.contains("id=endeavouros")
-            || content_lower.contains("id=manjaro")
-            || content_lower.contains("id_like=arch")
-        {
-            return Distro::Arch;
-        }
+/// Identify the current Linux distribution
+pub fn detect_distro_info() -> DistroInfo {
+    let mut info = DistroInfo::from_os_release(&os_release::read());
+
+    // Image-based systems must never be handed to their base distro's package
+    // manager, whatever their os-release says
+    info.family = if Path::new("/etc/steamos-release").exists() {
+        Distro::SteamOS
+    } else if ostree::detect_tool().is_some() {
+        Distro::Ostree
+    } else if info.family == Distro::Unknown {
+        detect_package_manager()
+    } else {
+        info.family
+    };
 
-        if content_lower.contains("id=debian")
-            || content_lower.contains("id=ubuntu")
-            || content_lower.contains("id=pop")
-            || content_lower.contains("id=linuxmint")
-            || content_lower.contains("id_like=debian")
-            || content_lower.contains("id_like=ubuntu")
-        {
-            return Distro::Debian;
-        }
+    info
+}
 
-        if content_lower.contains("id=fedora") || content_lower.contains("id_like=fedora") {
-            return Distro::Fedora;
-        }
-    }
+/// Detect the current Linux distribution family
+pub fn detect_distro() -> Distro {
+    detect_distro_info().family
+}
 
-    // Fallback: check for package managers
+/// Family of the available package manager, for unrecognized os-release IDs
+fn detect_package_manager() -> Distro {
     if Path::new("/usr/bin/pacman").exists() {
         return Distro::Arch;
     }
@@ -149,12 +193,15 @@ pub fn detect_distro() -> Distro {
     if Path::new("/usr/bin/dnf").exists() {
         return Distro::Fedora;
     }
+    if Path::new("/usr/bin/zypper").exists() {
+        return Distro::OpenSuse;
+    }
 
     Distro::Unknown
 }
 
 /// Check if a package is critical (requires reboot)
-fn is_critical_package(name: &str) -> bool {
+pub fn is_critical_package(name: &str) -> bool {
     let critical_packages = [
         "linux",
         "kernel",
@@ -178,7 +225,7 @@ fn is_critical_package(name: &str) -> bool {
 }
 
 /// Categorize a package by its name
-fn categorize_package(name: &str) -> PackageCategory {
+pub fn categorize_package(name: &str) -> PackageCategory {
     let name_lower = name.to_lowercase();
 
     // System packages
@@ -232,22 +279,225 @@ fn categorize_package(name: &str) -> PackageCategory {
     PackageCategory::Application
 }
 
+/// Parse a human readable size (`12.3 MB`, `1.5 KiB`, `512 bytes`) into bytes
+pub fn parse_size(size: &str) -> u64 {
+    let size = size.trim().trim_start_matches('<').trim();
+    let (number, unit) = size.split_once(' ').unwrap_or((size, "B"));
+    let Ok(number) = number.replace(',', ".").parse::<f64>() else {
+        return 0;
+    };
+
+    let multiplier = match unit.trim() {
+        "kB" | "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => 1.0, // B, bytes
+    };
+    (number * multiplier) as u64
+}
+
+/// Download size and installed size change of a pending upgrade
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct PackageSizes {
+    download: u64,
+    installed_delta: i64,
+}
+
+/// Fill in package sizes from a batch lookup
+fn apply_sizes(packages: &mut [UpdatePackage], sizes: &HashMap<String, PackageSizes>) {
+    for package in packages {
+        if let Some(sizes) = sizes.get(&package.name) {
+            package.size = sizes.download;
+            package.installed_size_delta = sizes.installed_delta;
+        }
+    }
+}
+
+/// Parse `pacman -Si`/`-Qi` output into name -> (download size, installed size)
+fn parse_pacman_info(stdout: &str) -> HashMap<String, (u64, u64)> {
+    let mut info = HashMap::new();
+    let mut name: Option<String> = None;
+    let mut download = 0;
+
+    for line in stdout.lines() {
+        let Some((key, value)) = line.split_once(" : ") else {
+            continue;
+        };
+        match key.trim() {
+            "Name" => {
+                name = Some(value.trim().to_string());
+                download = 0;
+            }
+            "Download Size" => download = parse_size(value),
+            "Installed Size" => {
+                // A package may be listed by several repositories, the first one wins
+                if let Some(name) = name.take() {
+                    info.entry(name).or_insert((download, parse_size(value)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    info
+}
+
+/// Parse tab-separated `name size [size]` rows printed by expac
+fn parse_expac(stdout: &str) -> HashMap<String, (u64, u64)> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let mut cols = line.split('\t');
+            let name = cols.next()?.trim().to_string();
+            let first: u64 = cols.next()?.trim().parse().ok()?;
+            match cols.next() {
+                Some(second) => Some((name, (first, second.trim().parse().ok()?))),
+                None => Some((name, (0, first))),
+            }
+        })
+        .collect()
+}
+
+/// Sizes of pending pacman upgrades, from expac when available, else pacman -Si/-Qi
+fn pacman_sizes(runner: &dyn CommandRunner, names: &[&str]) -> HashMap<String, PackageSizes> {
+    if names.is_empty() {
+        return HashMap::new();
+    }
+
+    let run = |program: &str, args: &[&str]| -> Result<String, String> {
+        let args: Vec<&str> = args.iter().chain(names).copied().collect();
+        runner.output(program, &args).map(|o| o.stdout)
+    };
+
+    let (sync, local) = match run("expac", &["-S", "%n\t%k\t%m"]) {
+        Ok(sync) => (
+            parse_expac(&sync),
+            parse_expac(&run("expac", &["-Q", "%n\t%m"]).unwrap_or_default()),
+        ),
+        Err(_) => (
+            parse_pacman_info(&run("pacman", &["-Si"]).unwrap_or_default()),
+            parse_pacman_info(&run("pacman", &["-Qi"]).unwrap_or_default()),
+        ),
+    };
+
+    sync.into_iter()
+        .map(|(name, (download, installed))| {
+            let current = local.get(&name).map_or(0, |(_, size)| *size);
+            let sizes = PackageSizes {
+                download,
+                installed_delta: installed as i64 - current as i64,
+            };
+            (name, sizes)
+        })
+        .collect()
+}
+
+/// Parse `apt-get --print-uris` lines: `'uri' name_version_arch.deb size hash`
+fn parse_apt_uris(stdout: &str) -> HashMap<String, u64> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let mut cols = line.split_whitespace();
+            let _uri = cols.next()?;
+            let file = cols.next()?;
+            let size = cols.next()?.parse().ok()?;
+            let name = file.split('_').next()?;
+            Some((name.to_string(), size))
+        })
+        .collect()
+}
+
+/// Parse `Package`/`Installed-Size` (KiB) fields of `apt-cache show` records
+fn parse_apt_installed_sizes(stdout: &str) -> HashMap<String, u64> {
+    let mut sizes = HashMap::new();
+    let mut name: Option<String> = None;
+
+    for line in stdout.lines() {
+        if let Some(value) = line.strip_prefix("Package:") {
+            name = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("Installed-Size:") {
+            if let (Some(name), Ok(kib)) = (name.take(), value.trim().parse::<u64>()) {
+                sizes.entry(name).or_insert(kib * 1024);
+            }
+        }
+    }
+
+    sizes
+}
+
+/// Sizes of pending apt upgrades (`(name, new_version)` pairs)
+fn apt_sizes(
+    runner: &dyn CommandRunner,
+    packages: &[(&str, &str)],
+) -> HashMap<String, PackageSizes> {
+    if packages.is_empty() {
+        return HashMap::new();
+    }
+
+    // Only lists what is not already in the apt cache, i.e. the actual download
+    let downloads = runner
+        .output("apt-get", &["--print-uris", "-qq", "-y", "upgrade"])
+        .map(|o| parse_apt_uris(&o.stdout))
+        .unwrap_or_default();
+
+    let versions: Vec<String> = packages
+        .iter()
+        .map(|(name, version)| format!("{}={}", name, version))
+        .collect();
+    let args: Vec<&str> = std::iter::once("show")
+        .chain(versions.iter().map(String::as_str))
+        .collect();
+    let candidates = runner
+        .output("apt-cache", &args)
+        .map(|o| parse_apt_installed_sizes(&o.stdout))
+        .unwrap_or_default();
+
+    let args: Vec<&str> = ["-W", "-f", "${Package}\t${Installed-Size}\n"]
+        .into_iter()
+        .chain(packages.iter().map(|(name, _)| *name))
+        .collect();
+    let installed: HashMap<String, u64> = runner
+        .output("dpkg-query", &args)
+        .map(|o| {
+            o.stdout
+                .lines()
+                .filter_map(|line| {
+                    let (name, kib) = line.split_once('\t')?;
+                    Some((name.to_string(), kib.trim().parse::<u64>().ok()? * 1024))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    packages
+        .iter()
+        .map(|(name, _)| {
+            let new = candidates.get(*name).copied().unwrap_or(0);
+            let current = installed.get(*name).copied().unwrap_or(new);
+            let sizes = PackageSizes {
+                download: downloads.get(*name).copied().unwrap_or(0),
+                installed_delta: new as i64 - current as i64,
+            };
+            (name.to_string(), sizes)
+        })
+        .collect()
+}
+
 /// Check for available updates using pacman (Arch-based)
-async fn check_updates_pacman() -> Result<Vec<UpdatePackage>, String> {
-    let output = Command::new("pacman")
-        .args(["-Qu"])
-        .output()
-        .map_err(|e| format!("Failed to run pacman: {}", e))?;
+async fn check_updates_pacman(runner: &dyn CommandRunner) -> Result<Vec<UpdatePackage>, String> {
+    let output = runner.output("pacman", &["-Qu"])?;
 
-    if !output.status.success() && output.stdout.is_empty() {
+    if !output.success() && output.stdout.is_empty() {
         // No updates available
         return Ok(vec![]);
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
     let mut packages = Vec::new();
 
-    for line in stdout.lines() {
+    for line in output.stdout.lines() {
         // Format: "package_name current_version -> new_version"
         let parts: Vec<&str> = line.split_whitespace().collect();
         if parts.len() >= 4 && parts[2] == "->" {
@@ -261,31 +511,29 @@ async fn check_updates_pacman() -> Result<Vec<UpdatePackage>, String> {
                 name,
                 current_version: current,
                 new_version: new,
-                size: 0, // Size estimation not available from pacman -Qu
+                size: 0, // Filled in by pacman_sizes
+                installed_size_delta: 0,
+                source: UpdateSource::System,
             });
         }
     }
 
+    let names: Vec<&str> = packages.iter().map(|p| p.name.as_str()).collect();
+    let sizes = pacman_sizes(runner, &names);
+    apply_sizes(&mut packages, &sizes);
+
     Ok(packages)
 }
 
 /// Check for available updates using apt (Debian-based)
-async fn check_updates_apt() -> Result<Vec<UpdatePackage>, String> {
+async fn check_updates_apt(runner: &dyn CommandRunner) -> Result<Vec<UpdatePackage>, String> {
     // First, update package list
-    Command::new("sudo")
-        .args(["apt", "update"])
-        .output()
-        .map_err(|e| format!("Failed to run apt update: {}", e))?;
-
-    let output = Command::new("apt")
-        .args(["list", "--upgradable"])
-        .output()
-        .map_err(|e| format!("Failed to run apt list: {}", e))?;
+    runner.output("sudo", &["apt", "update"])?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let output = runner.output("apt", &["list", "--upgradable"])?;
     let mut packages = Vec::new();
 
-    for line in stdout.lines().skip(1) {
+    for line in output.stdout.lines().skip(1) {
         // Skip header line
         // Format: "package/distribution version arch [upgradable from: current]"
         if let Some(name) = line.split('/').next() {
@@ -304,240 +552,1999 @@ async fn check_updates_apt() -> Result<Vec<UpdatePackage>, String> {
                     name: name.to_string(),
                     current_version,
                     new_version,
-                    size: 0,
+                    size: 0, // Filled in by apt_sizes
+                    installed_size_delta: 0,
+                    source: UpdateSource::System,
                 });
             }
         }
     }
 
+    let pending: Vec<(&str, &str)> = packages
+        .iter()
+        .map(|p| (p.name.as_str(), p.new_version.as_str()))
+        .collect();
+    let sizes = apt_sizes(runner, &pending);
+    apply_sizes(&mut packages, &sizes);
+
+    Ok(packages)
+}
+
+/// A package listed by `dnf check-update`
+#[derive(Debug, Clone, PartialEq)]
+struct DnfUpdate {
+    name: String,
+    arch: String,
+    new_version: String,
+    obsoletes: Option<String>, // Installed package replaced by this one
+}
+
+/// Parse `dnf check-update` output (dnf4 and dnf5).
+/// Packages under "Obsoleting Packages" are followed by the indented
+/// package(s) they replace; dnf4 wraps long names onto their own line.
+fn parse_dnf_check_update(stdout: &str) -> Vec<DnfUpdate> {
+    let mut updates: Vec<DnfUpdate> = Vec::new();
+    let mut obsoleting = false;
+    let mut wrapped: Option<String> = None;
+
+    for line in stdout.lines() {
+        if line.starts_with("Obsoleting Packages") {
+            obsoleting = true;
+            continue;
+        }
+
+        let indented = line.starts_with(' ');
+        let mut cols: Vec<&str> = line.split_whitespace().collect();
+        if let Some(name) = wrapped.take() {
+            if cols.len() == 2 {
+                cols.insert(0, name.as_str());
+                if let Some(update) = dnf_update_from_cols(&cols) {
+                    push_dnf_update(&mut updates, update, obsoleting, indented);
+                }
+                continue;
+            }
+        }
+        if cols.len() == 1 && cols[0].contains('.') && !cols[0].ends_with(['.', ':']) {
+            wrapped = Some(cols[0].to_string());
+            continue;
+        }
+
+        if let Some(update) = dnf_update_from_cols(&cols) {
+            push_dnf_update(&mut updates, update, obsoleting, indented);
+        }
+    }
+
+    updates
+}
+
+/// `name.arch version repo` (header and status lines have other shapes)
+fn dnf_update_from_cols(cols: &[&str]) -> Option<DnfUpdate> {
+    if cols.len() != 3 || !cols[1].starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let (name, arch) = cols[0].rsplit_once('.')?;
+
+    Some(DnfUpdate {
+        name: name.to_string(),
+        arch: arch.to_string(),
+        new_version: cols[1].to_string(),
+        obsoletes: None,
+    })
+}
+
+fn push_dnf_update(
+    updates: &mut Vec<DnfUpdate>,
+    update: DnfUpdate,
+    obsoleting: bool,
+    indented: bool,
+) {
+    if obsoleting && indented {
+        // Package replaced by the previous obsoleting package
+        if let Some(last) = updates.last_mut() {
+            last.obsoletes = Some(update.name);
+        }
+        return;
+    }
+
+    // Obsoleting packages may already be listed as regular updates
+    if let Some(pos) = updates
+        .iter()
+        .position(|u| u.name == update.name && u.arch == update.arch)
+    {
+        let existing = updates.remove(pos);
+        updates.push(existing);
+        return;
+    }
+
+    updates.push(update);
+}
+
+/// Parse `name.arch size` lines from `dnf repoquery`
+fn parse_dnf_sizes(stdout: &str) -> HashMap<String, u64> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let (name, size) = line.trim().split_once(' ')?;
+            Some((name.to_string(), size.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Download sizes of the pending upgrades, keyed by `name.arch`
+fn dnf_download_sizes(runner: &dyn CommandRunner) -> HashMap<String, u64> {
+    // dnf4 and dnf5 name the size tag differently
+    for tag in ["downloadsize", "download_size"] {
+        let output = runner.output(
+            "dnf",
+            &[
+                "repoquery",
+                "--cacheonly",
+                "--upgrades",
+                "--latest-limit",
+                "1",
+                "--queryformat",
+                &format!("%{{name}}.%{{arch}} %{{{}}}\n", tag),
+            ],
+        );
+
+        if let Ok(output) = output {
+            let sizes = parse_dnf_sizes(&output.stdout);
+            if !sizes.is_empty() {
+                return sizes;
+            }
+        }
+    }
+
+    HashMap::new()
+}
+
+/// Installed versions (`version-release`) of the given packages
+fn rpm_installed_versions(runner: &dyn CommandRunner, names: &[&str]) -> HashMap<String, String> {
+    if names.is_empty() {
+        return HashMap::new();
+    }
+
+    let args: Vec<&str> = ["-q", "--queryformat", "%{NAME} %{VERSION}-%{RELEASE}\n"]
+        .into_iter()
+        .chain(names.iter().copied())
+        .collect();
+    let Ok(output) = runner.output("rpm", &args) else {
+        return HashMap::new();
+    };
+
+    // Several kernels can be installed, the last one listed is the newest
+    output
+        .stdout
+        .lines()
+        .filter_map(|line| {
+            let (name, version) = line.split_once(' ')?;
+            Some((name.to_string(), version.to_string()))
+        })
+        .collect()
+}
+
+/// Check for available updates using dnf (Fedora)
+async fn check_updates_dnf(runner: &dyn CommandRunner) -> Result<Vec<UpdatePackage>, String> {
+    // Exit code 100 means updates are available, 0 none, anything else is an error
+    let output = runner.output("sudo", &["dnf", "check-update"])?;
+
+    match output.code {
+        Some(0) => return Ok(vec![]),
+        Some(100) => {}
+        _ => {
+            return Err(format!("dnf check-update failed: {}", output.stderr.trim()));
+        }
+    }
+
+    let updates = parse_dnf_check_update(&output.stdout);
+    let lookup: Vec<&str> = updates
+        .iter()
+        .map(|u| u.obsoletes.as_deref().unwrap_or(&u.name))
+        .collect();
+    let installed = rpm_installed_versions(runner, &lookup);
+    let sizes = dnf_download_sizes(runner);
+
+    // Multilib packages (x86_64 + i686) are listed once
+    let mut packages: Vec<UpdatePackage> = Vec::new();
+    for update in &updates {
+        let size = sizes
+            .get(&format!("{}.{}", update.name, update.arch))
+            .copied()
+            .unwrap_or(0);
+
+        if let Some(existing) = packages.iter_mut().find(|p| p.name == update.name) {
+            existing.size += size;
+            continue;
+        }
+
+        let installed_name = update.obsoletes.as_deref().unwrap_or(&update.name);
+        packages.push(UpdatePackage {
+            critical: is_critical_package(&update.name),
+            category: categorize_package(&update.name),
+            name: update.name.clone(),
+            current_version: installed
+                .get(installed_name)
+                .cloned()
+                .unwrap_or_else(|| "?".to_string()),
+            new_version: update.new_version.clone(),
+            size,
+            installed_size_delta: 0,
+            source: UpdateSource::System,
+        });
+    }
+
     Ok(packages)
 }
 
+/// One element of zypper's `--xmlout` output, with its text content
+#[derive(Debug, Clone, Default)]
+struct ZypperElement {
+    name: String,
+    attributes: HashMap<String, String>,
+    text: String,
+}
+
+impl ZypperElement {
+    fn attr(&self, key: &str) -> &str {
+        self.attributes.get(key).map_or("", |v| v.as_str())
+    }
+}
+
+/// Flatten zypper XML (a whole document or a single streamed line) into its elements
+fn parse_zypper_xml(xml: &str) -> Vec<ZypperElement> {
+    use quick_xml::events::{BytesStart, Event};
+    use quick_xml::Reader;
+
+    fn element(start: &BytesStart) -> ZypperElement {
+        ZypperElement {
+            name: String::from_utf8_lossy(start.name().as_ref()).to_string(),
+            attributes: start
+                .attributes()
+                .flatten()
+                .filter_map(|a| {
+                    let key = String::from_utf8_lossy(a.key.as_ref()).to_string();
+                    Some((key, a.unescape_value().ok()?.to_string()))
+                })
+                .collect(),
+            text: String::new(),
+        }
+    }
+
+    let mut reader = Reader::from_str(xml);
+    // Streamed lines close elements opened on earlier lines
+    reader.config_mut().check_end_names = false;
+
+    let mut elements: Vec<ZypperElement> = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => {
+                open.push(elements.len());
+                elements.push(element(&start));
+            }
+            Ok(Event::Empty(start)) => elements.push(element(&start)),
+            Ok(Event::End(_)) => {
+                open.pop();
+            }
+            Ok(Event::Text(text)) => {
+                if let (Some(&i), Ok(text)) = (open.last(), text.decode()) {
+                    elements[i].text.push_str(&text);
+                }
+            }
+            Ok(Event::GeneralRef(entity)) => {
+                let Some(&i) = open.last() else { continue };
+                if let Ok(Some(c)) = entity.resolve_char_ref() {
+                    elements[i].text.push(c);
+                } else if let Some(resolved) = entity
+                    .decode()
+                    .ok()
+                    .and_then(|name| quick_xml::escape::resolve_predefined_entity(&name))
+                {
+                    elements[i].text.push_str(resolved);
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            Ok(_) => {}
+        }
+    }
+
+    for element in &mut elements {
+        element.text = element.text.trim().to_string();
+    }
+    elements
+}
+
+/// Parse `zypper --xmlout list-updates` into update packages
+fn parse_zypper_updates(xml: &str) -> Vec<UpdatePackage> {
+    let mut packages: Vec<UpdatePackage> = Vec::new();
+
+    for update in parse_zypper_xml(xml)
+        .iter()
+        .filter(|e| e.name == "update" && matches!(e.attr("kind"), "" | "package"))
+    {
+        let name = update.attr("name");
+        if name.is_empty() || packages.iter().any(|p| p.name == name) {
+            continue;
+        }
+
+        packages.push(UpdatePackage {
+            critical: is_critical_package(name),
+            category: categorize_package(name),
+            name: name.to_string(),
+            current_version: match update.attr("edition-old") {
+                "" => "?".to_string(),
+                old => old.to_string(),
+            },
+            new_version: update.attr("edition").to_string(),
+            size: 0,
+            installed_size_delta: 0,
+            source: UpdateSource::System,
+        });
+    }
+
+    packages
+}
+
+/// Tumbleweed and Slowroll are rolling releases, updated with `zypper dup`
+fn is_rolling_opensuse() -> bool {
+    let id = DistroInfo::from_os_release(&os_release::read()).id;
+    id == "opensuse-tumbleweed" || id == "opensuse-slowroll"
+}
+
+/// Check for available updates using zypper (openSUSE)
+async fn check_updates_zypper(runner: &dyn CommandRunner) -> Result<Vec<UpdatePackage>, String> {
+    // Refresh repositories first (needs root)
+    runner.output("sudo", &["zypper", "--non-interactive", "refresh"])?;
+
+    let output = runner.output("zypper", &["--non-interactive", "--xmlout", "list-updates"])?;
+
+    if !output.success() {
+        let error = parse_zypper_xml(&output.stdout)
+            .into_iter()
+            .find(|e| e.name == "message" && e.attr("type") == "error")
+            .map(|e| e.text)
+            .unwrap_or_else(|| "zypper list-updates failed".to_string());
+        return Err(error);
+    }
+
+    Ok(parse_zypper_updates(&output.stdout))
+}
+
 /// Check for available system updates
 pub async fn check_system_updates() -> Result<UpdateCheckResult, String> {
-    let distro = detect_distro();
+    let backend = package_manager::current()?;
+    check_updates_with(backend.as_ref(), &package_holds::load()).await
+}
 
-    let packages = match distro {
-        Distro::Arch | Distro::SteamOS => check_updates_pacman().await?,
-        Distro::Debian => check_updates_apt().await?,
-        Distro::Fedora => {
-            // DNF support can be added later
-            return Err("Fedora updates not yet supported".to_string());
-        }
-        Distro::Unknown => {
-            return Err("Unable to detect Linux distribution".to_string());
+/// Check for updates through a backend, plus Flatpaks and Arch news unless in demo mode.
+/// Updates of held packages are listed apart, with the upgrades they would block.
+pub async fn check_updates_with(
+    backend: &dyn PackageManager,
+    holds: &[String],
+) -> Result<UpdateCheckResult, String> {
+    let distro = backend.distro();
+    let demo = backend.is_demo();
+    let (mut packages, held) = if backend.supports_holds() {
+        package_holds::split_held(backend.check().await?, holds)
+    } else {
+        (backend.check().await?, Vec::new())
+    };
+    let hold_conflicts = if held.is_empty() {
+        Vec::new()
+    } else {
+        backend.hold_conflicts(holds)
+    };
+
+    if !demo && crate::flatpak::is_available() {
+        match crate::flatpak::check_updates() {
+            Ok(flatpaks) => packages.extend(flatpaks),
+            Err(e) => log::warn!("Failed to check Flatpak updates: {}", e),
         }
+    }
+
+    let news = if distro == Distro::Arch && !demo {
+        let names: Vec<String> = packages.iter().map(|p| p.name.clone()).collect();
+        arch_news::unread_news(&names).await.unwrap_or_else(|e| {
+            log::warn!("Failed to check Arch news: {}", e);
+            Vec::new()
+        })
+    } else {
+        Vec::new()
     };
 
     let requires_reboot = packages.iter().any(|p| p.critical);
     let total_size: u64 = packages.iter().map(|p| p.size).sum();
+    let total_installed_size_delta: i64 = packages.iter().map(|p| p.installed_size_delta).sum();
 
     Ok(UpdateCheckResult {
         distro,
         packages,
         total_size,
+        total_installed_size_delta,
         requires_reboot,
+        news,
+        demo,
+        held,
+        hold_conflicts,
     })
 }
 
-/// Install system updates (requires sudoers configuration)
-pub async fn install_system_updates(window: tauri::Window) -> Result<UpdateReport, String> {
-    let distro = detect_distro();
+/// Install system updates (requires sudoers configuration),
+/// optionally taking a filesystem snapshot first. `pending` are the packages
+/// of the check the user accepted, recorded in the update history.
+pub async fn install_system_updates(
+    window: tauri::Window,
+    jobs: &UpdateJobs,
+    history: &UpdateHistory,
+    pending: &[UpdatePackage],
+    snapshot: bool,
+) -> Result<UpdateReport, String> {
+    let backend = package_manager::current()?;
+    install_updates_with(
+        backend.as_ref(),
+        Arc::new(window),
+        jobs,
+        history,
+        pending,
+        &package_holds::load(),
+        snapshot,
+    )
+    .await
+}
+
+/// Install updates through a backend, reporting the job's progress to `events`
+/// and recording the run in the update history. Held packages are left alone.
+pub async fn install_updates_with(
+    backend: &dyn PackageManager,
+    events: Arc<dyn UpdateEvents>,
+    jobs: &UpdateJobs,
+    history: &UpdateHistory,
+    pending: &[UpdatePackage],
+    holds: &[String],
+    snapshot: bool,
+) -> Result<UpdateReport, String> {
+    let holds = if backend.supports_holds() { holds } else { &[] };
+    let job = jobs.start(events)?;
+    let distro = backend.distro();
+    let started_at = Utc::now();
+
+    // History failures never block an update
+    let run_id = match history.start_run(&distro, backend.is_demo(), pending).await {
+        Ok(id) => Some(id),
+        Err(e) => {
+            log::warn!("Failed to record update run: {}", e);
+            None
+        }
+    };
+
+    let result = run_update_job(backend, &job, holds, snapshot).await;
+
+    if let Some(id) = run_id {
+        let logged = if backend.is_demo() {
+            Vec::new()
+        } else {
+            tokio::task::spawn_blocking(move || {
+                package_log::changes_between(&distro, Some(started_at), None)
+            })
+            .await
+            .unwrap_or_default()
+        };
+        if let Err(e) = history.finish_run(id, &result, &logged).await {
+            log::warn!("Failed to record update result: {}", e);
+        }
+    }
+
+    jobs.finish(&job);
+    result
+}
+
+async fn run_update_job(
+    backend: &dyn PackageManager,
+    job: &UpdateJob,
+    holds: &[String],
+    snapshot: bool,
+) -> Result<UpdateReport, String> {
     let start_time = std::time::Instant::now();
 
-    let result = match distro {
-        Distro::Arch | Distro::SteamOS => install_updates_pacman(window).await,
-        Distro::Debian => install_updates_apt(window).await,
-        _ => Err("Unsupported distribution for updates".to_string()),
+    // Better no upgrade than a partial one the package manager refuses halfway
+    let conflicts = if holds.is_empty() {
+        Vec::new()
+    } else {
+        backend.hold_conflicts(holds)
+    };
+    if !conflicts.is_empty() {
+        let error = package_holds::conflict_error(&conflicts);
+        return Ok(UpdateReport {
+            total_packages: error.packages.len() as u32,
+            installed_successfully: 0,
+            installed: Vec::new(),
+            failed: error.packages.clone(),
+            requires_reboot: false,
+            duration_seconds: start_time.elapsed().as_secs(),
+            error: Some(error),
+            snapshot: None,
+            flatpaks: Vec::new(),
+        });
+    }
+
+    // No snapshot, no update: the user asked for a way back
+    let snapshot = if snapshot && !backend.is_demo() {
+        let snapshot = tokio::task::spawn_blocking(snapshots::create_pre_update_snapshot)
+            .await
+            .map_err(|e| format!("Snapshot task failed: {}", e))?
+            .map_err(|e| format!("Failed to create pre-update snapshot: {}", e))?;
+        Some(snapshot)
+    } else {
+        None
     };
 
-    let duration = start_time.elapsed();
+    match backend.install(job, holds).await {
+        Ok(InstallOutcome {
+            mut installed,
+            mut failed,
+            mut error,
+            staged,
+        }) => {
+            let requires_reboot = staged || installed.iter().any(|name| is_critical_package(name));
+
+            // The package manager exits with an error when interrupted
+            if job.is_cancelled() {
+                error = Some(UpdateError::cancelled());
+            }
 
-    match result {
-        Ok((installed, failed)) => {
-            let requires_reboot = installed.iter().any(|name| is_critical_package(name));
+            // Flatpaks never require a reboot
+            let mut flatpaks = Vec::new();
+            if !backend.is_demo() && crate::flatpak::is_available() && !job.is_cancelled() {
+                let (flatpak_installed, flatpak_failures) =
+                    crate::flatpak::install_updates(job).await;
+                flatpaks = flatpak_installed
+                    .iter()
+                    .chain(&flatpak_failures)
+                    .cloned()
+                    .collect();
+                installed.extend(flatpak_installed);
+                failed.extend(flatpak_failures);
+            }
+            let duration = start_time.elapsed();
 
             Ok(UpdateReport {
                 total_packages: (installed.len() + failed.len()) as u32,
                 installed_successfully: installed.len() as u32,
+                installed,
                 failed,
                 requires_reboot,
                 duration_seconds: duration.as_secs(),
+                error,
+                snapshot,
+                flatpaks,
             })
         }
         Err(e) => Err(e),
     }
 }
 
-/// Install updates using pacman
-async fn install_updates_pacman(
-    window: tauri::Window,
-) -> Result<(Vec<String>, Vec<String>), String> {
-    use tauri::Emitter;
-
-    let mut cmd = TokioCommand::new("sudo")
-        .args(["pacman", "-Syu", "--noconfirm"])
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()
-        .map_err(|e| format!("Failed to start pacman: {}", e))?;
-
-    let stdout = cmd.stdout.take().expect("Failed to capture stdout");
-    let mut reader = BufReader::new(stdout).lines();
-
-    let mut installed = Vec::new();
-    let mut current_package;
-
-    while let Ok(Some(line)) = reader.next_line().await {
-        // Parse pacman output for progress
-        if line.contains("downloading") {
-            if let Some(pkg) = line.split_whitespace().nth(1) {
-                current_package = pkg.to_string();
-                let _ = window.emit(
-                    "update-progress",
-                    UpdateProgressEvent {
-                        stage: Status::Downloading,
-                        package_name: current_package.clone(),
-                        progress: 0.0,
-                        downloaded: "0 B".to_string(),
-                        total: "? B".to_string(),
-                        speed: "".to_string(),
-                        eta: "".to_string(),
-                    },
-                );
-            }
-        } else if line.contains("installing") {
-            if let Some(pkg) = line.split_whitespace().nth(1) {
-                current_package = pkg.to_string();
-                let _ = window.emit(
-                    "update-progress",
-                    UpdateProgressEvent {
-                        stage: Status::Installing,
-                        package_name: current_package.clone(),
-                        progress: 50.0,
-                        downloaded: "".to_string(),
-                        total: "".to_string(),
-                        speed: "".to_string(),
-                        eta: "".to_string(),
-                    },
-                );
-                installed.push(current_package.clone());
+/// Installed/failed packages of a run, with the classified error when it failed.
+/// Packages named by the error are moved from `installed` to `failed`.
+fn install_outcome(
+    mut installed: Vec<String>,
+    mut failed: Vec<String>,
+    error: Option<UpdateError>,
+) -> InstallOutcome {
+    if let Some(error) = &error {
+        for package in &error.packages {
+            if !failed.contains(package) {
+                failed.push(package.clone());
             }
         }
     }
+    installed.retain(|name| !failed.contains(name));
 
-    let status = cmd
-        .wait()
-        .await
-        .map_err(|e| format!("Failed to wait for pacman: {}", e))?;
+    InstallOutcome {
+        installed,
+        failed,
+        error,
+        staged: false,
+    }
+}
 
-    if !status.success() {
-        return Err("Pacman update failed".to_string());
+/// Format a byte count for progress events (`12.3 MiB`)
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
     }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
 
-    Ok((installed, vec![]))
+/// Format a remaining duration for progress events (`01:05`)
+fn format_eta(seconds: u64) -> String {
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
 }
 
-/// Install updates using apt
-async fn install_updates_apt(window: tauri::Window) -> Result<(Vec<String>, Vec<String>), String> {
-    use tauri::Emitter;
+/// Download counters shared by the pacman and apt progress parsers
+#[derive(Debug, Default)]
+struct DownloadStats {
+    total: u64,                // Total download size in bytes
+    started: Option<Duration>, // Elapsed time when the first download started
+}
 
-    let mut cmd = TokioCommand::new("sudo")
-        .args(["apt", "upgrade", "-y"])
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()
-        .map_err(|e| format!("Failed to start apt: {}", e))?;
-
-    let stdout = cmd.stdout.take().expect("Failed to capture stdout");
-    let mut reader = BufReader::new(stdout).lines();
-
-    let mut installed = Vec::new();
-
-    while let Ok(Some(line)) = reader.next_line().await {
-        // Parse apt output
-        if line.contains("Unpacking") || line.contains("Setting up") {
-            if let Some(pkg) = line.split_whitespace().nth(1) {
-                let _ = window.emit(
-                    "update-progress",
-                    UpdateProgressEvent {
-                        stage: if line.contains("Unpacking") {
-                            Status::Installing
-                        } else {
-                            Status::Configuring
-                        },
-                        package_name: pkg.to_string(),
-                        progress: 50.0,
-                        downloaded: "".to_string(),
-                        total: "".to_string(),
-                        speed: "".to_string(),
-                        eta: "".to_string(),
-                    },
-                );
-                installed.push(pkg.to_string());
-            }
+impl DownloadStats {
+    /// Downloaded/total/speed/ETA strings for `done` bytes at `elapsed`
+    fn describe(&mut self, done: u64, elapsed: Duration) -> (String, String, String, String) {
+        let started = *self.started.get_or_insert(elapsed);
+        let seconds = elapsed.saturating_sub(started).as_secs_f64();
+        let total = if self.total > 0 {
+            format_size(self.total)
+        } else {
+            "".to_string()
+        };
+
+        if seconds < 1.0 || done == 0 {
+            return (format_size(done), total, "".to_string(), "".to_string());
         }
-    }
 
-    let status = cmd
-        .wait()
-        .await
-        .map_err(|e| format!("Failed to wait for apt: {}", e))?;
+        let speed = done as f64 / seconds;
+        let eta = if self.total > done {
+            format_eta(((self.total - done) as f64 / speed) as u64)
+        } else {
+            "".to_string()
+        };
+        (
+            format_size(done),
+            total,
+            format!("{}/s", format_size(speed as u64)),
+            eta,
+        )
+    }
+}
 
-    if !status.success() {
-        return Err("Apt upgrade failed".to_string());
+/// Strip `segments` trailing `-`-separated fields (`linux-6.8.5.arch1-1-x86_64` -> `linux`)
+pub fn pacman_package_name(package: &str, segments: usize) -> String {
+    let mut name = package;
+    for _ in 0..segments {
+        match name.rsplit_once('-') {
+            Some((rest, _)) => name = rest,
+            None => return package.to_string(),
+        }
     }
+    name.to_string()
+}
 
-    Ok((installed, vec![]))
+/// Incremental parser for `pacman -Syu` output.
+/// Piped output has no progress bars, so counters are tracked here.
+#[derive(Debug, Default)]
+pub struct PacmanProgress {
+    total: usize,                // Packages in the transaction
+    sizes: HashMap<String, u64>, // Download size per package
+    downloads: Vec<String>,      // Packages whose download started, in order
+    steps: usize,                // Install/upgrade steps started
+    hooks: (usize, usize),       // Post-transaction hook counter
+    pub installed: Vec<String>,
+    stats: DownloadStats,
 }
 
-/// Check if system requires reboot (checks for various indicators)
-pub fn requires_system_reboot() -> bool {
-    // Check for Arch/CachyOS: kernel version mismatch
-    if let Ok(running) = std::fs::read_to_string("/proc/version") {
-        if let Ok(output) = Command::new("pacman").args(["-Q", "linux"]).output() {
-            let installed = String::from_utf8_lossy(&output.stdout);
-            // If versions differ, reboot needed
-            if !installed.is_empty()
-                && !running.contains(installed.trim().split_whitespace().last().unwrap_or(""))
-            {
-                return true;
-            }
+impl PacmanProgress {
+    pub fn new(sizes: HashMap<String, u64>) -> Self {
+        Self {
+            total: sizes.len(),
+            stats: DownloadStats {
+                total: sizes.values().sum(),
+                started: None,
+            },
+            sizes,
+            ..Default::default()
         }
     }
 
-    // Check for Debian/Ubuntu: /var/run/reboot-required
-    if Path::new("/var/run/reboot-required").exists() {
-        return true;
+    /// Overall percentage: downloads 45%, installs 45%, hooks 10%
+    fn overall(&self) -> f32 {
+        let total = self.total.max(1) as f32;
+        let downloads = if self.steps > 0 {
+            1.0
+        } else {
+            self.downloads.len() as f32 / total
+        };
+        let steps = self.steps as f32 / total;
+        let hooks = if self.hooks.1 > 0 {
+            self.hooks.0 as f32 / self.hooks.1 as f32
+        } else {
+            0.0
+        };
+        (downloads * 45.0 + steps * 45.0 + hooks * 10.0).min(100.0)
     }
 
-    false
-}
+    fn event(
+        &self,
+        stage: Status,
+        package_name: &str,
+        package_progress: f32,
+    ) -> UpdateProgressEvent {
+        UpdateProgressEvent {
+            stage,
+            package_name: package_name.to_string(),
+            progress: self.overall(),
+            package_progress,
+            downloaded: "".to_string(),
+            total: "".to_string(),
+            speed: "".to_string(),
+            eta: "".to_string(),
+        }
+    }
 
-/// Reboot the system
-pub fn reboot_system() -> Result<(), String> {
-    Command::new("sudo")
-        .args(["systemctl", "reboot"])
-        .spawn()
-        .map_err(|e| format!("Failed to reboot: {}", e))?;
+    /// Feed one output line; starting a package also completes the previous one
+    pub fn feed(&mut self, line: &str, elapsed: Duration) -> Vec<UpdateProgressEvent> {
+        let trimmed = line.trim();
+        let mut events = Vec::new();
 
-    Ok(())
-}
+        // "Packages (3) linux-6.8.5.arch1-1  mesa-1:24.0.5-1  foo-1.0-1"
+        if let Some(rest) = trimmed.strip_prefix("Packages (") {
+            if let Some((count, _)) = rest.split_once(')') {
+                self.total = count.parse().unwrap_or(self.total);
+            }
+            return events;
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // "Total Download Size:   150.00 MiB"
+        if let Some(size) = trimmed.strip_prefix("Total Download Size:") {
+            self.stats.total = parse_size(size);
+            return events;
+        }
 
-    #[test]
-    fn test_detect_distro() {
-        // This test will detect the actual running distribution
-        let distro = detect_distro();
-        println!("Detected distribution: {}", distro);
-        // Just verify it returns something
-        assert!(matches!(
-            distro,
-            Distro::Arch | Distro::SteamOS | Distro::Debian | Distro::Fedora | Distro::Unknown
-        ));
+        // " linux-6.8.5.arch1-1-x86_64 downloading..."
+        if let Some(file) = trimmed.strip_suffix(" downloading...") {
+            if !file.contains('-') {
+                return events; // Database refresh ("core downloading...")
+            }
+            let name = pacman_package_name(file, 3);
+            if let Some(previous) = self.downloads.last() {
+                events.push(self.event(Status::Downloading, previous, 100.0));
+            }
+
+            let done: u64 = self
+                .downloads
+                .iter()
+                .filter_map(|p| self.sizes.get(p))
+                .sum();
+            let (downloaded, total, speed, eta) = self.stats.describe(done, elapsed);
+            self.downloads.push(name.clone());
+            events.push(UpdateProgressEvent {
+                downloaded,
+                total,
+                speed,
+                eta,
+                ..self.event(Status::Downloading, &name, 0.0)
+            });
+            return events;
+        }
+
+        // "upgrading linux..." (only without progress bars, so no "(3/42)" prefix)
+        let step = ["upgrading ", "installing ", "reinstalling ", "downgrading "]
+            .iter()
+            .find_map(|verb| trimmed.strip_prefix(verb))
+            .and_then(|rest| rest.strip_suffix("..."));
+        if let Some(name) = step {
+            if let Some(previous) = self.installed.last() {
+                events.push(self.event(Status::Installing, previous, 100.0));
+            }
+            self.steps += 1;
+            self.installed.push(name.to_string());
+            events.push(self.event(Status::Installing, name, 0.0));
+            return events;
+        }
+
+        // "(1/5) Arming ConditionNeedsUpdate..."
+        if let Some(rest) = trimmed.strip_prefix('(') {
+            if let Some((counter, hook)) = rest.split_once(") ") {
+                if let Some((current, total)) = counter.split_once('/') {
+                    if let (Ok(current), Ok(total)) = (current.parse(), total.parse()) {
+                        self.hooks = (current, total);
+                        let hook = hook.trim_end_matches("...");
+                        events.push(self.event(Status::Configuring, hook, 0.0));
+                    }
+                }
+            }
+        }
+
+        events
+    }
+}
+
+/// SteamOS: the OS image, plus pacman packages once read-only mode is disabled
+async fn check_updates_steamos(runner: &dyn CommandRunner) -> Result<Vec<UpdatePackage>, String> {
+    let mut packages = if steamos::is_read_only() {
+        Vec::new()
+    } else {
+        check_updates_pacman(runner).await?
+    };
+    packages.extend(steamos::check_image_update().await?);
+    Ok(packages)
+}
+
+/// Install SteamOS updates, never touching pacman on a read-only root
+async fn install_updates_steamos(
+    runner: &dyn CommandRunner,
+    job: &UpdateJob,
+    holds: &[String],
+) -> Result<InstallOutcome, String> {
+    let mut outcome = if steamos::is_read_only() {
+        InstallOutcome::default()
+    } else {
+        install_updates_pacman(runner, job, false, holds).await?
+    };
+    if outcome.error.is_some() || job.is_cancelled() {
+        return Ok(outcome);
+    }
+
+    match steamos::install_image_update(job).await {
+        Ok(true) => {
+            outcome.installed.push(steamos::IMAGE_NAME.to_string());
+            outcome.staged = true;
+        }
+        Ok(false) => {}
+        Err(error) => {
+            outcome.failed.push(steamos::IMAGE_NAME.to_string());
+            outcome.error = Some(error);
+        }
+    }
+    Ok(outcome)
+}
+
+/// Stage the next ostree deployment; the running system is left untouched
+async fn install_updates_ostree(job: &UpdateJob) -> Result<InstallOutcome, String> {
+    let pending: Vec<String> = ostree::check_updates()
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| p.name)
+        .collect();
+
+    Ok(match ostree::stage_upgrade(job).await {
+        Ok(staged) => InstallOutcome {
+            installed: if staged { pending } else { Vec::new() },
+            staged,
+            ..Default::default()
+        },
+        Err(error) => InstallOutcome {
+            failed: pending,
+            error: Some(error),
+            ..Default::default()
+        },
+    })
+}
+
+/// pacman arguments skipping held packages for this transaction only
+fn pacman_ignore_args<'a>(mut args: Vec<&'a str>, ignore: &'a str) -> Vec<&'a str> {
+    if !ignore.is_empty() {
+        args.extend(["--ignore", ignore]);
+    }
+    args
+}
+
+/// Exact `--ignore` command line for the saved hold list
+fn pacman_hold_rules(username: &str, holds: &[String]) -> String {
+    if holds.is_empty() {
+        return String::new();
+    }
+    format!(
+        r#"
+# Allow Pixxiden to upgrade without the held packages
+{username} ALL=(ALL) NOPASSWD: /usr/bin/pacman -Syu --noconfirm --ignore {list}
+"#,
+        list = package_holds::sudoers_list(holds)
+    )
+}
+
+/// Upgrades needing a newer version of a held package, from a pacman dry run
+fn pacman_hold_conflicts(runner: &dyn CommandRunner, holds: &[String]) -> Vec<HoldConflict> {
+    let ignore = holds.join(",");
+    let args = pacman_ignore_args(vec!["-Sup", "--noconfirm"], &ignore);
+    match runner.output("pacman", &args) {
+        Ok(output) => {
+            package_holds::parse_pacman_conflicts(&format!("{}{}", output.stdout, output.stderr))
+        }
+        Err(e) => {
+            log::warn!("Failed to check held packages: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Install updates using pacman, checking Arch news first when `arch_news` is set
+async fn install_updates_pacman(
+    runner: &dyn CommandRunner,
+    job: &UpdateJob,
+    arch_news: bool,
+    holds: &[String],
+) -> Result<InstallOutcome, String> {
+    let mut pending = check_updates_pacman(runner).await.unwrap_or_default();
+    pending.retain(|p| !holds.contains(&p.name));
+
+    // Arch news asking for manual intervention on a pending package blocks the upgrade
+    if arch_news {
+        let names: Vec<String> = pending.iter().map(|p| p.name.clone()).collect();
+        match arch_news::unread_news(&names).await {
+            Ok(news) => {
+                if let Some(error) = arch_news::intervention_error(&news) {
+                    log::warn!("Upgrade blocked by Arch news: {}", error.message);
+                    return Ok(InstallOutcome {
+                        error: Some(error),
+                        ..Default::default()
+                    });
+                }
+            }
+            Err(e) => log::warn!("Failed to check Arch news: {}", e),
+        }
+    }
+
+    // Download sizes drive the speed/ETA estimates
+    let sizes: HashMap<String, u64> = pending.into_iter().map(|p| (p.name, p.size)).collect();
+
+    let mut progress = PacmanProgress::new(sizes);
+    let start = std::time::Instant::now();
+    // Dependency and conflict details are printed to stdout
+    let mut output: Vec<String> = Vec::new();
+    let ignore = holds.join(",");
+    let args = pacman_ignore_args(vec!["pacman", "-Syu", "--noconfirm"], &ignore);
+
+    let result = runner
+        .stream("sudo", &args, job, &mut |line| {
+            // The transaction is committed from here on
+            if line.starts_with(":: Processing package changes") {
+                job.enter_commit();
+            }
+            for event in progress.feed(&line, start.elapsed()) {
+                job.progress(event);
+            }
+            output.push(line);
+        })
+        .await?;
+
+    let error = (!result.success()).then(|| {
+        let mut lines = result.stderr_lines();
+        lines.extend(output);
+        update_failures::classify_pacman_failure(&lines)
+    });
+
+    Ok(install_outcome(progress.installed, Vec::new(), error))
+}
+
+/// Per-package percentage and stage for a dpkg status message
+fn apt_package_step(message: &str) -> Option<(Status, f32)> {
+    // "Preparing to configure" must be matched before "Preparing"
+    let step = if message.starts_with("Preparing to configure") {
+        (Status::Configuring, 60.0)
+    } else if message.starts_with("Preparing") {
+        (Status::Installing, 10.0)
+    } else if message.starts_with("Unpacking") {
+        (Status::Installing, 30.0)
+    } else if message.starts_with("Configuring") {
+        (Status::Configuring, 80.0)
+    } else if message.starts_with("Installed") {
+        (Status::Configuring, 100.0)
+    } else {
+        return None;
+    };
+    Some(step)
+}
+
+/// Incremental parser for `apt-get -o APT::Status-Fd=1` output
+#[derive(Debug, Default)]
+struct AptProgress {
+    package: String, // Package currently being downloaded
+    installed: Vec<String>,
+    stats: DownloadStats,
+}
+
+impl AptProgress {
+    fn feed(&mut self, line: &str, elapsed: Duration) -> Option<UpdateProgressEvent> {
+        // "Need to get 12.3 MB of archives." / "Need to get 1,024 kB/12.3 MB of archives."
+        if let Some(rest) = line.strip_prefix("Need to get ") {
+            let size = rest.split(" of archives").next().unwrap_or("");
+            let size = size.rsplit('/').next().unwrap_or(size);
+            self.stats.total = parse_size(&size.replace(',', ""));
+            return None;
+        }
+
+        // "Get:1 http://deb.debian.org/debian bookworm/main amd64 libc6 amd64 2.36-9 [2,757 kB]"
+        if line.starts_with("Get:") {
+            if let Some(package) = line.split_whitespace().nth(4) {
+                self.package = package.to_string();
+            }
+            return None;
+        }
+
+        // "dlstatus:2:33.3333:Retrieving file 2 of 3 (5s remaining)"
+        if let Some(rest) = line.strip_prefix("dlstatus:") {
+            let mut fields = rest.splitn(3, ':');
+            let _index = fields.next()?;
+            let percent: f32 = fields.next()?.parse().ok()?;
+            let done = (self.stats.total as f64 * percent as f64 / 100.0) as u64;
+            let (downloaded, total, speed, eta) = self.stats.describe(done, elapsed);
+
+            return Some(UpdateProgressEvent {
+                stage: Status::Downloading,
+                package_name: self.package.clone(),
+                progress: percent / 2.0,
+                package_progress: 0.0,
+                downloaded,
+                total,
+                speed,
+                eta,
+            });
+        }
+
+        // "pmstatus:libc6:amd64:20.0000:Unpacking libc6 (amd64)" (the arch qualifier is optional)
+        let rest = line.strip_prefix("pmstatus:")?;
+        let fields: Vec<&str> = rest.split(':').collect();
+        let percent_index = (1..fields.len()).find(|&i| fields[i].parse::<f32>().is_ok())?;
+        let package = fields[0];
+        let percent: f32 = fields[percent_index].parse().ok()?;
+        let message = fields[percent_index + 1..].join(":");
+        let (stage, package_progress) = apt_package_step(&message)?;
+
+        if package_progress >= 100.0 && !self.installed.iter().any(|p| p == package) {
+            self.installed.push(package.to_string());
+        }
+
+        Some(UpdateProgressEvent {
+            stage,
+            package_name: package.to_string(),
+            progress: 50.0 + percent / 2.0,
+            package_progress,
+            downloaded: "".to_string(),
+            total: "".to_string(),
+            speed: "".to_string(),
+            eta: "".to_string(),
+        })
+    }
+}
+
+/// Install updates using apt
+async fn install_updates_apt(
+    runner: &dyn CommandRunner,
+    job: &UpdateJob,
+) -> Result<InstallOutcome, String> {
+    let mut progress = AptProgress::default();
+    let start = std::time::Instant::now();
+    // dpkg reports per-package errors as pmerror status lines
+    let mut errors: Vec<String> = Vec::new();
+
+    // Machine-readable dlstatus/pmstatus lines are written to stdout
+    let args = ["apt-get", "-o", "APT::Status-Fd=1", "-y", "upgrade"];
+    let result = runner
+        .stream("sudo", &args, job, &mut |line| {
+            if let Some(event) = progress.feed(&line, start.elapsed()) {
+                job.progress(event);
+            }
+            if line.starts_with("pmerror:") {
+                errors.push(line);
+            }
+        })
+        .await?;
+
+    let error = (!result.success()).then(|| {
+        let mut lines = result.stderr_lines();
+        lines.extend(errors);
+        update_failures::classify_apt_failure(&lines)
+    });
+
+    Ok(install_outcome(progress.installed, Vec::new(), error))
+}
+
+/// Strip version, release, arch and `.rpm` from a dnf NEVRA
+/// (`kernel-core-0:6.8.5-301.fc40.x86_64` -> `kernel-core`)
+pub fn dnf_package_name(nevra: &str) -> String {
+    let nevra = nevra.trim_end_matches(".rpm");
+    // Some messages only carry the package name
+    let Some((without_arch, _arch)) = nevra.rsplit_once('.') else {
+        return nevra.to_string();
+    };
+
+    let mut parts = without_arch.rsplitn(3, '-');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(_release), Some(_version), Some(name)) => name.to_string(),
+        _ => nevra.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DnfSection {
+    None,
+    Failed,         // dnf4 "Failed:" summary
+    DownloadErrors, // "Error downloading packages:"
+}
+
+/// Incremental parser for `dnf upgrade` output (dnf4 and dnf5)
+#[derive(Debug)]
+struct DnfTransaction {
+    running: bool, // Past "Running transaction", i.e. done downloading
+    section: DnfSection,
+    installed: Vec<String>,
+    failed: Vec<String>,
+}
+
+impl DnfTransaction {
+    fn new() -> Self {
+        Self {
+            running: false,
+            section: DnfSection::None,
+            installed: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+
+    fn fail(&mut self, nevra: &str) {
+        let name = dnf_package_name(nevra);
+        if !self.failed.contains(&name) {
+            self.failed.push(name);
+        }
+    }
+
+    /// Feed one output line, returning a progress event when there is one
+    fn feed(&mut self, line: &str) -> Option<UpdateProgressEvent> {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("Running transaction") {
+            self.running = true;
+            return None;
+        }
+        if trimmed == "Failed:" {
+            self.section = DnfSection::Failed;
+            return None;
+        }
+        if trimmed.starts_with("Error downloading packages") {
+            self.section = DnfSection::DownloadErrors;
+            return None;
+        }
+
+        match self.section {
+            DnfSection::Failed if line.starts_with(' ') => {
+                for nevra in trimmed.split_whitespace() {
+                    self.fail(nevra);
+                }
+                return None;
+            }
+            DnfSection::DownloadErrors if line.starts_with(' ') => {
+                // "  foo-1.0-1.fc40.x86_64: Cannot download, all mirrors were already tried."
+                if let Some((nevra, _)) = trimmed.split_once(':') {
+                    if !nevra.contains(' ') {
+                        self.fail(nevra);
+                    }
+                }
+                return None;
+            }
+            DnfSection::None => {}
+            _ => self.section = DnfSection::None,
+        }
+
+        // "Error in POSTIN scriptlet in rpm package foo" (dnf4),
+        // ">>> Error in %post scriptlet: foo-1.0-1.fc40.x86_64" (dnf5)
+        if trimmed.contains("Error in") && trimmed.contains("scriptlet") {
+            if let Some(nevra) = trimmed.split_whitespace().last() {
+                self.fail(nevra);
+            }
+            return None;
+        }
+
+        // dnf5: ">>> Running %post scriptlet: kernel-core-0:6.8.5-301.fc40.x86_64"
+        if let Some(rest) = trimmed.strip_prefix(">>> Running") {
+            let nevra = rest.split_whitespace().last()?;
+            return self.transaction_step("Running scriptlet", nevra, 0.0);
+        }
+
+        // dnf4: "(1/3): kernel-6.8.5-301.fc40.x86_64.rpm   1.2 MB/s | 150 kB  00:00"
+        if trimmed.starts_with('(') && trimmed.contains("):") {
+            let (counter, rest) = trimmed.split_once("):")?;
+            let fields: Vec<&str> = rest.split('|').map(str::trim).collect();
+            let mut head = fields.first()?.split_whitespace();
+            let file = head.next()?;
+            let speed = head.collect::<Vec<_>>().join(" ");
+            let mut tail = fields.get(1).map_or("", |f| f).rsplitn(2, ' ');
+            let eta = tail.next().unwrap_or("").to_string();
+            let total = tail.next().unwrap_or("").trim().to_string();
+
+            return Some(UpdateProgressEvent {
+                stage: Status::Downloading,
+                package_name: dnf_package_name(file),
+                progress: counter_progress(counter.trim_start_matches('(')),
+                package_progress: 100.0, // Listed once downloaded
+                downloaded: total.clone(),
+                total,
+                speed,
+                eta,
+            });
+        }
+
+        // dnf5: "[3/8] Upgrading kernel-0:6.8.5-301.fc40.x86_64   100% | ..." and
+        // downloads as "[1/3] kernel-0:6.8.5-301.fc40.x86_64   100% | 1.2 MiB/s | 150.0 KiB | 00m00s"
+        if let Some(bracketed) = trimmed.strip_prefix('[') {
+            let (counter, rest) = bracketed.split_once(']')?;
+            let fields: Vec<&str> = rest.split('|').map(str::trim).collect();
+            let words: Vec<&str> = fields.first()?.split_whitespace().collect();
+            let progress = counter_progress(counter);
+
+            if !self.running {
+                let package = words.first()?;
+                return Some(UpdateProgressEvent {
+                    stage: Status::Downloading,
+                    package_name: dnf_package_name(package),
+                    progress,
+                    package_progress: 100.0, // Listed once downloaded
+                    downloaded: fields.get(2).unwrap_or(&"").to_string(),
+                    total: fields.get(2).unwrap_or(&"").to_string(),
+                    speed: fields.get(1).unwrap_or(&"").to_string(),
+                    eta: fields.get(3).unwrap_or(&"").to_string(),
+                });
+            }
+
+            return self.transaction_step(words.first()?, words.get(1)?, progress);
+        }
+
+        // dnf4: "  Upgrading        : kernel-core-6.8.5-301.fc40.x86_64     1/6"
+        let (action, rest) = trimmed.split_once(':')?;
+        let mut rest = rest.split_whitespace();
+        let nevra = rest.next()?;
+        let progress = counter_progress(rest.next()?);
+        self.transaction_step(action.trim(), nevra, progress)
+    }
+
+    fn transaction_step(
+        &mut self,
+        action: &str,
+        nevra: &str,
+        progress: f32,
+    ) -> Option<UpdateProgressEvent> {
+        let stage = match action {
+            "Upgrading" | "Installing" | "Reinstalling" | "Downgrading" => {
+                let name = dnf_package_name(nevra);
+                if !self.installed.contains(&name) {
+                    self.installed.push(name);
+                }
+                Status::Installing
+            }
+            "Running scriptlet" => Status::Configuring,
+            _ => return None,
+        };
+
+        Some(UpdateProgressEvent {
+            stage,
+            package_name: dnf_package_name(nevra),
+            progress,
+            package_progress: 0.0,
+            downloaded: "".to_string(),
+            total: "".to_string(),
+            speed: "".to_string(),
+            eta: "".to_string(),
+        })
+    }
+
+    /// Installed and failed package names
+    fn finish(self) -> (Vec<String>, Vec<String>) {
+        let failed = self.failed;
+        let installed = self
+            .installed
+            .into_iter()
+            .filter(|name| !failed.contains(name))
+            .collect();
+        (installed, failed)
+    }
+}
+
+/// Percentage from a `current/total` counter
+fn counter_progress(counter: &str) -> f32 {
+    let Some((current, total)) = counter.split_once('/') else {
+        return 0.0;
+    };
+    match (current.trim().parse::<f32>(), total.trim().parse::<f32>()) {
+        (Ok(current), Ok(total)) if total > 0.0 => (current / total * 100.0).min(100.0),
+        _ => 0.0,
+    }
+}
+
+/// Install updates using dnf
+async fn install_updates_dnf(
+    runner: &dyn CommandRunner,
+    job: &UpdateJob,
+    holds: &[String],
+) -> Result<InstallOutcome, String> {
+    let mut transaction = DnfTransaction::new();
+    let exclude = format!("--exclude={}", holds.join(","));
+    let mut args = vec!["dnf", "upgrade", "-y"];
+    if !holds.is_empty() {
+        args.push(&exclude);
+    }
+
+    let result = runner
+        .stream("sudo", &args, job, &mut |line| {
+            if let Some(event) = transaction.feed(&line) {
+                job.progress(event);
+            }
+        })
+        .await?;
+
+    // Scriptlet and download errors go to stderr
+    let stderr_lines = result.stderr_lines();
+    for line in &stderr_lines {
+        transaction.feed(line);
+    }
+    let (installed, failed) = transaction.finish();
+
+    let error = (!result.success()).then(|| {
+        UpdateError::unknown(
+            stderr_lines
+                .iter()
+                .find(|l| l.starts_with("Error"))
+                .cloned()
+                .unwrap_or_else(|| "DNF upgrade failed".to_string()),
+        )
+    });
+
+    Ok(install_outcome(installed, failed, error))
+}
+
+/// Upgrades needing a newer version of a held package, from a dnf dry run
+fn dnf_hold_conflicts(runner: &dyn CommandRunner, holds: &[String]) -> Vec<HoldConflict> {
+    let exclude = format!("--exclude={}", holds.join(","));
+    // --assumeno always exits with an error, the problems are what matters
+    match runner.output("sudo", &["dnf", "upgrade", "--assumeno", &exclude]) {
+        Ok(output) => {
+            package_holds::parse_dnf_conflicts(&format!("{}{}", output.stdout, output.stderr))
+        }
+        Err(e) => {
+            log::warn!("Failed to check held packages: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Upgrades apt keeps back because of a held package, from dry runs. Kept
+/// back packages are installed in a second simulation to learn why.
+fn apt_hold_conflicts(runner: &dyn CommandRunner, holds: &[String]) -> Vec<HoldConflict> {
+    let kept_back: Vec<String> = match runner.output("apt-get", &["-s", "upgrade"]) {
+        Ok(output) => package_holds::parse_apt_kept_back(&output.stdout),
+        Err(e) => {
+            log::warn!("Failed to check held packages: {}", e);
+            return Vec::new();
+        }
+    };
+    let blocked: Vec<&str> = kept_back
+        .iter()
+        .map(String::as_str)
+        .filter(|name| !holds.iter().any(|hold| hold == name))
+        .collect();
+    if blocked.is_empty() {
+        return Vec::new();
+    }
+
+    // Packages only needing new dependencies install fine, held ones leave unmet ones
+    let mut args = vec!["-s", "install"];
+    args.extend(blocked);
+    match runner.output("apt-get", &args) {
+        Ok(output) => {
+            package_holds::parse_apt_conflicts(&format!("{}{}", output.stdout, output.stderr))
+        }
+        Err(e) => {
+            log::warn!("Failed to check held packages: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Package name from an rpm file name or URL (`.../kernel-default-6.8.5-1.1.x86_64.rpm`)
+fn rpm_file_package_name(file: &str) -> String {
+    dnf_package_name(file.rsplit('/').next().unwrap_or(file))
+}
+
+/// Incremental parser for `zypper --xmlout dup`/`update` output
+#[derive(Debug, Default)]
+struct ZypperTransaction {
+    installed: Vec<String>,
+    failed: Vec<String>,
+}
+
+impl ZypperTransaction {
+    fn fail(&mut self, name: String) {
+        if !self.failed.contains(&name) {
+            self.failed.push(name);
+        }
+    }
+
+    /// Feed one output line, returning a progress event when there is one
+    fn feed(&mut self, line: &str) -> Option<UpdateProgressEvent> {
+        let mut event = None;
+
+        for element in parse_zypper_xml(line) {
+            match element.name.as_str() {
+                // <download url="..." percent="42" rate="1048576"/>
+                "download" => {
+                    let rate: f64 = element.attr("rate").parse().unwrap_or(0.0);
+                    event = Some(UpdateProgressEvent {
+                        stage: Status::Downloading,
+                        package_name: rpm_file_package_name(element.attr("url")),
+                        progress: element.attr("percent").parse().unwrap_or(0.0),
+                        package_progress: element.attr("percent").parse().unwrap_or(0.0),
+                        downloaded: "".to_string(),
+                        total: "".to_string(),
+                        speed: if rate > 0.0 {
+                            format!("{:.1} MB/s", rate / 1_000_000.0)
+                        } else {
+                            "".to_string()
+                        },
+                        eta: "".to_string(),
+                    });
+                }
+                // <progress id="..." name="(2/5) Installing: foo-1.0-1.1.x86_64" value="30"/>
+                "progress" => {
+                    let label = element.attr("name");
+                    let Some((counter, nevra)) = label.split_once(" Installing: ") else {
+                        continue;
+                    };
+                    let name = dnf_package_name(nevra.trim());
+                    if !self.installed.contains(&name) {
+                        self.installed.push(name.clone());
+                    }
+                    event = Some(UpdateProgressEvent {
+                        stage: Status::Installing,
+                        package_name: name,
+                        progress: counter_progress(counter.trim_matches(|c| c == '(' || c == ')')),
+                        package_progress: 0.0,
+                        downloaded: "".to_string(),
+                        total: "".to_string(),
+                        speed: "".to_string(),
+                        eta: "".to_string(),
+                    });
+                }
+                // <message type="error">Installation of foo-1.0-1.1.x86_64 failed:</message>
+                "message" if element.attr("type") == "error" => {
+                    let text = element.text.as_str();
+                    if let Some(rest) = text.strip_prefix("Installation of ") {
+                        if let Some(nevra) = rest.split_whitespace().next() {
+                            self.fail(dnf_package_name(nevra));
+                        }
+                    } else if let Some(rest) = text.strip_prefix("Failed to provide Package ") {
+                        if let Some(nevra) = rest.split_whitespace().next() {
+                            self.fail(dnf_package_name(nevra.trim_end_matches('.')));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        event
+    }
+
+    /// Installed and failed package names
+    fn finish(self) -> (Vec<String>, Vec<String>) {
+        let failed = self.failed;
+        let installed = self
+            .installed
+            .into_iter()
+            .filter(|name| !failed.contains(name))
+            .collect();
+        (installed, failed)
+    }
+}
+
+/// Install updates using zypper (`dup` on rolling releases, `update` on Leap)
+async fn install_updates_zypper(
+    runner: &dyn CommandRunner,
+    job: &UpdateJob,
+) -> Result<InstallOutcome, String> {
+    let action = if is_rolling_opensuse() {
+        "dup"
+    } else {
+        "update"
+    };
+    let mut transaction = ZypperTransaction::default();
+
+    let args = [
+        "zypper",
+        "--non-interactive",
+        "--xmlout",
+        action,
+        "--auto-agree-with-licenses",
+    ];
+    let result = runner
+        .stream("sudo", &args, job, &mut |line| {
+            if let Some(event) = transaction.feed(&line) {
+                job.progress(event);
+            }
+        })
+        .await?;
+    let (installed, failed) = transaction.finish();
+
+    let error =
+        (!result.success()).then(|| UpdateError::unknown(format!("Zypper {} failed", action)));
+
+    Ok(install_outcome(installed, failed, error))
+}
+
+/// Why installed updates wait for a reboot, plus processes still running
+/// deleted libraries
+pub fn requires_system_reboot() -> RebootStatus {
+    let Ok(backend) = package_manager::current() else {
+        return RebootStatus::new(Vec::new());
+    };
+    let mut reasons = backend.reboot_reasons();
+    if !backend.is_demo() {
+        reasons.extend(reboot::stale_libraries_reason());
+    }
+    RebootStatus::new(reasons)
+}
+
+/// Fedora: `needs-restarting -r` (dnf4) or `dnf needs-restarting -r` (dnf5)
+/// exits with 1 when core packages changed since boot
+fn dnf_needs_restarting(runner: &dyn CommandRunner) -> Option<RebootReason> {
+    let output = runner
+        .output("needs-restarting", &["-r"])
+        .or_else(|_| runner.output("dnf", &["needs-restarting", "-r"]))
+        .ok()?;
+    (output.code == Some(1)).then(|| RebootReason::CoreUpdated {
+        packages: reboot::parse_needs_restarting(&output.stdout),
+    })
+}
+
+fn sudoers_reboot_rules(username: &str) -> String {
+    format!(
+        r#"
+# Allow system reboot
+{username} ALL=(ALL) NOPASSWD: /usr/bin/systemctl reboot
+{username} ALL=(ALL) NOPASSWD: /usr/bin/systemctl poweroff
+"#
+    )
+}
+
+/// pacman (Arch-based distributions)
+pub struct Pacman {
+    runner: Arc<dyn CommandRunner>,
+    arch_news: bool, // Block upgrades on unread Arch news needing manual intervention
+    aur_helper: Option<AurHelper>, // Also upgrade AUR packages, as the user
+}
+
+impl Pacman {
+    pub fn new(runner: Arc<dyn CommandRunner>, arch_news: bool) -> Self {
+        Self {
+            runner,
+            arch_news,
+            aur_helper: None,
+        }
+    }
+
+    pub fn with_aur_helper(mut self, aur_helper: Option<AurHelper>) -> Self {
+        self.aur_helper = aur_helper;
+        self
+    }
+
+    fn rules(username: &str) -> String {
+        format!(
+            r#"
+# Allow Pixxiden to check for updates
+{username} ALL=(ALL) NOPASSWD: /usr/bin/pacman -Qu
+{username} ALL=(ALL) NOPASSWD: /usr/bin/pacman -Sy
+{username} ALL=(ALL) NOPASSWD: /usr/bin/pacman -Syy
+
+# Allow Pixxiden to install updates
+{username} ALL=(ALL) NOPASSWD: /usr/bin/pacman -Syu --noconfirm
+{username} ALL=(ALL) NOPASSWD: /usr/bin/pacman -Su --noconfirm
+
+# Allow Pixxiden to clean package cache
+{username} ALL=(ALL) NOPASSWD: /usr/bin/pacman -Sc --noconfirm
+"#
+        )
+    }
+}
+
+#[async_trait]
+impl PackageManager for Pacman {
+    fn distro(&self) -> Distro {
+        Distro::Arch
+    }
+
+    async fn check(&self) -> Result<Vec<UpdatePackage>, String> {
+        let mut packages = check_updates_pacman(self.runner.as_ref()).await?;
+        if let Some(helper) = self.aur_helper {
+            match aur::check_updates(self.runner.as_ref(), helper) {
+                Ok(aur_packages) => packages.extend(aur_packages),
+                Err(e) => log::warn!("Failed to check AUR updates: {}", e),
+            }
+        }
+        Ok(packages)
+    }
+
+    /// Repository packages first, AUR packages may depend on them
+    async fn install(&self, job: &UpdateJob, holds: &[String]) -> Result<InstallOutcome, String> {
+        let mut outcome =
+            install_updates_pacman(self.runner.as_ref(), job, self.arch_news, holds).await?;
+        let Some(helper) = self.aur_helper else {
+            return Ok(outcome);
+        };
+        if outcome.error.is_some() || job.is_cancelled() {
+            return Ok(outcome);
+        }
+
+        let mut pending = aur::check_updates(self.runner.as_ref(), helper).unwrap_or_else(|e| {
+            log::warn!("Failed to check AUR updates: {}", e);
+            Vec::new()
+        });
+        pending.retain(|p| !holds.contains(&p.name));
+        if pending.is_empty() {
+            return Ok(outcome);
+        }
+        let (installed, failed, error) =
+            aur::install_updates(self.runner.as_ref(), helper, job, &pending, holds).await?;
+        outcome.installed.extend(installed);
+        outcome.failed.extend(failed);
+        outcome.error = error;
+        Ok(outcome)
+    }
+
+    async fn install_packages(
+        &self,
+        job: &UpdateJob,
+        packages: &[String],
+    ) -> Result<InstallOutcome, String> {
+        prerequisites::install_packages_with(self.runner.as_ref(), &self.distro(), job, packages)
+            .await
+    }
+
+    fn supports_holds(&self) -> bool {
+        true
+    }
+
+    fn hold_conflicts(&self, holds: &[String]) -> Vec<HoldConflict> {
+        pacman_hold_conflicts(self.runner.as_ref(), holds)
+    }
+
+    fn hold_sudoers_rules(&self, username: &str, holds: &[String]) -> String {
+        pacman_hold_rules(username, holds)
+    }
+
+    fn reboot_reasons(&self) -> Vec<RebootReason> {
+        reboot::kernel_reason().into_iter().collect()
+    }
+
+    fn sudoers_rules(&self, username: &str) -> String {
+        Self::rules(username) + &sudoers_reboot_rules(username)
+    }
+}
+
+/// SteamOS image updates, plus pacman once read-only mode is disabled
+pub struct SteamOs {
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl SteamOs {
+    pub fn new(runner: Arc<dyn CommandRunner>) -> Self {
+        Self { runner }
+    }
+}
+
+#[async_trait]
+impl PackageManager for SteamOs {
+    fn distro(&self) -> Distro {
+        Distro::SteamOS
+    }
+
+    async fn check(&self) -> Result<Vec<UpdatePackage>, String> {
+        check_updates_steamos(self.runner.as_ref()).await
+    }
+
+    async fn install(&self, job: &UpdateJob, holds: &[String]) -> Result<InstallOutcome, String> {
+        install_updates_steamos(self.runner.as_ref(), job, holds).await
+    }
+
+    /// Holds apply to the pacman packages, not to the OS image
+    fn supports_holds(&self) -> bool {
+        true
+    }
+
+    fn hold_conflicts(&self, holds: &[String]) -> Vec<HoldConflict> {
+        if steamos::is_read_only() {
+            return Vec::new();
+        }
+        pacman_hold_conflicts(self.runner.as_ref(), holds)
+    }
+
+    fn hold_sudoers_rules(&self, username: &str, holds: &[String]) -> String {
+        pacman_hold_rules(username, holds)
+    }
+
+    fn reboot_reasons(&self) -> Vec<RebootReason> {
+        reboot::kernel_reason().into_iter().collect()
+    }
+
+    fn sudoers_rules(&self, username: &str) -> String {
+        // pacman stays whitelisted for when the user disables read-only mode
+        Pacman::rules(username)
+            + &sudoers_reboot_rules(username)
+            + &steamos::sudoers_rules(username)
+    }
+}
+
+/// rpm-ostree or bootc deployments (Fedora Atomic, Bazzite, bootc images)
+pub struct Ostree;
+
+#[async_trait]
+impl PackageManager for Ostree {
+    fn distro(&self) -> Distro {
+        Distro::Ostree
+    }
+
+    async fn check(&self) -> Result<Vec<UpdatePackage>, String> {
+        ostree::check_updates().await
+    }
+
+    async fn install(&self, job: &UpdateJob, _holds: &[String]) -> Result<InstallOutcome, String> {
+        install_updates_ostree(job).await
+    }
+
+    /// A staged deployment or a rollback waits for the next boot
+    fn reboot_reasons(&self) -> Vec<RebootReason> {
+        if ostree::reboot_pending() {
+            vec![RebootReason::StagedDeployment]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn sudoers_rules(&self, username: &str) -> String {
+        ostree::sudoers_rules(username) + &sudoers_reboot_rules(username)
+    }
+}
+
+/// apt (Debian-based distributions)
+pub struct Apt {
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl Apt {
+    pub fn new(runner: Arc<dyn CommandRunner>) -> Self {
+        Self { runner }
+    }
+}
+
+#[async_trait]
+impl PackageManager for Apt {
+    fn distro(&self) -> Distro {
+        Distro::Debian
+    }
+
+    async fn check(&self) -> Result<Vec<UpdatePackage>, String> {
+        check_updates_apt(self.runner.as_ref()).await
+    }
+
+    /// Held packages are marked with apt-mark when the hold list is saved,
+    /// apt keeps them and the upgrades depending on them back
+    async fn install(&self, job: &UpdateJob, _holds: &[String]) -> Result<InstallOutcome, String> {
+        install_updates_apt(self.runner.as_ref(), job).await
+    }
+
+    async fn install_packages(
+        &self,
+        job: &UpdateJob,
+        packages: &[String],
+    ) -> Result<InstallOutcome, String> {
+        prerequisites::install_packages_with(self.runner.as_ref(), &self.distro(), job, packages)
+            .await
+    }
+
+    fn supports_holds(&self) -> bool {
+        true
+    }
+
+    fn hold_conflicts(&self, holds: &[String]) -> Vec<HoldConflict> {
+        apt_hold_conflicts(self.runner.as_ref(), holds)
+    }
+
+    fn reboot_reasons(&self) -> Vec<RebootReason> {
+        reboot::kernel_reason()
+            .into_iter()
+            .chain(reboot::reboot_required_file())
+            .collect()
+    }
+
+    fn sudoers_rules(&self, username: &str) -> String {
+        format!(
+            r#"
+# Allow Pixxiden to check for updates
+{username} ALL=(ALL) NOPASSWD: /usr/bin/apt update
+{username} ALL=(ALL) NOPASSWD: /usr/bin/apt list --upgradable
+
+# Allow Pixxiden to install updates
+{username} ALL=(ALL) NOPASSWD: /usr/bin/apt upgrade -y
+{username} ALL=(ALL) NOPASSWD: /usr/bin/apt-get -o APT\:\:Status-Fd\=1 -y upgrade
+{username} ALL=(ALL) NOPASSWD: /usr/bin/apt full-upgrade -y
+{username} ALL=(ALL) NOPASSWD: /usr/bin/apt dist-upgrade -y
+
+# Allow Pixxiden to clean
+{username} ALL=(ALL) NOPASSWD: /usr/bin/apt autoremove -y
+{username} ALL=(ALL) NOPASSWD: /usr/bin/apt autoclean
+"#
+        ) + &sudoers_reboot_rules(username)
+    }
+}
+
+/// dnf (Fedora)
+pub struct Dnf {
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl Dnf {
+    pub fn new(runner: Arc<dyn CommandRunner>) -> Self {
+        Self { runner }
+    }
+}
+
+#[async_trait]
+impl PackageManager for Dnf {
+    fn distro(&self) -> Distro {
+        Distro::Fedora
+    }
+
+    async fn check(&self) -> Result<Vec<UpdatePackage>, String> {
+        check_updates_dnf(self.runner.as_ref()).await
+    }
+
+    async fn install(&self, job: &UpdateJob, holds: &[String]) -> Result<InstallOutcome, String> {
+        install_updates_dnf(self.runner.as_ref(), job, holds).await
+    }
+
+    async fn install_packages(
+        &self,
+        job: &UpdateJob,
+        packages: &[String],
+    ) -> Result<InstallOutcome, String> {
+        prerequisites::install_packages_with(self.runner.as_ref(), &self.distro(), job, packages)
+            .await
+    }
+
+    fn supports_holds(&self) -> bool {
+        true
+    }
+
+    fn hold_conflicts(&self, holds: &[String]) -> Vec<HoldConflict> {
+        dnf_hold_conflicts(self.runner.as_ref(), holds)
+    }
+
+    /// Exact `--exclude` command lines for the saved hold list
+    fn hold_sudoers_rules(&self, username: &str, holds: &[String]) -> String {
+        if holds.is_empty() {
+            return String::new();
+        }
+        format!(
+            r#"
+# Allow Pixxiden to upgrade without the held packages
+{username} ALL=(ALL) NOPASSWD: /usr/bin/dnf upgrade -y --exclude\={list}
+{username} ALL=(ALL) NOPASSWD: /usr/bin/dnf upgrade --assumeno --exclude\={list}
+"#,
+            list = package_holds::sudoers_list(holds)
+        )
+    }
+
+    fn reboot_reasons(&self) -> Vec<RebootReason> {
+        reboot::kernel_reason()
+            .into_iter()
+            .chain(dnf_needs_restarting(self.runner.as_ref()))
+            .collect()
+    }
+
+    fn sudoers_rules(&self, username: &str) -> String {
+        format!(
+            r#"
+# Allow Pixxiden to check for updates
+{username} ALL=(ALL) NOPASSWD: /usr/bin/dnf check-update
+
+# Allow Pixxiden to install updates
+{username} ALL=(ALL) NOPASSWD: /usr/bin/dnf upgrade -y
+
+# Allow Pixxiden to clean
+{username} ALL=(ALL) NOPASSWD: /usr/bin/dnf autoremove -y
+{username} ALL=(ALL) NOPASSWD: /usr/bin/dnf clean all
+"#
+        ) + &sudoers_reboot_rules(username)
+    }
+}
+
+/// zypper (openSUSE)
+pub struct Zypper {
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl Zypper {
+    pub fn new(runner: Arc<dyn CommandRunner>) -> Self {
+        Self { runner }
+    }
+}
+
+#[async_trait]
+impl PackageManager for Zypper {
+    fn distro(&self) -> Distro {
+        Distro::OpenSuse
+    }
+
+    async fn check(&self) -> Result<Vec<UpdatePackage>, String> {
+        check_updates_zypper(self.runner.as_ref()).await
+    }
+
+    async fn install(&self, job: &UpdateJob, _holds: &[String]) -> Result<InstallOutcome, String> {
+        install_updates_zypper(self.runner.as_ref(), job).await
+    }
+
+    async fn install_packages(
+        &self,
+        job: &UpdateJob,
+        packages: &[String],
+    ) -> Result<InstallOutcome, String> {
+        prerequisites::install_packages_with(self.runner.as_ref(), &self.distro(), job, packages)
+            .await
+    }
+
+    /// zypper exits with 102 when a reboot is needed, without saying why
+    fn reboot_reasons(&self) -> Vec<RebootReason> {
+        let needs_rebooting = self
+            .runner
+            .output("zypper", &["needs-rebooting"])
+            .is_ok_and(|output| output.code == Some(102));
+        reboot::kernel_reason()
+            .into_iter()
+            .chain(needs_rebooting.then(|| RebootReason::CoreUpdated {
+                packages: Vec::new(),
+            }))
+            .collect()
+    }
+
+    fn sudoers_rules(&self, username: &str) -> String {
+        format!(
+            r#"
+# Allow Pixxiden to refresh repositories
+{username} ALL=(ALL) NOPASSWD: /usr/bin/zypper --non-interactive refresh
+
+# Allow Pixxiden to install updates
+{username} ALL=(ALL) NOPASSWD: /usr/bin/zypper --non-interactive --xmlout dup --auto-agree-with-licenses
+{username} ALL=(ALL) NOPASSWD: /usr/bin/zypper --non-interactive --xmlout update --auto-agree-with-licenses
+"#
+        ) + &sudoers_reboot_rules(username)
+    }
+}
+
+/// Reboot the system
+pub fn reboot_system() -> Result<(), String> {
+    Command::new("sudo")
+        .args(["systemctl", "reboot"])
+        .spawn()
+        .map_err(|e| format!("Failed to reboot: {}", e))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_distro() {
+        // This test will detect the actual running distribution
+        let distro = detect_distro();
+        // Just verify it returns something
+        assert!(matches!(
+            distro,
+            Distro::Arch
+                | Distro::SteamOS
+                | Distro::Ostree
+                | Distro::Debian
+                | Distro::Fedora
+                | Distro::OpenSuse
+                | Distro::Unknown
+        ));
     }
 
     #[test]
@@ -573,4 +2580,367 @@ mod tests {
         assert!(!is_critical_package("firefox"));
         assert!(!is_critical_package("vim"));
     }
+
+    #[test]
+    fn test_parse_dnf_check_update() {
+        let stdout = "\
+Last metadata expiration check: 0:12:34 ago on Sat 18 Oct 2026 10:00:00 AM CEST.
+
+kernel.x86_64                          6.8.5-301.fc40          updates
+mesa-dri-drivers.i686                  24.0.5-1.fc40           updates
+mesa-dri-drivers.x86_64                24.0.5-1.fc40           updates
+gstreamer1-plugins-bad-free-extras.x86_64
+                                       1.22.9-1.fc40           updates
+Obsoleting Packages
+pipewire-pulseaudio.x86_64             1.0.5-1.fc40            updates
+    pulseaudio.x86_64                  16.1-5.fc40             @fedora
+";
+        let updates = parse_dnf_check_update(stdout);
+        let names: Vec<&str> = updates.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "kernel",
+                "mesa-dri-drivers",
+                "mesa-dri-drivers",
+                "gstreamer1-plugins-bad-free-extras",
+                "pipewire-pulseaudio"
+            ]
+        );
+        assert_eq!(updates[0].new_version, "6.8.5-301.fc40");
+        assert_eq!(updates[1].arch, "i686");
+        assert_eq!(updates[3].new_version, "1.22.9-1.fc40");
+        assert_eq!(updates[4].obsoletes.as_deref(), Some("pulseaudio"));
+    }
+
+    #[test]
+    fn test_parse_dnf_sizes() {
+        let sizes = parse_dnf_sizes("kernel.x86_64 153600\nbroken.x86_64 ?\n");
+        assert_eq!(sizes.get("kernel.x86_64"), Some(&153600));
+        assert_eq!(sizes.len(), 1);
+    }
+
+    #[test]
+    fn test_dnf_package_name() {
+        assert_eq!(
+            dnf_package_name("kernel-core-6.8.5-301.fc40.x86_64"),
+            "kernel-core"
+        );
+        assert_eq!(
+            dnf_package_name("kernel-core-0:6.8.5-301.fc40.x86_64"),
+            "kernel-core"
+        );
+        assert_eq!(
+            dnf_package_name("mesa-libGL-24.0.5-1.fc40.i686.rpm"),
+            "mesa-libGL"
+        );
+        assert_eq!(
+            dnf_package_name("xorg-x11-server-Xwayland"),
+            "xorg-x11-server-Xwayland"
+        );
+    }
+
+    #[test]
+    fn test_dnf4_transaction() {
+        let mut transaction = DnfTransaction::new();
+
+        let event = transaction
+            .feed("(1/2): kernel-6.8.5-301.fc40.x86_64.rpm        1.2 MB/s | 150 kB     00:00")
+            .unwrap();
+        assert_eq!(event.stage, Status::Downloading);
+        assert_eq!(event.package_name, "kernel");
+        assert_eq!(event.progress, 50.0);
+        assert_eq!(event.speed, "1.2 MB/s");
+        assert_eq!(event.total, "150 kB");
+
+        transaction.feed("Running transaction");
+        let event = transaction
+            .feed("  Upgrading        : kernel-6.8.5-301.fc40.x86_64                 1/4")
+            .unwrap();
+        assert_eq!(event.stage, Status::Installing);
+        assert_eq!(event.progress, 25.0);
+        transaction.feed("  Upgrading        : foo-2.0-1.fc40.x86_64                        2/4");
+        let event = transaction
+            .feed("  Running scriptlet: foo-2.0-1.fc40.x86_64                        2/4")
+            .unwrap();
+        assert_eq!(event.stage, Status::Configuring);
+        assert!(transaction
+            .feed("  Cleanup          : foo-1.0-1.fc40.x86_64     3/4")
+            .is_none());
+
+        transaction.feed("Failed:");
+        transaction.feed("  foo-2.0-1.fc40.x86_64");
+        transaction.feed("");
+        transaction.feed("Error: Transaction failed");
+
+        let (installed, failed) = transaction.finish();
+        assert_eq!(installed, ["kernel"]);
+        assert_eq!(failed, ["foo"]);
+    }
+
+    #[test]
+    fn test_dnf5_transaction() {
+        let mut transaction = DnfTransaction::new();
+
+        let event = transaction
+            .feed("[1/2] mesa-libGL-0:24.0.5-1.fc40.x86_64   100% |   1.2 MiB/s | 150.0 KiB |  00m00s")
+            .unwrap();
+        assert_eq!(event.stage, Status::Downloading);
+        assert_eq!(event.package_name, "mesa-libGL");
+        assert_eq!(event.speed, "1.2 MiB/s");
+        assert_eq!(event.total, "150.0 KiB");
+
+        transaction.feed("Running transaction");
+        assert!(transaction
+            .feed("[1/6] Verify package files        100% |   0.0   B/s |   2.0   B |  00m00s")
+            .is_none());
+        let event = transaction
+            .feed("[3/6] Upgrading mesa-libGL-0:24.0.5-1.fc40.x86_64   100% |  10.0 MiB/s | 500.0 KiB |  00m00s")
+            .unwrap();
+        assert_eq!(event.stage, Status::Installing);
+        assert_eq!(event.progress, 50.0);
+        transaction.feed(
+            "[4/6] Upgrading bar-0:2.0-1.fc40.x86_64   100% |  10.0 MiB/s | 500.0 KiB |  00m00s",
+        );
+        transaction.feed(">>> Error in %post scriptlet: bar-0:2.0-1.fc40.x86_64");
+
+        let (installed, failed) = transaction.finish();
+        assert_eq!(installed, ["mesa-libGL"]);
+        assert_eq!(failed, ["bar"]);
+    }
+
+    #[test]
+    fn test_dnf_download_errors() {
+        let mut transaction = DnfTransaction::new();
+        transaction.feed("Error downloading packages:");
+        transaction.feed("  steam-1.0.0.79-1.fc40.i686: Cannot download, all mirrors were already tried without success");
+        let (_, failed) = transaction.finish();
+        assert_eq!(failed, ["steam"]);
+    }
+
+    #[test]
+    fn test_parse_zypper_updates() {
+        let xml = r#"<?xml version='1.0'?>
+<stream>
+<message type="info">Loading repository data...</message>
+<message type="info">Reading installed packages...</message>
+<update-status version="0.6">
+<update-list>
+<update kind="package" name="kernel-default" edition="6.8.5-1.1" arch="x86_64" edition-old="6.8.4-1.1" >
+<summary>The Standard Kernel</summary>
+<description>Kernel &amp; modules</description>
+<license></license>
+<source url="https://download.opensuse.org/tumbleweed/repo/oss" alias="repo-oss"/>
+</update>
+<update kind="package" name="Mesa-libGL1" edition="24.0.5-1.1" arch="x86_64" edition-old="24.0.4-1.1" >
+<source url="https://download.opensuse.org/tumbleweed/repo/oss" alias="repo-oss"/>
+</update>
+<update kind="patch" name="openSUSE-2026-123" edition="1" arch="noarch" >
+</update>
+</update-list>
+</update-status>
+</stream>"#;
+
+        let packages = parse_zypper_updates(xml);
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name, "kernel-default");
+        assert_eq!(packages[0].current_version, "6.8.4-1.1");
+        assert_eq!(packages[0].new_version, "6.8.5-1.1");
+        assert!(packages[0].critical);
+        assert_eq!(packages[1].name, "Mesa-libGL1");
+
+        let description = parse_zypper_xml(xml)
+            .into_iter()
+            .find(|e| e.name == "description")
+            .unwrap();
+        assert_eq!(description.text, "Kernel & modules");
+    }
+
+    #[test]
+    fn test_zypper_transaction() {
+        let mut transaction = ZypperTransaction::default();
+
+        let event = transaction
+            .feed(r#"<download url="https://download.opensuse.org/tumbleweed/repo/oss/x86_64/kernel-default-6.8.5-1.1.x86_64.rpm" percent="42" rate="2000000"/>"#)
+            .unwrap();
+        assert_eq!(event.stage, Status::Downloading);
+        assert_eq!(event.package_name, "kernel-default");
+        assert_eq!(event.progress, 42.0);
+        assert_eq!(event.speed, "2.0 MB/s");
+
+        let event = transaction
+            .feed(r#"<progress id="install-resolvable" name="(1/2) Installing: kernel-default-6.8.5-1.1.x86_64" value="0"/>"#)
+            .unwrap();
+        assert_eq!(event.stage, Status::Installing);
+        assert_eq!(event.progress, 50.0);
+
+        transaction.feed(r#"<progress id="install-resolvable" name="(2/2) Installing: gamemode-1.8.1-1.1.x86_64" value="0"/>"#);
+        transaction.feed(
+            r#"<message type="error">Installation of gamemode-1.8.1-1.1.x86_64 failed:</message>"#,
+        );
+        assert!(transaction.feed("</stream>").is_none());
+
+        let (installed, failed) = transaction.finish();
+        assert_eq!(installed, ["kernel-default"]);
+        assert_eq!(failed, ["gamemode"]);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("12.3 MB"), 12_300_000);
+        assert_eq!(parse_size("1.5 kB"), 1_500);
+        assert_eq!(parse_size("2.00 KiB"), 2_048);
+        assert_eq!(parse_size("512 bytes"), 512);
+        assert_eq!(parse_size("< 1.0 kB"), 1_000);
+        assert_eq!(parse_size(""), 0);
+    }
+
+    #[test]
+    fn test_parse_pacman_info() {
+        let stdout = "\
+Repository      : core
+Name            : linux
+Version         : 6.8.5.arch1-1
+Download Size   : 136.50 MiB
+Installed Size  : 131.00 MiB
+
+Repository      : core-testing
+Name            : linux
+Version         : 6.9.0.arch1-1
+Download Size   : 140.00 MiB
+Installed Size  : 135.00 MiB
+";
+        let info = parse_pacman_info(stdout);
+        assert_eq!(info["linux"], (143_130_624, 137_363_456));
+
+        // -Qi has no download size
+        let info = parse_pacman_info("Name            : linux\nInstalled Size  : 2.00 KiB\n");
+        assert_eq!(info["linux"], (0, 2_048));
+    }
+
+    #[test]
+    fn test_parse_expac() {
+        let sync = parse_expac("mesa\t9000000\t40000000\nbroken\tn/a\t1\n");
+        assert_eq!(sync["mesa"], (9_000_000, 40_000_000));
+        assert!(!sync.contains_key("broken"));
+
+        let local = parse_expac("mesa\t38000000\n");
+        assert_eq!(local["mesa"], (0, 38_000_000));
+    }
+
+    #[test]
+    fn test_parse_apt_sizes() {
+        let uris = parse_apt_uris(
+            "'http://archive.ubuntu.com/ubuntu/pool/main/m/mesa/libgl1-mesa-dri_24.0.5-1_amd64.deb' libgl1-mesa-dri_24.0.5-1_amd64.deb 8765432 SHA256:abcdef\n",
+        );
+        assert_eq!(uris["libgl1-mesa-dri"], 8_765_432);
+
+        let sizes = parse_apt_installed_sizes(
+            "Package: libgl1-mesa-dri\nVersion: 24.0.5-1\nInstalled-Size: 30000\n\nPackage: firefox\nInstalled-Size: 250000\n",
+        );
+        assert_eq!(sizes["libgl1-mesa-dri"], 30_720_000);
+        assert_eq!(sizes["firefox"], 256_000_000);
+    }
+
+    #[test]
+    fn test_format_size_and_eta() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1_572_864), "1.5 MiB");
+        assert_eq!(format_eta(65), "01:05");
+    }
+
+    #[test]
+    fn test_pacman_progress() {
+        let sizes = HashMap::from([
+            ("linux".to_string(), 100 * 1024 * 1024),
+            ("mesa".to_string(), 50 * 1024 * 1024),
+        ]);
+        let mut progress = PacmanProgress::new(sizes);
+        let at = Duration::from_secs;
+
+        assert!(progress.feed(" core downloading...", at(0)).is_empty());
+        progress.feed("Packages (2) linux-6.8.5.arch1-1  mesa-1:24.0.5-1", at(1));
+        progress.feed("Total Download Size:   150.00 MiB", at(1));
+
+        let events = progress.feed(" linux-6.8.5.arch1-1-x86_64 downloading...", at(2));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].stage, Status::Downloading);
+        assert_eq!(events[0].package_name, "linux");
+        assert_eq!(events[0].total, "150.0 MiB");
+
+        let events = progress.feed(" mesa-1:24.0.5-1-x86_64 downloading...", at(12));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].package_name, "linux");
+        assert_eq!(events[0].package_progress, 100.0);
+        assert_eq!(events[1].package_name, "mesa");
+        assert_eq!(events[1].downloaded, "100.0 MiB");
+        assert_eq!(events[1].speed, "10.0 MiB/s");
+        assert_eq!(events[1].eta, "00:05");
+
+        // Not a package step: the old parser matched any "installing"
+        assert!(progress
+            .feed("warning: installing foo as a dependency", at(20))
+            .is_empty());
+
+        let events = progress.feed("upgrading linux...", at(20));
+        assert_eq!(events[0].stage, Status::Installing);
+        assert_eq!(events[0].progress, 45.0 + 22.5);
+        progress.feed("upgrading mesa...", at(21));
+
+        progress.feed(":: Running post-transaction hooks...", at(22));
+        let events = progress.feed("(5/5) Arming ConditionNeedsUpdate...", at(22));
+        assert_eq!(events[0].stage, Status::Configuring);
+        assert_eq!(events[0].package_name, "Arming ConditionNeedsUpdate");
+        assert_eq!(events[0].progress, 100.0);
+
+        assert_eq!(progress.installed, ["linux", "mesa"]);
+    }
+
+    #[test]
+    fn test_apt_progress() {
+        let mut progress = AptProgress::default();
+        let at = Duration::from_secs;
+
+        progress.feed("Need to get 1,024 kB/20.0 MB of archives.", at(0));
+        progress.feed(
+            "Get:1 http://deb.debian.org/debian bookworm/main amd64 libc6 amd64 2.36-9 [2,757 kB]",
+            at(0),
+        );
+        let event = progress
+            .feed(
+                "dlstatus:1:50.0000:Retrieving file 1 of 2 (2s remaining)",
+                at(2),
+            )
+            .unwrap();
+        assert_eq!(event.stage, Status::Downloading);
+        assert_eq!(event.package_name, "libc6");
+        assert_eq!(event.progress, 25.0);
+        assert_eq!(event.total, "19.1 MiB");
+
+        let event = progress
+            .feed(
+                "pmstatus:libc6:amd64:20.0000:Unpacking libc6 (amd64)",
+                at(5),
+            )
+            .unwrap();
+        assert_eq!(event.stage, Status::Installing);
+        assert_eq!(event.package_name, "libc6");
+        assert_eq!(event.progress, 60.0);
+        assert_eq!(event.package_progress, 30.0);
+
+        let event = progress
+            .feed(
+                "pmstatus:libc6:amd64:40.0000:Preparing to configure libc6 (amd64)",
+                at(6),
+            )
+            .unwrap();
+        assert_eq!(event.stage, Status::Configuring);
+        assert_eq!(event.package_progress, 60.0);
+
+        progress.feed(
+            "pmstatus:libc6:amd64:50.0000:Installed libc6 (amd64)",
+            at(7),
+        );
+        assert_eq!(progress.installed, ["libc6"]);
+    }
 }
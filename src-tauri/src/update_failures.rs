@@ -0,0 +1,466 @@
+//! Classification of failed package manager runs
+//!
+//! pacman and apt only tell what went wrong in their error output. The
+//! common failure modes are recognised here so the UI can name the packages
+//! involved and suggest how to get out of it.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+
+use crate::system_updates::pacman_package_name;
+
+/// Known cause of a failed update
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdateFailureKind {
+    FileConflict,       // Files on disk not owned by the updated package
+    Signature,          // Package or repository signature could not be verified
+    DatabaseLocked,     // Another package manager holds the lock
+    DpkgInterrupted,    // A previous dpkg run did not finish
+    Dependency,         // Dependencies cannot be satisfied
+    Cancelled,          // Cancelled by the user while downloading
+    ManualIntervention, // Unread Arch news about a pending package
+    Unknown,
+}
+
+/// Structured error of a failed update
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateError {
+    pub kind: UpdateFailureKind,
+    pub message: String,       // First error line reported by the package manager
+    pub packages: Vec<String>, // Packages involved, when they could be identified
+    pub remediation: String,
+}
+
+impl UpdateError {
+    /// Error that could not be classified any further
+    pub fn unknown(message: String) -> Self {
+        Self {
+            kind: UpdateFailureKind::Unknown,
+            message,
+            packages: Vec::new(),
+            remediation: "Run the update from a terminal to see the full output.".to_string(),
+        }
+    }
+
+    /// Update stopped on request before anything was installed
+    pub fn cancelled() -> Self {
+        Self {
+            kind: UpdateFailureKind::Cancelled,
+            message: "Update cancelled".to_string(),
+            packages: Vec::new(),
+            remediation: "Nothing was installed, the update can be started again.".to_string(),
+        }
+    }
+}
+
+/// Build an error from the output lines of a failed run
+fn classify(
+    lines: &[String],
+    kind_of: fn(&str) -> Option<UpdateFailureKind>,
+    package_of: fn(&str) -> Option<String>,
+    remediation: fn(UpdateFailureKind) -> &'static str,
+    fallback: &str,
+) -> UpdateError {
+    let mut kind = None;
+    let mut packages: Vec<String> = Vec::new();
+
+    for line in lines.iter().map(|l| l.trim()) {
+        // The first recognised cause wins, later errors are usually fallout
+        if kind.is_none() {
+            kind = kind_of(line);
+        }
+        if let Some(package) = package_of(line).filter(|p| !p.is_empty()) {
+            if !packages.contains(&package) {
+                packages.push(package);
+            }
+        }
+    }
+
+    let kind = kind.unwrap_or(UpdateFailureKind::Unknown);
+    let message = lines
+        .iter()
+        .map(|l| l.trim())
+        .find(|l| l.starts_with("error:") || l.starts_with("E:") || l.starts_with("dpkg: error"))
+        .unwrap_or(fallback)
+        .to_string();
+
+    UpdateError {
+        kind,
+        message,
+        packages,
+        remediation: remediation(kind).to_string(),
+    }
+}
+
+fn pacman_failure_kind(line: &str) -> Option<UpdateFailureKind> {
+    let kind = if line.contains("unable to lock database") || line.contains("db.lck") {
+        UpdateFailureKind::DatabaseLocked
+    } else if line.contains("exists in filesystem") || line.contains("(conflicting files)") {
+        UpdateFailureKind::FileConflict
+    } else if line.contains("signature from")
+        || line.contains("(PGP signature)")
+        || line.contains("could not be looked up remotely")
+        || line.contains("unknown trust")
+    {
+        UpdateFailureKind::Signature
+    } else if line.contains("could not satisfy dependencies")
+        || line.contains("breaks dependency")
+        || line.contains("unable to satisfy dependency")
+        || line.contains("are in conflict")
+    {
+        UpdateFailureKind::Dependency
+    } else {
+        return None;
+    };
+    Some(kind)
+}
+
+fn pacman_failed_package(line: &str) -> Option<String> {
+    // "gamemode: /usr/bin/gamemoded exists in filesystem (owned by gamemode-git)"
+    if line.contains(" exists in filesystem") {
+        return Some(line.split_once(": ")?.0.to_string());
+    }
+
+    // "error: mesa: signature from "Foo <foo@bar>" is unknown trust"
+    if line.contains(": signature from ") {
+        let rest = line.strip_prefix("error: ").unwrap_or(line);
+        return Some(rest.split_once(": ")?.0.to_string());
+    }
+
+    // "mesa-1:24.0.5-1-x86_64.pkg.tar.zst is corrupted (invalid or corrupted package (PGP signature))"
+    if let Some((file, _)) = line.split_once(" is corrupted") {
+        let file = file.rsplit(['/', ' ']).next().unwrap_or(file);
+        let stem = file.split(".pkg.tar").next().unwrap_or(file);
+        return Some(pacman_package_name(stem, 3));
+    }
+
+    // ":: installing mesa (24.1.0-1) breaks dependency 'mesa=24.0.5' required by lib32-mesa"
+    // ":: unable to satisfy dependency 'libfoo.so=2-64' required by bar"
+    if line.starts_with("::") {
+        if let Some((_, package)) = line.rsplit_once(" required by ") {
+            return Some(package.trim().to_string());
+        }
+    }
+
+    None
+}
+
+fn pacman_remediation(kind: UpdateFailureKind) -> &'static str {
+    match kind {
+        UpdateFailureKind::FileConflict => {
+            "Files from the update already exist on disk. Check their owner with `pacman -Qo <file>`, \
+             remove the leftovers, then retry."
+        }
+        UpdateFailureKind::Signature => {
+            "A package signature could not be verified. Refresh the keyring with \
+             `sudo pacman -Sy archlinux-keyring`, then retry."
+        }
+        UpdateFailureKind::DatabaseLocked => {
+            "Another package manager is running. Wait for it to finish; if none is, \
+             delete /var/lib/pacman/db.lck, then retry."
+        }
+        UpdateFailureKind::Dependency => {
+            "A dependency cannot be satisfied. Update or remove the listed packages \
+             from a terminal, then retry."
+        }
+        UpdateFailureKind::DpkgInterrupted
+        | UpdateFailureKind::Cancelled
+        | UpdateFailureKind::ManualIntervention
+        | UpdateFailureKind::Unknown => {
+            "Run `sudo pacman -Syu` from a terminal to see the full output."
+        }
+    }
+}
+
+/// Classify a failed `pacman -Syu` from its output
+pub fn classify_pacman_failure(lines: &[String]) -> UpdateError {
+    classify(
+        lines,
+        pacman_failure_kind,
+        pacman_failed_package,
+        pacman_remediation,
+        "Pacman update failed",
+    )
+}
+
+fn apt_failure_kind(line: &str) -> Option<UpdateFailureKind> {
+    let kind = if line.contains("Could not get lock")
+        || line.contains("Unable to acquire the dpkg frontend lock")
+        || line.contains("Unable to lock")
+    {
+        UpdateFailureKind::DatabaseLocked
+    } else if line.contains("dpkg was interrupted") {
+        UpdateFailureKind::DpkgInterrupted
+    } else if line.contains("trying to overwrite") {
+        UpdateFailureKind::FileConflict
+    } else if line.contains("NO_PUBKEY")
+        || line.contains("EXPKEYSIG")
+        || line.contains("signatures couldn't be verified")
+        || line.contains("is not signed")
+    {
+        UpdateFailureKind::Signature
+    } else if line.contains("Unmet dependencies")
+        || line.contains("unmet dependencies")
+        || line.contains("dependency problems")
+    {
+        UpdateFailureKind::Dependency
+    } else {
+        return None;
+    };
+    Some(kind)
+}
+
+fn apt_failed_package(line: &str) -> Option<String> {
+    // "dpkg: error processing archive /var/cache/apt/archives/mesa-vulkan-drivers_24.0.5-1_amd64.deb (--unpack):"
+    if let Some(rest) = line.strip_prefix("dpkg: error processing archive ") {
+        let file = rest.split_whitespace().next()?.rsplit('/').next()?;
+        return Some(file.split('_').next()?.to_string());
+    }
+
+    // "dpkg: error processing package steam-libs-i386:i386 (--configure):"
+    // "dpkg: dependency problems prevent configuration of steam-libs-i386:i386:"
+    let package = line
+        .strip_prefix("dpkg: error processing package ")
+        .or_else(|| line.strip_prefix("dpkg: dependency problems prevent configuration of "))
+        .and_then(|rest| rest.split_whitespace().next());
+    if let Some(package) = package {
+        let package = package.trim_end_matches(':');
+        return Some(package.split(':').next()?.to_string());
+    }
+
+    // "pmerror:mesa-vulkan-drivers:amd64:52.3:subprocess installed post-installation script returned error exit status 1"
+    if let Some(rest) = line.strip_prefix("pmerror:") {
+        return Some(rest.split(':').next()?.to_string());
+    }
+
+    // " steam-installer : Depends: steam-libs-i386 (= 1:1.0.0.79) but it is not going to be installed"
+    if line.contains(" : Depends: ")
+        || line.contains(" : PreDepends: ")
+        || line.contains(" : Breaks: ")
+    {
+        return Some(line.split(" : ").next()?.trim().to_string());
+    }
+
+    None
+}
+
+fn apt_remediation(kind: UpdateFailureKind) -> &'static str {
+    match kind {
+        UpdateFailureKind::FileConflict => {
+            "Two packages ship the same file. Run `sudo apt-get -f install`, then retry."
+        }
+        UpdateFailureKind::Signature => {
+            "A repository signing key is missing or expired. Import the repository key, \
+             then retry."
+        }
+        UpdateFailureKind::DatabaseLocked => {
+            "Another package manager (such as unattended-upgrades) is running. Wait for it \
+             to finish, then retry."
+        }
+        UpdateFailureKind::DpkgInterrupted => {
+            "A previous installation was interrupted. Run `sudo dpkg --configure -a`, then retry."
+        }
+        UpdateFailureKind::Dependency => {
+            "Some dependencies are broken. Run `sudo apt-get -f install`, then retry."
+        }
+        UpdateFailureKind::Cancelled
+        | UpdateFailureKind::ManualIntervention
+        | UpdateFailureKind::Unknown => {
+            "Run `sudo apt-get upgrade` from a terminal to see the full output."
+        }
+    }
+}
+
+/// Classify a failed `apt-get upgrade` from its output (stderr and dpkg status lines)
+pub fn classify_apt_failure(lines: &[String]) -> UpdateError {
+    classify(
+        lines,
+        apt_failure_kind,
+        apt_failed_package,
+        apt_remediation,
+        "Apt upgrade failed",
+    )
+}
+
+const PACMAN_LOCK: &str = "/var/lib/pacman/db.lck";
+const DPKG_UPDATES_DIR: &str = "/var/lib/dpkg/updates";
+
+/// Whether one of the given processes is running
+fn is_running(pattern: &str) -> bool {
+    Command::new("pgrep")
+        .args(["-x", pattern])
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// pacman lock left behind by a run that was killed
+fn stale_pacman_lock(lock: &Path, running: bool) -> Option<UpdateError> {
+    if running || !lock.exists() {
+        return None;
+    }
+
+    Some(UpdateError {
+        kind: UpdateFailureKind::DatabaseLocked,
+        message: format!("Stale pacman database lock {}", lock.display()),
+        packages: Vec::new(),
+        remediation: format!(
+            "No package manager is running. Delete the lock with `sudo rm {}`, then retry.",
+            lock.display()
+        ),
+    })
+}
+
+/// dpkg journal entries left by an interrupted install
+fn interrupted_dpkg(updates_dir: &Path, running: bool) -> Option<UpdateError> {
+    if running {
+        return None;
+    }
+    std::fs::read_dir(updates_dir).ok()?.next()?.ok()?;
+
+    Some(UpdateError {
+        kind: UpdateFailureKind::DpkgInterrupted,
+        message: "dpkg was interrupted during a previous update".to_string(),
+        packages: Vec::new(),
+        remediation: apt_remediation(UpdateFailureKind::DpkgInterrupted).to_string(),
+    })
+}
+
+/// Leftovers of an update that did not finish (app or system killed mid-way)
+pub fn detect_interrupted_update() -> Option<UpdateError> {
+    stale_pacman_lock(Path::new(PACMAN_LOCK), is_running("pacman|pamac-daemon")).or_else(|| {
+        interrupted_dpkg(
+            Path::new(DPKG_UPDATES_DIR),
+            is_running("dpkg|apt|apt-get|unattended-upgr"),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(output: &str) -> Vec<String> {
+        output.lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pacman_file_conflict() {
+        let error = classify_pacman_failure(&lines(
+            "error: failed to commit transaction (conflicting files)\n\
+             gamemode: /usr/bin/gamemoded exists in filesystem (owned by gamemode-git)\n\
+             gamemode: /usr/lib/libgamemode.so exists in filesystem\n\
+             Errors occurred, no packages were upgraded.",
+        ));
+        assert_eq!(error.kind, UpdateFailureKind::FileConflict);
+        assert_eq!(error.packages, ["gamemode"]);
+        assert_eq!(
+            error.message,
+            "error: failed to commit transaction (conflicting files)"
+        );
+        assert!(error.remediation.contains("pacman -Qo"));
+    }
+
+    #[test]
+    fn test_pacman_signature_and_lock() {
+        let error = classify_pacman_failure(&lines(
+            "error: mesa: signature from \"Foo Bar <foo@archlinux.org>\" is unknown trust\n\
+             :: File /var/cache/pacman/pkg/mesa-1:24.1.0-1-x86_64.pkg.tar.zst is corrupted (invalid or corrupted package (PGP signature)).\n\
+             error: failed to commit transaction (invalid or corrupted package (PGP signature))\n\
+             lib32-mesa-1:24.1.0-1-x86_64.pkg.tar.zst is corrupted (invalid or corrupted package (PGP signature))",
+        ));
+        assert_eq!(error.kind, UpdateFailureKind::Signature);
+        assert_eq!(error.packages, ["mesa", "lib32-mesa"]);
+
+        let error = classify_pacman_failure(&lines(
+            "error: failed to init transaction (unable to lock database)\n\
+             error: could not lock database: File exists\n  \
+             if you're sure a package manager is not already\n  \
+             running, you can remove /var/lib/pacman/db.lck",
+        ));
+        assert_eq!(error.kind, UpdateFailureKind::DatabaseLocked);
+        assert!(error.packages.is_empty());
+    }
+
+    #[test]
+    fn test_pacman_dependency() {
+        let error = classify_pacman_failure(&lines(
+            "error: failed to prepare transaction (could not satisfy dependencies)\n\
+             :: installing mesa (24.1.0-1) breaks dependency 'mesa=24.0.5' required by lib32-mesa",
+        ));
+        assert_eq!(error.kind, UpdateFailureKind::Dependency);
+        assert_eq!(error.packages, ["lib32-mesa"]);
+    }
+
+    #[test]
+    fn test_apt_failures() {
+        let error = classify_apt_failure(&lines(
+            "E: dpkg was interrupted, you must manually run 'sudo dpkg --configure -a' to correct the problem.",
+        ));
+        assert_eq!(error.kind, UpdateFailureKind::DpkgInterrupted);
+        assert!(error.remediation.contains("dpkg --configure -a"));
+
+        let error = classify_apt_failure(&lines(
+            "E: Could not get lock /var/lib/dpkg/lock-frontend. It is held by process 1234 (unattended-upgr)\n\
+             E: Unable to acquire the dpkg frontend lock (/var/lib/dpkg/lock-frontend), is another process using it?",
+        ));
+        assert_eq!(error.kind, UpdateFailureKind::DatabaseLocked);
+
+        let error = classify_apt_failure(&lines(
+            "pmerror:mesa-vulkan-drivers:amd64:52.3:trying to overwrite '/usr/share/vulkan/icd.d/radeon_icd.json', which is also in package amdvlk 2024.Q2.1\n\
+             dpkg: error processing archive /var/cache/apt/archives/mesa-vulkan-drivers_24.0.5-1_amd64.deb (--unpack):\n \
+             trying to overwrite '/usr/share/vulkan/icd.d/radeon_icd.json', which is also in package amdvlk 2024.Q2.1\n\
+             E: Sub-process /usr/bin/dpkg returned an error code (1)",
+        ));
+        assert_eq!(error.kind, UpdateFailureKind::FileConflict);
+        assert_eq!(error.packages, ["mesa-vulkan-drivers"]);
+        assert!(error.message.starts_with("dpkg: error processing archive"));
+    }
+
+    #[test]
+    fn test_apt_dependency() {
+        let error = classify_apt_failure(&lines(
+            "The following packages have unmet dependencies:\n \
+             steam-installer : Depends: steam-libs-i386 (= 1:1.0.0.79) but it is not going to be installed\n\
+             E: Unmet dependencies. Try 'apt --fix-broken install' with no packages (or specify a solution).",
+        ));
+        assert_eq!(error.kind, UpdateFailureKind::Dependency);
+        assert_eq!(error.packages, ["steam-installer"]);
+        assert_eq!(
+            error.message,
+            "E: Unmet dependencies. Try 'apt --fix-broken install' with no packages (or specify a solution)."
+        );
+    }
+
+    #[test]
+    fn test_detect_interrupted_update() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = dir.path().join("db.lck");
+        assert!(stale_pacman_lock(&lock, false).is_none());
+
+        std::fs::write(&lock, "").unwrap();
+        let error = stale_pacman_lock(&lock, false).unwrap();
+        assert_eq!(error.kind, UpdateFailureKind::DatabaseLocked);
+        assert!(error.remediation.contains("sudo rm"));
+        assert!(stale_pacman_lock(&lock, true).is_none());
+
+        let updates = dir.path().join("updates");
+        std::fs::create_dir(&updates).unwrap();
+        assert!(interrupted_dpkg(&updates, false).is_none());
+        std::fs::write(updates.join("0001"), "").unwrap();
+        assert_eq!(
+            interrupted_dpkg(&updates, false).unwrap().kind,
+            UpdateFailureKind::DpkgInterrupted
+        );
+    }
+
+    #[test]
+    fn test_unclassified_failure() {
+        let error = classify_pacman_failure(&lines("warning: something odd"));
+        assert_eq!(error.kind, UpdateFailureKind::Unknown);
+        assert_eq!(error.message, "Pacman update failed");
+    }
+}
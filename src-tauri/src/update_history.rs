@@ -0,0 +1,666 @@
+//! Update history, kept in the app database
+//!
+//! Every `install_system_updates` run is recorded with the packages it was
+//! about to upgrade (old → new versions), what actually got installed or
+//! failed, the error, the reboot flag and the pre-update snapshot, so a
+//! breakage can be traced back to an update. On pacman and dpkg systems the
+//! package manager log fills in exact versions and shows what was installed
+//! outside Pixxiden between two runs.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use sqlx::Row;
+use std::path::PathBuf;
+use tokio::sync::OnceCell;
+
+use crate::package_log::{self, PackageAction, PackageChange};
+use crate::system_updates::{Distro, UpdatePackage, UpdateReport, UpdateSource};
+use crate::update_failures::{UpdateError, UpdateFailureKind};
+
+/// Database shared with the frontend (`sqlite:pixxiden.db` of tauri-plugin-sql)
+pub const DATABASE_FILE: &str = "pixxiden.db";
+
+const EXTERNAL_CHANGES_MAX_AGE_DAYS: i64 = 30; // Log lookback before the first run
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS update_runs (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  started_at TEXT NOT NULL,
+  finished_at TEXT,
+  distro TEXT NOT NULL,
+  status TEXT NOT NULL,
+  error TEXT,
+  requires_reboot INTEGER NOT NULL DEFAULT 0,
+  snapshot_id TEXT,
+  demo INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS update_run_packages (
+  run_id INTEGER NOT NULL,
+  name TEXT NOT NULL,
+  source TEXT NOT NULL,
+  old_version TEXT NOT NULL DEFAULT '',
+  new_version TEXT NOT NULL DEFAULT '',
+  status TEXT NOT NULL,
+  PRIMARY KEY (run_id, name),
+  FOREIGN KEY (run_id) REFERENCES update_runs(id) ON DELETE CASCADE
+);
+"#;
+
+/// Outcome of a recorded run
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+    Interrupted, // Pixxiden quit or crashed before the end
+}
+
+/// What happened to one package of a run
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PackageStatus {
+    Pending,
+    Installed,
+    Failed,
+    Skipped, // Not reached, e.g. after an error or a cancellation
+}
+
+/// Summary of a recorded run
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateRun {
+    pub id: i64,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub distro: Distro,
+    pub status: RunStatus,
+    pub requires_reboot: bool,
+    pub snapshot_id: Option<String>,
+    pub demo: bool, // Replayed from a transcript
+    pub package_count: u32,
+    pub failed_count: u32,
+}
+
+/// A package of a recorded run
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RunPackage {
+    pub name: String,
+    pub source: UpdateSource,
+    pub old_version: String,
+    pub new_version: String,
+    pub status: PackageStatus,
+}
+
+/// Everything known about a run
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateRunDetails {
+    pub run: UpdateRun,
+    pub packages: Vec<RunPackage>,
+    pub error: Option<UpdateError>,
+    pub external_changes: Vec<PackageChange>, // Logged since the previous run, outside Pixxiden
+}
+
+/// Serde name of a unit enum variant, as stored in the database
+fn to_text<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn from_text<T: DeserializeOwned>(text: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(text.to_string()))
+        .map_err(|e| format!("Invalid value {} in update history: {}", text, e))
+}
+
+fn parse_time(text: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| format!("Invalid date {} in update history: {}", text, e))
+}
+
+fn db_error(action: &str) -> impl Fn(sqlx::Error) -> String + '_ {
+    move |e| format!("Failed to {}: {}", action, e)
+}
+
+/// Status of a finished run, from its report
+fn run_status(result: &Result<UpdateReport, String>) -> RunStatus {
+    match result {
+        Ok(report) => match &report.error {
+            Some(error) if error.kind == UpdateFailureKind::Cancelled => RunStatus::Cancelled,
+            Some(_) => RunStatus::Failed,
+            None if !report.failed.is_empty() => RunStatus::Failed,
+            None => RunStatus::Succeeded,
+        },
+        Err(_) => RunStatus::Failed,
+    }
+}
+
+/// Log changes made by the package manager during a run, by package name
+fn logged_versions(changes: &[PackageChange]) -> Vec<(&str, &str, &str)> {
+    changes
+        .iter()
+        .filter(|c| c.action != PackageAction::Removed)
+        .map(|c| {
+            (
+                c.name.as_str(),
+                c.old_version.as_deref().unwrap_or(""),
+                c.new_version.as_deref().unwrap_or(""),
+            )
+        })
+        .collect()
+}
+
+/// Update history stored in the app database
+pub struct UpdateHistory {
+    path: PathBuf,
+    pool: OnceCell<SqlitePool>,
+}
+
+impl UpdateHistory {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            pool: OnceCell::new(),
+        }
+    }
+
+    /// Open the database on first use. Runs left `running` by a previous
+    /// Pixxiden process never finished.
+    async fn pool(&self) -> Result<&SqlitePool, String> {
+        self.pool
+            .get_or_try_init(|| async {
+                if let Some(parent) = self.path.parent() {
+                    std::fs::create_dir_all(parent)
+                        .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+                }
+                // Same settings as the frontend connection, which shares the file
+                let options = SqliteConnectOptions::new()
+                    .filename(&self.path)
+                    .create_if_missing(true)
+                    .journal_mode(SqliteJournalMode::Wal)
+                    .busy_timeout(std::time::Duration::from_secs(5))
+                    .foreign_keys(true);
+                let pool = SqlitePoolOptions::new()
+                    .max_connections(1)
+                    .connect_with(options)
+                    .await
+                    .map_err(db_error("open the update history"))?;
+
+                sqlx::raw_sql(SCHEMA)
+                    .execute(&pool)
+                    .await
+                    .map_err(db_error("create the update history tables"))?;
+                sqlx::query("UPDATE update_runs SET status = ? WHERE status = ?")
+                    .bind(to_text(&RunStatus::Interrupted))
+                    .bind(to_text(&RunStatus::Running))
+                    .execute(&pool)
+                    .await
+                    .map_err(db_error("mark interrupted runs"))?;
+                Ok(pool)
+            })
+            .await
+    }
+
+    /// Record the start of a run with the packages about to be upgraded
+    pub async fn start_run(
+        &self,
+        distro: &Distro,
+        demo: bool,
+        pending: &[UpdatePackage],
+    ) -> Result<i64, String> {
+        let pool = self.pool().await?;
+        let mut tx = pool.begin().await.map_err(db_error("record the update"))?;
+
+        let id = sqlx::query(
+            "INSERT INTO update_runs (started_at, distro, status, demo) VALUES (?, ?, ?, ?)",
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(to_text(distro))
+        .bind(to_text(&RunStatus::Running))
+        .bind(demo)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error("record the update"))?
+        .last_insert_rowid();
+
+        for package in pending {
+            sqlx::query(
+                "INSERT OR REPLACE INTO update_run_packages \
+                 (run_id, name, source, old_version, new_version, status) VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(id)
+            .bind(&package.name)
+            .bind(to_text(&package.source))
+            .bind(&package.current_version)
+            .bind(&package.new_version)
+            .bind(to_text(&PackageStatus::Pending))
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error("record the updated packages"))?;
+        }
+
+        tx.commit().await.map_err(db_error("record the update"))?;
+        Ok(id)
+    }
+
+    /// Record how a run ended. `logged` are the package manager log entries
+    /// written during the run, which give the exact versions installed.
+    pub async fn finish_run(
+        &self,
+        id: i64,
+        result: &Result<UpdateReport, String>,
+        logged: &[PackageChange],
+    ) -> Result<(), String> {
+        let pool = self.pool().await?;
+        let mut tx = pool.begin().await.map_err(db_error("record the update"))?;
+
+        let (installed, failed, flatpaks, error, requires_reboot, snapshot_id) = match result {
+            Ok(report) => (
+                report.installed.as_slice(),
+                report.failed.as_slice(),
+                report.flatpaks.as_slice(),
+                report.error.clone(),
+                report.requires_reboot,
+                report.snapshot.as_ref().map(|s| s.id.clone()),
+            ),
+            Err(e) => (
+                &[][..],
+                &[][..],
+                &[][..],
+                Some(UpdateError::unknown(e.clone())),
+                false,
+                None,
+            ),
+        };
+
+        sqlx::query(
+            "UPDATE update_runs SET finished_at = ?, status = ?, error = ?, requires_reboot = ?, \
+             snapshot_id = ? WHERE id = ?",
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(to_text(&run_status(result)))
+        .bind(error.map(|e| serde_json::to_string(&e).unwrap_or_default()))
+        .bind(requires_reboot)
+        .bind(snapshot_id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error("record the update result"))?;
+
+        // Dependencies pulled in by the upgrade were not in the pending list
+        let statuses = installed
+            .iter()
+            .map(|name| (name, PackageStatus::Installed))
+            .chain(failed.iter().map(|name| (name, PackageStatus::Failed)));
+        for (name, status) in statuses {
+            let source = if flatpaks.contains(name) {
+                crate::flatpak::update_source(name)
+            } else {
+                UpdateSource::System
+            };
+            sqlx::query(
+                "INSERT INTO update_run_packages (run_id, name, source, status) VALUES (?, ?, ?, ?) \
+                 ON CONFLICT (run_id, name) DO UPDATE SET status = excluded.status",
+            )
+            .bind(id)
+            .bind(name)
+            .bind(to_text(&source))
+            .bind(to_text(&status))
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error("record the updated packages"))?;
+        }
+
+        for (name, old_version, new_version) in logged_versions(logged) {
+            sqlx::query(
+                "UPDATE update_run_packages SET old_version = ?, new_version = ? \
+                 WHERE run_id = ? AND name = ?",
+            )
+            .bind(old_version)
+            .bind(new_version)
+            .bind(id)
+            .bind(name)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error("record the installed versions"))?;
+        }
+
+        sqlx::query("UPDATE update_run_packages SET status = ? WHERE run_id = ? AND status = ?")
+            .bind(to_text(&PackageStatus::Skipped))
+            .bind(id)
+            .bind(to_text(&PackageStatus::Pending))
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error("record the skipped packages"))?;
+
+        tx.commit()
+            .await
+            .map_err(db_error("record the update result"))
+    }
+
+    /// Most recent runs first
+    pub async fn list_runs(&self, limit: u32) -> Result<Vec<UpdateRun>, String> {
+        self.query_runs("ORDER BY r.id DESC LIMIT ?", i64::from(limit))
+            .await
+    }
+
+    async fn query_runs(&self, clause: &str, param: i64) -> Result<Vec<UpdateRun>, String> {
+        let pool = self.pool().await?;
+        let rows = sqlx::query(&format!(
+            "SELECT r.id, r.started_at, r.finished_at, r.distro, r.status, r.requires_reboot, \
+             r.snapshot_id, r.demo, COUNT(p.name) AS package_count, \
+             COALESCE(SUM(p.status = 'failed'), 0) AS failed_count \
+             FROM update_runs r LEFT JOIN update_run_packages p ON p.run_id = r.id \
+             GROUP BY r.id {}",
+            clause
+        ))
+        .bind(param)
+        .fetch_all(pool)
+        .await
+        .map_err(db_error("read the update history"))?;
+
+        rows.iter()
+            .map(|row| {
+                let get_str = |column: &str| -> Result<String, String> {
+                    row.try_get(column)
+                        .map_err(db_error("read the update history"))
+                };
+                let finished_at: Option<String> = row
+                    .try_get("finished_at")
+                    .map_err(db_error("read the update history"))?;
+                Ok(UpdateRun {
+                    id: row
+                        .try_get("id")
+                        .map_err(db_error("read the update history"))?,
+                    started_at: parse_time(&get_str("started_at")?)?,
+                    finished_at: finished_at.as_deref().map(parse_time).transpose()?,
+                    distro: from_text(&get_str("distro")?)?,
+                    status: from_text(&get_str("status")?)?,
+                    requires_reboot: row.try_get("requires_reboot").unwrap_or(false),
+                    snapshot_id: row.try_get("snapshot_id").unwrap_or(None),
+                    demo: row.try_get("demo").unwrap_or(false),
+                    package_count: row.try_get("package_count").unwrap_or(0),
+                    failed_count: row.try_get("failed_count").unwrap_or(0),
+                })
+            })
+            .collect()
+    }
+
+    /// A run with its packages, error and the changes made outside Pixxiden
+    /// since the previous run
+    pub async fn run_details(&self, id: i64) -> Result<UpdateRunDetails, String> {
+        let run = self
+            .query_runs("HAVING r.id = ?", id)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("No update run with id {}", id))?;
+        let pool = self.pool().await?;
+
+        let error: Option<String> = sqlx::query("SELECT error FROM update_runs WHERE id = ?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .and_then(|row| row.try_get("error"))
+            .map_err(db_error("read the update history"))?;
+
+        let packages = sqlx::query(
+            "SELECT name, source, old_version, new_version, status FROM update_run_packages \
+             WHERE run_id = ? ORDER BY name",
+        )
+        .bind(id)
+        .fetch_all(pool)
+        .await
+        .map_err(db_error("read the updated packages"))?
+        .iter()
+        .map(|row| {
+            let get = |column: &str| -> Result<String, String> {
+                row.try_get(column)
+                    .map_err(db_error("read the updated packages"))
+            };
+            Ok(RunPackage {
+                name: get("name")?,
+                source: from_text(&get("source")?)?,
+                old_version: get("old_version")?,
+                new_version: get("new_version")?,
+                status: from_text(&get("status")?)?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+        // End of the previous run, or a month back for the first one
+        let previous_end: Option<String> = sqlx::query(
+            "SELECT COALESCE(finished_at, started_at) AS ended_at FROM update_runs \
+             WHERE id < ? ORDER BY id DESC LIMIT 1",
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(db_error("read the update history"))?
+        .map(|row| row.try_get("ended_at"))
+        .transpose()
+        .map_err(db_error("read the update history"))?;
+        let since = match previous_end {
+            Some(ended_at) => parse_time(&ended_at)?,
+            None => run.started_at - Duration::days(EXTERNAL_CHANGES_MAX_AGE_DAYS),
+        };
+
+        let distro = run.distro.clone();
+        let until = run.started_at;
+        let external_changes = tokio::task::spawn_blocking(move || {
+            package_log::changes_between(&distro, Some(since), Some(until))
+        })
+        .await
+        .map_err(|e| format!("Package log task failed: {}", e))?;
+
+        Ok(UpdateRunDetails {
+            run,
+            packages,
+            error: error.as_deref().and_then(|e| serde_json::from_str(e).ok()),
+            external_changes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system_updates::PackageCategory;
+
+    fn package(name: &str, current: &str, new: &str) -> UpdatePackage {
+        UpdatePackage {
+            name: name.to_string(),
+            current_version: current.to_string(),
+            new_version: new.to_string(),
+            category: PackageCategory::Graphics,
+            size: 0,
+            installed_size_delta: 0,
+            critical: false,
+            source: UpdateSource::System,
+        }
+    }
+
+    fn report(installed: &[&str], failed: &[&str], error: Option<UpdateError>) -> UpdateReport {
+        UpdateReport {
+            total_packages: (installed.len() + failed.len()) as u32,
+            installed_successfully: installed.len() as u32,
+            installed: installed.iter().map(|s| s.to_string()).collect(),
+            failed: failed.iter().map(|s| s.to_string()).collect(),
+            requires_reboot: false,
+            duration_seconds: 1,
+            error,
+            snapshot: None,
+            flatpaks: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = UpdateHistory::new(dir.path().join(DATABASE_FILE));
+
+        let pending = [
+            package("mesa", "1:24.1.1-1", "1:24.1.2-1"),
+            package("linux", "6.9.6.arch1-1", "6.9.7.arch1-1"),
+            package("gamemode", "1.8.1-1", "1.8.2-1"),
+        ];
+        let id = history
+            .start_run(&Distro::Arch, false, &pending)
+            .await
+            .unwrap();
+        assert_eq!(
+            history.list_runs(10).await.unwrap()[0].status,
+            RunStatus::Running
+        );
+
+        // A new dependency got pulled in, and the log knows the real versions
+        let logged = parse_log("[2024-07-01T09:58:41+0200] [ALPM] upgraded mesa (1:24.1.1-1 -> 1:24.1.2-2)\n[2024-07-01T09:58:42+0200] [ALPM] installed libdisplay-info (0.2.0-1)");
+        let result = Ok(report(
+            &["mesa", "libdisplay-info"],
+            &["linux"],
+            Some(UpdateError::unknown(
+                "error: failed to commit transaction".into(),
+            )),
+        ));
+        history.finish_run(id, &result, &logged).await.unwrap();
+
+        let details = history.run_details(id).await.unwrap();
+        assert_eq!(details.run.status, RunStatus::Failed);
+        assert_eq!(details.run.package_count, 4);
+        assert_eq!(details.run.failed_count, 1);
+        assert!(details.run.finished_at.is_some());
+        assert_eq!(
+            details.error.unwrap().message,
+            "error: failed to commit transaction"
+        );
+
+        let packages: Vec<(&str, &str, &str, PackageStatus)> = details
+            .packages
+            .iter()
+            .map(|p| {
+                (
+                    p.name.as_str(),
+                    p.old_version.as_str(),
+                    p.new_version.as_str(),
+                    p.status,
+                )
+            })
+            .collect();
+        assert_eq!(
+            packages,
+            [
+                ("gamemode", "1.8.1-1", "1.8.2-1", PackageStatus::Skipped),
+                ("libdisplay-info", "", "0.2.0-1", PackageStatus::Installed),
+                (
+                    "linux",
+                    "6.9.6.arch1-1",
+                    "6.9.7.arch1-1",
+                    PackageStatus::Failed
+                ),
+                ("mesa", "1:24.1.1-1", "1:24.1.2-2", PackageStatus::Installed),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_flatpak_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = UpdateHistory::new(dir.path().join(DATABASE_FILE));
+
+        // Updated without having been listed by the check
+        let id = history
+            .start_run(&Distro::Fedora, false, &[])
+            .await
+            .unwrap();
+        let mut report = report(
+            &["mesa-dri-drivers", "org.gnome.Boxes"],
+            &["org.freedesktop.Platform.GL.default"],
+            None,
+        );
+        report.flatpaks = vec![
+            "org.gnome.Boxes".to_string(),
+            "org.freedesktop.Platform.GL.default".to_string(),
+        ];
+        history.finish_run(id, &Ok(report), &[]).await.unwrap();
+
+        let sources: Vec<(String, UpdateSource)> = history
+            .run_details(id)
+            .await
+            .unwrap()
+            .packages
+            .into_iter()
+            .map(|p| (p.name, p.source))
+            .collect();
+        assert_eq!(
+            sources,
+            [
+                ("mesa-dri-drivers".to_string(), UpdateSource::System),
+                (
+                    "org.freedesktop.Platform.GL.default".to_string(),
+                    UpdateSource::FlatpakGl
+                ),
+                ("org.gnome.Boxes".to_string(), UpdateSource::Flatpak),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DATABASE_FILE);
+
+        let history = UpdateHistory::new(path.clone());
+        let first = history
+            .start_run(&Distro::Debian, false, &[])
+            .await
+            .unwrap();
+        history
+            .finish_run(
+                first,
+                &Ok(report(&[], &[], Some(UpdateError::cancelled()))),
+                &[],
+            )
+            .await
+            .unwrap();
+        history
+            .start_run(&Distro::Debian, false, &[])
+            .await
+            .unwrap();
+        drop(history);
+
+        // Next launch
+        let history = UpdateHistory::new(path);
+        let runs = history.list_runs(10).await.unwrap();
+        let statuses: Vec<RunStatus> = runs.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, [RunStatus::Interrupted, RunStatus::Cancelled]);
+        assert!(history.run_details(42).await.is_err());
+    }
+
+    #[test]
+    fn test_run_status() {
+        assert_eq!(
+            run_status(&Ok(report(&["mesa"], &[], None))),
+            RunStatus::Succeeded
+        );
+        assert_eq!(
+            run_status(&Ok(report(&["mesa"], &["org.gnome.Boxes"], None))),
+            RunStatus::Failed
+        );
+        assert_eq!(
+            run_status(&Err("Update already running".into())),
+            RunStatus::Failed
+        );
+    }
+
+    fn parse_log(contents: &str) -> Vec<PackageChange> {
+        package_log::parse_pacman_log(contents, &Utc)
+    }
+}
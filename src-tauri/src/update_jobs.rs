@@ -0,0 +1,246 @@
+//! Tracking of the running system update so it can be cancelled
+//!
+//! Only one update runs at a time. Cancelling interrupts the package manager
+//! while it is still downloading; once packages are being committed the
+//! request is refused, as stopping then leaves the system half-upgraded.
+
+use serde::Serialize;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use tauri::Emitter;
+
+use crate::system_updates::{Status, UpdateProgressEvent};
+
+/// Phase of an update job
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdatePhase {
+    Downloading, // Safe to cancel
+    Committing,  // Packages are being installed, cancelling is refused
+    Cancelled,
+    Finished,
+}
+
+/// Update job state sent to the frontend (`update-job` event)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateJobInfo {
+    pub id: u64,
+    pub phase: UpdatePhase,
+}
+
+/// Receiver of update job events: the app window, or a recorder in tests
+pub trait UpdateEvents: Send + Sync {
+    fn job_changed(&self, info: UpdateJobInfo);
+    fn progress(&self, event: UpdateProgressEvent);
+}
+
+impl UpdateEvents for tauri::Window {
+    fn job_changed(&self, info: UpdateJobInfo) {
+        let _ = self.emit("update-job", info);
+    }
+
+    fn progress(&self, event: UpdateProgressEvent) {
+        let _ = self.emit("update-progress", event);
+    }
+}
+
+/// A running system update
+pub struct UpdateJob {
+    pub id: u64,
+    phase: Mutex<UpdatePhase>,
+    pid: Mutex<Option<u32>>, // `sudo` process of the running package manager
+    events: Arc<dyn UpdateEvents>,
+}
+
+impl UpdateJob {
+    fn new(id: u64, events: Arc<dyn UpdateEvents>) -> Self {
+        Self {
+            id,
+            phase: Mutex::new(UpdatePhase::Downloading),
+            pid: Mutex::new(None),
+            events,
+        }
+    }
+
+    pub fn info(&self) -> UpdateJobInfo {
+        UpdateJobInfo {
+            id: self.id,
+            phase: *self.phase.lock().unwrap(),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.phase.lock().unwrap() == UpdatePhase::Cancelled
+    }
+
+    /// Remember the package manager process to interrupt on cancel
+    pub fn attach(&self, pid: Option<u32>) {
+        *self.pid.lock().unwrap() = pid;
+    }
+
+    pub fn detach(&self) {
+        *self.pid.lock().unwrap() = None;
+    }
+
+    /// Mark the start of the commit phase, after which cancelling is refused
+    pub fn enter_commit(&self) {
+        let mut phase = self.phase.lock().unwrap();
+        if *phase == UpdatePhase::Downloading {
+            *phase = UpdatePhase::Committing;
+            drop(phase);
+            self.events.job_changed(self.info());
+        }
+    }
+
+    /// Emit a progress event; anything past downloading is part of the commit
+    pub fn progress(&self, event: UpdateProgressEvent) {
+        if event.stage != Status::Downloading {
+            self.enter_commit();
+        }
+        self.events.progress(event);
+    }
+
+    fn cancel(&self) -> Result<(), String> {
+        let mut phase = self.phase.lock().unwrap();
+        match *phase {
+            UpdatePhase::Downloading => {}
+            UpdatePhase::Committing => {
+                return Err(
+                    "Packages are being installed, stopping now would leave the system \
+                     half-upgraded"
+                        .to_string(),
+                )
+            }
+            UpdatePhase::Cancelled => return Ok(()),
+            UpdatePhase::Finished => return Err("The update has already finished".to_string()),
+        }
+        *phase = UpdatePhase::Cancelled;
+
+        // sudo relays SIGINT to the package manager, which aborts its downloads cleanly
+        if let Some(pid) = *self.pid.lock().unwrap() {
+            Command::new("kill")
+                .args(["-INT", &pid.to_string()])
+                .status()
+                .map_err(|e| format!("Failed to interrupt the package manager: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Holder of the running update job
+pub struct UpdateJobs {
+    current: Mutex<Option<Arc<UpdateJob>>>,
+    next_id: Mutex<u64>,
+}
+
+impl UpdateJobs {
+    pub fn new() -> Self {
+        Self {
+            current: Mutex::new(None),
+            next_id: Mutex::new(1),
+        }
+    }
+
+    /// Register a new job, refused while another update is running
+    pub fn start(&self, events: Arc<dyn UpdateEvents>) -> Result<Arc<UpdateJob>, String> {
+        let mut current = self.current.lock().unwrap();
+        if current.is_some() {
+            return Err("A system update is already running".to_string());
+        }
+
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        let job = Arc::new(UpdateJob::new(id, events));
+        *current = Some(job.clone());
+
+        log::info!("System update job {} started", id);
+        job.events.job_changed(job.info());
+        Ok(job)
+    }
+
+    /// Forget a job once its package managers have exited
+    pub fn finish(&self, job: &UpdateJob) {
+        {
+            let mut phase = job.phase.lock().unwrap();
+            if *phase != UpdatePhase::Cancelled {
+                *phase = UpdatePhase::Finished;
+            }
+        }
+        let mut current = self.current.lock().unwrap();
+        if current.as_ref().is_some_and(|c| c.id == job.id) {
+            current.take();
+        }
+        job.events.job_changed(job.info());
+    }
+
+    /// The running job, if any
+    pub fn active(&self) -> Option<UpdateJobInfo> {
+        self.current.lock().unwrap().as_ref().map(|job| job.info())
+    }
+
+    /// Cancel a job while it is still downloading
+    pub fn cancel(&self, id: u64) -> Result<(), String> {
+        let job = self
+            .current
+            .lock()
+            .unwrap()
+            .clone()
+            .filter(|job| job.id == id)
+            .ok_or_else(|| format!("No running update job {}", id))?;
+        job.cancel()?;
+        log::info!("System update job {} cancelled", id);
+        Ok(())
+    }
+
+    /// Interrupt the running job when the app quits, if it is safe to do so
+    pub fn shutdown(&self) {
+        let Some(job) = self.current.lock().unwrap().clone() else {
+            return;
+        };
+        if let Err(e) = job.cancel() {
+            log::warn!("Leaving system update job {} running: {}", job.id, e);
+        }
+    }
+}
+
+impl Default for UpdateJobs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoEvents;
+
+    impl UpdateEvents for NoEvents {
+        fn job_changed(&self, _info: UpdateJobInfo) {}
+        fn progress(&self, _event: UpdateProgressEvent) {}
+    }
+
+    #[test]
+    fn test_cancel_only_while_downloading() {
+        let job = UpdateJob::new(1, Arc::new(NoEvents));
+        assert!(job.cancel().is_ok());
+        assert!(job.is_cancelled());
+
+        let job = UpdateJob::new(2, Arc::new(NoEvents));
+        *job.phase.lock().unwrap() = UpdatePhase::Committing;
+        assert!(job.cancel().unwrap_err().contains("half-upgraded"));
+        assert!(!job.is_cancelled());
+    }
+
+    #[test]
+    fn test_cancel_unknown_job() {
+        let jobs = UpdateJobs::new();
+        assert!(jobs.cancel(1).is_err());
+        assert!(jobs.active().is_none());
+    }
+}
@@ -0,0 +1,641 @@
+//! Background update checks
+//!
+//! Every few hours (plus some jitter, so devices do not all hit the mirrors
+//! at the same time) looks for system package, Flatpak, Proton-GE and
+//! Pixxiden updates, and tells the user when something new shows up: a
+//! freedesktop notification, and an `updates-available` event for the in-app
+//! badge. A check is postponed while on battery, on a metered connection, while
+//! a game runs or while an update is being installed.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::hash::{BuildHasher, Hasher};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+use tauri::{AppHandle, Emitter, Listener, Manager};
+use tokio::sync::Notify;
+use zbus::zvariant::Value;
+
+use crate::update_jobs::UpdateJobs;
+use crate::{dbus, flatpak, network, package_holds, package_manager, runners, sudoers, system};
+
+const APP_DIR: &str = "com.Pixxiden.launcher";
+const SCHEDULE_FILE: &str = "update-schedule.json";
+const LAST_CHECK_FILE: &str = "update-check.json";
+
+const UPOWER: &str = "org.freedesktop.UPower";
+const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+const NOTIFICATIONS: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+
+const STARTUP_DELAY_MINUTES: i64 = 5; // Let the session settle before the first check
+const RETRY_DELAY_MINUTES: i64 = 30; // After a postponed check
+const MAX_INTERVAL_HOURS: u32 = 24 * 7;
+const NOTIFIED_NAMES: usize = 3; // Updates named in the notification body
+
+/// When and under which conditions background checks run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UpdateSchedule {
+    pub enabled: bool,
+    pub interval_hours: u32,
+    pub skip_on_battery: bool,
+    pub skip_on_metered: bool,
+    pub skip_while_gaming: bool,
+    pub notify: bool, // Desktop notification when new updates show up
+}
+
+impl Default for UpdateSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_hours: 12,
+            skip_on_battery: true,
+            skip_on_metered: true,
+            skip_while_gaming: true,
+            notify: true,
+        }
+    }
+}
+
+/// What an available update applies to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateKind {
+    System,   // Distribution packages or OS image
+    Flatpak,  // Flatpak applications and runtimes
+    Runner,   // Proton-GE
+    Pixxiden, // Pixxiden itself
+}
+
+/// An update found by a background check
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AvailableUpdate {
+    pub kind: UpdateKind,
+    pub name: String,
+    pub current_version: String,
+    pub new_version: String,
+}
+
+/// Result of the last background check, sent as the `updates-available` payload
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledCheck {
+    pub checked_at: DateTime<Utc>,
+    pub updates: Vec<AvailableUpdate>,
+    pub new_updates: usize, // Not found by the previous check
+}
+
+/// Why a check was postponed
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SkipReason {
+    Updating,
+    Gaming,
+    Battery,
+    Metered,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SkipReason::Updating => "an update is being installed",
+            SkipReason::Gaming => "a game is running",
+            SkipReason::Battery => "running on battery",
+            SkipReason::Metered => "the connection is metered",
+        })
+    }
+}
+
+fn state_path(file: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_DIR).join(file))
+}
+
+fn load<T: DeserializeOwned>(file: &str) -> Option<T> {
+    let contents = std::fs::read_to_string(state_path(file)?).ok()?;
+    serde_json::from_str(&contents)
+        .map_err(|e| log::warn!("Ignoring invalid {}: {}", file, e))
+        .ok()
+}
+
+fn save<T: Serialize>(file: &str, value: &T) -> Result<(), String> {
+    let path = state_path(file).ok_or("No data directory")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("Failed to serialize {}: {}", file, e))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Random delay of up to a tenth of the interval
+fn jitter(interval_hours: u32, random: u64) -> Duration {
+    Duration::seconds((random % (u64::from(interval_hours) * 360 + 1)) as i64)
+}
+
+/// When the next check is due, never before `earliest`
+fn next_check(
+    last: Option<DateTime<Utc>>,
+    interval_hours: u32,
+    jitter: Duration,
+    earliest: DateTime<Utc>,
+) -> DateTime<Utc> {
+    last.map(|t| t + Duration::hours(interval_hours.into()) + jitter)
+        .map_or(earliest, |due| due.max(earliest))
+}
+
+/// Combine the results of each source, keeping what the previous check found
+/// for a source that failed this time (e.g. offline), so it is not reported
+/// as new again once it works
+fn merge_results(
+    previous: Option<&ScheduledCheck>,
+    results: Vec<(UpdateKind, Result<Vec<AvailableUpdate>, String>)>,
+) -> Vec<AvailableUpdate> {
+    let mut updates = Vec::new();
+    for (kind, result) in results {
+        match result {
+            Ok(found) => updates.extend(found),
+            Err(e) => {
+                log::warn!("Background {:?} update check failed: {}", kind, e);
+                updates.extend(
+                    previous
+                        .iter()
+                        .flat_map(|p| &p.updates)
+                        .filter(|u| u.kind == kind)
+                        .cloned(),
+                );
+            }
+        }
+    }
+    updates
+}
+
+/// Updates the previous check did not find
+fn new_updates<'a>(
+    previous: Option<&ScheduledCheck>,
+    updates: &'a [AvailableUpdate],
+) -> Vec<&'a AvailableUpdate> {
+    let seen: HashSet<(UpdateKind, &str, &str)> = previous
+        .iter()
+        .flat_map(|p| &p.updates)
+        .map(|u| (u.kind, u.name.as_str(), u.new_version.as_str()))
+        .collect();
+    updates
+        .iter()
+        .filter(|u| !seen.contains(&(u.kind, u.name.as_str(), u.new_version.as_str())))
+        .collect()
+}
+
+fn notification_body(new: &[&AvailableUpdate]) -> String {
+    let mut names: Vec<&str> = new
+        .iter()
+        .take(NOTIFIED_NAMES)
+        .map(|u| u.name.as_str())
+        .collect();
+    if new.len() > NOTIFIED_NAMES {
+        names.push("…");
+    }
+    let count = if new.len() == 1 {
+        "1 nouvelle mise à jour".to_string()
+    } else {
+        format!("{} nouvelles mises à jour", new.len())
+    };
+    format!("{} : {}", count, names.join(", "))
+}
+
+/// `gameId` of a `game-started`/`game-exited` payload
+fn game_id(payload: &str) -> Option<String> {
+    let json: serde_json::Value = serde_json::from_str(payload).ok()?;
+    json.get("gameId")?.as_str().map(str::to_string)
+}
+
+/// Steam wraps every game it launches in `reaper SteamLaunch AppId=...`
+fn is_steam_game(cmd: &[OsString]) -> bool {
+    cmd.iter().any(|arg| arg == "SteamLaunch")
+}
+
+fn steam_game_running() -> bool {
+    let mut sys = System::new();
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::new().with_cmd(sysinfo::UpdateKind::OnlyIfNotSet),
+    );
+    sys.processes().values().any(|p| is_steam_game(p.cmd()))
+}
+
+async fn on_battery() -> Result<bool, String> {
+    let conn = dbus::system_bus().await?;
+    let upower = dbus::proxy(&conn, UPOWER, UPOWER_PATH, UPOWER).await?;
+    upower
+        .get_property("OnBattery")
+        .await
+        .map_err(|e| format!("Failed to read battery state: {}", e))
+}
+
+async fn send_notification(summary: &str, body: &str) -> Result<(), String> {
+    let conn = dbus::session_bus().await?;
+    let notifications =
+        dbus::proxy(&conn, NOTIFICATIONS, NOTIFICATIONS_PATH, NOTIFICATIONS).await?;
+    let hints: HashMap<&str, Value> = HashMap::new();
+    let _id: u32 = notifications
+        .call(
+            "Notify",
+            &(
+                "Pixxiden",
+                0u32,
+                "system-software-update",
+                summary,
+                body,
+                Vec::<&str>::new(),
+                hints,
+                -1i32,
+            ),
+        )
+        .await
+        .map_err(|e| format!("Failed to send notification: {}", e))?;
+    Ok(())
+}
+
+async fn check_system() -> Result<Vec<AvailableUpdate>, String> {
+    // Without the sudoers rules, some package managers cannot refresh their metadata
+    if !sudoers::is_sudoers_configured()?.configured {
+        return Err("Sudoers is not configured".to_string());
+    }
+    let backend = package_manager::current()?;
+    let holds = if backend.supports_holds() {
+        package_holds::load()
+    } else {
+        Vec::new()
+    };
+    let packages = backend.check().await?;
+    // Held packages are not coming, no need to announce them
+    Ok(packages
+        .into_iter()
+        .filter(|p| !holds.contains(&p.name))
+        .map(|p| AvailableUpdate {
+            kind: UpdateKind::System,
+            name: p.name,
+            current_version: p.current_version,
+            new_version: p.new_version,
+        })
+        .collect())
+}
+
+async fn check_flatpaks() -> Result<Vec<AvailableUpdate>, String> {
+    if !flatpak::is_available() {
+        return Ok(Vec::new());
+    }
+    let packages = tauri::async_runtime::spawn_blocking(flatpak::check_updates)
+        .await
+        .map_err(|e| format!("Flatpak check task failed: {}", e))??;
+    Ok(packages
+        .into_iter()
+        .map(|p| AvailableUpdate {
+            kind: UpdateKind::Flatpak,
+            name: p.name,
+            current_version: p.current_version,
+            new_version: p.new_version,
+        })
+        .collect())
+}
+
+async fn check_runners(app: &AppHandle) -> Result<Vec<AvailableUpdate>, String> {
+    let runners_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data dir: {}", e))?
+        .join("runners");
+    Ok(runners::check_proton_update(&runners_dir)
+        .await?
+        .map(|(current, latest)| AvailableUpdate {
+            kind: UpdateKind::Runner,
+            name: "Proton-GE".to_string(),
+            current_version: current,
+            new_version: latest,
+        })
+        .into_iter()
+        .collect())
+}
+
+async fn check_pixxiden() -> Result<Vec<AvailableUpdate>, String> {
+    Ok(system::latest_pixxiden_release()
+        .await?
+        .map(|tag| AvailableUpdate {
+            kind: UpdateKind::Pixxiden,
+            name: "Pixxiden".to_string(),
+            current_version: env!("CARGO_PKG_VERSION").to_string(),
+            new_version: tag,
+        })
+        .into_iter()
+        .collect())
+}
+
+/// Runs the background checks and keeps their last result
+pub struct UpdateScheduler {
+    schedule: Mutex<UpdateSchedule>,
+    last_check: Mutex<Option<ScheduledCheck>>,
+    running_games: Arc<Mutex<HashSet<String>>>, // Launched by Pixxiden
+    changed: Notify,                            // Schedule updated
+    started: AtomicBool,
+}
+
+impl UpdateScheduler {
+    pub fn new() -> Self {
+        Self {
+            schedule: Mutex::new(load(SCHEDULE_FILE).unwrap_or_default()),
+            last_check: Mutex::new(load(LAST_CHECK_FILE)),
+            running_games: Arc::new(Mutex::new(HashSet::new())),
+            changed: Notify::new(),
+            started: AtomicBool::new(false),
+        }
+    }
+
+    pub fn schedule(&self) -> UpdateSchedule {
+        self.schedule.lock().unwrap().clone()
+    }
+
+    /// Save new settings and reschedule the next check
+    pub fn set_schedule(&self, schedule: UpdateSchedule) -> Result<(), String> {
+        if !(1..=MAX_INTERVAL_HOURS).contains(&schedule.interval_hours) {
+            return Err(format!(
+                "Check interval must be between 1 and {} hours",
+                MAX_INTERVAL_HOURS
+            ));
+        }
+        save(SCHEDULE_FILE, &schedule)?;
+        *self.schedule.lock().unwrap() = schedule;
+        self.changed.notify_one();
+        Ok(())
+    }
+
+    pub fn last_check(&self) -> Option<ScheduledCheck> {
+        self.last_check.lock().unwrap().clone()
+    }
+
+    /// Start the check loop and follow the games launched by Pixxiden
+    pub fn start(self: &Arc<Self>, app: AppHandle) {
+        if self.started.swap(true, Ordering::Relaxed) {
+            log::warn!("Update scheduler already running");
+            return;
+        }
+
+        let games = self.running_games.clone();
+        app.listen_any("game-started", move |event| {
+            if let Some(id) = game_id(event.payload()) {
+                games.lock().unwrap().insert(id);
+            }
+        });
+        let games = self.running_games.clone();
+        app.listen_any("game-exited", move |event| {
+            if let Some(id) = game_id(event.payload()) {
+                games.lock().unwrap().remove(&id);
+            }
+        });
+
+        let scheduler = self.clone();
+        tauri::async_runtime::spawn(async move { scheduler.run(app).await });
+    }
+
+    async fn run(self: Arc<Self>, app: AppHandle) {
+        let earliest = Utc::now() + Duration::minutes(STARTUP_DELAY_MINUTES);
+        let mut retry_at: Option<DateTime<Utc>> = None;
+
+        loop {
+            let schedule = self.schedule();
+            if !schedule.enabled {
+                self.changed.notified().await;
+                continue;
+            }
+
+            let last = self.last_check().map(|c| c.checked_at);
+            let random = RandomState::new().build_hasher().finish();
+            let due = retry_at.unwrap_or_else(|| {
+                next_check(
+                    last,
+                    schedule.interval_hours,
+                    jitter(schedule.interval_hours, random),
+                    earliest,
+                )
+            });
+            log::debug!("Next background update check at {}", due);
+
+            let wait = (due - Utc::now()).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = self.changed.notified() => {
+                    retry_at = None;
+                    continue;
+                }
+            }
+
+            if let Some(reason) = self.skip_reason(&app, &schedule).await {
+                log::info!("Postponing background update check: {}", reason);
+                retry_at = Some(Utc::now() + Duration::minutes(RETRY_DELAY_MINUTES));
+                continue;
+            }
+            retry_at = None;
+            self.check(&app, &schedule).await;
+        }
+    }
+
+    async fn skip_reason(&self, app: &AppHandle, schedule: &UpdateSchedule) -> Option<SkipReason> {
+        if app.state::<Arc<UpdateJobs>>().active().is_some() {
+            return Some(SkipReason::Updating);
+        }
+        if schedule.skip_while_gaming
+            && (!self.running_games.lock().unwrap().is_empty()
+                || tauri::async_runtime::spawn_blocking(steam_game_running)
+                    .await
+                    .unwrap_or(false))
+        {
+            return Some(SkipReason::Gaming);
+        }
+        // Unknown power or network state does not hold checks back
+        if schedule.skip_on_battery && on_battery().await.unwrap_or(false) {
+            return Some(SkipReason::Battery);
+        }
+        if schedule.skip_on_metered && network::is_metered().await.unwrap_or(false) {
+            return Some(SkipReason::Metered);
+        }
+        None
+    }
+
+    async fn check(&self, app: &AppHandle, schedule: &UpdateSchedule) {
+        log::info!("Running background update check");
+        let previous = self.last_check();
+        let results = vec![
+            (UpdateKind::System, check_system().await),
+            (UpdateKind::Flatpak, check_flatpaks().await),
+            (UpdateKind::Runner, check_runners(app).await),
+            (UpdateKind::Pixxiden, check_pixxiden().await),
+        ];
+        let updates = merge_results(previous.as_ref(), results);
+        let new = new_updates(previous.as_ref(), &updates);
+        let body = notification_body(&new);
+
+        let check = ScheduledCheck {
+            checked_at: Utc::now(),
+            new_updates: new.len(),
+            updates,
+        };
+        log::info!(
+            "{} update(s) available, {} new",
+            check.updates.len(),
+            check.new_updates
+        );
+
+        if let Err(e) = save(LAST_CHECK_FILE, &check) {
+            log::warn!("Failed to save background update check: {}", e);
+        }
+        *self.last_check.lock().unwrap() = Some(check.clone());
+        let _ = app.emit("updates-available", &check);
+
+        if check.new_updates > 0 && schedule.notify {
+            if let Err(e) = send_notification("Mises à jour disponibles", &body).await {
+                log::warn!("{}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(kind: UpdateKind, name: &str, new_version: &str) -> AvailableUpdate {
+        AvailableUpdate {
+            kind,
+            name: name.to_string(),
+            current_version: "1".to_string(),
+            new_version: new_version.to_string(),
+        }
+    }
+
+    fn previous_check(updates: Vec<AvailableUpdate>) -> ScheduledCheck {
+        ScheduledCheck {
+            checked_at: Utc::now(),
+            new_updates: updates.len(),
+            updates,
+        }
+    }
+
+    #[test]
+    fn test_next_check() {
+        let now = Utc::now();
+        let earliest = now + Duration::minutes(5);
+
+        // First run, or overdue after the app was closed for a while
+        assert_eq!(next_check(None, 12, Duration::zero(), earliest), earliest);
+        let overdue = now - Duration::days(3);
+        assert_eq!(
+            next_check(Some(overdue), 12, Duration::minutes(10), earliest),
+            earliest
+        );
+
+        let last = now - Duration::hours(2);
+        assert_eq!(
+            next_check(Some(last), 12, Duration::minutes(10), earliest),
+            last + Duration::hours(12) + Duration::minutes(10)
+        );
+    }
+
+    #[test]
+    fn test_jitter() {
+        assert_eq!(jitter(12, 0), Duration::zero());
+        assert!(jitter(12, u64::MAX) <= Duration::minutes(72));
+        assert!((0..1000).all(|r| jitter(1, r) <= Duration::minutes(6)));
+    }
+
+    #[test]
+    fn test_new_updates() {
+        let previous = previous_check(vec![
+            update(UpdateKind::System, "mesa", "24.1.1"),
+            update(UpdateKind::Runner, "Proton-GE", "GE-Proton9-10"),
+        ]);
+        let updates = vec![
+            update(UpdateKind::System, "mesa", "24.1.1"),
+            update(UpdateKind::System, "linux", "6.9.7"),
+            update(UpdateKind::Runner, "Proton-GE", "GE-Proton9-11"),
+        ];
+
+        let new = new_updates(Some(&previous), &updates);
+        let names: Vec<(&str, &str)> = new
+            .iter()
+            .map(|u| (u.name.as_str(), u.new_version.as_str()))
+            .collect();
+        assert_eq!(names, [("linux", "6.9.7"), ("Proton-GE", "GE-Proton9-11")]);
+        assert_eq!(new_updates(None, &updates).len(), 3);
+    }
+
+    #[test]
+    fn test_merge_keeps_failed_sources() {
+        let previous = previous_check(vec![
+            update(UpdateKind::System, "mesa", "24.1.1"),
+            update(UpdateKind::Pixxiden, "Pixxiden", "v0.2.0"),
+        ]);
+        let updates = merge_results(
+            Some(&previous),
+            vec![
+                (UpdateKind::System, Ok(vec![])),
+                (UpdateKind::Pixxiden, Err("offline".to_string())),
+            ],
+        );
+        assert_eq!(
+            updates,
+            [update(UpdateKind::Pixxiden, "Pixxiden", "v0.2.0")]
+        );
+        assert!(new_updates(Some(&previous), &updates).is_empty());
+    }
+
+    #[test]
+    fn test_notification_body() {
+        let updates = [
+            update(UpdateKind::System, "mesa", "24.1.1"),
+            update(UpdateKind::System, "linux", "6.9.7"),
+            update(UpdateKind::Flatpak, "Discord", "0.0.58"),
+            update(UpdateKind::Runner, "Proton-GE", "GE-Proton9-11"),
+        ];
+        let all: Vec<&AvailableUpdate> = updates.iter().collect();
+        assert_eq!(
+            notification_body(&all),
+            "4 nouvelles mises à jour : mesa, linux, Discord, …"
+        );
+        assert_eq!(
+            notification_body(&all[..1]),
+            "1 nouvelle mise à jour : mesa"
+        );
+    }
+
+    #[test]
+    fn test_game_events() {
+        assert_eq!(
+            game_id(r#"{"gameId":"epic-fortnite","exitCode":0}"#),
+            Some("epic-fortnite".to_string())
+        );
+        assert_eq!(game_id("null"), None);
+
+        let cmd = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        assert!(is_steam_game(&cmd(&[
+            "/home/deck/.steam/steam/ubuntu12_32/reaper",
+            "SteamLaunch",
+            "AppId=1091500",
+            "--",
+        ])));
+        assert!(!is_steam_game(&cmd(&["steam", "-silent"])));
+    }
+
+    #[test]
+    fn test_schedule_defaults_for_missing_fields() {
+        let schedule: UpdateSchedule = serde_json::from_str(r#"{"intervalHours": 6}"#).unwrap();
+        assert_eq!(schedule.interval_hours, 6);
+        assert!(schedule.enabled && schedule.notify);
+    }
+}
@@ -0,0 +1,69 @@
+/**
+ * Audio device API functions
+ */
+import { invoke } from "@tauri-apps/api/core";
+
+export type AudioDeviceKind = "sink" | "source";
+
+export interface AudioDevice {
+  index: number;
+  name: string;
+  description: string;
+  kind: AudioDeviceKind;
+  formFactor: string | null;
+  volume: number;
+  muted: boolean;
+  isDefault: boolean;
+}
+
+/** Emitted as "audio-changed" when devices or defaults change outside Pixxiden */
+export interface AudioState {
+  sinks: AudioDevice[];
+  sources: AudioDevice[];
+  defaultSink: string | null;
+  defaultSource: string | null;
+}
+
+export async function getAudioState(): Promise<AudioState> {
+  try {
+    return await invoke<AudioState>("get_audio_state");
+  } catch (error) {
+    console.error("Failed to get audio state:", error);
+    throw error;
+  }
+}
+
+export async function setDefaultAudioDevice(kind: AudioDeviceKind, name: string): Promise<void> {
+  try {
+    await invoke("set_default_audio_device", { kind, name });
+  } catch (error) {
+    console.error("Failed to set default audio device:", error);
+    throw error;
+  }
+}
+
+export async function setAudioVolume(
+  kind: AudioDeviceKind,
+  name: string,
+  volume: number,
+): Promise<void> {
+  try {
+    await invoke("set_audio_volume", { kind, name, volume: Math.round(volume) });
+  } catch (error) {
+    console.error("Failed to set audio volume:", error);
+    throw error;
+  }
+}
+
+export async function setAudioMute(
+  kind: AudioDeviceKind,
+  name: string,
+  muted: boolean,
+): Promise<void> {
+  try {
+    await invoke("set_audio_mute", { kind, name, muted });
+  } catch (error) {
+    console.error("Failed to set audio mute:", error);
+    throw error;
+  }
+}
@@ -0,0 +1,91 @@
+/**
+ * Bluetooth API functions
+ */
+import { invoke } from "@tauri-apps/api/core";
+
+export type BluetoothDeviceKind = "gamepad" | "headset" | "other";
+
+export interface BluetoothDevice {
+  address: string;
+  name: string;
+  kind: BluetoothDeviceKind;
+  paired: boolean;
+  trusted: boolean;
+  connected: boolean;
+  rssi: number | null;
+  battery: number | null;
+}
+
+export async function startBluetoothDiscovery(): Promise<void> {
+  try {
+    await invoke("start_bluetooth_discovery");
+  } catch (error) {
+    console.error("Failed to start Bluetooth discovery:", error);
+    throw error;
+  }
+}
+
+export async function stopBluetoothDiscovery(): Promise<void> {
+  try {
+    await invoke("stop_bluetooth_discovery");
+  } catch (error) {
+    console.error("Failed to stop Bluetooth discovery:", error);
+    throw error;
+  }
+}
+
+/** Gamepads and headsets only, unless all is set */
+export async function listBluetoothDevices(all = false): Promise<BluetoothDevice[]> {
+  try {
+    return await invoke<BluetoothDevice[]>("list_bluetooth_devices", { all });
+  } catch (error) {
+    console.error("Failed to list Bluetooth devices:", error);
+    throw error;
+  }
+}
+
+/** Pair, trust and connect. Controllers are announced via "gamepad-connected". */
+export async function pairBluetoothDevice(address: string): Promise<BluetoothDevice> {
+  try {
+    return await invoke<BluetoothDevice>("pair_bluetooth_device", { address });
+  } catch (error) {
+    console.error("Failed to pair Bluetooth device:", error);
+    throw error;
+  }
+}
+
+export async function trustBluetoothDevice(address: string): Promise<void> {
+  try {
+    await invoke("trust_bluetooth_device", { address });
+  } catch (error) {
+    console.error("Failed to trust Bluetooth device:", error);
+    throw error;
+  }
+}
+
+export async function connectBluetoothDevice(address: string): Promise<BluetoothDevice> {
+  try {
+    return await invoke<BluetoothDevice>("connect_bluetooth_device", { address });
+  } catch (error) {
+    console.error("Failed to connect Bluetooth device:", error);
+    throw error;
+  }
+}
+
+export async function disconnectBluetoothDevice(address: string): Promise<void> {
+  try {
+    await invoke("disconnect_bluetooth_device", { address });
+  } catch (error) {
+    console.error("Failed to disconnect Bluetooth device:", error);
+    throw error;
+  }
+}
+
+export async function forgetBluetoothDevice(address: string): Promise<void> {
+  try {
+    await invoke("forget_bluetooth_device", { address });
+  } catch (error) {
+    console.error("Failed to forget Bluetooth device:", error);
+    throw error;
+  }
+}
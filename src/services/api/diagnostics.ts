@@ -0,0 +1,18 @@
+/**
+ * Diagnostics API functions
+ */
+import { invoke } from "@tauri-apps/api/core";
+
+/**
+ * Export a diagnostics bundle (.tar.gz) to the given path.
+ * Logs and reports are redacted (home paths, username, tokens) before being archived.
+ * Returns the path of the written bundle.
+ */
+export async function exportDiagnosticsBundle(dest: string): Promise<string> {
+  try {
+    return await invoke<string>("export_diagnostics_bundle", { dest });
+  } catch (error) {
+    console.error("Failed to export diagnostics bundle:", error);
+    throw error;
+  }
+}
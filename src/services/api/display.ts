@@ -0,0 +1,72 @@
+/**
+ * Display output API functions
+ */
+import { invoke } from "@tauri-apps/api/core";
+
+export interface DisplayMode {
+  id: string;
+  width: number;
+  height: number;
+  refreshRate: number;
+  preferred: boolean;
+  current: boolean;
+}
+
+export interface DisplayOutput {
+  name: string;
+  enabled: boolean;
+  primary: boolean;
+  scale: number;
+  modes: DisplayMode[];
+}
+
+/** Unset fields are left unchanged */
+export interface DisplayChange {
+  output: string;
+  modeId?: string | null;
+  scale?: number | null;
+  enabled?: boolean | null;
+  primary?: boolean | null;
+}
+
+/** A change is reverted after revertInSeconds ("display-reverted" event) unless confirmed */
+export interface PendingDisplayChange {
+  id: number;
+  revertInSeconds: number;
+}
+
+export async function getDisplayOutputs(): Promise<DisplayOutput[]> {
+  try {
+    return await invoke<DisplayOutput[]>("get_display_outputs");
+  } catch (error) {
+    console.error("Failed to get display outputs:", error);
+    throw error;
+  }
+}
+
+export async function applyDisplayChange(change: DisplayChange): Promise<PendingDisplayChange> {
+  try {
+    return await invoke<PendingDisplayChange>("apply_display_change", { change });
+  } catch (error) {
+    console.error("Failed to apply display change:", error);
+    throw error;
+  }
+}
+
+export async function confirmDisplayChange(id: number): Promise<void> {
+  try {
+    await invoke("confirm_display_change", { id });
+  } catch (error) {
+    console.error("Failed to confirm display change:", error);
+    throw error;
+  }
+}
+
+export async function revertDisplayChange(id: number): Promise<void> {
+  try {
+    await invoke("revert_display_change", { id });
+  } catch (error) {
+    console.error("Failed to revert display change:", error);
+    throw error;
+  }
+}
@@ -22,19 +22,115 @@ export type { ApiKeysConfig, ApiKeysUpdateRequest, ApiKeyTestResult } from "./ap
 // System Updates
 export {
   getDistro,
+  getDistroInfo,
   isSudoersConfigured,
   configureSudoers,
   checkSystemUpdates,
   installSystemUpdates,
+  cancelSystemUpdate,
+  getActiveUpdateJob,
+  checkInterruptedUpdate,
+  markArchNewsRead,
+  getSnapshotTool,
+  listUpdateSnapshots,
+  getSteamOsStatus,
+  getOstreeStatus,
+  rollbackOstreeDeployment,
+  listFirmwareUpdates,
+  installFirmwareUpdate,
+  getUpdateSchedule,
+  setUpdateSchedule,
+  getLastUpdateCheck,
+  listUpdateHistory,
+  getUpdateRun,
+  getPackageHolds,
+  setPackageHolds,
   requiresSystemReboot,
   rebootSystem,
 } from "./updates";
 export type {
   Distro,
+  GamingVariant,
+  DistroInfo,
   PackageCategory,
+  UpdateSource,
   UpdatePackage,
   UpdateCheckResult,
+  HoldConflict,
+  NewsItem,
   UpdateReport,
+  UpdateFailureKind,
+  UpdateError,
+  UpdatePhase,
+  UpdateJobInfo,
+  SnapshotTool,
+  Snapshot,
+  SteamOsStatus,
+  OstreeTool,
+  Deployment,
+  OstreeStatus,
+  FirmwareUrgency,
+  FirmwareRelease,
+  FirmwareDevice,
+  FirmwareReport,
+  UpdateSchedule,
+  UpdateKind,
+  AvailableUpdate,
+  ScheduledCheck,
+  RunStatus,
+  UpdateRun,
+  RunPackageStatus,
+  RunPackage,
+  PackageAction,
+  PackageChange,
+  UpdateRunDetails,
+  StaleProcess,
+  RebootReason,
+  RebootStatus,
   SudoersStatus,
   UpdateProgressEvent,
 } from "./updates";
+
+// Gaming prerequisites
+export { checkGamingPrerequisites, installGamingPrerequisites } from "./prerequisites";
+export type { Prerequisite, PrerequisiteStatus } from "./prerequisites";
+
+// Audio
+export { getAudioState, setDefaultAudioDevice, setAudioVolume, setAudioMute } from "./audio";
+export type { AudioDeviceKind, AudioDevice, AudioState } from "./audio";
+
+// Display
+export {
+  getDisplayOutputs,
+  applyDisplayChange,
+  confirmDisplayChange,
+  revertDisplayChange,
+} from "./display";
+export type { DisplayMode, DisplayOutput, DisplayChange, PendingDisplayChange } from "./display";
+
+// Bluetooth
+export {
+  startBluetoothDiscovery,
+  stopBluetoothDiscovery,
+  listBluetoothDevices,
+  pairBluetoothDevice,
+  trustBluetoothDevice,
+  connectBluetoothDevice,
+  disconnectBluetoothDevice,
+  forgetBluetoothDevice,
+} from "./bluetooth";
+export type { BluetoothDeviceKind, BluetoothDevice } from "./bluetooth";
+
+// Wi-Fi
+export {
+  getWifiStatus,
+  setWifiEnabled,
+  scanWifiNetworks,
+  listWifiNetworks,
+  connectWifiNetwork,
+  forgetWifiNetwork,
+} from "./network";
+export type { WifiSecurity, WifiNetwork, WifiStatus } from "./network";
+
+// Diagnostics
+export { exportDiagnosticsBundle } from "./diagnostics";
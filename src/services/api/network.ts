@@ -0,0 +1,81 @@
+/**
+ * Wi-Fi network API functions
+ */
+import { invoke } from "@tauri-apps/api/core";
+
+export type WifiSecurity = "open" | "wep" | "wpa" | "wpa3" | "enterprise";
+
+export interface WifiNetwork {
+  ssid: string;
+  strength: number;
+  security: WifiSecurity;
+  frequency: number;
+  connected: boolean;
+  saved: boolean;
+}
+
+export interface WifiStatus {
+  enabled: boolean;
+  interface: string | null;
+  ssid: string | null;
+  strength: number | null;
+}
+
+export async function getWifiStatus(): Promise<WifiStatus> {
+  try {
+    return await invoke<WifiStatus>("get_wifi_status");
+  } catch (error) {
+    console.error("Failed to get Wi-Fi status:", error);
+    throw error;
+  }
+}
+
+export async function setWifiEnabled(enabled: boolean): Promise<void> {
+  try {
+    await invoke("set_wifi_enabled", { enabled });
+  } catch (error) {
+    console.error("Failed to toggle Wi-Fi:", error);
+    throw error;
+  }
+}
+
+export async function scanWifiNetworks(): Promise<void> {
+  try {
+    await invoke("scan_wifi_networks");
+  } catch (error) {
+    console.error("Failed to scan Wi-Fi networks:", error);
+    throw error;
+  }
+}
+
+export async function listWifiNetworks(): Promise<WifiNetwork[]> {
+  try {
+    return await invoke<WifiNetwork[]>("list_wifi_networks");
+  } catch (error) {
+    console.error("Failed to list Wi-Fi networks:", error);
+    throw error;
+  }
+}
+
+/** Saved networks reconnect without a password */
+export async function connectWifiNetwork(
+  ssid: string,
+  password?: string,
+  hidden = false,
+): Promise<void> {
+  try {
+    await invoke("connect_wifi_network", { ssid, password: password ?? null, hidden });
+  } catch (error) {
+    console.error("Failed to connect to Wi-Fi network:", error);
+    throw error;
+  }
+}
+
+export async function forgetWifiNetwork(ssid: string): Promise<void> {
+  try {
+    await invoke("forget_wifi_network", { ssid });
+  } catch (error) {
+    console.error("Failed to forget Wi-Fi network:", error);
+    throw error;
+  }
+}
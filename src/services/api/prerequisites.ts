@@ -0,0 +1,44 @@
+/**
+ * Gaming prerequisites API functions
+ */
+import { invoke } from "@tauri-apps/api/core";
+import type { UpdateReport } from "./updates";
+
+export type Prerequisite =
+  | "lib32Linker"
+  | "lib32Gl"
+  | "lib32Freetype"
+  | "lib32X11"
+  | "lib32Vulkan"
+  | "gamemode";
+
+export interface PrerequisiteStatus {
+  prerequisite: Prerequisite;
+  name: string;
+  installed: boolean;
+}
+
+export async function checkGamingPrerequisites(): Promise<PrerequisiteStatus[]> {
+  try {
+    return await invoke<PrerequisiteStatus[]>("check_gaming_prerequisites");
+  } catch (error) {
+    console.error("Failed to check gaming prerequisites:", error);
+    throw error;
+  }
+}
+
+/**
+ * Install missing prerequisites through the distro package manager.
+ * Runs as a system update job: progress is streamed as UpdateProgressEvent on
+ * "update-progress", and the job state on "update-job".
+ */
+export async function installGamingPrerequisites(
+  prerequisites: Prerequisite[],
+): Promise<UpdateReport> {
+  try {
+    return await invoke<UpdateReport>("install_gaming_prerequisites", { prerequisites });
+  } catch (error) {
+    console.error("Failed to install gaming prerequisites:", error);
+    throw error;
+  }
+}
@@ -3,7 +3,29 @@
  */
 import { invoke } from "@tauri-apps/api/core";
 
-export type Distro = "arch" | "steamos" | "debian" | "fedora" | "unknown";
+export type Distro =
+  | "arch"
+  | "steamos"
+  | "ostree"
+  | "debian"
+  | "fedora"
+  | "opensuse"
+  | "unknown";
+
+export type GamingVariant = "cachyos" | "nobara" | "bazzite" | "chimeraos" | "steamos";
+
+export interface DistroInfo {
+  /** Decides which package manager is used */
+  family: Distro;
+  id: string;
+  /** `ID_LIKE`, closest first */
+  idLike: string[];
+  name: string;
+  prettyName: string;
+  versionId: string | null;
+  variantId: string | null;
+  gamingVariant: GamingVariant | null;
+}
 
 export type PackageCategory =
   | "system"
@@ -13,28 +35,265 @@ export type PackageCategory =
   | "application"
   | "library";
 
+/**
+ * flatpakGl: Flatpak GL runtime extensions, which follow the host graphics driver
+ * osImage: SteamOS image or ostree deployment, applied on the next boot
+ * aur: AUR packages, built as the user by paru or yay
+ */
+export type UpdateSource = "system" | "flatpak" | "flatpakGl" | "osImage" | "aur";
+
 export interface UpdatePackage {
   name: string;
   currentVersion: string;
   newVersion: string;
   category: PackageCategory;
+  /** Download size in bytes */
   size: number;
+  /** Change in installed size in bytes (negative when the update shrinks) */
+  installedSizeDelta: number;
   critical: boolean;
+  source: UpdateSource;
+}
+
+/** Arch Linux news published since the last upgrade */
+export interface NewsItem {
+  title: string;
+  link: string;
+  /** RFC 3339 */
+  published: string;
+  /** Pending packages mentioned by the item; the upgrade is blocked while non-empty */
+  affectedPackages: string[];
 }
 
 export interface UpdateCheckResult {
   distro: Distro;
   packages: UpdatePackage[];
   totalSize: number;
+  totalInstalledSizeDelta: number;
   requiresReboot: boolean;
+  /** Unread Arch news (Arch only) */
+  news: NewsItem[];
+  /** Replayed from a transcript, nothing is changed on the system */
+  demo: boolean;
+  /** Updates skipped because of the hold list */
+  held: UpdatePackage[];
+  /** Upgrades the hold list would break, blocking the install */
+  holdConflicts: HoldConflict[];
+}
+
+/** Upgrade that needs a newer version of a held package */
+export interface HoldConflict {
+  package: string;
+  dependency: string;
+}
+
+export type UpdateFailureKind =
+  | "fileConflict"
+  | "signature"
+  | "databaseLocked"
+  | "dpkgInterrupted"
+  | "dependency"
+  | "cancelled"
+  | "manualIntervention"
+  | "unknown";
+
+export interface UpdateError {
+  kind: UpdateFailureKind;
+  /** First error line reported by the package manager */
+  message: string;
+  /** Packages involved, when they could be identified */
+  packages: string[];
+  remediation: string;
+}
+
+export type SnapshotTool = "snapper" | "timeshift" | "btrfs";
+
+export interface Snapshot {
+  tool: SnapshotTool;
+  /** snapper number, Timeshift name or btrfs subvolume name */
+  id: string;
+  /** YYYY-MM-DD HH:MM:SS */
+  date: string;
+  description: string;
 }
 
 export interface UpdateReport {
   totalPackages: number;
   installedSuccessfully: number;
+  installed: string[];
   failed: string[];
   requiresReboot: boolean;
   durationSeconds: number;
+  /** Set when the package manager exited with an error */
+  error: UpdateError | null;
+  /** Taken before updating, when requested */
+  snapshot: Snapshot | null;
+}
+
+/** Update jobs can only be cancelled while downloading */
+export type UpdatePhase = "downloading" | "committing" | "cancelled" | "finished";
+
+/** Payload of the `update-job` event */
+export interface UpdateJobInfo {
+  id: number;
+  phase: UpdatePhase;
+}
+
+/** SteamOS image versions and update channel */
+export interface SteamOsStatus {
+  currentVersion: string;
+  currentBuild: string;
+  /** Set when an image update is available */
+  availableVersion: string | null;
+  availableBuild: string | null;
+  channel: string | null;
+  /** pacman is only used once read-only mode is disabled */
+  readOnly: boolean;
+}
+
+export type OstreeTool = "rpmOstree" | "bootc";
+
+export interface Deployment {
+  version: string;
+  /** ostree commit or container image digest */
+  checksum: string;
+  /** RFC 3339 */
+  timestamp: string;
+  /** Container image reference or ostree refspec */
+  origin: string;
+  booted: boolean;
+  staged: boolean;
+}
+
+/** ostree deployments, in boot order */
+export interface OstreeStatus {
+  tool: OstreeTool;
+  deployments: Deployment[];
+  /** The next boot uses another deployment */
+  rebootPending: boolean;
+  rollbackAvailable: boolean;
+}
+
+export type FirmwareUrgency = "unknown" | "low" | "medium" | "high" | "critical";
+
+/** Firmware release published on LVFS */
+export interface FirmwareRelease {
+  version: string;
+  summary: string;
+  /** Plain text, one paragraph or bullet per line */
+  releaseNotes: string;
+  /** Download size in bytes */
+  size: number;
+  urgency: FirmwareUrgency;
+}
+
+/** Device with a firmware update available through fwupd */
+export interface FirmwareDevice {
+  id: string;
+  name: string;
+  vendor: string;
+  currentVersion: string;
+  /** The firmware is only applied on the next boot or restart */
+  requiresReboot: boolean;
+  release: FirmwareRelease;
+}
+
+export interface FirmwareReport {
+  deviceId: string;
+  version: string;
+  requiresReboot: boolean;
+}
+
+export interface UpdateSchedule {
+  enabled: boolean;
+  intervalHours: number;
+  skipOnBattery: boolean;
+  skipOnMetered: boolean;
+  skipWhileGaming: boolean;
+  /** Desktop notification when new updates show up */
+  notify: boolean;
+}
+
+export type UpdateKind = "system" | "flatpak" | "runner" | "pixxiden";
+
+export interface AvailableUpdate {
+  kind: UpdateKind;
+  name: string;
+  currentVersion: string;
+  newVersion: string;
+}
+
+/** Result of the last background check, also sent as `updates-available` events */
+export interface ScheduledCheck {
+  checkedAt: string;
+  updates: AvailableUpdate[];
+  /** Not found by the previous check */
+  newUpdates: number;
+}
+
+export type RunStatus = "running" | "succeeded" | "failed" | "cancelled" | "interrupted";
+
+/** Past `install_system_updates` run */
+export interface UpdateRun {
+  id: number;
+  startedAt: string;
+  finishedAt: string | null;
+  distro: Distro;
+  status: RunStatus;
+  requiresReboot: boolean;
+  snapshotId: string | null;
+  /** Replayed from a transcript */
+  demo: boolean;
+  packageCount: number;
+  failedCount: number;
+}
+
+export type RunPackageStatus = "pending" | "installed" | "failed" | "skipped";
+
+export interface RunPackage {
+  name: string;
+  source: UpdateSource;
+  oldVersion: string;
+  newVersion: string;
+  status: RunPackageStatus;
+}
+
+export type PackageAction = "installed" | "upgraded" | "downgraded" | "reinstalled" | "removed";
+
+/** Entry of the pacman/dpkg log */
+export interface PackageChange {
+  time: string;
+  action: PackageAction;
+  name: string;
+  oldVersion: string | null;
+  newVersion: string | null;
+}
+
+export interface UpdateRunDetails {
+  run: UpdateRun;
+  packages: RunPackage[];
+  error: UpdateError | null;
+  /** Logged since the previous run, outside Pixxiden */
+  externalChanges: PackageChange[];
+}
+
+/** Process mapping libraries that were deleted or replaced on disk */
+export interface StaleProcess {
+  pid: number;
+  name: string;
+  libraries: string[];
+}
+
+export type RebootReason =
+  | { kind: "kernelUpdated"; running: string }
+  | { kind: "coreUpdated"; packages: string[] }
+  | { kind: "stagedDeployment" }
+  | { kind: "staleLibraries"; processes: StaleProcess[] };
+
+export interface RebootStatus {
+  /** Stale libraries alone only need the processes restarted */
+  required: boolean;
+  reasons: RebootReason[];
 }
 
 export interface SudoersStatus {
@@ -46,7 +305,10 @@ export interface SudoersStatus {
 export interface UpdateProgressEvent {
   stage: "downloading" | "installing" | "configuring";
   packageName: string;
+  /** Overall progress, 0-100 */
   progress: number;
+  /** Progress of the current package, 0-100 */
+  packageProgress: number;
   downloaded: string;
   total: string;
   speed: string;
@@ -62,6 +324,15 @@ export async function getDistro(): Promise<Distro> {
   }
 }
 
+export async function getDistroInfo(): Promise<DistroInfo> {
+  try {
+    return await invoke<DistroInfo>("get_distro_info");
+  } catch (error) {
+    console.error("Failed to get distro info:", error);
+    throw error;
+  }
+}
+
 export async function isSudoersConfigured(): Promise<SudoersStatus> {
   try {
     return await invoke<SudoersStatus>("is_sudoers_configured");
@@ -89,18 +360,186 @@ export async function checkSystemUpdates(): Promise<UpdateCheckResult> {
   }
 }
 
-export async function installSystemUpdates(): Promise<UpdateReport> {
+export async function installSystemUpdates(
+  packages: UpdatePackage[],
+  snapshot = false,
+): Promise<UpdateReport> {
   try {
-    return await invoke<UpdateReport>("install_system_updates");
+    return await invoke<UpdateReport>("install_system_updates", { packages, snapshot });
   } catch (error) {
     console.error("Failed to install system updates:", error);
     throw error;
   }
 }
 
-export async function requiresSystemReboot(): Promise<boolean> {
+export async function cancelSystemUpdate(id: number): Promise<void> {
+  try {
+    await invoke("cancel_system_update", { id });
+  } catch (error) {
+    console.error("Failed to cancel system update:", error);
+    throw error;
+  }
+}
+
+export async function getActiveUpdateJob(): Promise<UpdateJobInfo | null> {
+  try {
+    return await invoke<UpdateJobInfo | null>("get_active_update_job");
+  } catch (error) {
+    console.error("Failed to get active update job:", error);
+    throw error;
+  }
+}
+
+export async function checkInterruptedUpdate(): Promise<UpdateError | null> {
+  try {
+    return await invoke<UpdateError | null>("check_interrupted_update");
+  } catch (error) {
+    console.error("Failed to check for an interrupted update:", error);
+    throw error;
+  }
+}
+
+export async function markArchNewsRead(): Promise<void> {
+  try {
+    await invoke("mark_arch_news_read");
+  } catch (error) {
+    console.error("Failed to mark Arch news as read:", error);
+    throw error;
+  }
+}
+
+export async function getSnapshotTool(): Promise<SnapshotTool | null> {
+  try {
+    return await invoke<SnapshotTool | null>("get_snapshot_tool");
+  } catch (error) {
+    console.error("Failed to detect snapshot tool:", error);
+    throw error;
+  }
+}
+
+export async function listUpdateSnapshots(): Promise<Snapshot[]> {
+  try {
+    return await invoke<Snapshot[]>("list_update_snapshots");
+  } catch (error) {
+    console.error("Failed to list update snapshots:", error);
+    throw error;
+  }
+}
+
+export async function getSteamOsStatus(): Promise<SteamOsStatus> {
+  try {
+    return await invoke<SteamOsStatus>("get_steamos_status");
+  } catch (error) {
+    console.error("Failed to get SteamOS status:", error);
+    throw error;
+  }
+}
+
+export async function getOstreeStatus(): Promise<OstreeStatus> {
+  try {
+    return await invoke<OstreeStatus>("get_ostree_status");
+  } catch (error) {
+    console.error("Failed to get ostree status:", error);
+    throw error;
+  }
+}
+
+/** Boots the previous deployment on the next restart */
+export async function rollbackOstreeDeployment(): Promise<void> {
+  try {
+    await invoke("rollback_ostree_deployment");
+  } catch (error) {
+    console.error("Failed to roll back ostree deployment:", error);
+    throw error;
+  }
+}
+
+export async function listFirmwareUpdates(): Promise<FirmwareDevice[]> {
+  try {
+    return await invoke<FirmwareDevice[]>("list_firmware_updates");
+  } catch (error) {
+    console.error("Failed to list firmware updates:", error);
+    throw error;
+  }
+}
+
+/** Progress is reported through `firmware-progress` events */
+export async function installFirmwareUpdate(deviceId: string): Promise<FirmwareReport> {
+  try {
+    return await invoke<FirmwareReport>("install_firmware_update", { deviceId });
+  } catch (error) {
+    console.error("Failed to install firmware update:", error);
+    throw error;
+  }
+}
+
+export async function getUpdateSchedule(): Promise<UpdateSchedule> {
+  try {
+    return await invoke<UpdateSchedule>("get_update_schedule");
+  } catch (error) {
+    console.error("Failed to get update schedule:", error);
+    throw error;
+  }
+}
+
+export async function setUpdateSchedule(schedule: UpdateSchedule): Promise<void> {
+  try {
+    await invoke("set_update_schedule", { schedule });
+  } catch (error) {
+    console.error("Failed to save update schedule:", error);
+    throw error;
+  }
+}
+
+export async function getLastUpdateCheck(): Promise<ScheduledCheck | null> {
+  try {
+    return await invoke<ScheduledCheck | null>("get_last_update_check");
+  } catch (error) {
+    console.error("Failed to get last update check:", error);
+    throw error;
+  }
+}
+
+export async function listUpdateHistory(limit?: number): Promise<UpdateRun[]> {
+  try {
+    return await invoke<UpdateRun[]>("list_update_history", { limit });
+  } catch (error) {
+    console.error("Failed to list update history:", error);
+    throw error;
+  }
+}
+
+export async function getUpdateRun(id: number): Promise<UpdateRunDetails> {
+  try {
+    return await invoke<UpdateRunDetails>("get_update_run", { id });
+  } catch (error) {
+    console.error("Failed to get update run:", error);
+    throw error;
+  }
+}
+
+export async function getPackageHolds(): Promise<string[]> {
+  try {
+    return await invoke<string[]>("get_package_holds");
+  } catch (error) {
+    console.error("Failed to get package holds:", error);
+    throw error;
+  }
+}
+
+/** Saving the hold list updates the sudoers rules, hence the password */
+export async function setPackageHolds(packages: string[], password: string): Promise<string[]> {
+  try {
+    return await invoke<string[]>("set_package_holds", { packages, password });
+  } catch (error) {
+    console.error("Failed to set package holds:", error);
+    throw error;
+  }
+}
+
+export async function requiresSystemReboot(): Promise<RebootStatus> {
   try {
-    return await invoke<boolean>("requires_system_reboot");
+    return await invoke<RebootStatus>("requires_system_reboot");
   } catch (error) {
     console.error("Failed to check reboot status:", error);
     throw error;
@@ -133,6 +133,9 @@ export class GameLaunchService {
 
     this.activeGames.set(game.id, handle);
 
+    // Lets background work (update checks) wait until the game exits
+    void emit("game-started", { gameId: game.id });
+
     // Monitor game exit in background (non-blocking)
     handle.completion
       .then(async (result) => {
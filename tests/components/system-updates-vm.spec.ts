@@ -26,7 +26,7 @@ describe("SystemUpdates (vm methods)", () => {
     vi.clearAllMocks();
     mockIsSudoersConfigured.mockResolvedValue({ configured: false });
     mockCheckSystemUpdates.mockResolvedValue({ packages: [], totalSize: 0, requiresReboot: false });
-    mockRequiresSystemReboot.mockResolvedValue(false);
+    mockRequiresSystemReboot.mockResolvedValue({ required: false, reasons: [] });
     mockInstallSystemUpdates.mockResolvedValue({ requiresReboot: true });
     mockRebootSystem.mockResolvedValue(undefined);
     mockListen.mockResolvedValue(vi.fn());
@@ -110,7 +110,10 @@ describe("SystemUpdates (vm methods)", () => {
 
     await vm.installUpdates();
 
-    expect(mockInstallSystemUpdates).toHaveBeenCalled();
+    expect(mockInstallSystemUpdates).toHaveBeenCalledWith(
+      [expect.objectContaining({ name: "linux" })],
+      expect.anything(),
+    );
     expect(vm.requiresReboot).toBe(true);
     expect(vm.updates).toEqual([]);
   });
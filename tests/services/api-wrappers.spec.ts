@@ -61,6 +61,9 @@ describe("API wrappers", () => {
       mockInvokeValue("debian");
       expect(await updatesApi.getDistro()).toBe("debian");
 
+      mockInvokeValue({ family: "arch", id: "cachyos", gamingVariant: "cachyos" });
+      expect((await updatesApi.getDistroInfo()).gamingVariant).toBe("cachyos");
+
       mockInvokeValue({ configured: true, distro: "debian", sudoersFileExists: true });
       expect((await updatesApi.isSudoersConfigured()).configured).toBe(true);
 
@@ -74,14 +77,100 @@ describe("API wrappers", () => {
       mockInvokeValue({
         totalPackages: 0,
         installedSuccessfully: 0,
+        installed: [],
         failed: [],
         requiresReboot: false,
         durationSeconds: 1,
+        error: null,
+        snapshot: null,
+      });
+      expect((await updatesApi.installSystemUpdates([])).durationSeconds).toBe(1);
+      expect(mockInvoke).toHaveBeenCalledWith("install_system_updates", {
+        packages: [],
+        snapshot: false,
+      });
+
+      mockInvokeValue(undefined);
+      await updatesApi.markArchNewsRead();
+      expect(mockInvoke).toHaveBeenCalledWith("mark_arch_news_read");
+
+      mockInvokeValue("snapper");
+      expect(await updatesApi.getSnapshotTool()).toBe("snapper");
+
+      mockInvokeValue([{ tool: "snapper", id: "42", date: "", description: "" }]);
+      expect((await updatesApi.listUpdateSnapshots())[0].id).toBe("42");
+
+      mockInvokeValue({ currentVersion: "3.5.19", channel: "stable", readOnly: true });
+      expect((await updatesApi.getSteamOsStatus()).channel).toBe("stable");
+
+      mockInvokeValue({ tool: "rpmOstree", deployments: [], rebootPending: true });
+      expect((await updatesApi.getOstreeStatus()).rebootPending).toBe(true);
+
+      mockInvokeValue(undefined);
+      await updatesApi.rollbackOstreeDeployment();
+      expect(mockInvoke).toHaveBeenCalledWith("rollback_ostree_deployment");
+
+      mockInvokeValue([{ id: "a45d", name: "Ultimate Controller", requiresReboot: false }]);
+      expect((await updatesApi.listFirmwareUpdates())[0].name).toBe("Ultimate Controller");
+
+      mockInvokeValue({ deviceId: "a45d", version: "1.0.12", requiresReboot: true });
+      expect((await updatesApi.installFirmwareUpdate("a45d")).requiresReboot).toBe(true);
+      expect(mockInvoke).toHaveBeenCalledWith("install_firmware_update", { deviceId: "a45d" });
+
+      mockInvokeValue(undefined);
+      await updatesApi.cancelSystemUpdate(3);
+      expect(mockInvoke).toHaveBeenCalledWith("cancel_system_update", { id: 3 });
+
+      mockInvokeValue({ id: 3, phase: "committing" });
+      expect((await updatesApi.getActiveUpdateJob())?.phase).toBe("committing");
+
+      mockInvokeValue(null);
+      expect(await updatesApi.checkInterruptedUpdate()).toBeNull();
+
+      mockInvokeValue({
+        enabled: true,
+        intervalHours: 12,
+        skipOnBattery: true,
+        skipOnMetered: true,
+        skipWhileGaming: true,
+        notify: true,
+      });
+      const schedule = await updatesApi.getUpdateSchedule();
+      expect(schedule.intervalHours).toBe(12);
+
+      mockInvokeValue(undefined);
+      await updatesApi.setUpdateSchedule({ ...schedule, intervalHours: 6 });
+      expect(mockInvoke).toHaveBeenCalledWith("set_update_schedule", {
+        schedule: { ...schedule, intervalHours: 6 },
+      });
+
+      mockInvokeValue(null);
+      expect(await updatesApi.getLastUpdateCheck()).toBeNull();
+
+      mockInvokeValue([]);
+      expect(await updatesApi.listUpdateHistory(20)).toEqual([]);
+      expect(mockInvoke).toHaveBeenCalledWith("list_update_history", { limit: 20 });
+
+      mockInvokeValue({ run: { id: 3 }, packages: [], error: null, externalChanges: [] });
+      expect((await updatesApi.getUpdateRun(3)).run.id).toBe(3);
+      expect(mockInvoke).toHaveBeenCalledWith("get_update_run", { id: 3 });
+
+      mockInvokeValue(["nvidia-utils"]);
+      expect(await updatesApi.getPackageHolds()).toEqual(["nvidia-utils"]);
+      expect(mockInvoke).toHaveBeenCalledWith("get_package_holds");
+
+      mockInvokeValue(["linux", "nvidia-utils"]);
+      expect(await updatesApi.setPackageHolds(["nvidia-utils", "linux"], "secret")).toEqual([
+        "linux",
+        "nvidia-utils",
+      ]);
+      expect(mockInvoke).toHaveBeenCalledWith("set_package_holds", {
+        packages: ["nvidia-utils", "linux"],
+        password: "secret",
       });
-      expect((await updatesApi.installSystemUpdates()).durationSeconds).toBe(1);
 
-      mockInvokeValue(false);
-      expect(await updatesApi.requiresSystemReboot()).toBe(false);
+      mockInvokeValue({ required: true, reasons: [{ kind: "stagedDeployment" }] });
+      expect((await updatesApi.requiresSystemReboot()).required).toBe(true);
 
       mockInvokeValue(undefined);
       await updatesApi.rebootSystem();